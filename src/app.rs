@@ -1,25 +1,37 @@
 use std::cell::RefCell;
+use std::fmt;
 use std::marker::PhantomData;
 use std::rc::Rc;
 
 use actix_http::body::{Body, MessageBody};
-use actix_http::{Extensions, PayloadStream, Request, Response};
+use actix_http::http::{header, HeaderMap, Method};
+use actix_http::{Error, Extensions, PayloadStream, Request, Response};
 use actix_router::{Path, ResourceDef, ResourceInfo, Router, Url};
 use actix_service::{
     AndThenNewService, ApplyNewService, IntoNewService, IntoNewTransform, NewService,
     NewTransform, Service,
 };
 use futures::future::{ok, Either, FutureResult};
-use futures::{Async, Future, IntoFuture, Poll};
+use futures::{Async, Future, Poll};
 
 use crate::helpers::{
     BoxedHttpNewService, BoxedHttpService, DefaultNewService, HttpDefaultNewService,
+    HttpDefaultService,
 };
+use crate::request::{HttpRequest, MatchedPattern};
 use crate::resource::Resource;
 use crate::service::{ServiceRequest, ServiceResponse};
-use crate::state::{State, StateFactory, StateFactoryResult};
+use crate::state::{Data, State, StateFactory, StateFactoryResult};
 
 type BoxedResponse = Box<Future<Item = ServiceResponse, Error = ()>>;
+type ExtensionFactory = Box<Fn(&mut Extensions)>;
+type ErrorRendererFn = Rc<Fn(Response, &str) -> Response>;
+type Fallbacks<P> = Rc<
+    Vec<(
+        String,
+        Rc<HttpDefaultNewService<ServiceRequest<P>, ServiceResponse>>,
+    )>,
+>;
 
 pub trait HttpServiceFactory<Request> {
     type Factory: NewService<Request = Request>;
@@ -29,13 +41,107 @@ pub trait HttpServiceFactory<Request> {
     fn create(self) -> Self::Factory;
 }
 
+/// Registered route description, see `App::routes()`
+#[derive(Clone, Debug)]
+pub struct RouteInfo {
+    pattern: String,
+    methods: Vec<Method>,
+}
+
+impl RouteInfo {
+    /// Resource pattern of the route
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Methods of the route, empty if route accepts any method
+    pub fn methods(&self) -> &[Method] {
+        &self.methods
+    }
+}
+
+impl fmt::Display for RouteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.methods.is_empty() {
+            write!(f, "* {}", self.pattern)
+        } else {
+            let methods: Vec<_> = self.methods.iter().map(|m| m.as_str()).collect();
+            write!(f, "{} {}", methods.join(","), self.pattern)
+        }
+    }
+}
+
+/// Handling of requests with multiple `Host` headers, see
+/// `App::duplicate_host()`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicateHost {
+    /// Reject request with *400 Bad Request* response
+    Reject,
+    /// Use the first `Host` header
+    UseFirst,
+}
+
+/// Application's route table, available in application extensions
+struct RouteTable(Vec<RouteInfo>);
+
+/// Renderer of extraction errors, available in application extensions
+pub(crate) struct ErrorRenderer(ErrorRendererFn);
+
+impl ErrorRenderer {
+    pub(crate) fn render(&self, err: Error) -> Response {
+        let message = err.to_string();
+        let res: Response = err.into();
+        let headers = res.headers().clone();
+
+        // keep headers of the extraction error, i.e. `WWW-Authenticate`,
+        // unless renderer replaced them
+        let mut res = (self.0)(res, &message);
+        for (name, value) in headers.iter() {
+            if name != header::CONTENT_TYPE
+                && name != header::CONTENT_LENGTH
+                && !res.headers().contains_key(name)
+            {
+                res.headers_mut().append(name.clone(), value.clone());
+            }
+        }
+        res
+    }
+}
+
+/// Convert trailing `{name:*}` segment to a pattern that matches the rest
+/// of the path
+fn subtree_pattern(path: &str) -> Option<String> {
+    if !path.ends_with(":*}") {
+        return None;
+    }
+    path.rfind('{').map(|idx| {
+        let name = &path[idx + 1..path.len() - 3];
+        format!("{}{{{}:.*}}", &path[..idx], name)
+    })
+}
+
 /// Application builder
 pub struct App<P, B, T> {
     services: Vec<(
         ResourceDef,
+        Rc<String>,
         BoxedHttpNewService<ServiceRequest<P>, ServiceResponse>,
     )>,
+    subtrees: Vec<(
+        ResourceDef,
+        Rc<String>,
+        BoxedHttpNewService<ServiceRequest<P>, ServiceResponse>,
+    )>,
+    routes: Vec<RouteInfo>,
+    duplicate_host: DuplicateHost,
+    strict_headers: bool,
+    not_found_hints: bool,
+    error_renderer: Option<ErrorRendererFn>,
     default: Option<Rc<HttpDefaultNewService<ServiceRequest<P>, ServiceResponse>>>,
+    prefix_defaults: Vec<(
+        String,
+        Rc<HttpDefaultNewService<ServiceRequest<P>, ServiceResponse>>,
+    )>,
     defaults: Vec<
         Rc<
             RefCell<
@@ -46,6 +152,7 @@ pub struct App<P, B, T> {
     endpoint: T,
     factory_ref: Rc<RefCell<Option<AppFactory<P>>>>,
     extensions: Extensions,
+    request_extensions: Vec<ExtensionFactory>,
     state: Vec<Box<StateFactory>>,
     _t: PhantomData<(P, B)>,
 }
@@ -82,16 +189,36 @@ impl App<PayloadStream, Body, AppEntry<PayloadStream>> {
         self
     }
 
+    /// Register application data.
+    ///
+    /// Data is extracted by its type with `Data<T>` extractor, values of
+    /// different types could be registered. Application instance is
+    /// constructed for each thread, use `App::register_data()` to share one
+    /// value between all threads.
+    pub fn data<T: 'static>(self, data: T) -> Self {
+        self.register_data(Data::new(data))
+    }
+
+    /// Register shared application data.
+    ///
+    /// Same as `App::data()`, but accepts already constructed `Data<T>`,
+    /// all clones of it share the same value.
+    pub fn register_data<T: 'static>(mut self, data: Data<T>) -> Self {
+        self.state.push(Box::new(data));
+        self
+    }
+
     /// Set application state. This function is
     /// similar to `.state()` but it accepts state factory. State get
     /// constructed asynchronously during application initialization.
-    pub fn state_factory<S, F, Out>(mut self, state: F) -> Self
+    ///
+    /// Failed construction stops application initialization, use
+    /// `StateFactory::with_retries()` to retry construction on failure.
+    pub fn state_factory<F>(mut self, state: F) -> Self
     where
-        F: Fn() -> Out + 'static,
-        Out: IntoFuture + 'static,
-        Out::Error: std::fmt::Debug,
+        F: StateFactory + 'static,
     {
-        self.state.push(Box::new(State::new(state)));
+        self.state.push(Box::new(state));
         self
     }
 
@@ -99,11 +226,19 @@ impl App<PayloadStream, Body, AppEntry<PayloadStream>> {
         let fref = Rc::new(RefCell::new(None));
         App {
             services: Vec::new(),
+            subtrees: Vec::new(),
+            routes: Vec::new(),
+            duplicate_host: DuplicateHost::Reject,
+            strict_headers: false,
+            not_found_hints: false,
+            error_renderer: None,
             default: None,
+            prefix_defaults: Vec::new(),
             defaults: Vec::new(),
             endpoint: AppEntry::new(fref.clone()),
             factory_ref: fref,
             extensions: Extensions::new(),
+            request_extensions: Vec::new(),
             state: Vec::new(),
             _t: PhantomData,
         }
@@ -153,6 +288,12 @@ where
     ///
     /// You can also specify a custom regex in the form `{identifier:regex}`:
     ///
+    /// Trailing segment in the form `{identifier:*}` mounts resource for the
+    /// whole sub-tree, i.e. `/proxy/{tail:*}` matches `/proxy/a/b` and the rest
+    /// of the path is available as `tail`. Sub-tree resources have lowest
+    /// precedence, more specific resources are matched first regardless of
+    /// registration order.
+    ///
     /// For instance, to route `GET`-requests on any route matching
     /// `/users/{userid}/{friend}` and store `userid` and `friend` in
     /// the exposed `Params` object:
@@ -178,20 +319,34 @@ where
                 InitError = (),
             > + 'static,
     {
-        let rdef = ResourceDef::new(path);
+        let subtree = subtree_pattern(path);
+        let rdef =
+            ResourceDef::new(subtree.as_ref().map(|s| s.as_str()).unwrap_or(path));
         let resource = f(Resource::new());
+        for methods in resource.route_methods() {
+            self.routes.push(RouteInfo {
+                pattern: path.to_owned(),
+                methods,
+            });
+        }
         self.defaults.push(resource.get_default());
-        self.services.push((
-            rdef,
-            Box::new(HttpNewService::new(resource.into_new_service())),
-        ));
+        let service: BoxedHttpNewService<_, _> =
+            Box::new(HttpNewService::new(resource.into_new_service()));
+        let pattern = Rc::new(path.to_owned());
+        if subtree.is_some() {
+            self.subtrees.push((rdef, pattern, service));
+        } else {
+            self.services.push((rdef, pattern, service));
+        }
         self
     }
 
     /// Default resource to be used if no matching route could be found.
     ///
-    /// Default resource works with resources only and does not work with
-    /// custom services.
+    /// Default resource handles requests with unmatched paths, and
+    /// requests to resources without own default resource if none of the
+    /// resource's routes matches. Default resource works with resources only
+    /// and does not work with custom services.
     pub fn default_resource<F, R, U>(mut self, f: F) -> Self
     where
         F: FnOnce(Resource<P>) -> R,
@@ -210,6 +365,47 @@ where
         self
     }
 
+    /// Default resource for unmatched paths under the prefix.
+    ///
+    /// Prefix defaults are consulted before global default resource,
+    /// longest matching prefix wins, i.e. `/api/v1` default is used for
+    /// `/api/v1/users` even if `/api` default is registered too. Prefix
+    /// matches whole path segments only, `/api` prefix matches `/api` and
+    /// `/api/users`, but not `/apiary`.
+    ///
+    /// ```rust,ignore
+    /// # extern crate actix_web2;
+    /// use actix_web2::{App, HttpResponse};
+    ///
+    /// fn main() {
+    ///     let app = App::new()
+    ///         .default_resource_for("/api", |r| {
+    ///             r.to(|| HttpResponse::NotFound().content_type("application/json").body("{}"))
+    ///         })
+    ///         .default_resource(|r| {
+    ///             r.to(|| HttpResponse::NotFound().content_type("text/html").body("404"))
+    ///         });
+    /// }
+    /// ```
+    pub fn default_resource_for<F, R, U>(mut self, prefix: &str, f: F) -> Self
+    where
+        F: FnOnce(Resource<P>) -> R,
+        R: IntoNewService<U>,
+        U: NewService<
+                Request = ServiceRequest<P>,
+                Response = ServiceResponse,
+                Error = (),
+            > + 'static,
+    {
+        self.prefix_defaults.push((
+            prefix.trim_end_matches('/').to_owned(),
+            Rc::new(Box::new(DefaultNewService::new(
+                f(Resource::new()).into_new_service(),
+            ))),
+        ));
+        self
+    }
+
     /// Register resource handler service.
     pub fn service<R, F, U>(mut self, rdef: R, factory: F) -> Self
     where
@@ -221,13 +417,62 @@ where
                 Error = (),
             > + 'static,
     {
+        let rdef = rdef.into();
+        self.routes.push(RouteInfo {
+            pattern: rdef.pattern().to_owned(),
+            methods: Vec::new(),
+        });
+        let pattern = Rc::new(rdef.pattern().to_owned());
         self.services.push((
-            rdef.into(),
+            rdef,
+            pattern,
             Box::new(HttpNewService::new(factory.into_new_service())),
         ));
         self
     }
 
+    /// Registered routes, in registration order.
+    ///
+    /// Each route of a resource is listed separately, services registered
+    /// with `App::service()` are listed as routes that accept any method.
+    ///
+    /// ```rust,ignore
+    /// # extern crate actix_web2;
+    /// use actix_web2::{App, HttpResponse};
+    ///
+    /// fn main() {
+    ///     let app = App::new()
+    ///         .resource("/index.html", |r| r.get(|| HttpResponse::Ok()));
+    ///
+    ///     for route in app.routes() {
+    ///         log::info!("route: {}", route);
+    ///     }
+    /// }
+    /// ```
+    pub fn routes(&self) -> &[RouteInfo] {
+        &self.routes
+    }
+
+    /// Register debug endpoint that renders application's route table.
+    ///
+    /// Endpoint responds to `GET` requests with `text/plain` table,
+    /// one route per line, i.e. `GET /index.html`. Table includes all routes
+    /// registered with the application, including routes registered after
+    /// the endpoint.
+    pub fn route_table(self, path: &str) -> Self {
+        self.resource(path, |r| {
+            r.get(|req: HttpRequest| {
+                let mut body = String::new();
+                if let Some(table) = req.app_extensions().get::<RouteTable>() {
+                    for route in table.0.iter() {
+                        body.push_str(&format!("{}\n", route));
+                    }
+                }
+                Response::Ok().content_type("text/plain").body(body)
+            })
+        })
+    }
+
     /// Register a middleware.
     pub fn middleware<M, B1, F>(
         self,
@@ -258,14 +503,102 @@ where
             endpoint,
             state: self.state,
             services: self.services,
+            subtrees: self.subtrees,
+            routes: self.routes,
+            duplicate_host: self.duplicate_host,
+            strict_headers: self.strict_headers,
+            not_found_hints: self.not_found_hints,
+            error_renderer: self.error_renderer,
             default: self.default,
+            prefix_defaults: self.prefix_defaults,
             defaults: Vec::new(),
             factory_ref: self.factory_ref,
             extensions: Extensions::new(),
+            request_extensions: self.request_extensions,
             _t: PhantomData,
         }
     }
 
+    /// Seed extensions of every request with a clone of the value.
+    ///
+    /// Value is available to handlers via `ReqData<T>` extractor and
+    /// to middlewares via `HttpRequest::extensions()`. This is useful for
+    /// small immutable per-process context, i.e. build version or region.
+    pub fn extension<T: Clone + 'static>(mut self, value: T) -> Self {
+        self.request_extensions
+            .push(Box::new(move |ext: &mut Extensions| {
+                ext.insert(value.clone());
+            }));
+        self
+    }
+
+    /// Set handling of requests with multiple `Host` headers.
+    ///
+    /// Multiple `Host` headers are a sign of request smuggling or host
+    /// spoofing, so by default such requests are rejected before routing
+    /// with *400 Bad Request* response. With `DuplicateHost::UseFirst`
+    /// the first `Host` header is used.
+    pub fn duplicate_host(mut self, handling: DuplicateHost) -> Self {
+        self.duplicate_host = handling;
+        self
+    }
+
+    /// Reject requests with non-visible characters in header values.
+    ///
+    /// Header names are allowed to contain only letters, digits and hyphens,
+    /// so names like `x_forwarded_for` can't be confused with their hyphenated
+    /// form. Header values are allowed to contain only visible ASCII
+    /// characters, spaces and tabs, so obsolete text and control characters
+    /// never reach handlers. Offending requests are rejected before routing
+    /// with *400 Bad Request* response. Strict mode is disabled by default.
+    pub fn strict_headers(mut self, enabled: bool) -> Self {
+        self.strict_headers = enabled;
+        self
+    }
+
+    /// Set renderer of handler argument extraction errors.
+    ///
+    /// Renderer gets response and message of the extraction error, i.e.
+    /// *413 Payload Too Large* if request body exceeds payload limit, so all
+    /// rejected requests could get the same error shape. Extractor's own
+    /// error handler is applied first, renderer gets its result and could
+    /// return it unchanged. Headers of the extraction error response, i.e.
+    /// `WWW-Authenticate`, are kept unless renderer sets them, except
+    /// `Content-Type` and `Content-Length`.
+    ///
+    /// ```rust,ignore
+    /// # extern crate actix_web2;
+    /// use actix_web2::{App, HttpResponse};
+    ///
+    /// fn main() {
+    ///     let app = App::new()
+    ///         .error_renderer(|res, message| {
+    ///             HttpResponse::build(res.status())
+    ///                 .content_type("application/json")
+    ///                 .body(format!("{{\"error\":{:?}}}", message))
+    ///         })
+    ///         .resource("/upload", |r| r.post(|body: String| body));
+    /// }
+    /// ```
+    pub fn error_renderer<F>(mut self, f: F) -> Self
+    where
+        F: Fn(Response, &str) -> Response + 'static,
+    {
+        self.error_renderer = Some(Rc::new(f));
+        self
+    }
+
+    /// List closest registered patterns in *404 Not Found* responses.
+    ///
+    /// Patterns are selected by the number of leading path segments that
+    /// match the request path. This is a debugging aid, it exposes route
+    /// table to the clients, so it is disabled by default and should not be
+    /// enabled in production.
+    pub fn not_found_hints(mut self, enabled: bool) -> Self {
+        self.not_found_hints = enabled;
+        self
+    }
+
     /// Register an external resource.
     ///
     /// External resources are useful for URL generation purposes only
@@ -313,7 +646,22 @@ where
         InitError = (),
     >,
 {
-    fn into_new_service(self) -> AndThenNewService<AppStateFactory<P>, T> {
+    fn into_new_service(mut self) -> AndThenNewService<AppStateFactory<P>, T> {
+        // route table for debug endpoint
+        let routes = std::mem::replace(&mut self.routes, Vec::new());
+        let hints = if self.not_found_hints {
+            let mut patterns: Vec<_> =
+                routes.iter().map(|r| r.pattern.clone()).collect();
+            patterns.dedup();
+            Some(Rc::new(patterns))
+        } else {
+            None
+        };
+        self.extensions.insert(RouteTable(routes));
+        if let Some(renderer) = self.error_renderer.take() {
+            self.extensions.insert(ErrorRenderer(renderer));
+        }
+
         // update resource default service
         if self.default.is_some() {
             for default in &self.defaults {
@@ -323,14 +671,30 @@ where
             }
         }
 
+        // sub-tree resources are matched last
+        let subtrees = std::mem::replace(&mut self.subtrees, Vec::new());
+        self.services.extend(subtrees);
+
+        // longest prefix goes first, global default is the last resort
+        let mut fallbacks = std::mem::replace(&mut self.prefix_defaults, Vec::new());
+        fallbacks.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+        if let Some(ref default) = self.default {
+            fallbacks.push((String::new(), default.clone()));
+        }
+
         // set factory
         *self.factory_ref.borrow_mut() = Some(AppFactory {
             services: Rc::new(self.services),
+            fallbacks: Rc::new(fallbacks),
+            duplicate_host: self.duplicate_host,
+            strict_headers: self.strict_headers,
+            hints,
         });
 
         AppStateFactory {
             state: self.state,
             extensions: Rc::new(RefCell::new(Rc::new(self.extensions))),
+            request_extensions: Rc::new(self.request_extensions),
             _t: PhantomData,
         }
         .and_then(self.endpoint)
@@ -341,6 +705,7 @@ where
 pub struct AppStateFactory<P> {
     state: Vec<Box<StateFactory>>,
     extensions: Rc<RefCell<Rc<Extensions>>>,
+    request_extensions: Rc<Vec<ExtensionFactory>>,
     _t: PhantomData<P>,
 }
 
@@ -356,6 +721,7 @@ impl<P: 'static> NewService for AppStateFactory<P> {
         AppStateFactoryResult {
             state: self.state.iter().map(|s| s.construct()).collect(),
             extensions: self.extensions.clone(),
+            request_extensions: self.request_extensions.clone(),
             _t: PhantomData,
         }
     }
@@ -365,6 +731,7 @@ impl<P: 'static> NewService for AppStateFactory<P> {
 pub struct AppStateFactoryResult<P> {
     state: Vec<Box<StateFactoryResult>>,
     extensions: Rc<RefCell<Rc<Extensions>>>,
+    request_extensions: Rc<Vec<ExtensionFactory>>,
     _t: PhantomData<P>,
 }
 
@@ -391,6 +758,7 @@ impl<P> Future for AppStateFactoryResult<P> {
 
         Ok(Async::Ready(AppStateService {
             extensions: self.extensions.borrow().clone(),
+            request_extensions: self.request_extensions.clone(),
             _t: PhantomData,
         }))
     }
@@ -399,6 +767,7 @@ impl<P> Future for AppStateFactoryResult<P> {
 /// Service to convert `Request` to a `ServiceRequest<S>`
 pub struct AppStateService<P> {
     extensions: Rc<Extensions>,
+    request_extensions: Rc<Vec<ExtensionFactory>>,
     _t: PhantomData<P>,
 }
 
@@ -413,11 +782,18 @@ impl<P> Service for AppStateService<P> {
     }
 
     fn call(&mut self, req: Request<P>) -> Self::Future {
-        ok(ServiceRequest::new(
+        let req = ServiceRequest::new(
             Path::new(Url::new(req.uri().clone())),
             req,
             self.extensions.clone(),
-        ))
+        );
+        if !self.request_extensions.is_empty() {
+            let mut ext = req.extensions_mut();
+            for f in self.request_extensions.iter() {
+                f(&mut ext);
+            }
+        }
+        ok(req)
     }
 }
 
@@ -425,9 +801,14 @@ pub struct AppFactory<P> {
     services: Rc<
         Vec<(
             ResourceDef,
+            Rc<String>,
             BoxedHttpNewService<ServiceRequest<P>, ServiceResponse>,
         )>,
     >,
+    fallbacks: Fallbacks<P>,
+    duplicate_host: DuplicateHost,
+    strict_headers: bool,
+    hints: Option<Rc<Vec<String>>>,
 }
 
 impl<P> NewService for AppFactory<P> {
@@ -443,13 +824,26 @@ impl<P> NewService for AppFactory<P> {
             fut: self
                 .services
                 .iter()
-                .map(|(path, service)| {
+                .map(|(path, pattern, service)| {
                     CreateAppServiceItem::Future(
-                        Some(path.clone()),
+                        Some((path.clone(), pattern.clone())),
                         service.new_service(),
                     )
                 })
                 .collect(),
+            fallbacks: self
+                .fallbacks
+                .iter()
+                .map(|(prefix, service)| {
+                    (
+                        prefix.clone(),
+                        CreateFallback::Future(service.new_service()),
+                    )
+                })
+                .collect(),
+            duplicate_host: self.duplicate_host,
+            strict_headers: self.strict_headers,
+            hints: self.hints.clone(),
         }
     }
 }
@@ -461,12 +855,25 @@ type HttpServiceFut<P> =
 #[doc(hidden)]
 pub struct CreateAppService<P> {
     fut: Vec<CreateAppServiceItem<P>>,
+    fallbacks: Vec<(String, CreateFallback<P>)>,
+    duplicate_host: DuplicateHost,
+    strict_headers: bool,
+    hints: Option<Rc<Vec<String>>>,
+}
+
+type HttpDefaultServiceFut<P> = Box<
+    Future<Item = HttpDefaultService<ServiceRequest<P>, ServiceResponse>, Error = ()>,
+>;
+
+enum CreateFallback<P> {
+    Future(HttpDefaultServiceFut<P>),
+    Service(HttpDefaultService<ServiceRequest<P>, ServiceResponse>),
 }
 
 enum CreateAppServiceItem<P> {
-    Future(Option<ResourceDef>, HttpServiceFut<P>),
+    Future(Option<(ResourceDef, Rc<String>)>, HttpServiceFut<P>),
     Service(
-        ResourceDef,
+        (ResourceDef, Rc<String>),
         BoxedHttpService<ServiceRequest<P>, ServiceResponse>,
     ),
 }
@@ -498,14 +905,40 @@ impl<P> Future for CreateAppService<P> {
             }
         }
 
+        // poll default services
+        for (_, item) in &mut self.fallbacks {
+            let res = match item {
+                CreateFallback::Future(ref mut fut) => match fut.poll()? {
+                    Async::Ready(service) => Some(service),
+                    Async::NotReady => {
+                        done = false;
+                        None
+                    }
+                },
+                CreateFallback::Service(_) => continue,
+            };
+
+            if let Some(service) = res {
+                *item = CreateFallback::Service(service);
+            }
+        }
+
         if done {
+            let fallbacks = self
+                .fallbacks
+                .drain(..)
+                .map(|(prefix, item)| match item {
+                    CreateFallback::Service(service) => (prefix, service),
+                    CreateFallback::Future(_) => unreachable!(),
+                })
+                .collect();
             let router = self
                 .fut
                 .drain(..)
                 .fold(Router::build(), |mut router, item| {
                     match item {
-                        CreateAppServiceItem::Service(path, service) => {
-                            router.rdef(path, service)
+                        CreateAppServiceItem::Service((path, pattern), service) => {
+                            router.rdef(path, (pattern, service))
                         }
                         CreateAppServiceItem::Future(_, _) => unreachable!(),
                     }
@@ -513,7 +946,11 @@ impl<P> Future for CreateAppService<P> {
                 });
             Ok(Async::Ready(AppService {
                 router: router.finish(),
+                fallbacks,
                 ready: None,
+                duplicate_host: self.duplicate_host,
+                strict_headers: self.strict_headers,
+                hints: self.hints.take(),
             }))
         } else {
             Ok(Async::NotReady)
@@ -522,8 +959,18 @@ impl<P> Future for CreateAppService<P> {
 }
 
 pub struct AppService<P> {
-    router: Router<BoxedHttpService<ServiceRequest<P>, ServiceResponse>>,
+    router: Router<(
+        Rc<String>,
+        BoxedHttpService<ServiceRequest<P>, ServiceResponse>,
+    )>,
+    fallbacks: Vec<(
+        String,
+        HttpDefaultService<ServiceRequest<P>, ServiceResponse>,
+    )>,
     ready: Option<(ServiceRequest<P>, ResourceInfo)>,
+    duplicate_host: DuplicateHost,
+    strict_headers: bool,
+    hints: Option<Rc<Vec<String>>>,
 }
 
 impl<P> Service for AppService<P> {
@@ -541,13 +988,99 @@ impl<P> Service for AppService<P> {
     }
 
     fn call(&mut self, mut req: ServiceRequest<P>) -> Self::Future {
-        if let Some((srv, _info)) = self.router.recognize_mut(req.match_info_mut()) {
+        if self.duplicate_host == DuplicateHost::Reject
+            && req.headers().get_all(header::HOST).iter().count() > 1
+        {
+            let req = req.into_request();
+            return Either::B(ok(ServiceResponse::new(
+                req,
+                Response::BadRequest().finish(),
+            )));
+        }
+
+        if self.strict_headers && !valid_headers(req.headers()) {
+            let req = req.into_request();
+            return Either::B(ok(ServiceResponse::new(
+                req,
+                Response::BadRequest().finish(),
+            )));
+        }
+
+        if let Some(((pattern, srv), _info)) =
+            self.router.recognize_mut(req.match_info_mut())
+        {
+            req.extensions_mut().insert(MatchedPattern(pattern.clone()));
+            Either::A(srv.call(req))
+        } else if let Some((_, srv)) = self
+            .fallbacks
+            .iter_mut()
+            .find(|(prefix, _)| prefix_matches(prefix, req.path()))
+        {
             Either::A(srv.call(req))
         } else {
             let req = req.into_request();
-            Either::B(ok(ServiceResponse::new(req, Response::NotFound().finish())))
+            let res = match self.hints {
+                Some(ref hints) => not_found_hints(req.path(), hints),
+                None => Response::NotFound().finish(),
+            };
+            Either::B(ok(ServiceResponse::new(req, res)))
+        }
+    }
+}
+
+/// Check that path is the prefix or lies under it, empty prefix matches
+/// any path
+fn prefix_matches(prefix: &str, path: &str) -> bool {
+    path.starts_with(prefix)
+        && (path.len() == prefix.len() || path[prefix.len()..].starts_with('/'))
+}
+
+/// Check that header names contain only alphanumerics and hyphens, and
+/// header values contain only visible ASCII characters, spaces and tabs
+fn valid_headers(headers: &HeaderMap) -> bool {
+    headers.iter().all(|(name, value)| {
+        name.as_str()
+            .bytes()
+            .all(|b| b == b'-' || b.is_ascii_alphanumeric())
+            && value
+                .as_bytes()
+                .iter()
+                .all(|b| *b == b'\t' || (*b >= 0x20 && *b < 0x7f))
+    })
+}
+
+/// *404 Not Found* response that lists closest registered patterns
+fn not_found_hints(path: &str, patterns: &[String]) -> Response {
+    let segments: Vec<_> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    let mut best = 0;
+    let mut closest = Vec::new();
+    for pattern in patterns {
+        let score = pattern
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .zip(segments.iter())
+            .take_while(|(p, s)| p == *s || p.starts_with('{'))
+            .count();
+        if score > best {
+            best = score;
+            closest.clear();
+        }
+        if score > 0 && score == best {
+            closest.push(pattern.as_str());
         }
     }
+
+    if closest.is_empty() {
+        Response::NotFound().finish()
+    } else {
+        Response::NotFound()
+            .content_type("text/plain")
+            .body(format!(
+                "Not found, closest routes:\n{}\n",
+                closest.join("\n")
+            ))
+    }
 }
 
 pub struct AppServiceResponse(Box<Future<Item = ServiceResponse, Error = ()>>);
@@ -1,31 +1,48 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 use std::rc::Rc;
-use std::{fmt, str};
+use std::{cmp, fmt, str};
 
-use bytes::Bytes;
+use bytes::{BufMut, Bytes, BytesMut};
 use encoding::all::UTF_8;
 use encoding::types::{DecoderTrap, Encoding};
 use futures::future::{err, ok, Either, FutureResult};
-use futures::{future, Async, Future, IntoFuture, Poll, Stream};
+use futures::{future, stream, try_ready, Async, Future, IntoFuture, Poll, Stream};
+use hmac::{Hmac, Mac};
 use mime::Mime;
+use percent_encoding::percent_decode;
 use serde::de::{self, DeserializeOwned};
-use serde::Serialize;
+use serde::{forward_to_deserialize_any, Serialize};
 use serde_json;
+use serde_qs;
 use serde_urlencoded;
+use sha2::Sha256;
 
-use actix_http::dev::{JsonBody, MessageBody, UrlEncoded};
+use actix_http::dev::MessageBody;
+#[cfg(feature = "uuid")]
+use actix_http::error::ResponseError;
 use actix_http::error::{
-    Error, ErrorBadRequest, ErrorNotFound, JsonPayloadError, PayloadError,
-    UrlencodedError,
+    Error, ErrorBadRequest, ErrorInternalServerError, ErrorNotFound, ErrorUnauthorized,
+    InternalError, JsonPayloadError, PayloadError, UrlencodedError,
 };
-use actix_http::http::StatusCode;
-use actix_http::{HttpMessage, Response};
+use actix_http::http::header::{
+    ContentDisposition, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_DISPOSITION,
+    CONTENT_LENGTH, CONTENT_TYPE, COOKIE, FORWARDED, WWW_AUTHENTICATE,
+};
+use actix_http::http::{HeaderMap, HttpTryFrom, StatusCode};
+use actix_http::{HttpMessage, Payload as HttpPayload, PayloadStream, Response};
 use actix_router::PathDeserializer;
+#[cfg(feature = "uuid")]
+use uuid::Uuid;
 
 use crate::handler::FromRequest;
 use crate::request::HttpRequest;
-use crate::responder::Responder;
+use crate::responder::{Either3, Responder};
 use crate::service::ServiceRequest;
+use crate::state::State;
 
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
 /// Extract typed information from the request's path.
@@ -80,6 +97,24 @@ use crate::service::ServiceRequest;
 ///     ); // <- use `with` extractor
 /// }
 /// ```
+///
+/// If all path segments have the same type, path information could be
+/// extracted to a fixed-size array. Extraction fails with *404 Not Found*
+/// if number of matched segments does not match the length of the array.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{App, Path};
+///
+/// /// extract path info from "/{x}/{y}/{z}" url
+/// fn index(point: Path<[u32; 3]>) -> String {
+///     format!("Point {:?}", *point)
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource("/{x}/{y}/{z}", |r| r.get(index));
+/// }
+/// ```
 pub struct Path<T> {
     inner: T,
 }
@@ -115,11 +150,66 @@ impl<T> Path<T> {
     where
         T: DeserializeOwned,
     {
+        // arrays have to cover all matched segments
+        if let Some(len) = expected_array_len::<T>() {
+            if len != req.match_info().len() {
+                return Err(de::Error::custom(format!(
+                    "wrong number of parameters: {} expected {}",
+                    req.match_info().len(),
+                    len
+                )));
+            }
+        }
+
         de::Deserialize::deserialize(PathDeserializer::new(req.match_info()))
             .map(|inner| Path { inner })
     }
 }
 
+/// Length of the array `T`, `None` for other types including tuples
+fn expected_array_len<T: DeserializeOwned>() -> Option<usize> {
+    let len = Cell::new(None);
+    let _ = T::deserialize(ArrayLen(&len));
+    len.get()
+}
+
+/// Deserializer that records length of the requested array,
+/// deserialization itself always fails
+struct ArrayLen<'a>(&'a Cell<Option<usize>>);
+
+impl<'de, 'a> de::Deserializer<'de> for ArrayLen<'a> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V>(self, _: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(de::Error::custom("not an array"))
+    }
+
+    fn deserialize_tuple<V>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        // arrays and tuples are both deserialized as tuples,
+        // only visitor's expectation tells them apart
+        if format!("{}", &visitor as &de::Expected).starts_with("an array") {
+            self.0.set(Some(len));
+        }
+        Err(de::Error::custom("not an array"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
+        byte_buf option unit unit_struct newtype_struct seq tuple_struct
+        map struct enum identifier ignored_any
+    }
+}
+
 impl<T> From<T> for Path<T> {
     fn from(inner: T) -> Path<T> {
         Path { inner }
@@ -135,7 +225,9 @@ where
 
     #[inline]
     fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
-        Self::extract(req).map_err(ErrorNotFound).into_future()
+        Self::extract(req)
+            .map_err(|e| PathConfig::from_state(req).handle(ErrorNotFound(e), req))
+            .into_future()
     }
 }
 
@@ -151,286 +243,2264 @@ impl<T: fmt::Display> fmt::Display for Path<T> {
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
-/// Extract typed information from from the request's query.
+/// Path extractors configuration.
 ///
-/// ## Example
+/// Configuration could be registered as an application state with
+/// `App::state()` method, otherwise default configuration is used.
 ///
 /// ```rust,ignore
-/// # extern crate bytes;
-/// # extern crate actix_web;
-/// # extern crate futures;
-/// #[macro_use] extern crate serde_derive;
-/// use actix_web::{App, Query, http};
+/// # extern crate actix_http;
+/// # extern crate actix_web2;
+/// use actix_http::error::ErrorBadRequest;
+/// use actix_web2::dev::PathConfig;
+/// use actix_web2::App;
 ///
+/// fn main() {
+///     let mut cfg = PathConfig::default();
+///     cfg.error_handler(|_, req| ErrorBadRequest(format!("Invalid path {}", req.path())));
 ///
-///#[derive(Debug, Deserialize)]
-///pub enum ResponseType {
-///    Token,
-///    Code
-///}
+///     let app = App::new().state(cfg);
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct PathConfig {
+    ehandler: Option<Rc<Fn(Error, &HttpRequest) -> Error>>,
+}
+
+impl PathConfig {
+    /// Set custom error handler for path extractors.
+    ///
+    /// Handler receives error of the extractor, i.e. *404 Not Found* error
+    /// of `Path`, and returns error to respond with.
+    pub fn error_handler<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(Error, &HttpRequest) -> Error + 'static,
+    {
+        self.ehandler = Some(Rc::new(f));
+        self
+    }
+
+    /// Route configuration, configuration registered with `App::state()`
+    /// or default one
+    fn from_state<P>(req: &ServiceRequest<P>) -> Self {
+        if let Some(cfg) = req.route_config::<PathConfig>() {
+            cfg
+        } else if let Some(cfg) = req.app_extensions().get::<State<PathConfig>>() {
+            cfg.get_ref().clone()
+        } else {
+            PathConfig::default()
+        }
+    }
+
+    fn handle(&self, e: Error, req: &HttpRequest) -> Error {
+        match self.ehandler {
+            Some(ref handler) => handler(e, req),
+            None => e,
+        }
+    }
+}
+
+/// Extract UUID path parameter.
 ///
-///#[derive(Deserialize)]
-///pub struct AuthRequest {
-///    id: u64,
-///    response_type: ResponseType,
-///}
+/// Resource has to have a single path parameter. Unlike `Path<T>`,
+/// extraction fails with *400 Bad Request* if the parameter is not a valid
+/// UUID, response body describes the problem. Errors are passed to
+/// `PathConfig` error handler. Extractor is available with `uuid` feature.
 ///
-/// // use `with` extractor for query info
-/// // this handler get called only if request's query contains `username` field
-/// // The correct request for this handler would be `/index.html?id=64&response_type=Code"`
-/// fn index(info: Query<AuthRequest>) -> String {
-///     format!("Authorization request for client with id={} and type={:?}!", info.id, info.response_type)
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{App, PathUuid};
+///
+/// fn user(id: PathUuid) -> String {
+///     format!("User {}", *id)
 /// }
 ///
 /// fn main() {
-///     let app = App::new().resource(
-///        "/index.html",
-///        |r| r.method(http::Method::GET).with(index)); // <- use `with` extractor
+///     let app = App::new().resource("/users/{id}", |r| r.get(user));
 /// }
 /// ```
-pub struct Query<T>(T);
-
-impl<T> Deref for Query<T> {
-    type Target = T;
+#[cfg(feature = "uuid")]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct PathUuid(pub Uuid);
 
-    fn deref(&self) -> &T {
-        &self.0
+#[cfg(feature = "uuid")]
+impl PathUuid {
+    /// Deconstruct to an inner value
+    pub fn into_inner(self) -> Uuid {
+        self.0
     }
 }
 
-impl<T> DerefMut for Query<T> {
-    fn deref_mut(&mut self) -> &mut T {
-        &mut self.0
-    }
-}
+#[cfg(feature = "uuid")]
+impl Deref for PathUuid {
+    type Target = Uuid;
 
-impl<T> Query<T> {
-    /// Deconstruct to a inner value
-    pub fn into_inner(self) -> T {
-        self.0
+    fn deref(&self) -> &Uuid {
+        &self.0
     }
 }
 
-impl<T, P> FromRequest<P> for Query<T>
-where
-    T: de::DeserializeOwned,
-{
+#[cfg(feature = "uuid")]
+impl<P> FromRequest<P> for PathUuid {
     type Error = Error;
     type Future = FutureResult<Self, Error>;
 
     #[inline]
     fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
-        serde_urlencoded::from_str::<T>(req.query_string())
-            .map(|val| ok(Query(val)))
-            .unwrap_or_else(|e| err(e.into()))
+        let cfg = PathConfig::from_state(req);
+
+        let value = match Path::<String>::extract(req) {
+            Ok(value) => value.into_inner(),
+            Err(e) => return err(cfg.handle(ErrorNotFound(e), req)),
+        };
+        match Uuid::parse_str(&value) {
+            Ok(id) => ok(PathUuid(id)),
+            Err(e) => {
+                let e = InvalidUuid {
+                    value,
+                    cause: e.to_string(),
+                };
+                err(cfg.handle(e.into(), req))
+            }
+        }
     }
 }
 
-impl<T: fmt::Debug> fmt::Debug for Query<T> {
+#[cfg(feature = "uuid")]
+impl fmt::Debug for PathUuid {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.0.fmt(f)
     }
 }
 
-impl<T: fmt::Display> fmt::Display for Query<T> {
+#[cfg(feature = "uuid")]
+impl fmt::Display for PathUuid {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.0.fmt(f)
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
-/// Extract typed information from the request's body.
+/// Malformed UUID path parameter
+#[cfg(feature = "uuid")]
+#[derive(Debug)]
+struct InvalidUuid {
+    value: String,
+    cause: String,
+}
+
+#[cfg(feature = "uuid")]
+impl fmt::Display for InvalidUuid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid UUID {:?} in path: {}", self.value, self.cause)
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl ResponseError for InvalidUuid {
+    fn error_response(&self) -> Response {
+        Response::build(StatusCode::BAD_REQUEST)
+            .content_type("text/plain")
+            .body(self.to_string())
+    }
+}
+
+/// Extract percent-decoded request's path.
 ///
-/// To extract typed information from request's body, the type `T` must
-/// implement the `Deserialize` trait from *serde*.
+/// Unlike `Path<T>`, extractor returns whole request's path as a single
+/// string, query string is not included. Extraction fails with
+/// *400 Bad Request* if decoded path is not a valid utf-8 string.
 ///
-/// [**FormConfig**](dev/struct.FormConfig.html) allows to configure extraction
-/// process.
+/// **Note**: all percent-encoded octets get decoded, including encoded
+/// slashes. For example, `/a%2Fb/c` is decoded to `/a/b/c`, so a single
+/// segment of the original path could turn to several segments of the
+/// decoded path. Decoded path should not be used for access control or for
+/// building file system paths without additional validation.
 ///
 /// ## Example
 ///
 /// ```rust,ignore
-/// # extern crate actix_web;
-/// #[macro_use] extern crate serde_derive;
-/// use actix_web::{App, Form, Result};
+/// # extern crate actix_web2;
+/// use actix_web2::{App, DecodedPath};
 ///
-/// #[derive(Deserialize)]
-/// struct FormData {
-///     username: String,
+/// fn proxy(path: DecodedPath) -> String {
+///     format!("Forward to {}", path)
 /// }
 ///
-/// /// extract form data using serde
-/// /// this handler get called only if content type is *x-www-form-urlencoded*
-/// /// and content of the request could be deserialized to a `FormData` struct
-/// fn index(form: Form<FormData>) -> Result<String> {
-///     Ok(format!("Welcome {}!", form.username))
+/// fn main() {
+///     let app = App::new().resource("/{tail:.*}", |r| r.get(proxy));
 /// }
-/// # fn main() {}
 /// ```
-pub struct Form<T>(pub T);
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub struct DecodedPath(String);
 
-impl<T> Form<T> {
+impl DecodedPath {
     /// Deconstruct to an inner value
-    pub fn into_inner(self) -> T {
+    pub fn into_inner(self) -> String {
         self.0
     }
 }
 
-impl<T> Deref for Form<T> {
-    type Target = T;
+impl Deref for DecodedPath {
+    type Target = String;
 
-    fn deref(&self) -> &T {
+    fn deref(&self) -> &String {
         &self.0
     }
 }
 
-impl<T> DerefMut for Form<T> {
-    fn deref_mut(&mut self) -> &mut T {
-        &mut self.0
-    }
-}
-
-impl<T, P> FromRequest<P> for Form<T>
-where
-    T: DeserializeOwned + 'static,
-    P: Stream<Item = Bytes, Error = PayloadError> + 'static,
-{
+impl<P> FromRequest<P> for DecodedPath {
     type Error = Error;
-    type Future = Box<Future<Item = Self, Error = Error>>;
+    type Future = FutureResult<Self, Error>;
 
     #[inline]
     fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
-        let cfg = FormConfig::default();
-
-        let req2 = req.clone();
-        let err = Rc::clone(&cfg.ehandler);
-        Box::new(
-            UrlEncoded::new(req)
-                .limit(cfg.limit)
-                .map_err(move |e| (*err)(e, &req2))
-                .map(Form),
-        )
+        percent_decode(req.path().as_bytes())
+            .decode_utf8()
+            .map(|path| ok(DecodedPath(path.into_owned())))
+            .unwrap_or_else(|_| err(ErrorBadRequest("Can not decode path")))
     }
 }
 
-impl<T: fmt::Debug> fmt::Debug for Form<T> {
+impl fmt::Debug for DecodedPath {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.0.fmt(f)
     }
 }
 
-impl<T: fmt::Display> fmt::Display for Form<T> {
+impl fmt::Display for DecodedPath {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.0.fmt(f)
     }
 }
 
-/// Form extractor configuration
+/// Extract the wildcard tail of the matched path.
 ///
-/// ```rust,ignore
-/// # extern crate actix_web;
-/// #[macro_use] extern crate serde_derive;
-/// use actix_web::{http, App, Form, Result};
+/// Value of the last path parameter gets percent-decoded, resource is
+/// expected to end with a wildcard parameter, i.e. `/files/{tail:.*}` or
+/// `/files/{tail:*}`. Extraction fails with *404 Not Found* if resource
+/// has no parameters and with *400 Bad Request* if value can not be
+/// decoded.
 ///
-/// #[derive(Deserialize)]
-/// struct FormData {
-///     username: String,
-/// }
+/// ## Example
 ///
-/// /// extract form data using serde.
-/// /// custom configuration is used for this handler, max payload size is 4k
-/// fn index(form: Form<FormData>) -> Result<String> {
-///     Ok(format!("Welcome {}!", form.username))
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{App, Tail};
+///
+/// fn file(tail: Tail) -> String {
+///     format!("File {}", tail)
 /// }
 ///
 /// fn main() {
-///     let app = App::new().resource(
-///         "/index.html",
-///         |r| {
-///             r.method(http::Method::GET)
-///                 // register form handler and change form extractor configuration
-///                 .with_config(index, |cfg| {cfg.0.limit(4096);})
-///         },
-///     );
+///     let app = App::new().resource("/files/{tail:.*}", |r| r.get(file));
 /// }
 /// ```
-pub struct FormConfig {
-    limit: usize,
-    ehandler: Rc<Fn(UrlencodedError, &HttpRequest) -> Error>,
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct Tail(pub String);
+
+impl Tail {
+    /// Deconstruct to an inner value
+    pub fn into_inner(self) -> String {
+        self.0
+    }
 }
 
-impl FormConfig {
-    /// Change max size of payload. By default max size is 256Kb
-    pub fn limit(&mut self, limit: usize) -> &mut Self {
-        self.limit = limit;
-        self
+impl Deref for Tail {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
     }
+}
 
-    /// Set custom error handler
-    pub fn error_handler<F>(&mut self, f: F) -> &mut Self
-    where
-        F: Fn(UrlencodedError, &HttpRequest) -> Error + 'static,
-    {
-        self.ehandler = Rc::new(f);
-        self
+impl<P> FromRequest<P> for Tail {
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        let tail = match req.match_info().iter().last() {
+            Some((_, tail)) => tail,
+            None => return err(ErrorNotFound("Path tail is missing")),
+        };
+        percent_decode(tail.as_bytes())
+            .decode_utf8()
+            .map(|tail| ok(Tail(tail.into_owned())))
+            .unwrap_or_else(|_| err(ErrorBadRequest("Can not decode path tail")))
     }
 }
 
-impl Default for FormConfig {
-    fn default() -> Self {
-        FormConfig {
-            limit: 262_144,
-            ehandler: Rc::new(|e, _| e.into()),
-        }
+impl fmt::Debug for Tail {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
     }
 }
 
-/// Json helper
-///
-/// Json can be used for two different purpose. First is for json response
-/// generation and second is for extracting typed information from request's
-/// payload.
-///
-/// To extract typed information from request's body, the type `T` must
-/// implement the `Deserialize` trait from *serde*.
+impl fmt::Display for Tail {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Extract pattern of the matched resource.
 ///
-/// [**JsonConfig**](dev/struct.JsonConfig.html) allows to configure extraction
-/// process.
+/// Pattern is returned as it is registered, i.e. `/users/{id}`, see
+/// `HttpRequest::matched_path()`. Extraction fails with
+/// *500 Internal Server Error* outside of a matched resource, i.e. in
+/// a default resource.
 ///
 /// ## Example
 ///
 /// ```rust,ignore
-/// # extern crate actix_web;
-/// #[macro_use] extern crate serde_derive;
-/// use actix_web::{App, Json, Result, http};
-///
-/// #[derive(Deserialize)]
-/// struct Info {
-///     username: String,
-/// }
+/// # extern crate actix_web2;
+/// use actix_web2::{App, MatchedPath};
 ///
-/// /// deserialize `Info` from request's body
-/// fn index(info: Json<Info>) -> Result<String> {
-///     Ok(format!("Welcome {}!", info.username))
+/// fn user(path: MatchedPath) -> String {
+///     format!("Route {}", path)
 /// }
 ///
 /// fn main() {
-///     let app = App::new().resource(
-///        "/index.html",
-///        |r| r.method(http::Method::POST).with(index));  // <- use `with` extractor
+///     let app = App::new().resource("/users/{id}", |r| r.get(user));
 /// }
 /// ```
-///
-/// The `Json` type allows you to respond with well-formed JSON data: simply
-/// return a value of type Json<T> where T is the type of a structure
-/// to serialize into *JSON*. The type `T` must implement the `Serialize`
-/// trait from *serde*.
-///
-/// ```rust,ignore
-/// # extern crate actix_web;
-/// # #[macro_use] extern crate serde_derive;
-/// # use actix_web::*;
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct MatchedPath(pub String);
+
+impl MatchedPath {
+    /// Deconstruct to an inner value
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl Deref for MatchedPath {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<P> FromRequest<P> for MatchedPath {
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        match req.matched_path() {
+            Some(pattern) => ok(MatchedPath(pattern)),
+            None => err(ErrorInternalServerError(
+                "Request is not routed to a resource",
+            )),
+        }
+    }
+}
+
+impl fmt::Debug for MatchedPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for MatchedPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Extract the raw query string of the request.
+///
+/// Query string is returned exactly as it is received, without percent
+/// decoding and with original parameter order, i.e. for signature
+/// verification. Extraction never fails, request without query yields
+/// an empty string.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{App, RawQuery};
+///
+/// fn callback(query: RawQuery) -> String {
+///     format!("Query {}", &*query)
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource("/callback", |r| r.get(callback));
+/// }
+/// ```
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct RawQuery(pub String);
+
+impl RawQuery {
+    /// Deconstruct to an inner value
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl Deref for RawQuery {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<P> FromRequest<P> for RawQuery {
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        ok(RawQuery(req.query_string().to_owned()))
+    }
+}
+
+impl fmt::Debug for RawQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for RawQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+/// Extract typed information from from the request's query.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate bytes;
+/// # extern crate actix_web;
+/// # extern crate futures;
+/// #[macro_use] extern crate serde_derive;
+/// use actix_web::{App, Query, http};
+///
+///
+///#[derive(Debug, Deserialize)]
+///pub enum ResponseType {
+///    Token,
+///    Code
+///}
+///
+///#[derive(Deserialize)]
+///pub struct AuthRequest {
+///    id: u64,
+///    response_type: ResponseType,
+///}
+///
+/// // use `with` extractor for query info
+/// // this handler get called only if request's query contains `username` field
+/// // The correct request for this handler would be `/index.html?id=64&response_type=Code"`
+/// fn index(info: Query<AuthRequest>) -> String {
+///     format!("Authorization request for client with id={} and type={:?}!", info.id, info.response_type)
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource(
+///        "/index.html",
+///        |r| r.method(http::Method::GET).with(index)); // <- use `with` extractor
+/// }
+/// ```
+pub struct Query<T>(T);
+
+impl<T> Deref for Query<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Query<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> Query<T> {
+    /// Deconstruct to a inner value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T, P> FromRequest<P> for Query<T>
+where
+    T: de::DeserializeOwned,
+{
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        let cfg = QueryConfig::from_state(req);
+        if let Err(e) = cfg.check(req.query_string()) {
+            return err(cfg.handle(e, req));
+        }
+        if let Some(val) = cfg.empty_default::<T>(req) {
+            return ok(Query(val));
+        }
+        serde_urlencoded::from_str::<T>(req.query_string())
+            .map(|val| ok(Query(val)))
+            .unwrap_or_else(|e| err(cfg.handle(e.into(), req)))
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Query<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Query<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Query extractors configuration.
+///
+/// Number of query parameters is checked before query gets deserialized,
+/// requests with more parameters than allowed are rejected with
+/// *400 Bad Request*. By default up to 1000 parameters are allowed.
+/// Configuration is used by `Query` and `QsQuery` extractors.
+///
+/// With `QueryConfig::default_if_empty()` enabled, fields of `T` get zero
+/// values, i.e. `0`, `false`, `None`, empty strings and collections, if
+/// request has no query string or query string is empty, i.e. `/search` or
+/// `/search?`. For types deriving `Default` that is `T::default()`.
+/// Non-empty query is deserialized as usual, so missing required fields is
+/// still an error.
+///
+/// ```rust,ignore
+/// # extern crate actix_http;
+/// # extern crate actix_web2;
+/// use actix_http::error::ErrorUnprocessableEntity;
+/// use actix_web2::dev::QueryConfig;
+/// use actix_web2::App;
+///
+/// fn main() {
+///     let mut cfg = QueryConfig::default();
+///     cfg.max_params(32)
+///         .default_if_empty(true)
+///         .error_handler(|e, _| ErrorUnprocessableEntity(e));
+///
+///     let app = App::new().state(cfg);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct QueryConfig {
+    max_params: usize,
+    default_if_empty: bool,
+    ehandler: Option<Rc<Fn(Error, &HttpRequest) -> Error>>,
+}
+
+impl QueryConfig {
+    /// Set maximum number of query parameters, default is 1000
+    pub fn max_params(&mut self, max: usize) -> &mut Self {
+        self.max_params = max;
+        self
+    }
+
+    /// Use zero values for absent or empty query string, disabled by default
+    pub fn default_if_empty(&mut self, enabled: bool) -> &mut Self {
+        self.default_if_empty = enabled;
+        self
+    }
+
+    /// Set custom error handler.
+    ///
+    /// Handler receives errors of the query extractors, i.e. deserialization
+    /// error, and returns error to respond with.
+    pub fn error_handler<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(Error, &HttpRequest) -> Error + 'static,
+    {
+        self.ehandler = Some(Rc::new(f));
+        self
+    }
+
+    /// Route configuration, configuration registered with `App::state()`
+    /// or default one
+    fn from_state<P>(req: &ServiceRequest<P>) -> Self {
+        if let Some(cfg) = req.route_config::<QueryConfig>() {
+            cfg
+        } else if let Some(cfg) = req.app_extensions().get::<State<QueryConfig>>() {
+            cfg.get_ref().clone()
+        } else {
+            QueryConfig::default()
+        }
+    }
+
+    fn check(&self, query: &str) -> Result<(), Error> {
+        let params = query.split('&').filter(|p| !p.is_empty()).count();
+        if params > self.max_params {
+            Err(ErrorBadRequest(format!(
+                "Too many query parameters, at most {} are allowed",
+                self.max_params
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn empty_default<T: DeserializeOwned>(&self, req: &HttpRequest) -> Option<T> {
+        if self.default_if_empty && req.query_string().is_empty() {
+            T::deserialize(ZeroValue).ok()
+        } else {
+            None
+        }
+    }
+
+    fn handle(&self, e: Error, req: &HttpRequest) -> Error {
+        match self.ehandler {
+            Some(ref handler) => handler(e, req),
+            None => e,
+        }
+    }
+}
+
+impl Default for QueryConfig {
+    fn default() -> Self {
+        QueryConfig {
+            max_params: 1000,
+            default_if_empty: false,
+            ehandler: None,
+        }
+    }
+}
+
+/// Deserializer that produces zero value of the requested type,
+/// structs get zero values for all of their fields
+#[derive(Clone, Copy)]
+struct ZeroValue;
+
+macro_rules! zero_value {
+    ($($method:ident => $visit:ident($val:expr),)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: de::Visitor<'de>,
+            {
+                visitor.$visit($val)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ZeroValue {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    zero_value! {
+        deserialize_bool => visit_bool(false),
+        deserialize_i8 => visit_i8(0),
+        deserialize_i16 => visit_i16(0),
+        deserialize_i32 => visit_i32(0),
+        deserialize_i64 => visit_i64(0),
+        deserialize_u8 => visit_u8(0),
+        deserialize_u16 => visit_u16(0),
+        deserialize_u32 => visit_u32(0),
+        deserialize_u64 => visit_u64(0),
+        deserialize_f32 => visit_f32(0.0),
+        deserialize_f64 => visit_f64(0.0),
+        deserialize_char => visit_char('\0'),
+        deserialize_str => visit_str(""),
+        deserialize_string => visit_str(""),
+        deserialize_bytes => visit_bytes(&[]),
+        deserialize_byte_buf => visit_bytes(&[]),
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_none()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_tuple(0, visitor)
+    }
+
+    fn deserialize_tuple<V>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let items = std::iter::repeat(self).take(len);
+        visitor.visit_seq(de::value::SeqDeserializer::new(items))
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let entries = std::iter::empty::<(ZeroValue, ZeroValue)>();
+        visitor.visit_map(de::value::MapDeserializer::new(entries))
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let entries = fields.iter().map(|field| (*field, self));
+        visitor.visit_map(de::value::MapDeserializer::new(entries))
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(de::Error::custom("enums have no zero value"))
+    }
+
+    forward_to_deserialize_any! {
+        unit unit_struct identifier ignored_any
+    }
+}
+
+impl<'de> de::IntoDeserializer<'de> for ZeroValue {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Extract typed information from the request's query with support of
+/// nested, bracketed parameters.
+///
+/// Unlike `Query`, query is parsed with *serde_qs*, so parameters like
+/// `filter[name]=x&filter[age]=30` get deserialized into nested structs
+/// and `ids[0]=1&ids[1]=2` into sequences. Percent-encoded brackets are
+/// accepted as well. Extraction fails with *400 Bad Request* if query can
+/// not be deserialized.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// #[macro_use] extern crate serde_derive;
+/// use actix_web2::{App, QsQuery};
+///
+/// #[derive(Deserialize)]
+/// struct Filter {
+///     name: String,
+///     age: u32,
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct Search {
+///     filter: Filter,
+/// }
+///
+/// // request could be `/users?filter[name]=x&filter[age]=30`
+/// fn index(search: QsQuery<Search>) -> String {
+///     format!("{} {}", search.filter.name, search.filter.age)
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource("/users", |r| r.get(index));
+/// }
+/// ```
+pub struct QsQuery<T>(T);
+
+impl<T> Deref for QsQuery<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for QsQuery<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> QsQuery<T> {
+    /// Deconstruct to a inner value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T, P> FromRequest<P> for QsQuery<T>
+where
+    T: de::DeserializeOwned,
+{
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        let cfg = QueryConfig::from_state(req);
+        if let Err(e) = cfg.check(req.query_string()) {
+            return err(cfg.handle(e, req));
+        }
+        if let Some(val) = cfg.empty_default::<T>(req) {
+            return ok(QsQuery(val));
+        }
+        // non-strict mode accepts percent-encoded brackets
+        serde_qs::Config::new(5, false)
+            .deserialize_str::<T>(req.query_string())
+            .map(|val| ok(QsQuery(val)))
+            .unwrap_or_else(|e| err(cfg.handle(ErrorBadRequest(e.to_string()), req)))
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for QsQuery<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+/// Extract typed information from the request's cookies.
+///
+/// All cookies from the `Cookie` headers get deserialized to a type `T`,
+/// the type `T` must implement the `Deserialize` trait from *serde*.
+/// Cookie names are used as field names. Extraction fails with
+/// *400 Bad Request* if required cookie is missing or can not be
+/// deserialized. `Cookies<HashMap<String, String>>` yields all cookies
+/// as a map. To extract a single cookie value use `Cookie<T>`.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// #[macro_use] extern crate serde_derive;
+/// use actix_web2::{App, Cookies};
+///
+/// #[derive(Deserialize)]
+/// struct Session {
+///     session: String,
+///     theme: Option<String>,
+/// }
+///
+/// fn index(cookies: Cookies<Session>) -> String {
+///     format!("Session {}!", cookies.session)
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource("/index.html", |r| r.get(index));
+/// }
+/// ```
+pub struct Cookies<T>(T);
+
+impl<T> Deref for Cookies<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Cookies<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> Cookies<T> {
+    /// Deconstruct to a inner value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T, P> FromRequest<P> for Cookies<T>
+where
+    T: de::DeserializeOwned,
+{
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        let cfg = HeaderConfig::from_state(req);
+        for hdr in req.headers().get_all(COOKIE) {
+            if let Err(e) = cfg.check(hdr) {
+                return err(e);
+            }
+        }
+
+        // re-encode cookies, so values could be parsed the same way as query
+        let cookies = match serde_urlencoded::to_string(request_cookies(req)) {
+            Ok(cookies) => cookies,
+            Err(_) => return err(ErrorBadRequest("Can not parse cookies")),
+        };
+        serde_urlencoded::from_str::<T>(&cookies)
+            .map(|val| ok(Cookies(val)))
+            .unwrap_or_else(|e| err(ErrorBadRequest(e)))
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Cookies<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Extract a single cookie value from the request.
+///
+/// Cookie name is configured with
+/// [**CookieConfig**](dev/struct.CookieConfig.html), registered with
+/// `App::state()` or as a route configuration. The type `T` must implement
+/// `FromStr`. Cookies are collected from all `Cookie` headers. Extraction
+/// fails with *400 Bad Request* if the cookie is missing or its value can
+/// not be parsed, errors are passed to `CookieConfig` error handler.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::CookieConfig;
+/// use actix_web2::{App, Cookie};
+///
+/// fn index(theme: Cookie<String>) -> String {
+///     format!("Theme {}!", *theme)
+/// }
+///
+/// fn main() {
+///     let app = App::new()
+///         .state(CookieConfig::new("theme"))
+///         .resource("/index.html", |r| r.get(index));
+/// }
+/// ```
+pub struct Cookie<T>(T);
+
+impl<T> Deref for Cookie<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Cookie<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> Cookie<T> {
+    /// Deconstruct to a inner value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T, P> FromRequest<P> for Cookie<T>
+where
+    T: str::FromStr,
+    T::Err: fmt::Display,
+{
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        let cfg = match CookieConfig::from_state(req) {
+            Some(cfg) => cfg,
+            None => {
+                return err(ErrorInternalServerError(
+                    "Cookie is not configured, use App::state()",
+                ));
+            }
+        };
+        let hcfg = HeaderConfig::from_state(req);
+        for hdr in req.headers().get_all(COOKIE) {
+            if let Err(e) = hcfg.check(hdr) {
+                return err(cfg.handle(e, req));
+            }
+        }
+
+        let value = request_cookies(req)
+            .into_iter()
+            .find(|(name, _)| *name == cfg.name)
+            .map(|(_, value)| value);
+        let res = match value {
+            Some(value) => value.parse().map(Cookie).map_err(|e| {
+                ErrorBadRequest(format!("Can not parse cookie {}: {}", cfg.name, e))
+            }),
+            None => Err(ErrorBadRequest(format!("Cookie {} is missing", cfg.name))),
+        };
+        res.map(ok).unwrap_or_else(|e| err(cfg.handle(e, req)))
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Cookie<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Cookie<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// `Cookie` extractor configuration.
+///
+/// ```rust,ignore
+/// # extern crate actix_http;
+/// # extern crate actix_web2;
+/// use actix_http::error::ErrorUnauthorized;
+/// use actix_web2::dev::CookieConfig;
+/// use actix_web2::App;
+///
+/// fn main() {
+///     let mut cfg = CookieConfig::new("session");
+///     cfg.error_handler(|_, _| ErrorUnauthorized("Session is required"));
+///
+///     let app = App::new().state(cfg);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct CookieConfig {
+    name: String,
+    ehandler: Option<Rc<Fn(Error, &HttpRequest) -> Error>>,
+}
+
+impl CookieConfig {
+    /// Create configuration for the cookie with specified name
+    pub fn new<T: Into<String>>(name: T) -> Self {
+        CookieConfig {
+            name: name.into(),
+            ehandler: None,
+        }
+    }
+
+    /// Set custom error handler.
+    ///
+    /// Handler receives error of the extractor, i.e. *400 Bad Request*
+    /// error for missing cookie, and returns error to respond with.
+    pub fn error_handler<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(Error, &HttpRequest) -> Error + 'static,
+    {
+        self.ehandler = Some(Rc::new(f));
+        self
+    }
+
+    /// Route configuration or configuration registered with `App::state()`
+    fn from_state<P>(req: &ServiceRequest<P>) -> Option<Self> {
+        if let Some(cfg) = req.route_config::<CookieConfig>() {
+            Some(cfg)
+        } else {
+            req.app_extensions()
+                .get::<State<CookieConfig>>()
+                .map(|cfg| cfg.get_ref().clone())
+        }
+    }
+
+    fn handle(&self, e: Error, req: &HttpRequest) -> Error {
+        match self.ehandler {
+            Some(ref handler) => handler(e, req),
+            None => e,
+        }
+    }
+}
+
+/// Parse cookies from all `Cookie` headers of the request
+pub(crate) fn request_cookies(req: &HttpRequest) -> Vec<(String, String)> {
+    let mut cookies = Vec::new();
+    for hdr in req.headers().get_all(COOKIE) {
+        if let Ok(val) = hdr.to_str() {
+            for pair in val.split(';').map(|pair| pair.trim()) {
+                if pair.is_empty() {
+                    continue;
+                }
+                if let Ok(cookie) = cookie::Cookie::parse_encoded(pair) {
+                    cookies.push((cookie.name().to_owned(), cookie.value().to_owned()));
+                }
+            }
+        }
+    }
+    cookies
+}
+
+/// Extract a value from the first present source.
+///
+/// Sources are registered with [**MultiSourceConfig**](dev/struct.MultiSourceConfig.html)
+/// and checked in registration order, value can come from a request header,
+/// a query parameter or a cookie. The type `T` must implement `FromStr`.
+/// Extraction fails with *401 Unauthorized* if none of the sources is present
+/// and with *400 Bad Request* if value can not be parsed.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::MultiSourceConfig;
+/// use actix_web2::{App, MultiSource};
+///
+/// fn index(key: MultiSource<String>) -> String {
+///     format!("Api key {}", key.into_inner())
+/// }
+///
+/// fn main() {
+///     let mut cfg = MultiSourceConfig::<String>::default();
+///     cfg.header("X-Api-Key").query("api_key").cookie("api_key");
+///
+///     let app = App::new()
+///         .state(cfg)
+///         .resource("/index.html", |r| r.get(index));
+/// }
+/// ```
+pub struct MultiSource<T>(pub T);
+
+impl<T> MultiSource<T> {
+    /// Deconstruct to an inner value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for MultiSource<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MultiSource<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T, P> FromRequest<P> for MultiSource<T>
+where
+    T: str::FromStr + 'static,
+{
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        let cfg = MultiSourceConfig::<T>::from_state(req);
+
+        match cfg.find(req) {
+            Some(val) => val
+                .parse()
+                .map(|val| ok(MultiSource(val)))
+                .unwrap_or_else(|_| err(ErrorBadRequest("Can not parse value"))),
+            None => err(ErrorUnauthorized("Value is missing")),
+        }
+    }
+}
+
+/// Source of the `MultiSource` value
+#[derive(Clone)]
+enum Source {
+    Header(HeaderName),
+    Query(String),
+    Cookie(String),
+}
+
+/// `MultiSource` extractor configuration
+///
+/// Configuration is registered per value type, so several `MultiSource`
+/// extractors with different value types could use different sources.
+pub struct MultiSourceConfig<T> {
+    sources: Vec<Source>,
+    _t: PhantomData<T>,
+}
+
+impl<T> MultiSourceConfig<T> {
+    /// Check request header
+    pub fn header<K>(&mut self, key: K) -> &mut Self
+    where
+        HeaderName: HttpTryFrom<K>,
+    {
+        #[allow(clippy::match_wild_err_arm)]
+        match HeaderName::try_from(key) {
+            Ok(key) => self.sources.push(Source::Header(key)),
+            Err(_) => panic!("Can not create header name"),
+        }
+        self
+    }
+
+    /// Check query parameter
+    pub fn query<N: Into<String>>(&mut self, name: N) -> &mut Self {
+        self.sources.push(Source::Query(name.into()));
+        self
+    }
+
+    /// Check cookie
+    pub fn cookie<N: Into<String>>(&mut self, name: N) -> &mut Self {
+        self.sources.push(Source::Cookie(name.into()));
+        self
+    }
+
+    /// Route configuration, configuration registered with `App::state()`
+    /// or default one
+    fn from_state<P>(req: &ServiceRequest<P>) -> Self
+    where
+        T: 'static,
+    {
+        if let Some(cfg) = req.route_config::<MultiSourceConfig<T>>() {
+            cfg
+        } else if let Some(cfg) =
+            req.app_extensions().get::<State<MultiSourceConfig<T>>>()
+        {
+            cfg.get_ref().clone()
+        } else {
+            MultiSourceConfig::default()
+        }
+    }
+
+    /// Value of the first present source
+    fn find(&self, req: &HttpRequest) -> Option<String> {
+        for source in &self.sources {
+            let val =
+                match source {
+                    Source::Header(name) => req
+                        .headers()
+                        .get(name)
+                        .and_then(|val| val.to_str().ok())
+                        .map(|val| val.to_owned()),
+                    Source::Query(name) => serde_urlencoded::from_str::<
+                        Vec<(String, String)>,
+                    >(req.query_string())
+                    .ok()
+                    .and_then(|params| {
+                        params.into_iter().find(|(n, _)| n == name).map(|(_, v)| v)
+                    }),
+                    Source::Cookie(name) => request_cookies(req)
+                        .into_iter()
+                        .find(|(n, _)| n == name)
+                        .map(|(_, v)| v),
+                };
+            if val.is_some() {
+                return val;
+            }
+        }
+        None
+    }
+}
+
+impl<T> Clone for MultiSourceConfig<T> {
+    fn clone(&self) -> Self {
+        MultiSourceConfig {
+            sources: self.sources.clone(),
+            _t: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for MultiSourceConfig<T> {
+    fn default() -> Self {
+        MultiSourceConfig {
+            sources: Vec::new(),
+            _t: PhantomData,
+        }
+    }
+}
+
+/// Extract parsed `Authorization` header.
+///
+/// Header value is split into the authentication scheme and the rest of the
+/// value, i.e. `Basic dXNlcjpwYXNz` gets split into `Basic` scheme and
+/// `dXNlcjpwYXNz` credentials. Credentials are not decoded, so handler
+/// could dispatch on the scheme itself. Extraction fails with
+/// *401 Unauthorized* if the header is missing and with *400 Bad Request*
+/// if the header is malformed. Use `Option<Authorization>` if the header
+/// is optional.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{App, Authorization};
+///
+/// fn index(auth: Authorization) -> String {
+///     if auth.is_scheme("Bearer") {
+///         format!("Token {}", auth.credentials())
+///     } else {
+///         format!("Unsupported scheme {}", auth.scheme())
+///     }
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource("/index.html", |r| r.get(index));
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Authorization {
+    scheme: String,
+    credentials: String,
+}
+
+impl Authorization {
+    /// Authentication scheme, as it is sent by the client
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// Raw credentials, value of the header after the scheme
+    pub fn credentials(&self) -> &str {
+        &self.credentials
+    }
+
+    /// Check authentication scheme, comparison is case-insensitive
+    pub fn is_scheme(&self, scheme: &str) -> bool {
+        self.scheme.eq_ignore_ascii_case(scheme)
+    }
+}
+
+impl<P> FromRequest<P> for Authorization {
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        let val = match req.headers().get(AUTHORIZATION) {
+            Some(val) => val,
+            None => return err(ErrorUnauthorized("Authorization header is expected")),
+        };
+        if let Err(e) = HeaderConfig::from_state(req).check(val) {
+            return err(e);
+        }
+        let val = match val.to_str() {
+            Ok(val) => val.trim(),
+            Err(_) => return err(ErrorBadRequest("Invalid Authorization header")),
+        };
+
+        let mut parts = val.splitn(2, ' ');
+        match parts.next() {
+            Some(scheme) if !scheme.is_empty() => ok(Authorization {
+                scheme: scheme.to_owned(),
+                credentials: parts.next().unwrap_or("").trim().to_owned(),
+            }),
+            _ => err(ErrorBadRequest("Invalid Authorization header")),
+        }
+    }
+}
+
+impl fmt::Display for Authorization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.scheme, self.credentials)
+    }
+}
+
+/// Extract bearer token that has all required scopes.
+///
+/// Token is taken from the `Authorization: Bearer` header and verified with
+/// `RequireScopesConfig` verifier, verifier returns scopes of the token, i.e.
+/// value of the JWT `scope` claim. Missing or invalid tokens are rejected
+/// with *401 Unauthorized*, valid tokens without required scopes with
+/// *403 Forbidden*, responses carry `WWW-Authenticate` header as described
+/// in RFC 6750. Configuration has to be registered with `App::state()`,
+/// required scopes could be overridden per route with
+/// `RouteBuilder::config()`.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::RequireScopesConfig;
+/// use actix_web2::{App, RequireScopes};
+///
+/// fn orders(token: RequireScopes) -> String {
+///     format!("Scopes {:?}", token.scopes())
+/// }
+///
+/// fn main() {
+///     let cfg = RequireScopesConfig::new(|token| verify_jwt(token).map(|claims| claims.scopes));
+///
+///     let app = App::new().state(cfg.clone()).resource("/orders", |r| {
+///         r.route(|r| r.config(cfg.scope("orders:read")).to(orders))
+///     });
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RequireScopes {
+    token: String,
+    scopes: Vec<String>,
+}
+
+impl RequireScopes {
+    /// Raw bearer token
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// All scopes of the token
+    pub fn scopes(&self) -> &[String] {
+        &self.scopes
+    }
+
+    /// Check if token has the scope
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+impl<P> FromRequest<P> for RequireScopes {
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        let cfg = match RequireScopesConfig::from_state(req) {
+            Some(cfg) => cfg,
+            None => {
+                return err(ErrorInternalServerError(
+                    "RequireScopes is not configured, use App::state()",
+                ));
+            }
+        };
+
+        let token = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|val| val.to_str().ok())
+            .and_then(|val| {
+                let mut parts = val.trim().splitn(2, ' ');
+                match (parts.next(), parts.next()) {
+                    (Some(scheme), Some(token))
+                        if scheme.eq_ignore_ascii_case("Bearer") =>
+                    {
+                        Some(token.trim().to_owned())
+                    }
+                    _ => None,
+                }
+            });
+        let token = match token {
+            Some(token) => token,
+            None => {
+                return err(bearer_error(
+                    StatusCode::UNAUTHORIZED,
+                    "Bearer".to_owned(),
+                    "Bearer token is expected",
+                ));
+            }
+        };
+
+        let scopes = match (cfg.verifier)(&token) {
+            Some(scopes) => scopes,
+            None => {
+                return err(bearer_error(
+                    StatusCode::UNAUTHORIZED,
+                    "Bearer error=\"invalid_token\"".to_owned(),
+                    "Invalid bearer token",
+                ));
+            }
+        };
+
+        if cfg.scopes.iter().all(|scope| scopes.contains(scope)) {
+            ok(RequireScopes { token, scopes })
+        } else {
+            err(bearer_error(
+                StatusCode::FORBIDDEN,
+                format!(
+                    "Bearer error=\"insufficient_scope\", scope=\"{}\"",
+                    cfg.scopes.join(" ")
+                ),
+                "Insufficient scope",
+            ))
+        }
+    }
+}
+
+/// Bearer token error with `WWW-Authenticate` challenge
+fn bearer_error(status: StatusCode, challenge: String, msg: &'static str) -> Error {
+    let res = Response::build(status)
+        .header(WWW_AUTHENTICATE, challenge)
+        .content_type("text/plain")
+        .body(msg);
+    InternalError::from_response(msg, res).into()
+}
+
+/// `RequireScopes` extractor configuration
+///
+/// Verifier receives raw bearer token and returns scopes of the token,
+/// or `None` if token is invalid.
+#[derive(Clone)]
+pub struct RequireScopesConfig {
+    verifier: Rc<Fn(&str) -> Option<Vec<String>>>,
+    scopes: Vec<String>,
+}
+
+impl RequireScopesConfig {
+    /// Create configuration with token verifier, no scopes are required
+    pub fn new<F>(verifier: F) -> Self
+    where
+        F: Fn(&str) -> Option<Vec<String>> + 'static,
+    {
+        RequireScopesConfig {
+            verifier: Rc::new(verifier),
+            scopes: Vec::new(),
+        }
+    }
+
+    /// Add required scope
+    pub fn scope<T: Into<String>>(mut self, scope: T) -> Self {
+        self.scopes.push(scope.into());
+        self
+    }
+
+    /// Route configuration or configuration registered with `App::state()`
+    fn from_state<P>(req: &ServiceRequest<P>) -> Option<Self> {
+        if let Some(cfg) = req.route_config::<RequireScopesConfig>() {
+            Some(cfg)
+        } else {
+            req.app_extensions()
+                .get::<State<RequireScopesConfig>>()
+                .map(|cfg| cfg.get_ref().clone())
+        }
+    }
+}
+
+/// Header extractors configuration.
+///
+/// Header extractors, i.e. `Authorization` and `Cookies`, reject header
+/// values longer than the limit with *400 Bad Request* before parsing.
+/// Configuration could be registered as an application state with
+/// `App::state()` method, otherwise default configuration is used.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::HeaderConfig;
+/// use actix_web2::App;
+///
+/// fn main() {
+///     let mut cfg = HeaderConfig::default();
+///     cfg.max_length(1024);
+///
+///     let app = App::new().state(cfg);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct HeaderConfig {
+    max_length: usize,
+}
+
+impl HeaderConfig {
+    /// Change max length of header value. By default max length is 8Kb
+    pub fn max_length(&mut self, max_length: usize) -> &mut Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Route configuration, configuration registered with `App::state()`
+    /// or default one
+    fn from_state<P>(req: &ServiceRequest<P>) -> Self {
+        if let Some(cfg) = req.route_config::<HeaderConfig>() {
+            cfg
+        } else if let Some(cfg) = req.app_extensions().get::<State<HeaderConfig>>() {
+            cfg.get_ref().clone()
+        } else {
+            HeaderConfig::default()
+        }
+    }
+
+    /// Check header value length
+    fn check(&self, val: &HeaderValue) -> Result<(), Error> {
+        if val.len() > self.max_length {
+            Err(ErrorBadRequest("Header value is too long"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for HeaderConfig {
+    fn default() -> Self {
+        HeaderConfig { max_length: 8192 }
+    }
+}
+
+/// Extract parsed `Forwarded` header, see RFC 7239.
+///
+/// Each proxy adds a hop with `for`, `by`, `host` and `proto` parameters,
+/// hops are listed in the order they were added, so the first hop describes
+/// the client facing proxy. Hops from all `Forwarded` headers of the request
+/// are combined, quoted values are unquoted. Extraction fails with
+/// *400 Bad Request* if the header is missing or malformed. Use
+/// `Option<Forwarded>` if the header is optional.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{App, Forwarded};
+///
+/// fn index(forwarded: Forwarded) -> String {
+///     let client = forwarded.hops()[0].forwarded_for().unwrap_or("unknown");
+///     format!("Hello {}!", client)
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource("/index.html", |r| r.get(index));
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forwarded {
+    hops: Vec<ForwardedHop>,
+}
+
+/// Single hop of the `Forwarded` header
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForwardedHop {
+    forwarded_for: Option<String>,
+    by: Option<String>,
+    host: Option<String>,
+    proto: Option<String>,
+}
+
+impl ForwardedHop {
+    /// Node that made the request to the proxy, `for` parameter
+    pub fn forwarded_for(&self) -> Option<&str> {
+        self.forwarded_for.as_ref().map(|s| s.as_str())
+    }
+
+    /// Interface where the request came in to the proxy, `by` parameter
+    pub fn by(&self) -> Option<&str> {
+        self.by.as_ref().map(|s| s.as_str())
+    }
+
+    /// Original `Host` header of the request, `host` parameter
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_ref().map(|s| s.as_str())
+    }
+
+    /// Protocol used to make the request, `proto` parameter
+    pub fn proto(&self) -> Option<&str> {
+        self.proto.as_ref().map(|s| s.as_str())
+    }
+}
+
+impl Forwarded {
+    /// Parse all `Forwarded` headers, result is empty if there is no header
+    pub fn from_headers(headers: &HeaderMap) -> Result<Forwarded, Error> {
+        let mut hops = Vec::new();
+        for hdr in headers.get_all(FORWARDED) {
+            let val = hdr
+                .to_str()
+                .map_err(|_| ErrorBadRequest("Invalid Forwarded header"))?;
+
+            for el in split_quoted(val, ',') {
+                let mut hop = ForwardedHop::default();
+                let mut empty = true;
+
+                for pair in split_quoted(el, ';') {
+                    let pair = pair.trim();
+                    if pair.is_empty() {
+                        continue;
+                    }
+                    let mut items = pair.splitn(2, '=');
+                    let name = items.next().unwrap_or("").trim();
+                    let value = match items.next() {
+                        Some(value) if !name.is_empty() => unquote(value.trim()),
+                        _ => return Err(ErrorBadRequest("Invalid Forwarded header")),
+                    };
+                    empty = false;
+
+                    match &name.to_lowercase() as &str {
+                        "for" => hop.forwarded_for = Some(value),
+                        "by" => hop.by = Some(value),
+                        "host" => hop.host = Some(value),
+                        "proto" => hop.proto = Some(value),
+                        _ => (),
+                    }
+                }
+
+                if !empty {
+                    hops.push(hop);
+                }
+            }
+        }
+        Ok(Forwarded { hops })
+    }
+
+    /// Hops in the order they were added by proxies
+    pub fn hops(&self) -> &[ForwardedHop] {
+        &self.hops
+    }
+
+    /// Check if no hops are found
+    pub fn is_empty(&self) -> bool {
+        self.hops.is_empty()
+    }
+}
+
+impl<P> FromRequest<P> for Forwarded {
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        match Forwarded::from_headers(req.headers()) {
+            Ok(ref forwarded) if forwarded.is_empty() => {
+                err(ErrorBadRequest("Forwarded header is expected"))
+            }
+            Ok(forwarded) => ok(forwarded),
+            Err(e) => err(e),
+        }
+    }
+}
+
+/// Split value by the delimiter, delimiters within quoted strings are ignored
+fn split_quoted(val: &str, delim: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (idx, ch) in val.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if quoted && ch == '\\' {
+            escaped = true;
+        } else if ch == '"' {
+            quoted = !quoted;
+        } else if ch == delim && !quoted {
+            parts.push(&val[start..idx]);
+            start = idx + ch.len_utf8();
+        }
+    }
+    parts.push(&val[start..]);
+    parts
+}
+
+/// Remove quotes and escapes of the quoted string
+fn unquote(val: &str) -> String {
+    if val.len() < 2 || !val.starts_with('"') || !val.ends_with('"') {
+        return val.to_owned();
+    }
+
+    let mut res = String::with_capacity(val.len() - 2);
+    let mut escaped = false;
+    for ch in val[1..val.len() - 1].chars() {
+        if !escaped && ch == '\\' {
+            escaped = true;
+        } else {
+            escaped = false;
+            res.push(ch);
+        }
+    }
+    res
+}
+
+/// Verified TLS client certificate of the connection.
+///
+/// Certificate info is stored in request extensions by `dev::TlsH1Service`
+/// from the peer certificate verified by TLS acceptor, see
+/// `dev::TlsStream`. As an extractor `ClientCert` fails with
+/// *401 Unauthorized* if client did not present a certificate.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{App, ClientCert};
+///
+/// fn index(cert: ClientCert) -> String {
+///     format!("Hello {}!", cert.common_name().unwrap_or("anonymous"))
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource("/index.html", |r| r.get(index));
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCert {
+    subject: String,
+    common_name: Option<String>,
+    fingerprint: String,
+}
+
+impl ClientCert {
+    /// Create certificate info from subject distinguished name,
+    /// i.e. `CN=client,O=Example`, common name of the subject and
+    /// certificate fingerprint.
+    ///
+    /// Common name should be read from the certificate's subject entries,
+    /// distinguished name string is for display only and could not be
+    /// parsed back reliably.
+    pub fn new<S, F>(subject: S, common_name: Option<String>, fingerprint: F) -> Self
+    where
+        S: Into<String>,
+        F: Into<String>,
+    {
+        ClientCert {
+            common_name,
+            subject: subject.into(),
+            fingerprint: fingerprint.into(),
+        }
+    }
+
+    /// Subject distinguished name
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Common name (CN) of the subject
+    pub fn common_name(&self) -> Option<&str> {
+        self.common_name.as_ref().map(|cn| cn.as_str())
+    }
+
+    /// Certificate fingerprint
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+}
+
+impl<P> FromRequest<P> for ClientCert {
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        match req.extensions().get::<ClientCert>() {
+            Some(cert) => ok(cert.clone()),
+            None => err(ErrorUnauthorized("Client certificate is expected")),
+        }
+    }
+}
+
+/// Negotiated TLS session parameters of the connection.
+///
+/// Session info is stored in request extensions by `dev::TlsH1Service`,
+/// same as `ClientCert`. As an extractor `TlsInfo` fails with
+/// *400 Bad Request* for plaintext connections, use `Option<TlsInfo>`
+/// to accept both.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{App, TlsInfo};
+///
+/// fn index(tls: Option<TlsInfo>) -> String {
+///     match tls {
+///         Some(tls) => format!("{} {}", tls.version(), tls.cipher()),
+///         None => "plaintext".to_owned(),
+///     }
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource("/index.html", |r| r.get(index));
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    version: String,
+    cipher: String,
+}
+
+impl TlsInfo {
+    /// Create session info from negotiated protocol version,
+    /// i.e. `TLSv1.3`, and cipher suite name.
+    pub fn new<V: Into<String>, C: Into<String>>(version: V, cipher: C) -> Self {
+        TlsInfo {
+            version: version.into(),
+            cipher: cipher.into(),
+        }
+    }
+
+    /// Negotiated protocol version
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Negotiated cipher suite
+    pub fn cipher(&self) -> &str {
+        &self.cipher
+    }
+}
+
+impl<P> FromRequest<P> for TlsInfo {
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        match req.extensions().get::<TlsInfo>() {
+            Some(info) => ok(info.clone()),
+            None => err(ErrorBadRequest("TLS connection is expected")),
+        }
+    }
+}
+
+/// Extract request extension seeded with `App::extension()`.
+///
+/// Extractor returns a clone of the value, so it is suited for small
+/// immutable context, i.e. build version or region. Extraction fails with
+/// *500 Internal Server Error* if extension of type `T` is not registered.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{App, ReqData};
+///
+/// #[derive(Clone)]
+/// struct Region(&'static str);
+///
+/// fn index(region: ReqData<Region>) -> String {
+///     format!("Served from {}", region.0)
+/// }
+///
+/// fn main() {
+///     let app = App::new()
+///         .extension(Region("eu-west"))
+///         .resource("/index.html", |r| r.get(index));
+/// }
+/// ```
+pub struct ReqData<T>(T);
+
+impl<T> ReqData<T> {
+    /// Deconstruct to an inner value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ReqData<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T, P> FromRequest<P> for ReqData<T>
+where
+    T: Clone + 'static,
+{
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        match req.extensions().get::<T>() {
+            Some(val) => ok(ReqData(val.clone())),
+            None => err(ErrorInternalServerError(
+                "ReqData is not configured, use App::extension()",
+            )),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ReqData<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+/// Extract typed information from the request's body.
+///
+/// To extract typed information from request's body, the type `T` must
+/// implement the `Deserialize` trait from *serde*.
+///
+/// [**FormConfig**](dev/struct.FormConfig.html) allows to configure extraction
+/// process.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web;
+/// #[macro_use] extern crate serde_derive;
+/// use actix_web::{App, Form, Result};
+///
+/// #[derive(Deserialize)]
+/// struct FormData {
+///     username: String,
+/// }
+///
+/// /// extract form data using serde
+/// /// this handler get called only if content type is *x-www-form-urlencoded*
+/// /// and content of the request could be deserialized to a `FormData` struct
+/// fn index(form: Form<FormData>) -> Result<String> {
+///     Ok(format!("Welcome {}!", form.username))
+/// }
+/// # fn main() {}
+/// ```
+pub struct Form<T>(pub T);
+
+impl<T> Form<T> {
+    /// Deconstruct to an inner value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Form<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Form<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T, P> FromRequest<P> for Form<T>
+where
+    T: DeserializeOwned + 'static,
+    P: Stream<Item = Bytes, Error = PayloadError> + 'static,
+{
+    type Error = Error;
+    type Future = Box<Future<Item = Self, Error = Error>>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        let cfg = FormConfig::from_state(req);
+
+        let req2 = req.clone();
+        let err = Rc::clone(&cfg.ehandler);
+
+        // check content-type and charset
+        if req.content_type().to_lowercase() != "application/x-www-form-urlencoded" {
+            return Box::new(future::err((*err)(UrlencodedError::ContentType, &req2)));
+        }
+        let encoding = match req.encoding() {
+            Ok(enc) => enc,
+            Err(_) => {
+                return Box::new(future::err((*err)(
+                    UrlencodedError::ContentType,
+                    &req2,
+                )));
+            }
+        };
+
+        let length = declared_length(req);
+        if let Some(length) = length {
+            if length > cfg.limit {
+                return Box::new(future::err((*err)(UrlencodedError::Overflow, &req2)));
+            }
+        }
+
+        let err2 = Rc::clone(&err);
+        let req3 = req2.clone();
+        Box::new(
+            MessageBody::new(req)
+                .limit(cfg.limit)
+                .map_err(move |e| match e {
+                    PayloadError::Overflow => (*err)(UrlencodedError::Overflow, &req2),
+                    e => (*err)(UrlencodedError::Payload(e), &req2),
+                })
+                .and_then(move |body| check_length(length, body))
+                .and_then(move |body| {
+                    let enc: *const Encoding = encoding as *const Encoding;
+                    let form = if enc == UTF_8 {
+                        serde_urlencoded::from_bytes::<T>(&body)
+                            .map_err(|_| UrlencodedError::Parse)
+                    } else {
+                        encoding
+                            .decode(&body, DecoderTrap::Strict)
+                            .map_err(|_| UrlencodedError::Parse)
+                            .and_then(|body| {
+                                serde_urlencoded::from_str::<T>(&body)
+                                    .map_err(|_| UrlencodedError::Parse)
+                            })
+                    };
+                    form.map_err(|e| (*err2)(e, &req3))
+                })
+                .map(Form),
+        )
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Form<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Form<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Form extractor configuration
+///
+/// Configuration could be registered for a route with
+/// `RouteBuilder::config()` or as an application state with `App::state()`
+/// method, otherwise default configuration is used.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// #[macro_use] extern crate serde_derive;
+/// use actix_web2::dev::FormConfig;
+/// use actix_web2::{App, Form};
+///
+/// #[derive(Deserialize)]
+/// struct FormData {
+///     username: String,
+/// }
+///
+/// /// extract form data using serde.
+/// /// custom configuration is used for this handler, max payload size is 4k
+/// fn index(form: Form<FormData>) -> String {
+///     format!("Welcome {}!", form.username)
+/// }
+///
+/// fn main() {
+///     let mut cfg = FormConfig::default();
+///     cfg.limit(4096);
+///
+///     let app = App::new().resource("/index.html", |r| {
+///         r.route(|r| r.config(cfg).to(index))
+///     });
+/// }
+/// ```
+#[derive(Clone)]
+pub struct FormConfig {
+    limit: usize,
+    ehandler: Rc<Fn(UrlencodedError, &HttpRequest) -> Error>,
+}
+
+impl FormConfig {
+    /// Change max size of payload. By default max size is 256Kb
+    pub fn limit(&mut self, limit: usize) -> &mut Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Set custom error handler
+    pub fn error_handler<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(UrlencodedError, &HttpRequest) -> Error + 'static,
+    {
+        self.ehandler = Rc::new(f);
+        self
+    }
+
+    /// Route configuration, configuration registered with `App::state()`
+    /// or default one
+    fn from_state<P>(req: &ServiceRequest<P>) -> Self {
+        if let Some(cfg) = req.route_config::<FormConfig>() {
+            cfg
+        } else if let Some(cfg) = req.app_extensions().get::<State<FormConfig>>() {
+            cfg.get_ref().clone()
+        } else {
+            FormConfig::default()
+        }
+    }
+}
+
+impl Default for FormConfig {
+    fn default() -> Self {
+        FormConfig {
+            limit: 262_144,
+            ehandler: Rc::new(|e, _| e.into()),
+        }
+    }
+}
+
+/// Json helper
+///
+/// Json can be used for two different purpose. First is for json response
+/// generation and second is for extracting typed information from request's
+/// payload.
+///
+/// To extract typed information from request's body, the type `T` must
+/// implement the `Deserialize` trait from *serde*.
+///
+/// [**JsonConfig**](dev/struct.JsonConfig.html) allows to configure extraction
+/// process.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web;
+/// #[macro_use] extern crate serde_derive;
+/// use actix_web::{App, Json, Result, http};
+///
+/// #[derive(Deserialize)]
+/// struct Info {
+///     username: String,
+/// }
+///
+/// /// deserialize `Info` from request's body
+/// fn index(info: Json<Info>) -> Result<String> {
+///     Ok(format!("Welcome {}!", info.username))
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource(
+///        "/index.html",
+///        |r| r.method(http::Method::POST).with(index));  // <- use `with` extractor
+/// }
+/// ```
+///
+/// The `Json` type allows you to respond with well-formed JSON data: simply
+/// return a value of type Json<T> where T is the type of a structure
+/// to serialize into *JSON*. The type `T` must implement the `Serialize`
+/// trait from *serde*.
+///
+/// ```rust,ignore
+/// # extern crate actix_web;
+/// # #[macro_use] extern crate serde_derive;
+/// # use actix_web::*;
 /// #
 /// #[derive(Serialize)]
 /// struct MyObj {
@@ -453,134 +2523,962 @@ impl<T> Json<T> {
     }
 }
 
-impl<T> Deref for Json<T> {
-    type Target = T;
+impl<T> Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Json<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> fmt::Debug for Json<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Json: {:?}", self.0)
+    }
+}
+
+impl<T> fmt::Display for Json<T>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl<T: Serialize> Responder for Json<T> {
+    type Error = Error;
+    type Future = FutureResult<Response, Error>;
+
+    fn respond_to(self, _: &HttpRequest) -> Self::Future {
+        let body = match serde_json::to_string(&self.0) {
+            Ok(body) => body,
+            Err(e) => return err(e.into()),
+        };
+
+        ok(Response::build(StatusCode::OK)
+            .content_type("application/json")
+            .body(body))
+    }
+}
+
+impl<T, P> FromRequest<P> for Json<T>
+where
+    T: DeserializeOwned + 'static,
+    P: Stream<Item = Bytes, Error = PayloadError> + 'static,
+{
+    type Error = Error;
+    type Future = Box<Future<Item = Self, Error = Error>>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        let cfg = JsonConfig::from_state(req);
+
+        let req2 = req.clone();
+        let err = Rc::clone(&cfg.ehandler);
+
+        // reject oversized payload before reading it
+        let length = declared_length(req);
+        if let Some(length) = length {
+            if length > cfg.limit {
+                return Box::new(future::err((*err)(JsonPayloadError::Overflow, &req2)));
+            }
+        }
+
+        if let Err(e) = cfg.check(req) {
+            return Box::new(future::err((*err)(e, &req2)));
+        }
+
+        let err2 = Rc::clone(&err);
+        let req3 = req2.clone();
+        Box::new(
+            JsonKindBody::new(req.take_payload(), cfg.limit, cfg.kind)
+                .map_err(move |e| (*err)(e, &req2))
+                .and_then(move |body| check_length(length, body))
+                .and_then(move |body| {
+                    serde_json::from_slice(&body)
+                        .map_err(|e| (*err2)(JsonPayloadError::Deserialize(e), &req3))
+                })
+                .map(Json),
+        )
+    }
+}
+
+/// Expected kind of the top-level json value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JsonKind {
+    /// Json object, `{...}`
+    Object,
+    /// Json array, `[...]`
+    Array,
+}
+
+impl JsonKind {
+    fn matches(self, byte: u8) -> bool {
+        match self {
+            JsonKind::Object => byte == b'{',
+            JsonKind::Array => byte == b'[',
+        }
+    }
+}
+
+impl fmt::Display for JsonKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonKind::Object => write!(f, "object"),
+            JsonKind::Array => write!(f, "array"),
+        }
+    }
+}
+
+/// Json body future, optionally checks kind of the top-level value
+/// with the first non-whitespace byte, before whole body is loaded
+struct JsonKindBody<S> {
+    stream: HttpPayload<S>,
+    buf: BytesMut,
+    limit: usize,
+    kind: Option<JsonKind>,
+}
+
+impl<S> JsonKindBody<S> {
+    fn new(stream: HttpPayload<S>, limit: usize, kind: Option<JsonKind>) -> Self {
+        JsonKindBody {
+            stream,
+            limit,
+            kind,
+            buf: BytesMut::new(),
+        }
+    }
+}
+
+impl<S> Future for JsonKindBody<S>
+where
+    S: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Item = Bytes;
+    type Error = JsonPayloadError;
+
+    fn poll(&mut self) -> Poll<Bytes, JsonPayloadError> {
+        loop {
+            match self.stream.poll()? {
+                Async::Ready(Some(chunk)) => {
+                    if self.buf.len() + chunk.len() > self.limit {
+                        return Err(JsonPayloadError::Overflow);
+                    }
+                    if let Some(kind) = self.kind {
+                        if let Some(byte) =
+                            chunk.iter().find(|b| !b.is_ascii_whitespace())
+                        {
+                            if !kind.matches(*byte) {
+                                return Err(JsonPayloadError::Deserialize(
+                                    de::Error::custom(format!("expected json {}", kind)),
+                                ));
+                            }
+                            self.kind = None;
+                        }
+                    }
+                    self.buf.extend_from_slice(&chunk);
+                }
+                Async::Ready(None) => return Ok(Async::Ready(self.buf.take().freeze())),
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+/// Json extractor configuration
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// #[macro_use] extern crate serde_derive;
+/// use actix_http::error;
+/// use actix_web2::dev::JsonConfig;
+/// use actix_web2::{http, App, HttpResponse, Json, Result};
+///
+/// #[derive(Deserialize)]
+/// struct Info {
+///     username: String,
+/// }
+///
+/// /// deserialize `Info` from request's body, max payload size is 4kb
+/// fn index(info: Json<Info>) -> Result<String> {
+///     Ok(format!("Welcome {}!", info.username))
+/// }
+///
+/// fn main() {
+///     let mut cfg = JsonConfig::default();
+///     cfg.limit(4096)   // <- change json extractor configuration
+///         .error_handler(|err, req| {  // <- create custom error response
+///             error::InternalError::from_response(
+///                 err, HttpResponse::Conflict().finish()).into()
+///         });
+///
+///     let app = App::new().resource("/index.html", |r| {
+///         r.route(|r| r.method(http::Method::POST).config(cfg).to(index))
+///     });
+/// }
+/// ```
+#[derive(Clone)]
+pub struct JsonConfig {
+    limit: usize,
+    kind: Option<JsonKind>,
+    content_types: Vec<Mime>,
+    ehandler: Rc<Fn(JsonPayloadError, &HttpRequest) -> Error>,
+}
+
+impl JsonConfig {
+    /// Change max size of payload. By default max size is 256Kb
+    ///
+    /// Limit is checked against `Content-Length` header before payload is
+    /// read, oversized requests are rejected with *413 Payload Too Large*
+    /// through the error handler. Payloads without `Content-Length`, i.e.
+    /// chunked, are checked while payload is streamed and rejected as soon
+    /// as limit is exceeded, before whole payload is loaded. Same applies
+    /// to free-form `Json<serde_json::Value>` values.
+    pub fn limit(&mut self, limit: usize) -> &mut Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Set expected kind of the top-level json value.
+    ///
+    /// Payload is rejected as soon as first non-whitespace byte of the body
+    /// can not start value of expected kind, without loading rest of the body.
+    pub fn expect_kind(&mut self, kind: JsonKind) -> &mut Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Add accepted content type.
+    ///
+    /// By default only `application/json` is accepted. Once content types
+    /// are added, only added types are accepted, so `application/json` has
+    /// to be added as well to keep it. Types are compared without parameters,
+    /// so `application/json; charset=utf-8` matches `application/json`.
+    /// Requests with other content types are passed to the error handler
+    /// with `JsonPayloadError::ContentType` error.
+    pub fn content_type(&mut self, mt: Mime) -> &mut Self {
+        self.content_types.push(mt);
+        self
+    }
+
+    /// Set custom error handler
+    pub fn error_handler<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(JsonPayloadError, &HttpRequest) -> Error + 'static,
+    {
+        self.ehandler = Rc::new(f);
+        self
+    }
+
+    /// Route configuration, configuration registered with `App::state()`
+    /// or default one
+    fn from_state<P>(req: &ServiceRequest<P>) -> Self {
+        if let Some(cfg) = req.route_config::<JsonConfig>() {
+            cfg
+        } else if let Some(cfg) = req.app_extensions().get::<State<JsonConfig>>() {
+            cfg.get_ref().clone()
+        } else {
+            JsonConfig::default()
+        }
+    }
+
+    /// Check content type
+    fn check<P>(&self, req: &ServiceRequest<P>) -> Result<(), JsonPayloadError> {
+        let json = if let Ok(Some(mt)) = req.mime_type() {
+            let accepts = |ct: &Mime| {
+                ct.type_() == mt.type_()
+                    && ct.subtype() == mt.subtype()
+                    && ct.suffix() == mt.suffix()
+            };
+            if self.content_types.is_empty() {
+                accepts(&mime::APPLICATION_JSON)
+            } else {
+                self.content_types.iter().any(accepts)
+            }
+        } else {
+            false
+        };
+        if json {
+            Ok(())
+        } else {
+            Err(JsonPayloadError::ContentType)
+        }
+    }
+}
+
+impl Default for JsonConfig {
+    fn default() -> Self {
+        JsonConfig {
+            limit: 262_144,
+            kind: None,
+            content_types: Vec::new(),
+            ehandler: Rc::new(|e, _| e.into()),
+        }
+    }
+}
+
+/// Extract request's body according to the request's content type.
+///
+/// Allows one handler to accept several payload formats. Variant is
+/// selected by `Content-Type` header:
+///
+/// * `application/json` and `application/*+json` - `Body::Json`
+/// * `application/x-www-form-urlencoded` - `Body::Form`
+/// * `multipart/form-data` - `Body::Multipart`, raw multipart payload
+/// * any other or missing content type - `Body::Bytes`
+///
+/// Same size limit is applied to all variants, by default max size
+/// is 256Kb.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{App, Body};
+///
+/// fn index(body: Body) -> String {
+///     match body {
+///         Body::Json(val) => format!("Json {}", val),
+///         Body::Form(map) => format!("Form {:?}", map),
+///         Body::Multipart(data) => format!("Multipart {} bytes", data.len()),
+///         Body::Bytes(data) => format!("Bytes {} bytes", data.len()),
+///     }
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource("/index.html", |r| r.post(index));
+/// }
+/// ```
+#[derive(Debug)]
+pub enum Body {
+    /// Json payload
+    Json(serde_json::Value),
+    /// Url encoded form
+    Form(HashMap<String, String>),
+    /// Raw multipart payload
+    Multipart(Bytes),
+    /// Payload of any other content type
+    Bytes(Bytes),
+}
+
+enum BodyKind {
+    Json,
+    Form,
+    Multipart,
+    Bytes,
+}
+
+impl<P> FromRequest<P> for Body
+where
+    P: Stream<Item = Bytes, Error = PayloadError> + 'static,
+{
+    type Error = Error;
+    type Future =
+        Either<Box<Future<Item = Body, Error = Error>>, FutureResult<Body, Error>>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        let cfg = PayloadConfig::from_state(req);
+
+        let kind = match req.mime_type() {
+            Ok(Some(ref mt))
+                if mt.subtype() == mime::JSON || mt.suffix() == Some(mime::JSON) =>
+            {
+                BodyKind::Json
+            }
+            Ok(Some(ref mt))
+                if mt.type_() == mime::APPLICATION
+                    && mt.subtype() == mime::WWW_FORM_URLENCODED =>
+            {
+                BodyKind::Form
+            }
+            Ok(Some(ref mt))
+                if mt.type_() == mime::MULTIPART && mt.subtype() == mime::FORM_DATA =>
+            {
+                BodyKind::Multipart
+            }
+            Ok(_) => BodyKind::Bytes,
+            Err(e) => return Either::B(err(e.into())),
+        };
+
+        Either::A(Box::new(cfg.read(req).and_then(move |body| {
+            match kind {
+                BodyKind::Json => serde_json::from_slice(&body)
+                    .map(Body::Json)
+                    .map_err(|e| JsonPayloadError::Deserialize(e).into()),
+                BodyKind::Form => serde_urlencoded::from_bytes(&body)
+                    .map(Body::Form)
+                    .map_err(|_| UrlencodedError::Parse.into()),
+                BodyKind::Multipart => Ok(Body::Multipart(body)),
+                BodyKind::Bytes => Ok(Body::Bytes(body)),
+            }
+        })))
+    }
+}
+
+/// Streaming request payload extractor.
+///
+/// Unlike `Bytes` and `String` extractors, payload is not buffered and no
+/// size limit is applied, handler receives a stream of body chunks as they
+/// arrive. Payload is read on demand: next chunk is read from the
+/// connection only when the stream is polled, so a slow consumer applies
+/// backpressure to the client. Dropping the stream discards the rest
+/// of the payload.
+///
+/// If `PayloadConfig::buffer_size()` is set, incoming chunks are collected
+/// into chunks of the buffer size, only the last chunk could be smaller.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// # extern crate futures;
+/// use actix_web2::{App, Error, HttpResponse, Payload};
+/// use futures::{Future, Stream};
+///
+/// fn upload(body: Payload) -> impl Future<Item = HttpResponse, Error = Error> {
+///     body.map_err(Error::from)
+///         .fold(0, |size, chunk| Ok::<_, Error>(size + chunk.len()))
+///         .map(|size| HttpResponse::Ok().body(format!("Received {} bytes", size)))
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource("/upload", |r| r.to_async(upload));
+/// }
+/// ```
+pub struct Payload<P = PayloadStream> {
+    stream: HttpPayload<P>,
+    buf: BytesMut,
+    buffer_size: Option<usize>,
+}
+
+impl<P> Payload<P> {
+    /// Deconstruct to an inner value
+    pub fn into_inner(self) -> HttpPayload<P> {
+        self.stream
+    }
+}
+
+impl<P> Stream for Payload<P>
+where
+    P: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Item = Bytes;
+    type Error = PayloadError;
+
+    fn poll(&mut self) -> Poll<Option<Bytes>, PayloadError> {
+        let buffer_size = match self.buffer_size {
+            Some(size) => size,
+            None => return self.stream.poll(),
+        };
+
+        loop {
+            if self.buf.len() >= buffer_size {
+                return Ok(Async::Ready(Some(self.buf.split_to(buffer_size).freeze())));
+            }
+            match self.stream.poll()? {
+                Async::Ready(Some(chunk)) => self.buf.extend_from_slice(&chunk),
+                Async::Ready(None) => {
+                    if self.buf.is_empty() {
+                        return Ok(Async::Ready(None));
+                    } else {
+                        return Ok(Async::Ready(Some(self.buf.take().freeze())));
+                    }
+                }
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+impl<P> FromRequest<P> for Payload<P>
+where
+    P: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
 
-    fn deref(&self) -> &T {
-        &self.0
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        let buffer_size = PayloadConfig::from_state(req)
+            .buffer_size
+            .map(|size| cmp::max(size, 1));
+        ok(Payload {
+            stream: req.take_payload(),
+            buf: BytesMut::with_capacity(buffer_size.unwrap_or(0)),
+            buffer_size,
+        })
     }
 }
 
-impl<T> DerefMut for Json<T> {
-    fn deref_mut(&mut self) -> &mut T {
-        &mut self.0
+impl<P> fmt::Debug for Payload<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Payload")
     }
 }
 
-impl<T> fmt::Debug for Json<T>
+/// Extract *multipart/form-data* fields from the request.
+///
+/// `Multipart` is a stream of fields, each field is a stream of body
+/// chunks. Payload is parsed on demand while fields are polled, field has
+/// to be read before the next one is requested, otherwise the rest of the
+/// field is skipped. Extraction fails with *400 Bad Request* if content
+/// type is not *multipart/form-data* or boundary is missing.
+///
+/// Size of part headers, field body and whole payload are limited,
+/// [**MultipartConfig**](dev/struct.MultipartConfig.html) allows to
+/// configure limits. Oversized part headers are rejected with
+/// *400 Bad Request*, oversized field or payload with
+/// *413 Payload Too Large*.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// # extern crate futures;
+/// use actix_web2::{App, Error, HttpResponse, Multipart};
+/// use futures::{Future, Stream};
+///
+/// fn upload(mp: Multipart) -> impl Future<Item = HttpResponse, Error = Error> {
+///     mp.and_then(|field| {
+///         let name = field.name().to_owned();
+///         field
+///             .fold(0, |size, chunk| Ok::<_, Error>(size + chunk.len()))
+///             .map(move |size| format!("{}: {} bytes", name, size))
+///     })
+///     .collect()
+///     .map(|fields| HttpResponse::Ok().body(fields.join("\n")))
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource("/upload", |r| r.to_async(upload));
+/// }
+/// ```
+pub struct Multipart<P = PayloadStream> {
+    inner: Rc<RefCell<MultipartInner<P>>>,
+}
+
+impl<P> Stream for Multipart<P>
 where
-    T: fmt::Debug,
+    P: Stream<Item = Bytes, Error = PayloadError>,
 {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Json: {:?}", self.0)
+    type Item = MultipartField<P>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<MultipartField<P>>, Error> {
+        let (id, headers) = {
+            let mut inner = self.inner.borrow_mut();
+            match inner.poll_field() {
+                Ok(Async::Ready(Some(field))) => field,
+                Ok(Async::Ready(None)) => return Ok(Async::Ready(None)),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => {
+                    inner.state = MultipartState::Eof;
+                    return Err(e);
+                }
+            }
+        };
+
+        let disposition = headers
+            .get(CONTENT_DISPOSITION)
+            .and_then(|val| ContentDisposition::from_raw(val).ok())
+            .and_then(|cd| {
+                let name = cd.get_name()?.to_owned();
+                Some((name, cd.get_filename().map(|f| f.to_owned())))
+            });
+        let (name, filename) = match disposition {
+            Some(disposition) => disposition,
+            None => {
+                self.inner.borrow_mut().state = MultipartState::Eof;
+                return Err(ErrorBadRequest("Multipart field name is missing"));
+            }
+        };
+        let content_type = headers
+            .get(CONTENT_TYPE)
+            .and_then(|val| val.to_str().ok())
+            .and_then(|val| val.parse().ok())
+            .unwrap_or(mime::TEXT_PLAIN);
+
+        Ok(Async::Ready(Some(MultipartField {
+            id,
+            name,
+            filename,
+            content_type,
+            headers,
+            inner: Rc::clone(&self.inner),
+        })))
     }
 }
 
-impl<T> fmt::Display for Json<T>
+impl<P> FromRequest<P> for Multipart<P>
 where
-    T: fmt::Display,
+    P: Stream<Item = Bytes, Error = PayloadError>,
 {
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        let boundary = match req.mime_type() {
+            Ok(Some(ref mt))
+                if mt.type_() == mime::MULTIPART && mt.subtype() == mime::FORM_DATA =>
+            {
+                match mt.get_param(mime::BOUNDARY) {
+                    Some(boundary) if !boundary.as_str().is_empty() => {
+                        boundary.as_str().to_owned()
+                    }
+                    _ => return err(ErrorBadRequest("Multipart boundary is missing")),
+                }
+            }
+            _ => {
+                return err(ErrorBadRequest("Content type is not multipart/form-data"));
+            }
+        };
+
+        ok(Multipart {
+            inner: Rc::new(RefCell::new(MultipartInner {
+                cfg: MultipartConfig::from_state(req),
+                payload: req.take_payload(),
+                buf: BytesMut::new(),
+                delimiter: format!("\r\n--{}", boundary).into_bytes(),
+                state: MultipartState::Preamble,
+                total: 0,
+                field_size: 0,
+                fields: 0,
+            })),
+        })
+    }
+}
+
+impl<P> fmt::Debug for Multipart<P> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Display::fmt(&self.0, f)
+        write!(f, "Multipart")
     }
 }
 
-impl<T: Serialize> Responder for Json<T> {
-    type Error = Error;
-    type Future = FutureResult<Response, Error>;
+/// Field of the multipart payload, stream of body chunks
+pub struct MultipartField<P = PayloadStream> {
+    id: usize,
+    name: String,
+    filename: Option<String>,
+    content_type: Mime,
+    headers: HeaderMap,
+    inner: Rc<RefCell<MultipartInner<P>>>,
+}
 
-    fn respond_to(self, _: &HttpRequest) -> Self::Future {
-        let body = match serde_json::to_string(&self.0) {
-            Ok(body) => body,
-            Err(e) => return err(e.into()),
-        };
+impl<P> MultipartField<P> {
+    /// Field name from `Content-Disposition` header
+    pub fn name(&self) -> &str {
+        &self.name
+    }
 
-        ok(Response::build(StatusCode::OK)
-            .content_type("application/json")
-            .body(body))
+    /// File name from `Content-Disposition` header, if field is a file
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_ref().map(|f| f.as_str())
+    }
+
+    /// Field content type, by default `text/plain`
+    pub fn content_type(&self) -> &Mime {
+        &self.content_type
+    }
+
+    /// Field headers
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
     }
 }
 
-impl<T, P> FromRequest<P> for Json<T>
+impl<P> Stream for MultipartField<P>
 where
-    T: DeserializeOwned + 'static,
-    P: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    P: Stream<Item = Bytes, Error = PayloadError>,
 {
+    type Item = Bytes;
     type Error = Error;
-    type Future = Box<Future<Item = Self, Error = Error>>;
 
-    #[inline]
-    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
-        let cfg = JsonConfig::default();
+    fn poll(&mut self) -> Poll<Option<Bytes>, Error> {
+        let mut inner = self.inner.borrow_mut();
+        match inner.state {
+            MultipartState::Body(id) if id == self.id => match inner.poll_chunk() {
+                Ok(Async::Ready(Some(chunk))) => {
+                    // only delivered chunks count, skipped fields are not limited
+                    inner.field_size += chunk.len();
+                    if inner.field_size > inner.cfg.field_limit {
+                        inner.state = MultipartState::Eof;
+                        return Err(PayloadError::Overflow.into());
+                    }
+                    Ok(Async::Ready(Some(chunk)))
+                }
+                Err(e) => {
+                    inner.state = MultipartState::Eof;
+                    Err(e)
+                }
+                res => res,
+            },
+            _ => Ok(Async::Ready(None)),
+        }
+    }
+}
 
-        let req2 = req.clone();
-        let err = Rc::clone(&cfg.ehandler);
-        Box::new(
-            JsonBody::new(req)
-                .limit(cfg.limit)
-                .map_err(move |e| (*err)(e, &req2))
-                .map(Json),
+impl<P> fmt::Debug for MultipartField<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "MultipartField {{ name: {:?}, filename: {:?}, content_type: {} }}",
+            self.name, self.filename, self.content_type
         )
     }
 }
 
-/// Json extractor configuration
-///
-/// ```rust,ignore
-/// # extern crate actix_web;
-/// #[macro_use] extern crate serde_derive;
-/// use actix_web::{error, http, App, HttpResponse, Json, Result};
+#[derive(Clone, Copy)]
+enum MultipartState {
+    /// Data before the first boundary
+    Preamble,
+    /// Boundary is consumed, `\r\n` or final `--` follows
+    Boundary,
+    /// Part headers
+    Headers,
+    /// Body of the field with specified id
+    Body(usize),
+    Eof,
+}
+
+struct MultipartInner<P> {
+    cfg: MultipartConfig,
+    payload: HttpPayload<P>,
+    buf: BytesMut,
+    /// `\r\n--boundary`
+    delimiter: Vec<u8>,
+    state: MultipartState,
+    total: usize,
+    field_size: usize,
+    fields: usize,
+}
+
+impl<P> MultipartInner<P>
+where
+    P: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Read next payload chunk to the buffer
+    fn fill(&mut self) -> Poll<(), Error> {
+        match self.payload.poll()? {
+            Async::Ready(Some(chunk)) => {
+                self.total += chunk.len();
+                if self.total > self.cfg.total_limit {
+                    return Err(PayloadError::Overflow.into());
+                }
+                self.buf.extend_from_slice(&chunk);
+                Ok(Async::Ready(()))
+            }
+            Async::Ready(None) => Err(ErrorBadRequest("Incomplete multipart payload")),
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+
+    /// Skip to the next part and parse its headers
+    fn poll_field(&mut self) -> Poll<Option<(usize, HeaderMap)>, Error> {
+        loop {
+            match self.state {
+                MultipartState::Preamble => {
+                    let boundary_len = self.delimiter.len() - 2;
+                    if let Some(pos) = find_bytes(&self.buf, &self.delimiter[2..]) {
+                        self.buf.split_to(pos + boundary_len);
+                        self.state = MultipartState::Boundary;
+                        continue;
+                    }
+                    // keep bytes that could be a start of the boundary
+                    if self.buf.len() >= boundary_len {
+                        let len = self.buf.len() - boundary_len + 1;
+                        self.buf.split_to(len);
+                    }
+                }
+                MultipartState::Boundary => {
+                    if self.buf.len() >= 2 {
+                        if &self.buf[..2] == b"--" {
+                            self.state = MultipartState::Eof;
+                        } else if &self.buf[..2] == b"\r\n" {
+                            self.buf.split_to(2);
+                            self.state = MultipartState::Headers;
+                        } else {
+                            return Err(ErrorBadRequest("Invalid multipart boundary"));
+                        }
+                        continue;
+                    }
+                }
+                MultipartState::Headers => {
+                    let end = if self.buf.starts_with(b"\r\n") {
+                        Some((0, 2))
+                    } else {
+                        find_bytes(&self.buf, b"\r\n\r\n").map(|pos| (pos, pos + 4))
+                    };
+                    if let Some((pos, _)) = end {
+                        if pos > self.cfg.header_limit {
+                            return Err(multipart_headers_overflow());
+                        }
+                    }
+                    match end {
+                        Some((pos, len)) => {
+                            let headers = parse_part_headers(&self.buf[..pos])?;
+                            self.buf.split_to(len);
+                            self.fields += 1;
+                            self.field_size = 0;
+                            self.state = MultipartState::Body(self.fields);
+                            return Ok(Async::Ready(Some((self.fields, headers))));
+                        }
+                        None if self.buf.len() > self.cfg.header_limit + 4 => {
+                            return Err(multipart_headers_overflow());
+                        }
+                        None => (),
+                    }
+                }
+                MultipartState::Body(_) => {
+                    // skip the rest of the previous field
+                    while try_ready!(self.poll_chunk()).is_some() {}
+                    continue;
+                }
+                MultipartState::Eof => return Ok(Async::Ready(None)),
+            }
+            try_ready!(self.fill());
+        }
+    }
+
+    /// Read next chunk of the current field
+    fn poll_chunk(&mut self) -> Poll<Option<Bytes>, Error> {
+        loop {
+            if let MultipartState::Boundary = self.state {
+                return Ok(Async::Ready(None));
+            }
+
+            let chunk = if let Some(pos) = find_bytes(&self.buf, &self.delimiter) {
+                let chunk = self.buf.split_to(pos).freeze();
+                self.buf.split_to(self.delimiter.len());
+                self.state = MultipartState::Boundary;
+                chunk
+            } else if self.buf.len() >= self.delimiter.len() {
+                // keep bytes that could be a start of the delimiter
+                let len = self.buf.len() - self.delimiter.len() + 1;
+                self.buf.split_to(len).freeze()
+            } else {
+                Bytes::new()
+            };
+
+            if !chunk.is_empty() {
+                return Ok(Async::Ready(Some(chunk)));
+            }
+            if let MultipartState::Body(_) = self.state {
+                try_ready!(self.fill());
+            }
+        }
+    }
+}
+
+fn multipart_headers_overflow() -> Error {
+    ErrorBadRequest("Multipart part headers are too large")
+}
+
+/// Position of the first occurrence of `pat` in `data`
+fn find_bytes(data: &[u8], pat: &[u8]) -> Option<usize> {
+    if data.len() < pat.len() {
+        return None;
+    }
+    data.windows(pat.len()).position(|window| window == pat)
+}
+
+fn parse_part_headers(data: &[u8]) -> Result<HeaderMap, Error> {
+    let invalid = || ErrorBadRequest("Invalid multipart part header");
+
+    let mut headers = HeaderMap::new();
+    if data.is_empty() {
+        return Ok(headers);
+    }
+    for line in str::from_utf8(data).map_err(|_| invalid())?.split("\r\n") {
+        let mut parts = line.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) => {
+                let name = HeaderName::from_bytes(name.trim().as_bytes())
+                    .map_err(|_| invalid())?;
+                let value =
+                    HeaderValue::from_str(value.trim()).map_err(|_| invalid())?;
+                headers.append(name, value);
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(headers)
+}
+
+/// Multipart extractor configuration.
 ///
-/// #[derive(Deserialize)]
-/// struct Info {
-///     username: String,
-/// }
+/// Configuration could be registered as an application state with
+/// `App::state()` method or as a route configuration, otherwise default
+/// configuration is used.
 ///
-/// /// deserialize `Info` from request's body, max payload size is 4kb
-/// fn index(info: Json<Info>) -> Result<String> {
-///     Ok(format!("Welcome {}!", info.username))
-/// }
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::MultipartConfig;
+/// use actix_web2::App;
 ///
 /// fn main() {
-///     let app = App::new().resource("/index.html", |r| {
-///         r.method(http::Method::POST)
-///               .with_config(index, |cfg| {
-///                   cfg.0.limit(4096)   // <- change json extractor configuration
-///                      .error_handler(|err, req| {  // <- create custom error response
-///                          error::InternalError::from_response(
-///                              err, HttpResponse::Conflict().finish()).into()
-///                          });
-///               })
-///     });
+///     let mut cfg = MultipartConfig::default();
+///     cfg.field_limit(10_485_760).total_limit(52_428_800);
+///
+///     let app = App::new().state(cfg);
 /// }
 /// ```
-pub struct JsonConfig {
-    limit: usize,
-    ehandler: Rc<Fn(JsonPayloadError, &HttpRequest) -> Error>,
+#[derive(Clone)]
+pub struct MultipartConfig {
+    field_limit: usize,
+    total_limit: usize,
+    header_limit: usize,
 }
 
-impl JsonConfig {
-    /// Change max size of payload. By default max size is 256Kb
-    pub fn limit(&mut self, limit: usize) -> &mut Self {
-        self.limit = limit;
+impl MultipartConfig {
+    /// Change max size of a field body. By default max size is 1Mb
+    pub fn field_limit(&mut self, limit: usize) -> &mut Self {
+        self.field_limit = limit;
         self
     }
 
-    /// Set custom error handler
-    pub fn error_handler<F>(&mut self, f: F) -> &mut Self
-    where
-        F: Fn(JsonPayloadError, &HttpRequest) -> Error + 'static,
-    {
-        self.ehandler = Rc::new(f);
+    /// Change max size of the whole payload. By default max size is 8Mb
+    pub fn total_limit(&mut self, limit: usize) -> &mut Self {
+        self.total_limit = limit;
+        self
+    }
+
+    /// Change max size of a part headers. By default max size is 8Kb
+    pub fn header_limit(&mut self, limit: usize) -> &mut Self {
+        self.header_limit = limit;
         self
     }
+
+    /// Route configuration, configuration registered with `App::state()`
+    /// or default one
+    fn from_state<P>(req: &ServiceRequest<P>) -> Self {
+        if let Some(cfg) = req.route_config::<MultipartConfig>() {
+            cfg
+        } else if let Some(cfg) = req.app_extensions().get::<State<MultipartConfig>>() {
+            cfg.get_ref().clone()
+        } else {
+            MultipartConfig::default()
+        }
+    }
 }
 
-impl Default for JsonConfig {
+impl Default for MultipartConfig {
     fn default() -> Self {
-        JsonConfig {
-            limit: 262_144,
-            ehandler: Rc::new(|e, _| e.into()),
+        MultipartConfig {
+            field_limit: 1_048_576,
+            total_limit: 8_388_608,
+            header_limit: 8192,
         }
     }
 }
@@ -619,13 +3517,168 @@ where
 
     #[inline]
     fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
-        let cfg = PayloadConfig::default();
+        let cfg = PayloadConfig::from_state(req);
 
         if let Err(e) = cfg.check_mimetype(req) {
             return Either::B(err(e));
         }
 
-        Either::A(Box::new(MessageBody::new(req).limit(cfg.limit).from_err()))
+        Either::A(cfg.read(req))
+    }
+}
+
+/// Request payload extractor that verifies payload's HMAC signature.
+///
+/// Loads request's payload, computes *HMAC-SHA256* over it and compares
+/// result with the signature from the configured request header. Signature
+/// is expected to be hex encoded, optionally prefixed with `sha256=`.
+/// Raw payload is returned only if signature is valid, otherwise
+/// *401 Unauthorized* response is generated. Signatures are compared in
+/// constant time.
+///
+/// Signing key and header name are configured with
+/// [**SignedPayloadConfig**](dev/struct.SignedPayloadConfig.html), it has to be
+/// registered as an application state.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::SignedPayloadConfig;
+/// use actix_web2::{App, SignedPayload};
+///
+/// /// payload is available only if it is signed with a "secret" key
+/// fn webhook(body: SignedPayload) -> String {
+///     format!("Body {:?}!", body.into_inner())
+/// }
+///
+/// fn main() {
+///     let app = App::new()
+///         .state(SignedPayloadConfig::new("secret", "X-Hub-Signature-256"))
+///         .resource("/webhook", |r| r.post(webhook));
+/// }
+/// ```
+pub struct SignedPayload(pub Bytes);
+
+impl SignedPayload {
+    /// Deconstruct to an inner value
+    pub fn into_inner(self) -> Bytes {
+        self.0
+    }
+}
+
+impl Deref for SignedPayload {
+    type Target = Bytes;
+
+    fn deref(&self) -> &Bytes {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SignedPayload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SignedPayload: {:?}", self.0)
+    }
+}
+
+impl<P> FromRequest<P> for SignedPayload
+where
+    P: Stream<Item = Bytes, Error = PayloadError> + 'static,
+{
+    type Error = Error;
+    type Future =
+        Either<Box<Future<Item = Self, Error = Error>>, FutureResult<Self, Error>>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        let cfg = match req.app_extensions().get::<State<SignedPayloadConfig>>() {
+            Some(cfg) => cfg.clone(),
+            None => {
+                return Either::B(err(ErrorInternalServerError(
+                    "SignedPayload is not configured, use App::state()",
+                )));
+            }
+        };
+
+        let signature = match req
+            .headers()
+            .get(&cfg.header)
+            .and_then(|val| val.to_str().ok())
+            .and_then(decode_signature)
+        {
+            Some(signature) => signature,
+            None => return Either::B(err(ErrorUnauthorized("Signature is expected"))),
+        };
+
+        let length = declared_length(req);
+        Either::A(Box::new(
+            MessageBody::new(req)
+                .limit(cfg.limit)
+                .from_err()
+                .and_then(move |body| check_length(length, body))
+                .and_then(move |body| {
+                    let mut mac = Hmac::<Sha256>::new_varkey(&cfg.key)
+                        .map_err(|_| ErrorInternalServerError("Invalid signing key"))?;
+                    mac.input(body.as_ref());
+                    mac.verify(&signature)
+                        .map_err(|_| ErrorUnauthorized("Invalid signature"))?;
+                    Ok(SignedPayload(body))
+                }),
+        ))
+    }
+}
+
+/// Decode hex encoded signature, `sha256=` prefix is optional
+pub(crate) fn decode_signature(val: &str) -> Option<Vec<u8>> {
+    let val = val.trim();
+    let val = if val.starts_with("sha256=") {
+        &val[7..]
+    } else {
+        val
+    };
+    if val.is_empty() || val.len() % 2 != 0 || !val.is_ascii() {
+        return None;
+    }
+    (0..val.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(&val[idx..idx + 2], 16).ok())
+        .collect()
+}
+
+/// Signed payload extractor configuration
+///
+/// Configuration has to be registered as an application state with
+/// `App::state()` method.
+pub struct SignedPayloadConfig {
+    key: Vec<u8>,
+    header: HeaderName,
+    limit: usize,
+}
+
+impl SignedPayloadConfig {
+    /// Create configuration with signing key and name of the header
+    /// that contains payload signature.
+    pub fn new<K, H>(key: K, header: H) -> Self
+    where
+        K: AsRef<[u8]>,
+        HeaderName: HttpTryFrom<H>,
+    {
+        #[allow(clippy::match_wild_err_arm)]
+        let header = match HeaderName::try_from(header) {
+            Ok(header) => header,
+            Err(_) => panic!("Can not create header name"),
+        };
+        SignedPayloadConfig {
+            header,
+            key: key.as_ref().to_vec(),
+            limit: 262_144,
+        }
+    }
+
+    /// Change max size of payload. By default max size is 256Kb
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
     }
 }
 
@@ -666,7 +3719,7 @@ where
 
     #[inline]
     fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
-        let cfg = PayloadConfig::default();
+        let cfg = PayloadConfig::from_state(req);
 
         // check content-type
         if let Err(e) = cfg.check_mimetype(req) {
@@ -679,23 +3732,25 @@ where
             Err(e) => return Either::B(err(e.into())),
         };
 
-        Either::A(Box::new(
-            MessageBody::new(req)
-                .limit(cfg.limit)
-                .from_err()
-                .and_then(move |body| {
-                    let enc: *const Encoding = encoding as *const Encoding;
-                    if enc == UTF_8 {
-                        Ok(str::from_utf8(body.as_ref())
-                            .map_err(|_| ErrorBadRequest("Can not decode body"))?
-                            .to_owned())
-                    } else {
-                        Ok(encoding
-                            .decode(&body, DecoderTrap::Strict)
-                            .map_err(|_| ErrorBadRequest("Can not decode body"))?)
-                    }
-                }),
-        ))
+        let decoded_limit = cfg.decoded_limit;
+        Either::A(Box::new(cfg.read(req).and_then(move |body| {
+            let enc: *const Encoding = encoding as *const Encoding;
+            let text = if enc == UTF_8 {
+                str::from_utf8(body.as_ref())
+                    .map_err(|_| ErrorBadRequest("Can not decode body"))?
+                    .to_owned()
+            } else {
+                encoding
+                    .decode(&body, DecoderTrap::Strict)
+                    .map_err(|_| ErrorBadRequest("Can not decode body"))?
+            };
+            match decoded_limit {
+                Some(limit) if text.len() > limit => {
+                    Err(ErrorBadRequest("Decoded body is too large"))
+                }
+                _ => Ok(text),
+            }
+        })))
     }
 }
 
@@ -825,9 +3880,300 @@ where
     }
 }
 
+/// Extract one of three values, variants are tried in order.
+///
+/// `A` is tried first, then `B`, then `C`. The first successful value is
+/// passed to the handler, if all variants fail, error of `C` is returned.
+/// `B` and `C` are constructed only after previous variant fails.
+///
+/// Request payload is loaded once, before `A` is tried, and every variant
+/// reads its own copy, so body extractors could be combined, i.e.
+/// `Either3<Json<T>, Form<T>, Bytes>`. Payload size is limited by
+/// [**PayloadConfig**](dev/struct.PayloadConfig.html).
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// #[macro_use] extern crate serde_derive;
+/// use actix_web2::{App, Either3, Query};
+///
+/// #[derive(Deserialize)]
+/// struct ById {
+///     id: u32,
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct ByName {
+///     name: String,
+/// }
+///
+/// fn index(q: Either3<Query<ById>, Query<ByName>, ()>) -> String {
+///     match q {
+///         Either3::A(q) => format!("User #{}", q.id),
+///         Either3::B(q) => format!("User {}", q.name),
+///         Either3::C(_) => "All users".to_owned(),
+///     }
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource("/users", |r| r.get(index));
+/// }
+/// ```
+impl<A, B, C> FromRequest<PayloadStream> for Either3<A, B, C>
+where
+    A: FromRequest<PayloadStream> + 'static,
+    A::Future: 'static,
+    B: FromRequest<PayloadStream> + 'static,
+    B::Future: 'static,
+    C: FromRequest<PayloadStream> + 'static,
+    C::Future: 'static,
+{
+    type Error = Error;
+    type Future = Box<Future<Item = Self, Error = Error>>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<PayloadStream>) -> Self::Future {
+        let body = PayloadConfig::from_state(req).read(req);
+        let req = req.request().clone();
+
+        Box::new(body.and_then(move |body| {
+            let (req_b, body_b) = (req.clone(), body.clone());
+
+            A::from_request(&mut replay(&req, &body))
+                .map(Either3::A)
+                .or_else(move |_| {
+                    let (req_c, body_c) = (req_b.clone(), body_b.clone());
+
+                    B::from_request(&mut replay(&req_b, &body_b))
+                        .map(Either3::B)
+                        .or_else(move |_| {
+                            C::from_request(&mut replay(&req_c, &body_c))
+                                .map(Either3::C)
+                                .map_err(|e| e.into())
+                        })
+                })
+        }))
+    }
+}
+
+/// Request with payload replayed from the loaded body
+fn replay(req: &HttpRequest, body: &Bytes) -> ServiceRequest<PayloadStream> {
+    let payload = if body.is_empty() {
+        HttpPayload::None
+    } else {
+        HttpPayload::Stream(Box::new(stream::once(Ok(body.clone()))) as PayloadStream)
+    };
+    ServiceRequest::from_parts(req.clone(), payload)
+}
+
+/// Extractor that wraps a deserialized value, i.e. `Json<T>` or `Form<T>`
+pub trait Extracted {
+    /// Type of the wrapped value
+    type Inner;
+
+    /// Deconstruct to an inner value
+    fn into_inner(self) -> Self::Inner;
+}
+
+impl<T> Extracted for Path<T> {
+    type Inner = T;
+
+    fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> Extracted for Query<T> {
+    type Inner = T;
+
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Extracted for QsQuery<T> {
+    type Inner = T;
+
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Extracted for Cookies<T> {
+    type Inner = T;
+
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Extracted for Cookie<T> {
+    type Inner = T;
+
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Extracted for MultiSource<T> {
+    type Inner = T;
+
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Extracted for Form<T> {
+    type Inner = T;
+
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Extracted for Json<T> {
+    type Inner = T;
+
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+/// Extract domain type from the extractor's value with `TryFrom`.
+///
+/// Value is extracted with extractor `X`, i.e. `Json<Dto>`, then the inner
+/// `Dto` is converted with `T::try_from()`, so the same conversion works
+/// for any extractor of the dto. Conversion errors are returned as
+/// *422 Unprocessable Entity* response, response body contains error's
+/// message. Extraction errors of `X` are passed through as is.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// #[macro_use] extern crate serde_derive;
+/// use std::convert::TryFrom;
+/// use actix_web2::{App, Json, Valid};
+///
+/// #[derive(Deserialize)]
+/// struct UserDto {
+///     email: String,
+/// }
+///
+/// struct User {
+///     email: String,
+/// }
+///
+/// impl TryFrom<UserDto> for User {
+///     type Error = &'static str;
+///
+///     fn try_from(dto: UserDto) -> Result<User, Self::Error> {
+///         if dto.email.contains('@') {
+///             Ok(User { email: dto.email })
+///         } else {
+///             Err("Invalid email")
+///         }
+///     }
+/// }
+///
+/// fn create(user: Valid<Json<UserDto>, User>) -> String {
+///     format!("Created {}", user.email)
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource("/users", |r| r.post(create));
+/// }
+/// ```
+pub struct Valid<X, T> {
+    inner: T,
+    _x: PhantomData<X>,
+}
+
+impl<X, T> Valid<X, T> {
+    /// Deconstruct to an inner value
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<X, T> Deref for Valid<X, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<X, T> DerefMut for Valid<X, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<X, T, P> FromRequest<P> for Valid<X, T>
+where
+    X: FromRequest<P> + Extracted + 'static,
+    X::Future: 'static,
+    T: TryFrom<X::Inner> + 'static,
+    T::Error: fmt::Display,
+{
+    type Error = Error;
+    type Future = Box<Future<Item = Self, Error = Error>>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        Box::new(
+            X::from_request(req)
+                .map_err(|e| e.into())
+                .and_then(|value| {
+                    T::try_from(value.into_inner())
+                        .map(|inner| Valid {
+                            inner,
+                            _x: PhantomData,
+                        })
+                        .map_err(|e| {
+                            let msg = e.to_string();
+                            let res = Response::build(StatusCode::UNPROCESSABLE_ENTITY)
+                                .content_type("text/plain")
+                                .body(msg.clone());
+                            InternalError::from_response(msg, res).into()
+                        })
+                }),
+        )
+    }
+}
+
+impl<X, T: fmt::Debug> fmt::Debug for Valid<X, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
 /// Payload configuration for request's payload.
+///
+/// Configuration could be registered as an application state with
+/// `App::state()` method, otherwise default configuration is used.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::PayloadConfig;
+/// use actix_web2::App;
+///
+/// fn main() {
+///     let mut cfg = PayloadConfig::default();
+///     cfg.limit(16_777_216).buffer_size(1_048_576);
+///
+///     let app = App::new()
+///         .state(cfg)
+///         .resource("/upload", |r| r.post(|body: bytes::Bytes| body));
+/// }
+/// ```
+#[derive(Clone)]
 pub struct PayloadConfig {
     limit: usize,
+    decoded_limit: Option<usize>,
+    buffer_size: Option<usize>,
     mimetype: Option<Mime>,
 }
 
@@ -838,6 +4184,28 @@ impl PayloadConfig {
         self
     }
 
+    /// Change max size of decoded text body.
+    ///
+    /// `String` extractor checks size of the body after charset decoding,
+    /// decoded text could be much larger than the payload. Limit of the
+    /// payload size is applied as usual. By default decoded size is not
+    /// limited.
+    pub fn decoded_limit(&mut self, limit: usize) -> &mut Self {
+        self.decoded_limit = Some(limit);
+        self
+    }
+
+    /// Set size of the buffer used for reading payload.
+    ///
+    /// Buffer of specified size is allocated up front and grows by the same
+    /// amount, so large payloads get loaded with fewer reallocations.
+    /// `Payload` stream yields chunks of the buffer size. By default buffer
+    /// grows with incoming chunks.
+    pub fn buffer_size(&mut self, size: usize) -> &mut Self {
+        self.buffer_size = Some(size);
+        self
+    }
+
     /// Set required mime-type of the request. By default mime type is not
     /// enforced.
     pub fn mimetype(&mut self, mt: Mime) -> &mut Self {
@@ -845,6 +4213,48 @@ impl PayloadConfig {
         self
     }
 
+    /// Route configuration, configuration registered with `App::state()`
+    /// or default one
+    fn from_state<P>(req: &ServiceRequest<P>) -> Self {
+        if let Some(cfg) = req.route_config::<PayloadConfig>() {
+            cfg
+        } else if let Some(cfg) = req.app_extensions().get::<State<PayloadConfig>>() {
+            cfg.get_ref().clone()
+        } else {
+            PayloadConfig::default()
+        }
+    }
+
+    /// Load request's payload
+    fn read<P>(
+        &self,
+        req: &mut ServiceRequest<P>,
+    ) -> Box<Future<Item = Bytes, Error = Error>>
+    where
+        P: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        let length = declared_length(req);
+        if let Some(buffer_size) = self.buffer_size {
+            if let Some(length) = length {
+                if length > self.limit {
+                    return Box::new(err::<Bytes, Error>(PayloadError::Overflow.into()));
+                }
+            }
+            Box::new(
+                PayloadBuffer::new(req.take_payload(), self.limit, buffer_size)
+                    .from_err()
+                    .and_then(move |body| check_length(length, body)),
+            )
+        } else {
+            Box::new(
+                MessageBody::new(req)
+                    .limit(self.limit)
+                    .from_err()
+                    .and_then(move |body| check_length(length, body)),
+            )
+        }
+    }
+
     fn check_mimetype<P>(&self, req: &ServiceRequest<P>) -> Result<(), Error> {
         // check content-type
         if let Some(ref mt) = self.mimetype {
@@ -870,11 +4280,88 @@ impl Default for PayloadConfig {
     fn default() -> Self {
         PayloadConfig {
             limit: 262_144,
+            decoded_limit: None,
+            buffer_size: None,
             mimetype: None,
         }
     }
 }
 
+/// Content length declared with request's `Content-Length` header
+fn declared_length<P>(req: &ServiceRequest<P>) -> Option<usize> {
+    req.headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|val| val.to_str().ok())
+        .and_then(|val| val.parse::<usize>().ok())
+}
+
+/// Check that loaded payload matches declared content length, payloads
+/// that are shorter or longer than declared are rejected with
+/// *400 Bad Request*
+fn check_length(declared: Option<usize>, body: Bytes) -> Result<Bytes, Error> {
+    match declared {
+        Some(length) if body.len() < length => Err(ErrorBadRequest(format!(
+            "Payload is shorter than Content-Length, expected {} bytes, got {}",
+            length,
+            body.len()
+        ))),
+        Some(length) if body.len() > length => Err(ErrorBadRequest(format!(
+            "Payload is longer than Content-Length, expected {} bytes, got {}",
+            length,
+            body.len()
+        ))),
+        _ => Ok(body),
+    }
+}
+
+/// Future that loads payload into a buffer of configured size
+struct PayloadBuffer<S> {
+    stream: S,
+    buf: BytesMut,
+    limit: usize,
+    buffer_size: usize,
+}
+
+impl<S> PayloadBuffer<S> {
+    fn new(stream: S, limit: usize, buffer_size: usize) -> Self {
+        let buffer_size = cmp::max(buffer_size, 1);
+        PayloadBuffer {
+            stream,
+            buf: BytesMut::with_capacity(cmp::min(buffer_size, limit)),
+            limit,
+            buffer_size,
+        }
+    }
+}
+
+impl<S> Future for PayloadBuffer<S>
+where
+    S: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Item = Bytes;
+    type Error = PayloadError;
+
+    fn poll(&mut self) -> Poll<Bytes, PayloadError> {
+        loop {
+            match self.stream.poll()? {
+                Async::Ready(Some(chunk)) => {
+                    if self.buf.len() + chunk.len() > self.limit {
+                        return Err(PayloadError::Overflow);
+                    }
+                    let remaining = self.buf.remaining_mut();
+                    if remaining < chunk.len() {
+                        let steps = (chunk.len() - remaining) / self.buffer_size + 1;
+                        self.buf.reserve(remaining + steps * self.buffer_size);
+                    }
+                    self.buf.put_slice(&chunk);
+                }
+                Async::Ready(None) => return Ok(Async::Ready(self.buf.take().freeze())),
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
 macro_rules! tuple_from_req ({$fut_type:ident, $(($n:tt, $T:ident)),+} => {
 
     /// FromRequest implementation for tuple
@@ -884,7 +4371,15 @@ macro_rules! tuple_from_req ({$fut_type:ident, $(($n:tt, $T:ident)),+} => {
         type Future = $fut_type<P, $($T),+>;
 
         fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+            let aggregate = req
+                .app_extensions()
+                .get::<State<ExtractConfig>>()
+                .map(|cfg| cfg.aggregate_errors)
+                .unwrap_or(false);
+
             $fut_type {
+                aggregate,
+                errors: Vec::new(),
                 items: <($(Option<$T>,)+)>::default(),
                 futs: ($($T::from_request(req),)+),
             }
@@ -893,6 +4388,8 @@ macro_rules! tuple_from_req ({$fut_type:ident, $(($n:tt, $T:ident)),+} => {
 
     #[doc(hidden)]
     pub struct $fut_type<P, $($T: FromRequest<P>),+> {
+        aggregate: bool,
+        errors: Vec<(usize, Error)>,
         items: ($(Option<$T>,)+),
         futs: ($($T::Future,)+),
     }
@@ -906,28 +4403,97 @@ macro_rules! tuple_from_req ({$fut_type:ident, $(($n:tt, $T:ident)),+} => {
             let mut ready = true;
 
             $(
-                if self.items.$n.is_none() {
+                if self.items.$n.is_none()
+                    && !self.errors.iter().any(|(idx, _)| *idx == $n)
+                {
                     match self.futs.$n.poll() {
                         Ok(Async::Ready(item)) => {
                             self.items.$n = Some(item);
                         }
                         Ok(Async::NotReady) => ready = false,
-                        Err(e) => return Err(e.into()),
+                        Err(e) => {
+                            if !self.aggregate {
+                                return Err(e.into());
+                            }
+                            self.errors.push(($n, e.into()));
+                        }
                     }
                 }
             )+
 
-                if ready {
+                if !ready {
+                    Ok(Async::NotReady)
+                } else if !self.errors.is_empty() {
+                    let errors = std::mem::replace(&mut self.errors, Vec::new());
+                    Err(aggregated_error(errors))
+                } else {
                     Ok(Async::Ready(
                         ($(self.items.$n.take().unwrap(),)+)
                     ))
-                } else {
-                    Ok(Async::NotReady)
                 }
         }
     }
 });
 
+/// Combine errors of failed extractors into one error.
+///
+/// Error of the only failed extractor is returned as is, otherwise error
+/// has status of the most severe error, i.e. the highest status code, and
+/// lists messages of all errors.
+fn aggregated_error(mut errors: Vec<(usize, Error)>) -> Error {
+    if errors.len() == 1 {
+        return errors.pop().unwrap().1;
+    }
+
+    let status = errors
+        .iter()
+        .map(|(_, e)| e.as_response_error().error_response().status())
+        .max_by_key(|status| status.as_u16())
+        .unwrap_or(StatusCode::BAD_REQUEST);
+    let msg = errors
+        .iter()
+        .map(|(idx, e)| format!("argument {}: {}", idx, e))
+        .collect::<Vec<_>>()
+        .join("\n");
+    InternalError::new(msg, status).into()
+}
+
+/// Handler arguments extraction configuration.
+///
+/// Configuration could be registered as an application state with
+/// `App::state()` method, otherwise default configuration is used.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::ExtractConfig;
+/// use actix_web2::App;
+///
+/// fn main() {
+///     let mut cfg = ExtractConfig::default();
+///     cfg.aggregate_errors(true);
+///
+///     let app = App::new().state(cfg);
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct ExtractConfig {
+    aggregate_errors: bool,
+}
+
+impl ExtractConfig {
+    /// Report errors of all failed handler arguments.
+    ///
+    /// By default extraction stops on the first failed argument and its
+    /// error is returned. With aggregation enabled all arguments are
+    /// extracted. If only one of them fails, its error is returned,
+    /// otherwise response lists every failure and has status of the most
+    /// severe one.
+    pub fn aggregate_errors(&mut self, enabled: bool) -> &mut Self {
+        self.aggregate_errors = enabled;
+        self
+    }
+}
+
 impl<P> FromRequest<P> for () {
     type Error = Error;
     type Future = FutureResult<(), Error>;
@@ -1016,6 +4582,55 @@ mod tests {
         hello: String,
     }
 
+    /// Stream that yields one chunk per poll
+    struct Chunks(Vec<Bytes>, bool);
+
+    impl Stream for Chunks {
+        type Item = Bytes;
+        type Error = PayloadError;
+
+        fn poll(&mut self) -> Poll<Option<Bytes>, PayloadError> {
+            self.1 = !self.1;
+            if self.1 {
+                Ok(Async::NotReady)
+            } else if self.0.is_empty() {
+                Ok(Async::Ready(None))
+            } else {
+                Ok(Async::Ready(Some(self.0.remove(0))))
+            }
+        }
+    }
+
+    #[test]
+    fn test_payload_buffer_size() {
+        let chunks = vec![Bytes::from(vec![b'x'; 40]), Bytes::from(vec![b'x'; 40])];
+        let mut fut = PayloadBuffer::new(Chunks(chunks, true), 1024, 64);
+        assert_eq!(fut.buf.capacity(), 64);
+
+        // first chunk fits into initial buffer
+        assert!(fut.poll().unwrap().is_not_ready());
+        assert_eq!(fut.buf.len(), 40);
+        assert_eq!(fut.buf.capacity(), 64);
+
+        // buffer grows by configured size
+        assert!(fut.poll().unwrap().is_not_ready());
+        assert_eq!(fut.buf.len(), 80);
+        assert!(fut.buf.capacity() >= 128);
+
+        match fut.poll().unwrap() {
+            Async::Ready(s) => assert_eq!(s, Bytes::from(vec![b'x'; 80])),
+            _ => unreachable!(),
+        }
+
+        let chunks = vec![Bytes::from(vec![b'x'; 40]), Bytes::from(vec![b'x'; 40])];
+        let mut fut = PayloadBuffer::new(Chunks(chunks, true), 64, 64);
+        assert!(fut.poll().unwrap().is_not_ready());
+        match fut.poll() {
+            Err(PayloadError::Overflow) => (),
+            _ => unreachable!(),
+        }
+    }
+
     #[test]
     fn test_bytes() {
         let cfg = PayloadConfig::default();
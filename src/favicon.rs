@@ -0,0 +1,142 @@
+use std::fs;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::rc::Rc;
+
+use actix_http::http::header::CACHE_CONTROL;
+use actix_http::Response;
+use actix_service::{IntoNewService, NewService, Service};
+use bytes::Bytes;
+use futures::future::{ok, FutureResult};
+use futures::{Async, Poll};
+
+use crate::service::{ServiceRequest, ServiceResponse};
+
+/// Service that serves application's favicon.
+///
+/// Icon file is loaded once, during service construction. Icon is served
+/// with `image/x-icon` content type and with `Cache-Control` header, by
+/// default browsers are allowed to cache icon for one day. If icon file can
+/// not be loaded, *204 No Content* response is returned, so favicon requests
+/// do not clutter *404 Not Found* logs.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{App, Favicon};
+///
+/// fn main() {
+///     let app = App::new().service("/favicon.ico", Favicon::new("static/favicon.ico"));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Favicon {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    icon: Option<Bytes>,
+    max_age: u32,
+}
+
+impl Default for Favicon {
+    /// Construct `Favicon` service without icon, *204 No Content*
+    /// response is returned for all requests.
+    fn default() -> Self {
+        Favicon {
+            inner: Rc::new(Inner {
+                icon: None,
+                max_age: 86_400,
+            }),
+        }
+    }
+}
+
+impl Favicon {
+    /// Construct `Favicon` service and load icon from the file.
+    pub fn new<T: AsRef<Path>>(path: T) -> Self {
+        let icon = match fs::read(path.as_ref()) {
+            Ok(data) => Some(Bytes::from(data)),
+            Err(e) => {
+                log::error!("Can not load favicon {}: {}", path.as_ref().display(), e);
+                None
+            }
+        };
+        Favicon {
+            inner: Rc::new(Inner {
+                icon,
+                max_age: 86_400,
+            }),
+        }
+    }
+
+    /// Set max age of the icon in seconds. By default max age is one day.
+    pub fn max_age(mut self, max_age: u32) -> Self {
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .max_age = max_age;
+        self
+    }
+}
+
+impl<P> IntoNewService<FaviconFactory<P>> for Favicon {
+    fn into_new_service(self) -> FaviconFactory<P> {
+        FaviconFactory {
+            inner: self.inner,
+            _t: PhantomData,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct FaviconFactory<P> {
+    inner: Rc<Inner>,
+    _t: PhantomData<P>,
+}
+
+impl<P> NewService for FaviconFactory<P> {
+    type Request = ServiceRequest<P>;
+    type Response = ServiceResponse;
+    type Error = ();
+    type InitError = ();
+    type Service = FaviconService<P>;
+    type Future = FutureResult<Self::Service, Self::InitError>;
+
+    fn new_service(&self) -> Self::Future {
+        ok(FaviconService {
+            inner: self.inner.clone(),
+            _t: PhantomData,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub struct FaviconService<P> {
+    inner: Rc<Inner>,
+    _t: PhantomData<P>,
+}
+
+impl<P> Service for FaviconService<P> {
+    type Request = ServiceRequest<P>;
+    type Response = ServiceResponse;
+    type Error = ();
+    type Future = FutureResult<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, req: ServiceRequest<P>) -> Self::Future {
+        let res = if let Some(ref icon) = self.inner.icon {
+            Response::Ok()
+                .content_type("image/x-icon")
+                .header(
+                    CACHE_CONTROL,
+                    format!("public, max-age={}", self.inner.max_age),
+                )
+                .body(icon.clone())
+        } else {
+            Response::NoContent().finish()
+        };
+        ok(req.into_response(res))
+    }
+}
@@ -1,8 +1,14 @@
 //! Route match predicates
 #![allow(non_snake_case)]
 use actix_http::http::{self, header, HttpTryFrom};
+use hmac::{Hmac, Mac};
+use mime::Mime;
+use serde_urlencoded;
+use sha2::Sha256;
 
+use crate::extractor::{decode_signature, request_cookies, Forwarded, TlsInfo};
 use crate::request::HttpRequest;
+use crate::responder::{accept_quality, accept_ranges};
 
 /// Trait defines resource predicate.
 /// Predicate can modify request object. It is also possible to
@@ -11,6 +17,15 @@ use crate::request::HttpRequest;
 pub trait Filter {
     /// Check if request matches predicate
     fn check(&self, request: &HttpRequest) -> bool;
+
+    /// Http methods matched by predicate.
+    ///
+    /// Resource uses methods of route filters to respond with
+    /// *405 Method Not Allowed* and `Allow` header. `None` means predicate
+    /// does not restrict request method.
+    fn methods(&self) -> Option<&[http::Method]> {
+        None
+    }
 }
 
 /// Return filter that matches if any of supplied filters.
@@ -110,6 +125,38 @@ impl Filter for NotFilter {
     }
 }
 
+/// Return predicate that matches if supplied closure returns `true`.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{filter, App, HttpRequest, HttpResponse};
+///
+/// fn main() {
+///     App::new().resource("/index.html", |r| {
+///         r.route(|mut r| {
+///             r.filter(filter::Pred(|req: &HttpRequest| {
+///                 req.headers().contains_key("x-internal")
+///                     && req.query_string().contains("debug=1")
+///             }));
+///             r.to(|| HttpResponse::Ok().body("debug"))
+///         })
+///         .to(|| HttpResponse::Ok())
+///     });
+/// }
+/// ```
+pub fn Pred<F: Fn(&HttpRequest) -> bool + 'static>(f: F) -> FnFilter<F> {
+    FnFilter(f)
+}
+
+#[doc(hidden)]
+pub struct FnFilter<F>(F);
+
+impl<F: Fn(&HttpRequest) -> bool + 'static> Filter for FnFilter<F> {
+    fn check(&self, request: &HttpRequest) -> bool {
+        (self.0)(request)
+    }
+}
+
 /// Http method predicate
 #[doc(hidden)]
 pub struct MethodFilter(http::Method);
@@ -118,6 +165,10 @@ impl Filter for MethodFilter {
     fn check(&self, request: &HttpRequest) -> bool {
         request.method() == self.0
     }
+
+    fn methods(&self) -> Option<&[http::Method]> {
+        Some(std::slice::from_ref(&self.0))
+    }
 }
 
 /// Predicate to match *GET* http method
@@ -170,6 +221,40 @@ pub fn Method(method: http::Method) -> MethodFilter {
     MethodFilter(method)
 }
 
+/// Predicate to match any of specified http methods
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::http::Method;
+/// use actix_web2::{filter, App, HttpResponse};
+///
+/// fn main() {
+///     App::new().resource("/index.html", |r| {
+///         r.route(|mut r| {
+///             r.filter(filter::Methods(&[Method::GET, Method::HEAD]));
+///             r.to(|| HttpResponse::Ok())
+///         })
+///         .to(|| HttpResponse::MethodNotAllowed())
+///     });
+/// }
+/// ```
+pub fn Methods(methods: &[http::Method]) -> MethodsFilter {
+    MethodsFilter(methods.to_vec())
+}
+
+#[doc(hidden)]
+pub struct MethodsFilter(Vec<http::Method>);
+
+impl Filter for MethodsFilter {
+    fn check(&self, request: &HttpRequest) -> bool {
+        self.0.iter().any(|m| request.method() == m)
+    }
+
+    fn methods(&self) -> Option<&[http::Method]> {
+        Some(&self.0)
+    }
+}
+
 /// Return predicate that matches if request contains specified header and
 /// value.
 pub fn Header(name: &'static str, value: &'static str) -> HeaderFilter {
@@ -191,52 +276,397 @@ impl Filter for HeaderFilter {
     }
 }
 
+/// Return predicate that matches if request's `Content-Type` is the
+/// specified media type.
+///
+/// Request's `Content-Type` is parsed as a media type, type and subtype
+/// are compared case insensitive, parameters, i.e. `charset`, are ignored.
+/// Predicate does not match if header is missing or could not be parsed.
+/// Request body is not read. Panics if `mime` is not a valid media type.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{filter, App, HttpResponse};
+///
+/// fn main() {
+///     App::new().resource("/submit", |r| {
+///         r.route(|mut r| {
+///             r.filter(filter::ContentType("application/json"));
+///             r.to(|| HttpResponse::Ok().body("json"))
+///         })
+///         .route(|mut r| {
+///             r.filter(filter::ContentType("application/x-www-form-urlencoded"));
+///             r.to(|| HttpResponse::Ok().body("form"))
+///         })
+///     });
+/// }
+/// ```
+pub fn ContentType(mime: &'static str) -> ContentTypeFilter {
+    ContentTypeFilter(
+        mime.parse()
+            .expect("ContentType filter requires a valid media type"),
+    )
+}
+
+#[doc(hidden)]
+pub struct ContentTypeFilter(Mime);
+
+impl Filter for ContentTypeFilter {
+    fn check(&self, req: &HttpRequest) -> bool {
+        req.headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|val| val.to_str().ok())
+            .and_then(|val| val.parse::<Mime>().ok())
+            .map(|mime| {
+                mime.type_() == self.0.type_()
+                    && mime.subtype() == self.0.subtype()
+                    && mime.suffix() == self.0.suffix()
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// Return predicate that matches if specified media type is acceptable
+/// for the request.
+///
+/// Media ranges of the `Accept` header are matched against the media
+/// type, wildcards like `*/*` and `text/*` are supported and the most
+/// specific matching range is used. Media type is not acceptable if no
+/// range matches or matching range has `q=0`. Requests without `Accept`
+/// header, or with header none of which ranges could be parsed, accept any
+/// media type. Panics if `mime` is not a valid media type.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{filter, App, HttpResponse};
+///
+/// fn main() {
+///     App::new().resource("/users", |r| {
+///         r.route(|mut r| {
+///             r.filter(filter::Accepts("text/html"));
+///             r.to(|| HttpResponse::Ok().content_type("text/html").body("<ul></ul>"))
+///         })
+///         .to(|| HttpResponse::Ok().content_type("application/json").body("[]"))
+///     });
+/// }
+/// ```
+pub fn Accepts(mime: &'static str) -> AcceptsFilter {
+    AcceptsFilter(
+        mime.parse()
+            .expect("Accepts filter requires a valid media type"),
+    )
+}
+
+#[doc(hidden)]
+pub struct AcceptsFilter(Mime);
+
+impl Filter for AcceptsFilter {
+    fn check(&self, req: &HttpRequest) -> bool {
+        let ranges = accept_ranges(req);
+        if ranges.is_empty() {
+            return true;
+        }
+        accept_quality(&ranges, &self.0)
+            .map(|q| q > 0)
+            .unwrap_or(false)
+    }
+}
+
+/// Return predicate that matches if request contains cookie with specified
+/// name and value.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{filter, App, HttpResponse};
+///
+/// fn main() {
+///     App::new().resource("/index.html", |r| {
+///         r.route(|mut r| {
+///             r.filter(filter::Cookie("visited", "true"));
+///             r.to(|| HttpResponse::Ok().body("Welcome back!"))
+///         })
+///         .to(|| HttpResponse::Ok().body("Welcome!"))
+///     });
+/// }
+/// ```
+pub fn Cookie<N: Into<String>, V: Into<String>>(name: N, value: V) -> CookieFilter {
+    CookieFilter(name.into(), Some(value.into()))
+}
+
+/// Return predicate that matches if request contains cookie with specified
+/// name, regardless of its value.
+pub fn CookiePresent<N: Into<String>>(name: N) -> CookieFilter {
+    CookieFilter(name.into(), None)
+}
+
+#[doc(hidden)]
+pub struct CookieFilter(String, Option<String>);
+
+impl Filter for CookieFilter {
+    fn check(&self, req: &HttpRequest) -> bool {
+        request_cookies(req).iter().any(|(name, value)| {
+            *name == self.0 && self.1.as_ref().map(|v| v == value).unwrap_or(true)
+        })
+    }
+}
+
+/// Return predicate that matches if request's query string contains
+/// parameter with specified name and value.
+///
+/// Values are url decoded before comparison, repeated parameter matches
+/// if any of its values matches.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{filter, App, HttpResponse};
+///
+/// fn main() {
+///     App::new().resource("/index.html", |r| {
+///         r.route(|mut r| {
+///             r.filter(filter::Query("beta", "true"));
+///             r.to(|| HttpResponse::Ok().body("New version"))
+///         })
+///         .to(|| HttpResponse::Ok().body("Old version"))
+///     });
+/// }
+/// ```
+pub fn Query<N: Into<String>, V: Into<String>>(name: N, value: V) -> QueryFilter {
+    QueryFilter(name.into(), Some(value.into()))
+}
+
+/// Return predicate that matches if request's query string contains
+/// parameter with specified name, regardless of its value.
+pub fn QueryPresent<N: Into<String>>(name: N) -> QueryFilter {
+    QueryFilter(name.into(), None)
+}
+
+#[doc(hidden)]
+pub struct QueryFilter(String, Option<String>);
+
+impl Filter for QueryFilter {
+    fn check(&self, req: &HttpRequest) -> bool {
+        serde_urlencoded::from_str::<Vec<(String, String)>>(req.query_string())
+            .map(|params| {
+                params.iter().any(|(name, value)| {
+                    *name == self.0
+                        && self.1.as_ref().map(|v| v == value).unwrap_or(true)
+                })
+            })
+            .unwrap_or(false)
+    }
+}
+
 /// Return predicate that matches if request contains specified Host name.
 ///
-/// ```rust
-/// # extern crate actix_web;
-/// use actix_web::{pred, App, HttpResponse};
+/// Host is taken from the `Host` header or request uri. Forwarded headers
+/// are set by clients as well, so they are used only if enabled with
+/// `HostFilter::trust_forwarded()`. Comparison is case insensitive, port of
+/// the request's host is ignored unless filter's host contains a port.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{filter, App, HttpResponse};
 ///
 /// fn main() {
 ///     App::new().resource("/index.html", |r| {
-///         r.route()
-///             .filter(pred::Host("www.rust-lang.org"))
-///             .f(|_| HttpResponse::MethodNotAllowed())
+///         r.route(|mut r| {
+///             r.filter(filter::Host("www.rust-lang.org"));
+///             r.to(|| HttpResponse::Ok())
+///         })
+///         .to(|| HttpResponse::NotFound())
 ///     });
 /// }
 /// ```
 pub fn Host<H: AsRef<str>>(host: H) -> HostFilter {
-    HostFilter(host.as_ref().to_string(), None)
+    HostFilter {
+        host: host.as_ref().to_string(),
+        scheme: None,
+        forwarded: false,
+    }
 }
 
 #[doc(hidden)]
-pub struct HostFilter(String, Option<String>);
+pub struct HostFilter {
+    host: String,
+    scheme: Option<String>,
+    forwarded: bool,
+}
 
 impl HostFilter {
     /// Set reuest scheme to match
+    ///
+    /// Scheme is taken from request uri if it is absolute. Server requests
+    /// usually carry only a path, in that case `https` is used for
+    /// connections accepted by the tls service and `http` otherwise.
     pub fn scheme<H: AsRef<str>>(&mut self, scheme: H) {
-        self.1 = Some(scheme.as_ref().to_string())
+        self.scheme = Some(scheme.as_ref().to_string())
+    }
+
+    /// Use host and scheme from forwarded headers.
+    ///
+    /// First hop of the `Forwarded` header, `X-Forwarded-Host` and
+    /// `X-Forwarded-Proto` headers take precedence over `Host` header and
+    /// request uri. Enable only if application is behind a proxy that
+    /// overwrites these headers.
+    pub fn trust_forwarded(&mut self) {
+        self.forwarded = true
     }
 }
 
 impl Filter for HostFilter {
-    fn check(&self, _req: &HttpRequest) -> bool {
-        // let info = req.connection_info();
-        // if let Some(ref scheme) = self.1 {
-        //     self.0 == info.host() && scheme == info.scheme()
-        // } else {
-        //     self.0 == info.host()
-        // }
-        false
+    fn check(&self, req: &HttpRequest) -> bool {
+        let forwarded = if self.forwarded {
+            Forwarded::from_headers(req.headers()).ok()
+        } else {
+            None
+        };
+        let hop = forwarded.as_ref().and_then(|f| f.hops().first());
+
+        let host = hop
+            .and_then(|hop| hop.host())
+            .or_else(|| self.forwarded_value(req, X_FORWARDED_HOST))
+            .or_else(|| {
+                req.headers()
+                    .get(header::HOST)
+                    .and_then(|val| val.to_str().ok())
+            })
+            .or_else(|| req.uri().authority_part().map(|a| a.as_str()));
+        let host = match host {
+            Some(host) => host.trim(),
+            None => return false,
+        };
+
+        let host = if has_port(&self.host) {
+            host
+        } else {
+            strip_port(host)
+        };
+        if !host.eq_ignore_ascii_case(&self.host) {
+            return false;
+        }
+
+        if let Some(ref scheme) = self.scheme {
+            let req_scheme = hop
+                .and_then(|hop| hop.proto())
+                .or_else(|| self.forwarded_value(req, X_FORWARDED_PROTO))
+                .or_else(|| req.uri().scheme_part().map(|s| s.as_str()))
+                .unwrap_or_else(|| {
+                    if req.extensions().get::<TlsInfo>().is_some() {
+                        "https"
+                    } else {
+                        "http"
+                    }
+                });
+            req_scheme.eq_ignore_ascii_case(scheme)
+        } else {
+            true
+        }
+    }
+}
+
+impl HostFilter {
+    fn forwarded_value<'a>(&self, req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+        if self.forwarded {
+            first_value(req, name)
+        } else {
+            None
+        }
+    }
+}
+
+const X_FORWARDED_HOST: &str = "x-forwarded-host";
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+
+/// First value of comma separated header
+fn first_value<'a>(req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    req.headers()
+        .get(name)
+        .and_then(|val| val.to_str().ok())
+        .and_then(|val| val.split(',').next())
+        .map(|val| val.trim())
+}
+
+/// Check if host contains a port, ipv6 addresses are enclosed in brackets
+fn has_port(host: &str) -> bool {
+    match host.rfind(']') {
+        Some(pos) => host[pos..].contains(':'),
+        None => host.contains(':'),
+    }
+}
+
+fn strip_port(host: &str) -> &str {
+    match host.rfind(']') {
+        Some(pos) => &host[..=pos],
+        None => host.split(':').next().unwrap_or(host),
+    }
+}
+
+/// Return predicate that matches if request target is signed with the key.
+///
+/// Signature is hex encoded *HMAC-SHA256* of the request target, path and
+/// query string as they are received, taken from the specified header,
+/// `sha256=` prefix is optional.
+///
+/// Filters are checked before request body is read, so body is not covered
+/// by the signature. To verify signature of the body use `SignedPayload`
+/// extractor. Filter could be used for routing requests with missing or
+/// invalid signature to a separate handler.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{filter, App, HttpResponse};
+///
+/// fn main() {
+///     App::new().resource("/hook", |r| {
+///         r.route(|mut r| {
+///             r.filter(filter::Signed("secret", "X-Signature"));
+///             r.to(|| HttpResponse::Ok())
+///         })
+///         .to(|| HttpResponse::Unauthorized())
+///     });
+/// }
+/// ```
+pub fn Signed<K: AsRef<[u8]>>(key: K, header: &'static str) -> SignedFilter {
+    SignedFilter {
+        key: key.as_ref().to_vec(),
+        header: header::HeaderName::try_from(header).unwrap(),
+    }
+}
+
+#[doc(hidden)]
+pub struct SignedFilter {
+    key: Vec<u8>,
+    header: header::HeaderName,
+}
+
+impl Filter for SignedFilter {
+    fn check(&self, req: &HttpRequest) -> bool {
+        let signature = match req
+            .headers()
+            .get(&self.header)
+            .and_then(|val| val.to_str().ok())
+            .and_then(decode_signature)
+        {
+            Some(signature) => signature,
+            None => return false,
+        };
+
+        match Hmac::<Sha256>::new_varkey(&self.key) {
+            Ok(mut mac) => {
+                mac.input(req.request_target().as_bytes());
+                mac.verify(&signature).is_ok()
+            }
+            Err(_) => false,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use actix_http::http::{header, Method};
-    use actix_http::test::TestRequest;
 
     use super::*;
+    use crate::test::TestRequest;
 
     #[test]
     fn test_header() {
@@ -244,84 +674,122 @@ mod tests {
             header::TRANSFER_ENCODING,
             header::HeaderValue::from_static("chunked"),
         )
-        .finish();
+        .request();
 
         let pred = Header("transfer-encoding", "chunked");
-        assert!(pred.check(&req, req.state()));
+        assert!(pred.check(&req));
 
         let pred = Header("transfer-encoding", "other");
-        assert!(!pred.check(&req, req.state()));
+        assert!(!pred.check(&req));
 
         let pred = Header("content-type", "other");
-        assert!(!pred.check(&req, req.state()));
+        assert!(!pred.check(&req));
     }
 
     #[test]
     fn test_host() {
-        let req = TestRequest::default()
-            .header(
-                header::HOST,
-                header::HeaderValue::from_static("www.rust-lang.org"),
-            )
-            .finish();
+        let req = TestRequest::with_header(header::HOST, "www.rust-lang.org").request();
 
         let pred = Host("www.rust-lang.org");
-        assert!(pred.check(&req, req.state()));
+        assert!(pred.check(&req));
+
+        let pred = Host("WWW.Rust-Lang.org");
+        assert!(pred.check(&req));
 
         let pred = Host("localhost");
-        assert!(!pred.check(&req, req.state()));
+        assert!(!pred.check(&req));
+
+        let mut pred = Host("www.rust-lang.org");
+        pred.scheme("https");
+        assert!(!pred.check(&req));
+        req.extensions_mut()
+            .insert(TlsInfo::new("TLSv1.3", "TLS_AES_128_GCM_SHA256"));
+        assert!(pred.check(&req));
+
+        let req = TestRequest::with_header(header::HOST, "www.rust-lang.org:8080")
+            .header("x-forwarded-proto", "https")
+            .request();
+
+        let mut pred = Host("www.rust-lang.org");
+        pred.scheme("https");
+        assert!(!pred.check(&req));
+        pred.trust_forwarded();
+        assert!(pred.check(&req));
+        assert!(Host("www.rust-lang.org:8080").check(&req));
+        assert!(!Host("www.rust-lang.org:443").check(&req));
+
+        let req = TestRequest::with_header(header::HOST, "10.0.0.1")
+            .header("x-forwarded-host", "rust-lang.org, 10.0.0.1")
+            .header(header::FORWARDED, "host=www.rust-lang.org")
+            .request();
+        assert!(!Host("rust-lang.org").check(&req));
+        assert!(Host("10.0.0.1").check(&req));
+
+        let mut pred = Host("www.rust-lang.org");
+        pred.trust_forwarded();
+        assert!(pred.check(&req));
+
+        let req = TestRequest::with_header(header::HOST, "10.0.0.1")
+            .header("x-forwarded-host", "rust-lang.org, 10.0.0.1")
+            .request();
+        let mut pred = Host("rust-lang.org");
+        pred.trust_forwarded();
+        assert!(pred.check(&req));
+
+        let req = TestRequest::with_header(header::HOST, "[::1]:8080").request();
+        assert!(Host("[::1]").check(&req));
     }
 
     #[test]
     fn test_methods() {
-        let req = TestRequest::default().finish();
-        let req2 = TestRequest::default().method(Method::POST).finish();
+        let req = TestRequest::default().request();
+        let req2 = TestRequest::default().method(Method::POST).request();
 
-        assert!(Get().check(&req, req.state()));
-        assert!(!Get().check(&req2, req2.state()));
-        assert!(Post().check(&req2, req2.state()));
-        assert!(!Post().check(&req, req.state()));
+        assert!(Get().check(&req));
+        assert!(!Get().check(&req2));
+        assert!(Post().check(&req2));
+        assert!(!Post().check(&req));
 
-        let r = TestRequest::default().method(Method::PUT).finish();
-        assert!(Put().check(&r, r.state()));
-        assert!(!Put().check(&req, req.state()));
+        let r = TestRequest::default().method(Method::PUT).request();
+        assert!(Put().check(&r));
+        assert!(!Put().check(&req));
 
-        let r = TestRequest::default().method(Method::DELETE).finish();
-        assert!(Delete().check(&r, r.state()));
-        assert!(!Delete().check(&req, req.state()));
+        let r = TestRequest::default().method(Method::DELETE).request();
+        assert!(Delete().check(&r));
+        assert!(!Delete().check(&req));
 
-        let r = TestRequest::default().method(Method::HEAD).finish();
-        assert!(Head().check(&r, r.state()));
-        assert!(!Head().check(&req, req.state()));
+        let r = TestRequest::default().method(Method::HEAD).request();
+        assert!(Head().check(&r));
+        assert!(!Head().check(&req));
 
-        let r = TestRequest::default().method(Method::OPTIONS).finish();
-        assert!(Options().check(&r, r.state()));
-        assert!(!Options().check(&req, req.state()));
+        let r = TestRequest::default().method(Method::OPTIONS).request();
+        assert!(Options().check(&r));
+        assert!(!Options().check(&req));
 
-        let r = TestRequest::default().method(Method::CONNECT).finish();
-        assert!(Connect().check(&r, r.state()));
-        assert!(!Connect().check(&req, req.state()));
+        let r = TestRequest::default().method(Method::CONNECT).request();
+        assert!(Connect().check(&r));
+        assert!(!Connect().check(&req));
 
-        let r = TestRequest::default().method(Method::PATCH).finish();
-        assert!(Patch().check(&r, r.state()));
-        assert!(!Patch().check(&req, req.state()));
+        let r = TestRequest::default().method(Method::PATCH).request();
+        assert!(Patch().check(&r));
+        assert!(!Patch().check(&req));
 
-        let r = TestRequest::default().method(Method::TRACE).finish();
-        assert!(Trace().check(&r, r.state()));
-        assert!(!Trace().check(&req, req.state()));
+        let r = TestRequest::default().method(Method::TRACE).request();
+        assert!(Trace().check(&r));
+        assert!(!Trace().check(&req));
     }
 
     #[test]
     fn test_preds() {
-        let r = TestRequest::default().method(Method::TRACE).finish();
+        let r = TestRequest::default().method(Method::TRACE).request();
 
-        assert!(Not(Get()).check(&r, r.state()));
-        assert!(!Not(Trace()).check(&r, r.state()));
+        assert!(Not(Get()).check(&r));
+        assert!(!Not(Trace()).check(&r));
 
-        assert!(All(Trace()).and(Trace()).check(&r, r.state()));
-        assert!(!All(Get()).and(Trace()).check(&r, r.state()));
+        assert!(All(Trace()).and(Trace()).check(&r));
+        assert!(!All(Get()).and(Trace()).check(&r));
 
-        assert!(Any(Get()).or(Trace()).check(&r, r.state()));
-        assert!(!Any(Get()).or(Get()).check(&r, r.state()));
+        assert!(Any(Get()).or(Trace()).check(&r));
+        assert!(!Any(Get()).or(Get()).check(&r));
     }
 }
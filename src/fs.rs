@@ -56,11 +56,11 @@ use crate::service::{ServiceRequest, ServiceResponse};
 ///
 ///## Example
 ///
-///```rust
+///```rust,ignore
 /// extern crate mime;
-/// extern crate actix_web;
-/// use actix_web::http::header::DispositionType;
-/// use actix_web::fs::{StaticFileConfig, NamedFile};
+/// extern crate actix_web2;
+/// use actix_web2::http::header::DispositionType;
+/// use actix_web2::fs::{StaticFileConfig, NamedFile};
 ///
 /// #[derive(Default)]
 /// struct MyConfig;
@@ -144,10 +144,10 @@ impl NamedFile {
     ///
     /// # Examples
     ///
-    /// ```no_run
-    /// extern crate actix_web;
+    /// ```rust,ignore
+    /// extern crate actix_web2;
     ///
-    /// use actix_web::fs::NamedFile;
+    /// use actix_web2::fs::NamedFile;
     /// use std::io::{self, Write};
     /// use std::env;
     /// use std::fs::File;
@@ -167,8 +167,8 @@ impl NamedFile {
     ///
     /// # Examples
     ///
-    /// ```rust
-    /// use actix_web::fs::NamedFile;
+    /// ```rust,ignore
+    /// use actix_web2::fs::NamedFile;
     ///
     /// let file = NamedFile::open("foo.txt");
     /// ```
@@ -185,10 +185,10 @@ impl<C: StaticFileConfig> NamedFile<C> {
     ///
     /// # Examples
     ///
-    /// ```no_run
-    /// extern crate actix_web;
+    /// ```rust,ignore
+    /// extern crate actix_web2;
     ///
-    /// use actix_web::fs::{DefaultConfig, NamedFile};
+    /// use actix_web2::fs::{DefaultConfig, NamedFile};
     /// use std::io::{self, Write};
     /// use std::env;
     /// use std::fs::File;
@@ -251,8 +251,8 @@ impl<C: StaticFileConfig> NamedFile<C> {
     ///
     /// # Examples
     ///
-    /// ```rust
-    /// use actix_web::fs::{DefaultConfig, NamedFile};
+    /// ```rust,ignore
+    /// use actix_web2::fs::{DefaultConfig, NamedFile};
     ///
     /// let file = NamedFile::open_with_config("foo.txt", DefaultConfig);
     /// ```
@@ -273,9 +273,9 @@ impl<C: StaticFileConfig> NamedFile<C> {
     ///
     /// # Examples
     ///
-    /// ```rust
+    /// ```rust,ignore
     /// # use std::io;
-    /// use actix_web::fs::NamedFile;
+    /// use actix_web2::fs::NamedFile;
     ///
     /// # fn path() -> io::Result<()> {
     /// let file = NamedFile::open("test.txt")?;
@@ -393,7 +393,10 @@ fn any_match(etag: Option<&header::EntityTag>, req: &HttpRequest) -> bool {
 }
 
 /// Returns true if `req` doesn't have an `If-None-Match` header matching `req`.
-fn none_match(etag: Option<&header::EntityTag>, req: &HttpRequest) -> bool {
+pub(crate) fn none_match(
+    etag: Option<&header::EntityTag>,
+    req: &HttpRequest,
+) -> bool {
     match req.get_header::<header::IfNoneMatch>() {
         Some(header::IfNoneMatch::Any) => false,
         Some(header::IfNoneMatch::Items(ref items)) => {
@@ -421,7 +424,8 @@ impl<C: StaticFileConfig> Responder for NamedFile<C> {
                 .header(
                     header::CONTENT_DISPOSITION,
                     self.content_disposition.to_string(),
-                );
+                )
+                .header(header::ACCEPT_RANGES, "none");
             // TODO blocking by compressing
             // if let Some(current_encoding) = self.encoding {
             //     resp.content_encoding(current_encoding);
@@ -567,6 +571,20 @@ pub struct ChunkedReadFile {
     counter: u64,
 }
 
+impl ChunkedReadFile {
+    /// Read whole file of specified size on the default cpu pool
+    pub(crate) fn new(file: File, size: u64) -> Self {
+        ChunkedReadFile {
+            size,
+            offset: 0,
+            cpu_pool: DEFAULT_CPUPOOL.lock().clone(),
+            file: Some(file),
+            fut: None,
+            counter: 0,
+        }
+    }
+}
+
 impl Stream for ChunkedReadFile {
     type Item = Bytes;
     type Error = Error;
@@ -717,17 +735,23 @@ fn directory_listing(
 
 /// Static files handling
 ///
-/// `StaticFile` handler must be registered with `App::handler()` method,
-/// because `StaticFile` handler requires access sub-path information.
+/// `StaticFiles` service must be registered with `App::service()` method
+/// under a prefix resource, files are looked up by the rest of the path
+/// after the prefix.
 ///
-/// ```rust
-/// # extern crate actix_web;
-/// use actix_web::{fs, App};
+/// ```rust,ignore
+/// # extern crate actix_router;
+/// # extern crate actix_service;
+/// # extern crate actix_web2;
+/// use actix_router::ResourceDef;
+/// use actix_service::NewService;
+/// use actix_web2::{fs, App};
 ///
 /// fn main() {
-///     let app = App::new()
-///         .handler("/static", fs::StaticFiles::new(".").unwrap())
-///         .finish();
+///     let app = App::new().service(
+///         ResourceDef::prefix("/static"),
+///         fs::StaticFiles::new(".").unwrap().map_err(|_| ()),
+///     );
 /// }
 /// ```
 pub struct StaticFiles<S, C = DefaultConfig> {
@@ -977,7 +1001,7 @@ impl<P> FromRequest<P> for PathBuf {
 /// Env variable for default cpu pool size
 const ENV_CPU_POOL_VAR: &str = "ACTIX_CPU_POOL";
 lazy_static! {
-    static ref DEFAULT_CPUPOOL: Mutex<CpuPool> = {
+    pub(crate) static ref DEFAULT_CPUPOOL: Mutex<CpuPool> = {
         let default = match env::var(ENV_CPU_POOL_VAR) {
             Ok(val) => {
                 if let Ok(val) = val.parse() {
@@ -1034,7 +1058,7 @@ impl ResponseError for UriSegmentError {
 
 /// HTTP Range header representation.
 #[derive(Debug, Clone, Copy)]
-struct HttpRange {
+pub(crate) struct HttpRange {
     pub start: u64,
     pub length: u64,
 }
@@ -1047,7 +1071,7 @@ impl HttpRange {
     ///
     /// `header` is HTTP Range header (e.g. `bytes=bytes=0-9`).
     /// `size` is full size of response (file).
-    fn parse(header: &str, size: u64) -> Result<Vec<HttpRange>, ()> {
+    pub(crate) fn parse(header: &str, size: u64) -> Result<Vec<HttpRange>, ()> {
         if header.is_empty() {
             return Ok(Vec::new());
         }
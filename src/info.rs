@@ -1,16 +1,22 @@
 use std::cell::Ref;
+use std::net::SocketAddr;
 
 use actix_http::http::header::{self, HeaderName};
 use actix_http::RequestHead;
 
+use crate::extractor::Forwarded;
+
 const X_FORWARDED_FOR: &[u8] = b"x-forwarded-for";
 const X_FORWARDED_HOST: &[u8] = b"x-forwarded-host";
 const X_FORWARDED_PROTO: &[u8] = b"x-forwarded-proto";
 
-pub enum ConnectionInfoError {
-    UnknownHost,
-    UnknownScheme,
-}
+/// Address of the connection peer.
+///
+/// Address is stored in request extensions by the transport, i.e.
+/// `dev::TlsH1Service` stores address reported by
+/// `dev::TlsStream::peer_addr()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerAddr(pub SocketAddr);
 
 /// `HttpRequest` connection information
 #[derive(Clone, Default)]
@@ -18,56 +24,29 @@ pub struct ConnectionInfo {
     scheme: String,
     host: String,
     remote: Option<String>,
-    peer: Option<String>,
 }
 
 impl ConnectionInfo {
     /// Create *ConnectionInfo* instance for a request.
     pub fn get(req: &RequestHead) -> Ref<Self> {
         if !req.extensions().contains::<ConnectionInfo>() {
-            req.extensions_mut().insert(ConnectionInfo::new(req));
+            let info = ConnectionInfo::new(req);
+            req.extensions_mut().insert(info);
         }
         Ref::map(req.extensions(), |e| e.get().unwrap())
     }
 
-    #[cfg_attr(feature = "cargo-clippy", allow(cyclomatic_complexity))]
     fn new(req: &RequestHead) -> ConnectionInfo {
         let mut host = None;
         let mut scheme = None;
         let mut remote = None;
-        let mut peer = None;
-
-        // load forwarded header
-        for hdr in req.headers.get_all(header::FORWARDED) {
-            if let Ok(val) = hdr.to_str() {
-                for pair in val.split(';') {
-                    for el in pair.split(',') {
-                        let mut items = el.trim().splitn(2, '=');
-                        if let Some(name) = items.next() {
-                            if let Some(val) = items.next() {
-                                match &name.to_lowercase() as &str {
-                                    "for" => {
-                                        if remote.is_none() {
-                                            remote = Some(val.trim());
-                                        }
-                                    }
-                                    "proto" => {
-                                        if scheme.is_none() {
-                                            scheme = Some(val.trim());
-                                        }
-                                    }
-                                    "host" => {
-                                        if host.is_none() {
-                                            host = Some(val.trim());
-                                        }
-                                    }
-                                    _ => (),
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+
+        // load forwarded header, first hop describes client facing proxy
+        let forwarded = Forwarded::from_headers(&req.headers).ok();
+        if let Some(hop) = forwarded.as_ref().and_then(|f| f.hops().first()) {
+            remote = hop.forwarded_for();
+            scheme = hop.proto();
+            host = hop.host();
         }
 
         // scheme
@@ -82,9 +61,6 @@ impl ConnectionInfo {
             }
             if scheme.is_none() {
                 scheme = req.uri.scheme_part().map(|a| a.as_str());
-                if scheme.is_none() && req.server_settings().secure() {
-                    scheme = Some("https")
-                }
             }
         }
 
@@ -104,9 +80,6 @@ impl ConnectionInfo {
                 }
                 if host.is_none() {
                     host = req.uri.authority_part().map(|a| a.as_str());
-                    if host.is_none() {
-                        host = Some(req.server_settings().host());
-                    }
                 }
             }
         }
@@ -121,17 +94,20 @@ impl ConnectionInfo {
                     remote = h.split(',').next().map(|v| v.trim());
                 }
             }
-            if remote.is_none() {
-                // get peeraddr from socketaddr
-                peer = req.peer_addr().map(|addr| format!("{}", addr));
-            }
         }
+        let remote = match remote {
+            Some(remote) => Some(remote.to_owned()),
+            // get peer address of the connection
+            None => req
+                .extensions()
+                .get::<PeerAddr>()
+                .map(|peer| peer.0.ip().to_string()),
+        };
 
         ConnectionInfo {
+            remote,
             scheme: scheme.unwrap_or("http").to_owned(),
             host: host.unwrap_or("localhost").to_owned(),
-            remote: remote.map(|s| s.to_owned()),
-            peer: peer,
         }
     }
 
@@ -155,7 +131,8 @@ impl ConnectionInfo {
     /// - X-Forwarded-Host
     /// - Host
     /// - Uri
-    /// - Server hostname
+    ///
+    /// `localhost` is used if hostname is unknown.
     pub fn host(&self) -> &str {
         &self.host
     }
@@ -166,16 +143,15 @@ impl ConnectionInfo {
     ///
     /// - Forwarded
     /// - X-Forwarded-For
-    /// - peer name of opened socket
+    /// - peer address of the connection, see `dev::PeerAddr`
+    ///
+    /// Both headers are set by clients as well, so the value should not be
+    /// trusted unless application is behind a proxy that overwrites them.
+    /// Peer address is available only if transport stores it, i.e. for
+    /// connections served by `dev::TlsH1Service`.
     #[inline]
     pub fn remote(&self) -> Option<&str> {
-        if let Some(ref r) = self.remote {
-            Some(r)
-        } else if let Some(ref peer) = self.peer {
-            Some(peer)
-        } else {
-            None
-        }
+        self.remote.as_ref().map(|r| r.as_str())
     }
 }
 
@@ -187,20 +163,19 @@ mod tests {
     #[test]
     fn test_forwarded() {
         let req = TestRequest::default().request();
-        let mut info = ConnectionInfo::default();
-        info.update(&req);
+        let info = ConnectionInfo::get(req.head());
         assert_eq!(info.scheme(), "http");
-        assert_eq!(info.host(), "localhost:8080");
+        assert_eq!(info.host(), "localhost");
 
         let req = TestRequest::default()
             .header(
                 header::FORWARDED,
-                "for=192.0.2.60; proto=https; by=203.0.113.43; host=rust-lang.org",
+                "for=192.0.2.60; proto=https; by=203.0.113.43; host=rust-lang.org, \
+                 for=198.51.100.17",
             )
             .request();
 
-        let mut info = ConnectionInfo::default();
-        info.update(&req);
+        let info = ConnectionInfo::get(req.head());
         assert_eq!(info.scheme(), "https");
         assert_eq!(info.host(), "rust-lang.org");
         assert_eq!(info.remote(), Some("192.0.2.60"));
@@ -209,8 +184,7 @@ mod tests {
             .header(header::HOST, "rust-lang.org")
             .request();
 
-        let mut info = ConnectionInfo::default();
-        info.update(&req);
+        let info = ConnectionInfo::get(req.head());
         assert_eq!(info.scheme(), "http");
         assert_eq!(info.host(), "rust-lang.org");
         assert_eq!(info.remote(), None);
@@ -218,23 +192,35 @@ mod tests {
         let req = TestRequest::default()
             .header(X_FORWARDED_FOR, "192.0.2.60")
             .request();
-        let mut info = ConnectionInfo::default();
-        info.update(&req);
+        let info = ConnectionInfo::get(req.head());
         assert_eq!(info.remote(), Some("192.0.2.60"));
 
         let req = TestRequest::default()
             .header(X_FORWARDED_HOST, "192.0.2.60")
             .request();
-        let mut info = ConnectionInfo::default();
-        info.update(&req);
+        let info = ConnectionInfo::get(req.head());
         assert_eq!(info.host(), "192.0.2.60");
         assert_eq!(info.remote(), None);
 
         let req = TestRequest::default()
             .header(X_FORWARDED_PROTO, "https")
             .request();
-        let mut info = ConnectionInfo::default();
-        info.update(&req);
+        let info = ConnectionInfo::get(req.head());
         assert_eq!(info.scheme(), "https");
+
+        // peer address is used if headers are missing
+        let req = TestRequest::default().request();
+        req.extensions_mut()
+            .insert(PeerAddr("192.0.2.1:8080".parse().unwrap()));
+        let info = ConnectionInfo::get(req.head());
+        assert_eq!(info.remote(), Some("192.0.2.1"));
+
+        let req = TestRequest::default()
+            .header(X_FORWARDED_FOR, "192.0.2.60")
+            .request();
+        req.extensions_mut()
+            .insert(PeerAddr("192.0.2.1:8080".parse().unwrap()));
+        let info = ConnectionInfo::get(req.head());
+        assert_eq!(info.remote(), Some("192.0.2.60"));
     }
 }
@@ -8,9 +8,10 @@ extern crate lazy_static;
 
 mod app;
 mod extractor;
+mod favicon;
 pub mod handler;
 mod helpers;
-// mod info;
+mod info;
 pub mod filter;
 pub mod middleware;
 mod request;
@@ -19,24 +20,45 @@ mod responder;
 mod route;
 mod service;
 mod state;
-mod fs;
+pub mod test;
+mod tls;
+pub mod fs;
 
 // re-export for convenience
 pub use actix_http::Response as HttpResponse;
 pub use actix_http::{http, Error, HttpMessage, ResponseError};
 
 pub use crate::app::App;
-pub use crate::extractor::{Form, Json, Path, Query};
+pub use crate::extractor::{
+    Authorization, Body, ClientCert, Cookie, Cookies, DecodedPath, Form, Forwarded, Json,
+    MatchedPath, MultiSource, Multipart, Path, Payload, QsQuery, Query, RawQuery,
+    ReqData, RequireScopes, SignedPayload, Tail, TlsInfo, Valid,
+};
+#[cfg(feature = "uuid")]
+pub use crate::extractor::PathUuid;
+pub use crate::favicon::Favicon;
 pub use crate::handler::FromRequest;
 pub use crate::request::HttpRequest;
 pub use crate::resource::Resource;
-pub use crate::responder::{Either, Responder};
+pub use crate::responder::{Either, Either3, Responder};
 pub use crate::service::{ServiceRequest, ServiceResponse};
-pub use crate::state::State;
+pub use crate::state::{Data, ReloadableState, State};
 
 pub mod dev {
-    pub use crate::app::AppService;
+    pub use crate::app::{AppService, DuplicateHost, RouteInfo};
+    pub use crate::extractor::{
+        CookieConfig, ExtractConfig, Extracted, FormConfig, ForwardedHop, HeaderConfig,
+        JsonConfig, JsonKind, MultiSourceConfig, MultipartConfig, MultipartField,
+        PathConfig, PayloadConfig, QueryConfig, RequireScopesConfig, SignedPayloadConfig,
+    };
     pub use crate::handler::{AsyncFactory, Extract, Factory, Handle};
-    pub use crate::route::{Route, RouteBuilder};
-    // pub use crate::info::ConnectionInfo;
+    pub use crate::responder::{
+        Attachment, Blob, BodyStream, Channel, CustomizeResponder, Deadline, Deferred,
+        Encoded, Envelope, EnvelopeConfig, EnvelopeError, JsonResponse, Negotiate,
+        Problem, Progress, Spooled, Sse, SseEvent,
+    };
+    pub use crate::route::{BudgetExceeded, Route, RouteBuilder};
+    pub use crate::state::{StateFactory, StateRetry};
+    pub use crate::tls::{TlsH1Service, TlsStream};
+    pub use crate::info::{ConnectionInfo, PeerAddr};
 }
@@ -0,0 +1,210 @@
+//! Middleware for logging request and response bodies
+use std::rc::Rc;
+use std::{cmp, fmt};
+
+use actix_http::body::{Body, BodyLength, MessageBody, ResponseBody};
+use actix_http::error::PayloadError;
+use actix_http::{Error, HttpMessage, Payload, PayloadStream};
+use actix_service::{IntoNewTransform, Service, Transform};
+use bytes::{Bytes, BytesMut};
+use futures::{Async, Future, Poll, Stream};
+use log::debug;
+
+use crate::middleware::MiddlewareFactory;
+use crate::service::{ServiceRequest, ServiceResponse};
+
+/// `Middleware` for capturing request and response bodies to the log.
+///
+/// Bodies are logged with `debug` level once they are completely consumed.
+/// Capture never buffers more than `limit` bytes of each body, the rest of
+/// the body is still passed to the handler and to the client, but logged
+/// body is truncated. By default limit is 4Kb.
+///
+/// Middleware could be registered for the whole application or only for the
+/// specific resources.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{middleware, App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new().resource("/upload", |r| {
+///         r.middleware(middleware::BodyCapture::new().limit(1024))
+///             .post(|body: bytes::Bytes| HttpResponse::Ok().body(body))
+///     });
+/// }
+/// ```
+#[derive(Clone)]
+pub struct BodyCapture {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    limit: usize,
+}
+
+impl Default for BodyCapture {
+    fn default() -> Self {
+        BodyCapture {
+            inner: Rc::new(Inner { limit: 4096 }),
+        }
+    }
+}
+
+impl BodyCapture {
+    /// Construct `BodyCapture` middleware.
+    pub fn new() -> BodyCapture {
+        BodyCapture::default()
+    }
+
+    /// Set maximum number of bytes captured from each body.
+    pub fn limit(mut self, limit: usize) -> Self {
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .limit = limit;
+        self
+    }
+}
+
+impl<S, B> IntoNewTransform<MiddlewareFactory<BodyCapture, S>, S> for BodyCapture
+where
+    S: Service<Request = ServiceRequest<PayloadStream>, Response = ServiceResponse<B>>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    fn into_new_transform(self) -> MiddlewareFactory<BodyCapture, S> {
+        MiddlewareFactory::new(self)
+    }
+}
+
+impl<S, B> Transform<S> for BodyCapture
+where
+    S: Service<Request = ServiceRequest<PayloadStream>, Response = ServiceResponse<B>>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Request = ServiceRequest<PayloadStream>;
+    type Response = ServiceResponse;
+    type Error = S::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn call(
+        &mut self,
+        mut req: ServiceRequest<PayloadStream>,
+        srv: &mut S,
+    ) -> Self::Future {
+        let limit = self.inner.limit;
+        let label = Rc::new(format!("{} {}", req.method(), req.path()));
+
+        let payload = req.take_payload();
+        req.set_payload(Payload::Stream(Box::new(CapturePayload {
+            payload,
+            captured: Captured::new(limit),
+            label: label.clone(),
+        })));
+
+        Box::new(srv.call(req).map(move |res| {
+            res.map_body(move |_, body| {
+                ResponseBody::Body(Body::Message(Box::new(CaptureBody {
+                    body,
+                    captured: Captured::new(limit),
+                    label,
+                })))
+            })
+        }))
+    }
+}
+
+/// Bounded buffer for captured body
+struct Captured {
+    buf: BytesMut,
+    size: usize,
+    limit: usize,
+}
+
+impl Captured {
+    fn new(limit: usize) -> Self {
+        Captured {
+            buf: BytesMut::new(),
+            size: 0,
+            limit,
+        }
+    }
+
+    fn push(&mut self, chunk: &[u8]) {
+        let remaining = self.limit.saturating_sub(self.buf.len());
+        let len = cmp::min(remaining, chunk.len());
+        self.buf.extend_from_slice(&chunk[..len]);
+        self.size += chunk.len();
+    }
+}
+
+impl fmt::Display for Captured {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.buf))?;
+        if self.size > self.buf.len() {
+            write!(
+                f,
+                "... [truncated, {} of {} bytes]",
+                self.buf.len(),
+                self.size
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct CapturePayload {
+    payload: Payload<PayloadStream>,
+    captured: Captured,
+    label: Rc<String>,
+}
+
+impl Stream for CapturePayload {
+    type Item = Bytes;
+    type Error = PayloadError;
+
+    fn poll(&mut self) -> Poll<Option<Bytes>, PayloadError> {
+        match self.payload.poll()? {
+            Async::Ready(Some(chunk)) => {
+                self.captured.push(&chunk);
+                Ok(Async::Ready(Some(chunk)))
+            }
+            Async::Ready(None) => {
+                debug!("{} request body: {}", self.label, self.captured);
+                Ok(Async::Ready(None))
+            }
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+}
+
+struct CaptureBody<B> {
+    body: ResponseBody<B>,
+    captured: Captured,
+    label: Rc<String>,
+}
+
+impl<B: MessageBody> MessageBody for CaptureBody<B> {
+    fn length(&self) -> BodyLength {
+        self.body.length()
+    }
+
+    fn poll_next(&mut self) -> Poll<Option<Bytes>, Error> {
+        match self.body.poll_next()? {
+            Async::Ready(Some(chunk)) => {
+                self.captured.push(&chunk);
+                Ok(Async::Ready(Some(chunk)))
+            }
+            Async::Ready(None) => {
+                debug!("{} response body: {}", self.label, self.captured);
+                Ok(Async::Ready(None))
+            }
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Middleware for running tasks after response is sent
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use actix_http::body::{BodyLength, MessageBody, ResponseBody};
+use actix_http::http::StatusCode;
+use actix_http::Error;
+use actix_rt::Arbiter;
+use actix_service::{IntoNewTransform, Service, Transform};
+use bytes::Bytes;
+use futures::{Async, Future, IntoFuture, Poll};
+
+use crate::middleware::MiddlewareFactory;
+use crate::service::{ServiceRequest, ServiceResponse};
+
+/// Summary of the completed response, see `OnComplete`
+#[derive(Clone, Copy, Debug)]
+pub struct ResponseSummary {
+    status: StatusCode,
+    size: usize,
+    duration: Duration,
+}
+
+impl ResponseSummary {
+    /// Response status code
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Number of body bytes sent to the client
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Time from request start till response completion
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+type CompleteFn = Rc<Fn(ResponseSummary) -> Box<Future<Item = (), Error = ()>>>;
+
+/// `Middleware` that runs a task after response is sent.
+///
+/// Callback is invoked when response body is written or connection is
+/// dropped, it receives `ResponseSummary` and returns a future. Future is
+/// spawned on the current arbiter, so it never delays the client.
+/// This is useful for audit logging or cleanup.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{middleware, App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .middleware(middleware::OnComplete::new(|summary| {
+///             audit(summary.status(), summary.size(), summary.duration());
+///             Ok(())
+///         }))
+///         .resource("/index.html", |r| r.get(|| HttpResponse::Ok()));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct OnComplete {
+    f: CompleteFn,
+}
+
+impl OnComplete {
+    /// Construct `OnComplete` middleware with specified callback.
+    pub fn new<F, R>(f: F) -> Self
+    where
+        F: Fn(ResponseSummary) -> R + 'static,
+        R: IntoFuture<Item = (), Error = ()>,
+        R::Future: 'static,
+    {
+        OnComplete {
+            f: Rc::new(move |summary| {
+                let fut: Box<Future<Item = (), Error = ()>> =
+                    Box::new(f(summary).into_future());
+                fut
+            }),
+        }
+    }
+}
+
+impl<S, P, B> IntoNewTransform<MiddlewareFactory<OnComplete, S>, S> for OnComplete
+where
+    B: MessageBody,
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse<B>>,
+    S::Future: 'static,
+{
+    fn into_new_transform(self) -> MiddlewareFactory<OnComplete, S> {
+        MiddlewareFactory::new(self)
+    }
+}
+
+impl<S, P, B> Transform<S> for OnComplete
+where
+    B: MessageBody,
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse<B>>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest<P>;
+    type Response = ServiceResponse<CompleteBody<B>>;
+    type Error = S::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
+        let f = self.f.clone();
+        let start = Instant::now();
+
+        Box::new(srv.call(req).map(move |res| {
+            res.map_body(move |head, body| {
+                ResponseBody::Body(CompleteBody {
+                    body,
+                    status: head.status,
+                    size: 0,
+                    start,
+                    f: Some(f),
+                })
+            })
+        }))
+    }
+}
+
+#[doc(hidden)]
+pub struct CompleteBody<B> {
+    body: ResponseBody<B>,
+    status: StatusCode,
+    size: usize,
+    start: Instant,
+    f: Option<CompleteFn>,
+}
+
+impl<B: MessageBody> MessageBody for CompleteBody<B> {
+    fn length(&self) -> BodyLength {
+        self.body.length()
+    }
+
+    fn poll_next(&mut self) -> Poll<Option<Bytes>, Error> {
+        match self.body.poll_next()? {
+            Async::Ready(Some(chunk)) => {
+                self.size += chunk.len();
+                Ok(Async::Ready(Some(chunk)))
+            }
+            res => Ok(res),
+        }
+    }
+}
+
+impl<B> Drop for CompleteBody<B> {
+    fn drop(&mut self) {
+        if let Some(f) = self.f.take() {
+            Arbiter::spawn(f(ResponseSummary {
+                status: self.status,
+                size: self.size,
+                duration: self.start.elapsed(),
+            }));
+        }
+    }
+}
@@ -4,7 +4,7 @@ use std::{cmp, fmt, io};
 
 use actix_http::body::{Body, BodyLength, MessageBody, ResponseBody};
 use actix_http::http::header::{
-    ContentEncoding, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING,
+    ContentEncoding, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, VARY,
 };
 use actix_http::http::{HttpTryFrom, StatusCode};
 use actix_http::{Error, Head, ResponseHead};
@@ -21,12 +21,67 @@ use flate2::write::{GzEncoder, ZlibEncoder};
 use crate::middleware::MiddlewareFactory;
 use crate::service::{ServiceRequest, ServiceResponse};
 
+/// `Middleware` for compressing response bodies.
+///
+/// Encoding is negotiated with `Accept-Encoding` request header. With
+/// `ContentEncoding::Auto` supported encoding with highest quality value is
+/// used, otherwise only the specified encoding is used if client accepts it.
+/// Encoded responses get `Content-Encoding` header, all negotiated
+/// responses, encoded or not, get `Vary: Accept-Encoding` header.
+///
+/// Responses that already contain `Content-Encoding` header are sent as is,
+/// so handlers could return pre-encoded bodies, see `dev::Encoded`. Bodies
+/// of known size smaller than `Compress::min_size()` are not encoded either.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::http::ContentEncoding;
+/// use actix_web2::{middleware, App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .middleware(
+///             middleware::Compress::new(ContentEncoding::Auto)
+///                 .level(6)
+///                 .min_size(1024),
+///         )
+///         .resource("/index.html", |r| r.get(|| HttpResponse::Ok()));
+/// }
+/// ```
 #[derive(Debug, Clone)]
-pub struct Compress(ContentEncoding);
+pub struct Compress {
+    encoding: ContentEncoding,
+    level: Option<u32>,
+    min_size: usize,
+}
 
 impl Compress {
+    /// Construct `Compress` middleware for the specified encoding.
     pub fn new(encoding: ContentEncoding) -> Self {
-        Compress(encoding)
+        Compress {
+            encoding,
+            level: None,
+            min_size: 0,
+        }
+    }
+
+    /// Set compression level.
+    ///
+    /// Level is passed to the encoder as is, `gzip` and `deflate` accept
+    /// levels from 0 to 9, `br` from 0 to 11. By default fast compression
+    /// is used.
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Set minimal size of response body to compress.
+    ///
+    /// Only bodies with known size are checked, streaming bodies are
+    /// always compressed. By default all bodies are compressed.
+    pub fn min_size(mut self, size: usize) -> Self {
+        self.min_size = size;
+        self
     }
 }
 
@@ -56,7 +111,7 @@ where
         // negotiate content-encoding
         let encoding = if let Some(val) = req.headers.get(ACCEPT_ENCODING) {
             if let Ok(enc) = val.to_str() {
-                AcceptEncoding::parse(enc, self.0)
+                AcceptEncoding::parse(enc, self.encoding)
             } else {
                 ContentEncoding::Identity
             }
@@ -66,6 +121,8 @@ where
 
         CompressResponse {
             encoding,
+            level: self.level,
+            min_size: self.min_size,
             fut: srv.call(req),
         }
     }
@@ -81,6 +138,8 @@ where
 {
     fut: S::Future,
     encoding: ContentEncoding,
+    level: Option<u32>,
+    min_size: usize,
 }
 
 impl<S, P, B> Future for CompressResponse<S, P, B>
@@ -96,8 +155,9 @@ where
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         let resp = futures::try_ready!(self.fut.poll());
 
+        let (encoding, level, min_size) = (self.encoding, self.level, self.min_size);
         Ok(Async::Ready(resp.map_body(move |head, body| {
-            Encoder::body(self.encoding, head, body)
+            Encoder::body(encoding, level, min_size, head, body)
         })))
     }
 }
@@ -183,61 +243,62 @@ fn update_head(encoding: ContentEncoding, head: &mut ResponseHead) {
 impl<B: MessageBody> Encoder<B> {
     fn body(
         encoding: ContentEncoding,
+        level: Option<u32>,
+        min_size: usize,
         head: &mut ResponseHead,
         body: ResponseBody<B>,
     ) -> ResponseBody<Encoder<B>> {
-        let has_ce = head.headers().contains_key(CONTENT_ENCODING);
-        match body {
-            ResponseBody::Other(b) => match b {
-                Body::None => ResponseBody::Other(Body::None),
-                Body::Empty => ResponseBody::Other(Body::Empty),
-                Body::Bytes(buf) => {
-                    if !(has_ce
-                        || encoding == ContentEncoding::Identity
-                        || encoding == ContentEncoding::Auto)
-                    {
-                        let mut enc = ContentEncoder::encoder(encoding).unwrap();
-
-                        // TODO return error!
-                        let _ = enc.write(buf.as_ref());
-                        let body = enc.finish().unwrap();
-                        update_head(encoding, head);
-                        ResponseBody::Other(Body::Bytes(body))
-                    } else {
-                        ResponseBody::Other(Body::Bytes(buf))
-                    }
-                }
-                Body::Message(stream) => {
-                    if has_ce || head.status == StatusCode::SWITCHING_PROTOCOLS {
-                        ResponseBody::Body(Encoder {
-                            body: EncoderBody::Other(stream),
-                            encoder: None,
-                        })
-                    } else {
-                        update_head(encoding, head);
-                        head.no_chunking = false;
-                        ResponseBody::Body(Encoder {
-                            body: EncoderBody::Other(stream),
-                            encoder: ContentEncoder::encoder(encoding),
-                        })
-                    }
-                }
-            },
-            ResponseBody::Body(stream) => {
-                if has_ce || head.status == StatusCode::SWITCHING_PROTOCOLS {
-                    ResponseBody::Body(Encoder {
-                        body: EncoderBody::Body(stream),
-                        encoder: None,
-                    })
-                } else {
-                    update_head(encoding, head);
-                    head.no_chunking = false;
-                    ResponseBody::Body(Encoder {
-                        body: EncoderBody::Body(stream),
-                        encoder: ContentEncoder::encoder(encoding),
-                    })
-                }
+        let negotiated = !head.headers().contains_key(CONTENT_ENCODING)
+            && head.status != StatusCode::SWITCHING_PROTOCOLS;
+        if negotiated {
+            // representation depends on accept-encoding even if response
+            // is sent as is, caches must not reuse it for other clients
+            head.headers_mut()
+                .append(VARY, HeaderValue::from_static("accept-encoding"));
+        }
+
+        let skip = !negotiated
+            || match body.length() {
+                BodyLength::None | BodyLength::Empty => true,
+                BodyLength::Sized(len) => len < min_size,
+                BodyLength::Sized64(len) => len < min_size as u64,
+                BodyLength::Stream => false,
+            };
+        let encoder = if skip {
+            None
+        } else {
+            ContentEncoder::encoder(encoding, level)
+        };
+
+        match (body, encoder) {
+            (ResponseBody::Other(Body::Bytes(buf)), Some(mut enc)) => {
+                // TODO return error!
+                let _ = enc.write(buf.as_ref());
+                let body = enc.finish().unwrap();
+                update_head(encoding, head);
+                ResponseBody::Other(Body::Bytes(body))
             }
+            (ResponseBody::Other(Body::Message(stream)), Some(enc)) => {
+                update_head(encoding, head);
+                head.no_chunking = false;
+                ResponseBody::Body(Encoder {
+                    body: EncoderBody::Other(stream),
+                    encoder: Some(enc),
+                })
+            }
+            (ResponseBody::Body(stream), Some(enc)) => {
+                update_head(encoding, head);
+                head.no_chunking = false;
+                ResponseBody::Body(Encoder {
+                    body: EncoderBody::Body(stream),
+                    encoder: Some(enc),
+                })
+            }
+            (ResponseBody::Body(stream), None) => ResponseBody::Body(Encoder {
+                body: EncoderBody::Body(stream),
+                encoder: None,
+            }),
+            (ResponseBody::Other(body), _) => ResponseBody::Other(body),
         }
     }
 }
@@ -290,26 +351,38 @@ impl fmt::Debug for ContentEncoder {
 }
 
 impl ContentEncoder {
-    fn encoder(encoding: ContentEncoding) -> Option<Self> {
+    fn encoder(encoding: ContentEncoding, level: Option<u32>) -> Option<Self> {
         match encoding {
             #[cfg(feature = "flate2")]
             ContentEncoding::Deflate => Some(ContentEncoder::Deflate(ZlibEncoder::new(
                 Writer::new(),
-                flate2::Compression::fast(),
+                level.map_or_else(flate2::Compression::fast, flate2::Compression::new),
             ))),
             #[cfg(feature = "flate2")]
             ContentEncoding::Gzip => Some(ContentEncoder::Gzip(GzEncoder::new(
                 Writer::new(),
-                flate2::Compression::fast(),
+                level.map_or_else(flate2::Compression::fast, flate2::Compression::new),
             ))),
             #[cfg(feature = "brotli")]
-            ContentEncoding::Br => {
-                Some(ContentEncoder::Br(BrotliEncoder::new(Writer::new(), 3)))
-            }
+            ContentEncoding::Br => Some(ContentEncoder::Br(BrotliEncoder::new(
+                Writer::new(),
+                level.unwrap_or(3),
+            ))),
             _ => None,
         }
     }
 
+    /// Check if encoder for the encoding is available
+    fn supported(encoding: ContentEncoding) -> bool {
+        match encoding {
+            #[cfg(feature = "flate2")]
+            ContentEncoding::Deflate | ContentEncoding::Gzip => true,
+            #[cfg(feature = "brotli")]
+            ContentEncoding::Br => true,
+            _ => false,
+        }
+    }
+
     #[inline]
     pub(crate) fn take(&mut self) -> Bytes {
         match *self {
@@ -412,7 +485,7 @@ impl AcceptEncoding {
         };
         let quality = match parts.len() {
             1 => encoding.quality(),
-            _ => match f64::from_str(parts[1]) {
+            _ => match f64::from_str(parts[1].trim_start_matches("q=")) {
                 Ok(q) => q,
                 Err(_) => 0.0,
             },
@@ -429,13 +502,16 @@ impl AcceptEncoding {
             .collect();
         encodings.sort();
 
-        for enc in encodings {
-            if let Some(enc) = enc {
-                if encoding == ContentEncoding::Auto {
+        for enc in encodings.into_iter().flatten() {
+            if enc.quality <= 0.0 {
+                continue;
+            }
+            if encoding == ContentEncoding::Auto {
+                if ContentEncoder::supported(enc.encoding) {
                     return enc.encoding;
-                } else if encoding == enc.encoding {
-                    return encoding;
                 }
+            } else if encoding == enc.encoding {
+                return encoding;
             }
         }
         ContentEncoding::Identity
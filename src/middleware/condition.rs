@@ -0,0 +1,121 @@
+//! Middleware for conditionally enabled middlewares
+use actix_http::body::{BodyLength, MessageBody, ResponseBody};
+use actix_http::Error;
+use actix_service::{IntoNewTransform, Service, Transform};
+use bytes::Bytes;
+use futures::{Async, Future, Poll};
+
+use crate::middleware::MiddlewareFactory;
+use crate::service::{ServiceRequest, ServiceResponse};
+
+/// `Middleware` that applies wrapped middleware only if it is enabled.
+///
+/// Condition is checked once, during construction. Disabled middleware
+/// is never called, requests are passed to the next service directly.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::middleware::{Condition, Logger};
+/// use actix_web2::{App, HttpResponse};
+///
+/// fn main() {
+///     let enable_logger = std::env::var("ACCESS_LOG").is_ok();
+///     let app = App::new()
+///         .middleware(Condition::new(enable_logger, Logger::default()))
+///         .resource("/index.html", |r| r.get(|| HttpResponse::Ok()));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Condition<T> {
+    tr: T,
+    enabled: bool,
+}
+
+impl<T> Condition<T> {
+    /// Construct `Condition` middleware for the specified middleware.
+    pub fn new(enabled: bool, tr: T) -> Self {
+        Condition { tr, enabled }
+    }
+}
+
+impl<S, T, P, B, B1> IntoNewTransform<MiddlewareFactory<Condition<T>, S>, S>
+    for Condition<T>
+where
+    B: MessageBody,
+    B1: MessageBody,
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse<B>>,
+    S::Future: 'static,
+    T: Transform<
+            S,
+            Request = ServiceRequest<P>,
+            Response = ServiceResponse<B1>,
+            Error = S::Error,
+        > + Clone,
+    T::Future: 'static,
+{
+    fn into_new_transform(self) -> MiddlewareFactory<Condition<T>, S> {
+        MiddlewareFactory::new(self)
+    }
+}
+
+impl<S, T, P, B, B1> Transform<S> for Condition<T>
+where
+    B: MessageBody,
+    B1: MessageBody,
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse<B>>,
+    S::Future: 'static,
+    T: Transform<
+        S,
+        Request = ServiceRequest<P>,
+        Response = ServiceResponse<B1>,
+        Error = S::Error,
+    >,
+    T::Future: 'static,
+{
+    type Request = ServiceRequest<P>;
+    type Response = ServiceResponse<ConditionBody<B1, B>>;
+    type Error = S::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        if self.enabled {
+            self.tr.poll_ready()
+        } else {
+            Ok(Async::Ready(()))
+        }
+    }
+
+    fn call(&mut self, req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
+        if self.enabled {
+            Box::new(self.tr.call(req, srv).map(|res| {
+                res.map_body(|_, body| ResponseBody::Body(ConditionBody::Enabled(body)))
+            }))
+        } else {
+            Box::new(srv.call(req).map(|res| {
+                res.map_body(|_, body| ResponseBody::Body(ConditionBody::Disabled(body)))
+            }))
+        }
+    }
+}
+
+#[doc(hidden)]
+pub enum ConditionBody<A, B> {
+    Enabled(ResponseBody<A>),
+    Disabled(ResponseBody<B>),
+}
+
+impl<A: MessageBody, B: MessageBody> MessageBody for ConditionBody<A, B> {
+    fn length(&self) -> BodyLength {
+        match *self {
+            ConditionBody::Enabled(ref body) => body.length(),
+            ConditionBody::Disabled(ref body) => body.length(),
+        }
+    }
+
+    fn poll_next(&mut self) -> Poll<Option<Bytes>, Error> {
+        match *self {
+            ConditionBody::Enabled(ref mut body) => body.poll_next(),
+            ConditionBody::Disabled(ref mut body) => body.poll_next(),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Middleware for `ETag` and conditional `GET` requests
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use actix_http::body::{Body, ResponseBody};
+use actix_http::http::header::{
+    EntityTag, HeaderValue, HttpDate, IfModifiedSince, CONTENT_LENGTH, ETAG,
+    IF_NONE_MATCH, LAST_MODIFIED,
+};
+use actix_http::http::{Method, StatusCode};
+use actix_http::{Head, HttpMessage};
+use actix_service::{IntoNewTransform, Service, Transform};
+use futures::future::Either;
+use futures::{Async, Future, Poll};
+
+use crate::fs::none_match;
+use crate::middleware::MiddlewareFactory;
+use crate::service::{ServiceRequest, ServiceResponse};
+
+/// `Middleware` that handles conditional `GET` and `HEAD` requests.
+///
+/// Successful responses with in-memory body get weak `ETag` computed
+/// from the body, unless handler already set `ETag` header. If request's
+/// `If-None-Match` header matches response's `ETag`, or, when request has
+/// no `If-None-Match` header, response's `Last-Modified` is not newer than
+/// request's `If-Modified-Since`, body is dropped and
+/// *304 Not Modified* response is sent.
+///
+/// Requests with other methods and streaming responses are not changed.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{middleware, App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .middleware(middleware::ConditionalGet)
+///         .resource("/index.json", |r| r.get(|| HttpResponse::Ok().body("{}")));
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConditionalGet;
+
+impl<S, P> IntoNewTransform<MiddlewareFactory<ConditionalGet, S>, S> for ConditionalGet
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+    S::Future: 'static,
+{
+    fn into_new_transform(self) -> MiddlewareFactory<ConditionalGet, S> {
+        MiddlewareFactory::new(self)
+    }
+}
+
+impl<S, P> Transform<S> for ConditionalGet
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest<P>;
+    type Response = ServiceResponse;
+    type Error = S::Error;
+    type Future =
+        Either<S::Future, Box<Future<Item = Self::Response, Error = Self::Error>>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
+        if req.method() == Method::GET || req.method() == Method::HEAD {
+            Either::B(Box::new(srv.call(req).map(conditional)))
+        } else {
+            Either::A(srv.call(req))
+        }
+    }
+}
+
+fn conditional(mut res: ServiceResponse) -> ServiceResponse {
+    if res.response().status() != StatusCode::OK {
+        return res;
+    }
+
+    let etag = if let Some(val) = res.headers().get(ETAG) {
+        val.to_str()
+            .ok()
+            .and_then(|val| val.parse::<EntityTag>().ok())
+    } else {
+        let etag = body_etag(res.response().body());
+        if let Some(ref etag) = etag {
+            if let Ok(val) = HeaderValue::from_str(&etag.to_string()) {
+                res.headers_mut().insert(ETAG, val);
+            }
+        }
+        etag
+    };
+    let last_modified = res
+        .headers()
+        .get(LAST_MODIFIED)
+        .and_then(|val| val.to_str().ok())
+        .and_then(|val| val.parse::<HttpDate>().ok());
+
+    let not_modified = {
+        let req = res.request();
+        if !none_match(etag.as_ref(), req) {
+            true
+        } else if req.headers().contains_key(IF_NONE_MATCH) {
+            false
+        } else if let (Some(ref m), Some(IfModifiedSince(ref since))) =
+            (last_modified, req.get_header())
+        {
+            m <= since
+        } else {
+            false
+        }
+    };
+
+    if not_modified {
+        res.map_body(|head, _| {
+            head.status = StatusCode::NOT_MODIFIED;
+            head.headers_mut().remove(CONTENT_LENGTH);
+            ResponseBody::Other(Body::None)
+        })
+    } else {
+        res
+    }
+}
+
+/// Weak `ETag` of in-memory body, streaming bodies are not checked
+fn body_etag(body: &ResponseBody<Body>) -> Option<EntityTag> {
+    match *body {
+        ResponseBody::Body(Body::Bytes(ref bytes))
+        | ResponseBody::Other(Body::Bytes(ref bytes)) => {
+            let mut hasher = DefaultHasher::new();
+            bytes.hash(&mut hasher);
+            Some(EntityTag::weak(format!(
+                "{:x}-{:x}",
+                bytes.len(),
+                hasher.finish()
+            )))
+        }
+        _ => None,
+    }
+}
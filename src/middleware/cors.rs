@@ -0,0 +1,298 @@
+//! Cross-origin resource sharing (CORS) middleware
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use actix_http::http::header::{
+    HeaderName, HeaderValue, ACCESS_CONTROL_ALLOW_CREDENTIALS,
+    ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS,
+    ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+};
+use actix_http::http::{HeaderMap, HttpTryFrom, Method};
+use actix_http::Response;
+use actix_service::{IntoNewTransform, Service, Transform};
+use futures::future::{ok, Either, FutureResult};
+use futures::{Async, Future, Poll};
+
+use crate::middleware::MiddlewareFactory;
+use crate::service::{ServiceRequest, ServiceResponse};
+
+/// `Middleware` for cross-origin resource sharing.
+///
+/// Preflight requests, `OPTIONS` requests with
+/// `Access-Control-Request-Method` header, are answered with
+/// *204 No Content* response by the middleware, registered resources
+/// are not called. Responses of the actual requests get
+/// `Access-Control-Allow-Origin` header.
+///
+/// Requests from disallowed origins are not rejected, responses just do
+/// not contain `Access-Control-Allow-*` headers, so browser blocks
+/// the response. Same applies to preflight requests for disallowed
+/// methods or headers.
+///
+/// By default any origin is allowed, `GET`, `HEAD` and `POST` methods
+/// and any request headers are allowed.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::http::{header, Method};
+/// use actix_web2::{middleware, App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .middleware(
+///             middleware::Cors::new()
+///                 .allowed_origin("https://www.rust-lang.org")
+///                 .allowed_methods(vec![Method::GET, Method::PUT])
+///                 .allowed_headers(vec![header::AUTHORIZATION, header::CONTENT_TYPE])
+///                 .max_age(3600)
+///                 .supports_credentials(),
+///         )
+///         .resource("/index.html", |r| r.get(|| HttpResponse::Ok()));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Cors {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    origins: Option<HashSet<String>>,
+    methods: Vec<Method>,
+    headers: Option<HashSet<HeaderName>>,
+    max_age: Option<usize>,
+    credentials: bool,
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Cors {
+            inner: Rc::new(Inner {
+                origins: None,
+                methods: vec![Method::GET, Method::HEAD, Method::POST],
+                headers: None,
+                max_age: None,
+                credentials: false,
+            }),
+        }
+    }
+}
+
+impl Cors {
+    /// Construct `Cors` middleware.
+    pub fn new() -> Cors {
+        Cors::default()
+    }
+
+    /// Add allowed origin, i.e. `https://www.rust-lang.org`.
+    ///
+    /// Origin is matched exactly, `*` allows any origin. Once an origin
+    /// is added, only added origins are allowed.
+    pub fn allowed_origin(mut self, origin: &str) -> Self {
+        let inner = Rc::get_mut(&mut self.inner).expect("Multiple copies exist");
+        if origin == "*" {
+            inner.origins = None;
+        } else {
+            inner
+                .origins
+                .get_or_insert_with(HashSet::new)
+                .insert(origin.to_owned());
+        }
+        self
+    }
+
+    /// Set allowed methods.
+    pub fn allowed_methods<I, M>(mut self, methods: I) -> Self
+    where
+        I: IntoIterator<Item = M>,
+        Method: HttpTryFrom<M>,
+    {
+        #[allow(clippy::match_wild_err_arm)]
+        let methods = methods
+            .into_iter()
+            .map(|m| match Method::try_from(m) {
+                Ok(method) => method,
+                Err(_) => panic!("Can not create method"),
+            })
+            .collect();
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .methods = methods;
+        self
+    }
+
+    /// Set allowed request headers. By default any headers are allowed.
+    pub fn allowed_headers<I, H>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = H>,
+        HeaderName: HttpTryFrom<H>,
+    {
+        #[allow(clippy::match_wild_err_arm)]
+        let headers = headers
+            .into_iter()
+            .map(|h| match HeaderName::try_from(h) {
+                Ok(header) => header,
+                Err(_) => panic!("Can not create header name"),
+            })
+            .collect();
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .headers = Some(headers);
+        self
+    }
+
+    /// Set how long preflight response could be cached, in seconds.
+    pub fn max_age(mut self, max_age: usize) -> Self {
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .max_age = Some(max_age);
+        self
+    }
+
+    /// Allow requests with credentials, i.e. cookies.
+    ///
+    /// Applies to explicitly added origins only. If any origin is allowed,
+    /// responses get `*` without `Access-Control-Allow-Credentials`
+    /// header, so browser does not send credentials to the application.
+    pub fn supports_credentials(mut self) -> Self {
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .credentials = true;
+        self
+    }
+}
+
+impl Inner {
+    /// Allowed origin of the request
+    fn origin(&self, headers: &HeaderMap) -> Option<HeaderValue> {
+        let origin = headers.get(ORIGIN)?;
+        match self.origins {
+            None => Some(HeaderValue::from_static("*")),
+            Some(ref origins) => {
+                if origins.contains(origin.to_str().ok()?) {
+                    Some(origin.clone())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Response headers for the preflight request, if it is allowed
+    fn preflight(&self, headers: &HeaderMap) -> Option<HeaderMap> {
+        let origin = self.origin(headers)?;
+        let method = headers
+            .get(ACCESS_CONTROL_REQUEST_METHOD)
+            .and_then(|val| val.to_str().ok())
+            .and_then(|val| Method::from_bytes(val.as_bytes()).ok())?;
+        if !self.methods.contains(&method) {
+            return None;
+        }
+        let requested = headers
+            .get(ACCESS_CONTROL_REQUEST_HEADERS)
+            .and_then(|val| val.to_str().ok())
+            .unwrap_or("");
+        for name in requested
+            .split(',')
+            .map(|h| h.trim())
+            .filter(|h| !h.is_empty())
+        {
+            let name = HeaderName::try_from(name).ok()?;
+            if let Some(ref allowed) = self.headers {
+                if !allowed.contains(&name) {
+                    return None;
+                }
+            }
+        }
+
+        let mut res = HeaderMap::new();
+        self.set_origin(&mut res, origin);
+        let methods: Vec<_> = self.methods.iter().map(|m| m.as_str()).collect();
+        res.insert(
+            ACCESS_CONTROL_ALLOW_METHODS,
+            HeaderValue::try_from(methods.join(", ")).ok()?,
+        );
+        let allowed = match self.headers {
+            Some(ref headers) => {
+                let headers: Vec<_> = headers.iter().map(|h| h.as_str()).collect();
+                headers.join(", ")
+            }
+            None => requested.to_owned(),
+        };
+        if !allowed.is_empty() {
+            res.insert(
+                ACCESS_CONTROL_ALLOW_HEADERS,
+                HeaderValue::try_from(allowed).ok()?,
+            );
+        }
+        if let Some(max_age) = self.max_age {
+            res.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from(max_age));
+        }
+        Some(res)
+    }
+
+    fn set_origin(&self, headers: &mut HeaderMap, origin: HeaderValue) {
+        if origin != "*" {
+            headers.append(VARY, HeaderValue::from_static("Origin"));
+        }
+        let credentials = self.credentials && origin != "*";
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        if credentials {
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+    }
+}
+
+impl<S, P> IntoNewTransform<MiddlewareFactory<Cors, S>, S> for Cors
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+    S::Future: 'static,
+{
+    fn into_new_transform(self) -> MiddlewareFactory<Cors, S> {
+        MiddlewareFactory::new(self)
+    }
+}
+
+impl<S, P> Transform<S> for Cors
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest<P>;
+    type Response = ServiceResponse;
+    type Error = S::Error;
+    type Future = Either<
+        FutureResult<Self::Response, Self::Error>,
+        Box<Future<Item = Self::Response, Error = Self::Error>>,
+    >;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
+        if req.method() == Method::OPTIONS
+            && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+        {
+            let mut res = Response::NoContent().finish();
+            if let Some(headers) = self.inner.preflight(req.headers()) {
+                for (name, value) in headers.iter() {
+                    res.headers_mut().append(name.clone(), value.clone());
+                }
+            }
+            return Either::A(ok(req.into_response(res)));
+        }
+
+        let origin = self.inner.origin(req.headers());
+        let inner = self.inner.clone();
+        Either::B(Box::new(srv.call(req).map(move |mut res| {
+            if let Some(origin) = origin {
+                inner.set_origin(res.headers_mut(), origin);
+            }
+            res
+        })))
+    }
+}
@@ -1,7 +1,10 @@
 //! Middleware for setting default response headers
 use std::rc::Rc;
+use std::time::SystemTime;
 
-use actix_http::http::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use actix_http::http::header::{
+    HeaderName, HeaderValue, HttpDate, CONTENT_TYPE, DATE, SERVER,
+};
 use actix_http::http::{HeaderMap, HttpTryFrom};
 use actix_service::{IntoNewTransform, Service, Transform};
 use futures::{Async, Future, Poll};
@@ -12,6 +15,8 @@ use crate::service::{ServiceRequest, ServiceResponse};
 /// `Middleware` for setting default response headers.
 ///
 /// This middleware does not set header if response headers already contains it.
+/// The only exception is `Server` header, value set with
+/// `DefaultHeaders::server()` overrides value set by the handler.
 ///
 /// ```rust
 /// # extern crate actix_web;
@@ -19,7 +24,12 @@ use crate::service::{ServiceRequest, ServiceResponse};
 ///
 /// fn main() {
 ///     let app = App::new()
-///         .middleware(middleware::DefaultHeaders::new().header("X-Version", "0.2"))
+///         .middleware(
+///             middleware::DefaultHeaders::new()
+///                 .header("X-Version", "0.2")
+///                 .server("my-service")
+///                 .date(),
+///         )
 ///         .resource("/test", |r| {
 ///             r.method(http::Method::GET).f(|_| HttpResponse::Ok());
 ///             r.method(http::Method::HEAD)
@@ -27,6 +37,26 @@ use crate::service::{ServiceRequest, ServiceResponse};
 ///         });
 /// }
 /// ```
+///
+/// Middleware could be registered for a single resource with
+/// `Resource::middleware()`, in that case headers are set only on
+/// responses of the resource.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{middleware, App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .resource("/account", |r| {
+///             r.middleware(
+///                 middleware::DefaultHeaders::new().header("Cache-Control", "no-store"),
+///             )
+///             .get(|| HttpResponse::Ok())
+///         })
+///         .resource("/index.html", |r| r.get(|| HttpResponse::Ok()));
+/// }
+/// ```
 #[derive(Clone)]
 pub struct DefaultHeaders {
     inner: Rc<Inner>,
@@ -34,14 +64,25 @@ pub struct DefaultHeaders {
 
 struct Inner {
     ct: bool,
+    date: bool,
+    server: Server,
     headers: HeaderMap,
 }
 
+/// Handling of the `Server` header
+enum Server {
+    Keep,
+    Set(HeaderValue),
+    Remove,
+}
+
 impl Default for DefaultHeaders {
     fn default() -> Self {
         DefaultHeaders {
             inner: Rc::new(Inner {
                 ct: false,
+                date: false,
+                server: Server::Keep,
                 headers: HeaderMap::new(),
             }),
         }
@@ -84,6 +125,40 @@ impl DefaultHeaders {
             .ct = true;
         self
     }
+
+    /// Set *SERVER* header, overrides header set by the handler.
+    pub fn server<V>(mut self, value: V) -> Self
+    where
+        HeaderValue: HttpTryFrom<V>,
+    {
+        #[allow(clippy::match_wild_err_arm)]
+        match HeaderValue::try_from(value) {
+            Ok(value) => {
+                Rc::get_mut(&mut self.inner)
+                    .expect("Multiple copies exist")
+                    .server = Server::Set(value);
+            }
+            Err(_) => panic!("Can not create header value"),
+        }
+        self
+    }
+
+    /// Remove *SERVER* header from all responses.
+    pub fn remove_server(mut self) -> Self {
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .server = Server::Remove;
+        self
+    }
+
+    /// Set *DATE* header to the current time if response does not contain
+    /// this header.
+    pub fn date(mut self) -> Self {
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .date = true;
+        self
+    }
 }
 
 impl<S, State, B> IntoNewTransform<MiddlewareFactory<DefaultHeaders, S>, S>
@@ -128,6 +203,23 @@ where
                     HeaderValue::from_static("application/octet-stream"),
                 );
             }
+            // server
+            match inner.server {
+                Server::Keep => (),
+                Server::Set(ref value) => {
+                    res.headers_mut().insert(SERVER, value.clone());
+                }
+                Server::Remove => {
+                    res.headers_mut().remove(SERVER);
+                }
+            }
+            // date
+            if inner.date && !res.headers().contains_key(DATE) {
+                let date = HttpDate::from(SystemTime::now());
+                if let Ok(value) = HeaderValue::from_str(&date.to_string()) {
+                    res.headers_mut().insert(DATE, value);
+                }
+            }
 
             res
         }))
@@ -1,64 +1,73 @@
-use hashbrown::hash_map::HashMap;
+//! Middleware for rewriting responses with specific status codes
 use std::rc::Rc;
-use futures::{Async, Future, Poll};
 
-use actix_http::error::Result;
-use actix_http::{Response, http::StatusCode};
+use actix_http::http::StatusCode;
 use actix_service::{IntoNewTransform, Service, Transform};
+use futures::{Async, Future, Poll};
+use hashbrown::hash_map::HashMap;
 
-use crate::service::ServiceRequest;
 use crate::middleware::MiddlewareFactory;
+use crate::service::{ServiceRequest, ServiceResponse};
 
-type ErrorHandler<P> = Fn(&ServiceRequest<P>, Response) -> Result<Response>;
+type ErrorHandler<B> = Fn(ServiceResponse<B>) -> ServiceResponse<B>;
 
 /// `Middleware` for allowing custom handlers for responses.
 ///
 /// You can use `ErrorHandlers::handler()` method  to register a custom error
 /// handler for specific status code. You can modify existing response or
-/// create completely new one.
+/// create completely new one. Responses with other status codes are
+/// sent as is.
 ///
 /// ## Example
 ///
-/// ```rust
-/// # extern crate actix_web;
-/// use actix_web::middleware::{ErrorHandlers, Response};
-/// use actix_web::{http, App, ServiceRequest, Response, Result};
+/// ```rust,ignore
+/// # extern crate actix_http;
+/// # extern crate actix_web2;
+/// use actix_http::body::{Body, ResponseBody};
+/// use actix_web2::http::{header, StatusCode};
+/// use actix_web2::middleware::ErrorHandlers;
+/// use actix_web2::{App, HttpResponse, ServiceResponse};
 ///
-/// fn render_500<P>(_: &ServiceRequest<P>, resp: Response) -> Result<Response> {
-///     let mut builder = resp.into_builder();
-///     builder.header(http::header::CONTENT_TYPE, "application/json");
-///     Ok(Response::Done(builder.into()))
+/// fn render_500(res: ServiceResponse) -> ServiceResponse {
+///     res.map_body(|head, _| {
+///         head.headers_mut().insert(
+///             header::CONTENT_TYPE,
+///             header::HeaderValue::from_static("application/json"),
+///         );
+///         ResponseBody::Body(Body::from(r#"{"error":"internal"}"#))
+///     })
 /// }
 ///
 /// fn main() {
 ///     let app = App::new()
 ///         .middleware(
 ///             ErrorHandlers::new()
-///                 .handler(http::StatusCode::INTERNAL_SERVER_ERROR, render_500),
+///                 .handler(StatusCode::INTERNAL_SERVER_ERROR, render_500),
 ///         )
-///         .resource("/test", |r| {
-///             r.method(http::Method::GET).f(|_| Response::Ok());
-///             r.method(http::Method::HEAD)
-///                 .f(|_| Response::MethodNotAllowed());
-///         })
-///         .finish();
+///         .resource("/test", |r| r.get(|| HttpResponse::InternalServerError()));
 /// }
 /// ```
-
-#[derive(Clone)]
-pub struct ErrorHandlers<P> {
-    handlers: HashMap<StatusCode, Rc<ErrorHandler<P>>>,
+pub struct ErrorHandlers<B> {
+    handlers: Rc<HashMap<StatusCode, Box<ErrorHandler<B>>>>,
 }
 
-impl<P> Default for ErrorHandlers<P> {
+impl<B> Default for ErrorHandlers<B> {
     fn default() -> Self {
         ErrorHandlers {
-            handlers: HashMap::new(),
+            handlers: Rc::new(HashMap::new()),
         }
     }
 }
 
-impl<P> ErrorHandlers<P> {
+impl<B> Clone for ErrorHandlers<B> {
+    fn clone(&self) -> Self {
+        ErrorHandlers {
+            handlers: self.handlers.clone(),
+        }
+    }
+}
+
+impl<B> ErrorHandlers<B> {
     /// Construct new `ErrorHandlers` instance
     pub fn new() -> Self {
         ErrorHandlers::default()
@@ -67,31 +76,35 @@ impl<P> ErrorHandlers<P> {
     /// Register error handler for specified status code
     pub fn handler<F>(mut self, status: StatusCode, handler: F) -> Self
     where
-        F: Fn(&ServiceRequest<P>, Response) -> Result<Response> + 'static,
+        F: Fn(ServiceResponse<B>) -> ServiceResponse<B> + 'static,
     {
-        self.handlers.insert(status, Rc::new(handler));
+        Rc::get_mut(&mut self.handlers)
+            .expect("Multiple copies exist")
+            .insert(status, Box::new(handler));
         self
     }
 }
 
-impl<S, P: Clone> IntoNewTransform<MiddlewareFactory<ErrorHandlers<P>, S>, S>
-    for ErrorHandlers<P>
+impl<S, P, B> IntoNewTransform<MiddlewareFactory<ErrorHandlers<B>, S>, S>
+    for ErrorHandlers<B>
 where
-    S: Service<Request = ServiceRequest<P>, Response = Response>,
+    B: 'static,
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse<B>>,
     S::Future: 'static,
 {
-    fn into_new_transform(self) -> MiddlewareFactory<ErrorHandlers<P>, S> {
+    fn into_new_transform(self) -> MiddlewareFactory<ErrorHandlers<B>, S> {
         MiddlewareFactory::new(self)
     }
 }
 
-impl<S, P> Transform<S> for ErrorHandlers<P>
+impl<S, P, B> Transform<S> for ErrorHandlers<B>
 where
-    S: Service<Request = ServiceRequest<P>, Response = Response>,
+    B: 'static,
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse<B>>,
     S::Future: 'static,
 {
     type Request = ServiceRequest<P>;
-    type Response = Response;
+    type Response = ServiceResponse<B>;
     type Error = S::Error;
     type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
 
@@ -100,84 +113,86 @@ where
     }
 
     fn call(&mut self, req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
-        let x = srv.call(req).map(move |mut resp| {
-            if let Some(handler) = self.handlers.get(&resp.status()) {
-                handler(&req, resp)
+        let handlers = self.handlers.clone();
+
+        Box::new(srv.call(req).map(move |res| {
+            if let Some(handler) = handlers.get(&res.response().status()) {
+                handler(res)
             } else {
-                Ok(resp)
+                res
             }
-        });
-        unimplemented!()
+        }))
     }
 }
 
-// impl<S: 'static> Middleware<P> for ErrorHandlers<P> {
-//     fn response(&self, req: &ServiceRequest<P>, resp: Response) -> Result<Response> {
-//         if let Some(handler) = self.handlers.get(&resp.status()) {
-//             handler(req, resp)
-//         } else {
-//             Ok(Response::Done(resp))
-//         }
-//     }
-// }
-
 #[cfg(test)]
 mod tests {
+    use actix_http::body::{Body, ResponseBody};
+    use actix_http::error::ErrorInternalServerError;
+    use actix_http::http::header::{HeaderValue, CONTENT_TYPE};
+    use actix_http::http::StatusCode;
+    use actix_http::{PayloadStream, Response};
+    use futures::future::{ok, FutureResult};
+
     use super::*;
-    use error::{Error, ErrorInternalServerError};
-    use http::header::CONTENT_TYPE;
-    use http::StatusCode;
-    use httpmessage::HttpMessage;
-    use middleware::Started;
-    use test::{self, TestRequest};
-
-    fn render_500<P>(_: &ServiceRequest<P>, resp: Response) -> Result<Response> {
-        let mut builder = resp.into_builder();
-        builder.header(CONTENT_TYPE, "0001");
-        Ok(Response::Done(builder.into()))
+    use crate::test::{self, TestRequest};
+
+    fn render_500(mut res: ServiceResponse) -> ServiceResponse {
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("0001"));
+        res
     }
 
-    #[test]
-    fn test_handler() {
-        let mw =
-            ErrorHandlers::new().handler(StatusCode::INTERNAL_SERVER_ERROR, render_500);
+    /// Service that responds with response of the factory
+    struct Respond<F>(F);
+
+    impl<F: Fn() -> Response> Service for Respond<F> {
+        type Request = ServiceRequest<PayloadStream>;
+        type Response = ServiceResponse;
+        type Error = ();
+        type Future = FutureResult<ServiceResponse, ()>;
+
+        fn poll_ready(&mut self) -> Poll<(), ()> {
+            Ok(Async::Ready(()))
+        }
 
-        let mut req = TestRequest::default().finish();
-        let resp = Response::InternalServerError().finish();
-        let resp = match mw.response(&mut req, resp) {
-            Ok(Response::Done(resp)) => resp,
-            _ => panic!(),
-        };
-        assert_eq!(resp.headers().get(CONTENT_TYPE).unwrap(), "0001");
-
-        let resp = Response::Ok().finish();
-        let resp = match mw.response(&mut req, resp) {
-            Ok(Response::Done(resp)) => resp,
-            _ => panic!(),
-        };
-        assert!(!resp.headers().contains_key(CONTENT_TYPE));
+        fn call(&mut self, req: ServiceRequest<PayloadStream>) -> Self::Future {
+            ok(req.into_response((self.0)()))
+        }
     }
 
-    struct MiddlewareOne;
+    #[test]
+    fn test_handler() {
+        let mut mw =
+            ErrorHandlers::new().handler(StatusCode::INTERNAL_SERVER_ERROR, render_500);
 
-    impl<P> Middleware<P> for MiddlewareOne {
-        fn start(&self, _: &ServiceRequest<P>) -> Result<Started, Error> {
-            Err(ErrorInternalServerError("middleware error"))
+        let mut srv = Respond(|| Response::InternalServerError().finish());
+        let res =
+            test::block_on(mw.call(TestRequest::default().finish(), &mut srv)).unwrap();
+        assert_eq!(res.headers().get(CONTENT_TYPE).unwrap(), "0001");
+
+        let mut srv = Respond(|| Response::Ok().content_type("text/plain").body("ok"));
+        let res =
+            test::block_on(mw.call(TestRequest::default().finish(), &mut srv)).unwrap();
+        assert_eq!(res.response().status(), StatusCode::OK);
+        assert_eq!(res.headers().get(CONTENT_TYPE).unwrap(), "text/plain");
+        match res.response().body() {
+            ResponseBody::Body(Body::Bytes(ref bytes))
+            | ResponseBody::Other(Body::Bytes(ref bytes)) => assert_eq!(bytes, "ok"),
+            _ => panic!("body is replaced"),
         }
     }
 
     #[test]
-    fn test_middleware_start_error() {
-        let mut srv = test::TestServer::new(move |app| {
-            app.middleware(
-                ErrorHandlers::new()
-                    .handler(StatusCode::INTERNAL_SERVER_ERROR, render_500),
-            ).middleware(MiddlewareOne)
-            .handler(|_| Response::Ok())
-        });
-
-        let request = srv.get().finish().unwrap();
-        let response = srv.execute(request.send()).unwrap();
-        assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), "0001");
+    fn test_middleware_call_error() {
+        let mut mw =
+            ErrorHandlers::new().handler(StatusCode::INTERNAL_SERVER_ERROR, render_500);
+
+        // inner middleware fails before request reaches the endpoint
+        let mut srv = Respond(|| ErrorInternalServerError("middleware error").into());
+        let res =
+            test::block_on(mw.call(TestRequest::default().finish(), &mut srv)).unwrap();
+        assert_eq!(res.response().status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(res.headers().get(CONTENT_TYPE).unwrap(), "0001");
     }
 }
@@ -0,0 +1,166 @@
+//! Middleware for validating response headers
+use std::rc::Rc;
+
+use actix_http::error::ErrorInternalServerError;
+use actix_http::http::HeaderMap;
+use actix_service::{IntoNewTransform, Service, Transform};
+use futures::{Async, Future, Poll};
+use log::warn;
+
+use crate::middleware::MiddlewareFactory;
+use crate::service::{ServiceRequest, ServiceResponse};
+
+/// `Middleware` for limiting number and size of response headers.
+///
+/// Size of a header is the length of its name plus the length of its value.
+/// Headers set by the server itself, i.e. `Date` or `Content-Length`,
+/// are not checked. If response exceeds limits, it gets replaced with
+/// *500 Internal Server Error* response, or, if truncation is enabled,
+/// headers that do not fit in limits are removed and warning is logged.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{middleware, App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .middleware(
+///             middleware::HeaderLimits::new()
+///                 .max_headers(32)
+///                 .max_size(8192)
+///                 .truncate(),
+///         )
+///         .resource("/index.html", |r| r.get(|| HttpResponse::Ok()));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct HeaderLimits {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    max_headers: usize,
+    max_size: usize,
+    truncate: bool,
+}
+
+impl Default for HeaderLimits {
+    fn default() -> Self {
+        HeaderLimits {
+            inner: Rc::new(Inner {
+                max_headers: 100,
+                max_size: 65_536,
+                truncate: false,
+            }),
+        }
+    }
+}
+
+impl HeaderLimits {
+    /// Construct `HeaderLimits` middleware.
+    pub fn new() -> HeaderLimits {
+        HeaderLimits::default()
+    }
+
+    /// Set max number of response headers. By default 100 headers.
+    pub fn max_headers(mut self, max: usize) -> Self {
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .max_headers = max;
+        self
+    }
+
+    /// Set max total size of response headers. By default 64Kb.
+    pub fn max_size(mut self, max: usize) -> Self {
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .max_size = max;
+        self
+    }
+
+    /// Remove headers that exceed limits instead of returning error response.
+    pub fn truncate(mut self) -> Self {
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .truncate = true;
+        self
+    }
+}
+
+impl Inner {
+    /// Check if headers fit in limits
+    fn check(&self, headers: &HeaderMap) -> bool {
+        let size: usize = headers
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len())
+            .sum();
+        headers.len() <= self.max_headers && size <= self.max_size
+    }
+
+    /// Keep only headers that fit in limits, returns number of removed headers
+    fn truncate(&self, headers: &mut HeaderMap) -> usize {
+        let total = headers.len();
+        let mut size = 0;
+        let mut result = HeaderMap::with_capacity(self.max_headers);
+
+        for (name, value) in headers.iter() {
+            let len = name.as_str().len() + value.len();
+            if result.len() >= self.max_headers || size + len > self.max_size {
+                continue;
+            }
+            size += len;
+            result.append(name.clone(), value.clone());
+        }
+
+        let removed = total - result.len();
+        *headers = result;
+        removed
+    }
+}
+
+impl<S, P> IntoNewTransform<MiddlewareFactory<HeaderLimits, S>, S> for HeaderLimits
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+    S::Future: 'static,
+{
+    fn into_new_transform(self) -> MiddlewareFactory<HeaderLimits, S> {
+        MiddlewareFactory::new(self)
+    }
+}
+
+impl<S, P> Transform<S> for HeaderLimits
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest<P>;
+    type Response = ServiceResponse;
+    type Error = S::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
+        let inner = self.inner.clone();
+
+        Box::new(srv.call(req).map(move |mut res| {
+            if inner.check(res.headers()) {
+                res
+            } else if inner.truncate {
+                let removed = inner.truncate(res.headers_mut());
+                warn!(
+                    "{} response headers are removed for {}, limits are exceeded",
+                    removed,
+                    res.request().path()
+                );
+                res
+            } else {
+                res.error_response(ErrorInternalServerError(
+                    "Response headers exceed limits",
+                ))
+            }
+        }))
+    }
+}
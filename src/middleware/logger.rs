@@ -1,49 +1,54 @@
 //! Request logging middleware
-use hashbrown::hash_set::HashSet;
 use std::env;
 use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+use std::time::Instant;
 
+use actix_http::body::{BodyLength, MessageBody, ResponseBody};
+use actix_http::http::HeaderValue;
+use actix_http::{Error, Response};
+use actix_service::{IntoNewTransform, Service, Transform};
+use bytes::Bytes;
 use futures::{Async, Future, Poll};
+use hashbrown::hash_set::HashSet;
+use log::{info, trace};
 use regex::Regex;
 use time;
-use log::{trace, info};
-
-use actix_http::error::Result;
-use actix_http::{Response};
-use actix_service::{IntoNewTransform, Service, Transform};
 
-use crate::service::ServiceRequest;
 use crate::middleware::MiddlewareFactory;
+use crate::request::HttpRequest;
+use crate::service::{ServiceRequest, ServiceResponse};
 
 /// `Middleware` for logging request and response info to the terminal.
 ///
 /// `Logger` middleware uses standard log crate to log information. You should
-/// enable logger for `actix_web` package to see access log.
+/// enable logger for `actix_web2` package to see access log.
 /// ([`env_logger`](https://docs.rs/env_logger/*/env_logger/) or similar)
 ///
 /// ## Usage
 ///
-/// Create `Logger` middleware with the specified `format`.
+/// Create `Logger` middleware with the specified `format`. Log line is
+/// emitted at `info` level once response body is sent to the client.
 /// Default `Logger` could be created with `default` method, it uses the
 /// default format:
 ///
 /// ```ignore
 ///  %a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T
 /// ```
-/// ```rust
-/// # extern crate actix_web;
+/// ```rust,ignore
+/// # extern crate actix_web2;
 /// extern crate env_logger;
-/// use actix_web::middleware::Logger;
-/// use actix_web::App;
+/// use actix_web2::middleware::Logger;
+/// use actix_web2::{App, HttpResponse};
 ///
 /// fn main() {
-///     std::env::set_var("RUST_LOG", "actix_web=info");
+///     std::env::set_var("RUST_LOG", "actix_web2=info");
 ///     env_logger::init();
 ///
 ///     let app = App::new()
 ///         .middleware(Logger::default())
 ///         .middleware(Logger::new("%a %{User-Agent}i"))
-///         .finish();
+///         .resource("/index.html", |r| r.get(|| HttpResponse::Ok()));
 /// }
 /// ```
 ///
@@ -59,7 +64,7 @@ use crate::middleware::MiddlewareFactory;
 ///
 /// `%s`  Response status code
 ///
-/// `%b`  Size of response in bytes, including HTTP headers
+/// `%b`  Size of response body in bytes
 ///
 /// `%T` Time taken to serve the request, in seconds with floating fraction in
 /// .06f format
@@ -71,9 +76,12 @@ use crate::middleware::MiddlewareFactory;
 /// `%{FOO}o`  response.headers['FOO']
 ///
 /// `%{FOO}e`  os.environ['FOO']
-///
 #[derive(Clone)]
 pub struct Logger {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
     format: Format,
     exclude: HashSet<String>,
 }
@@ -82,14 +90,19 @@ impl Logger {
     /// Create `Logger` middleware with the specified `format`.
     pub fn new(format: &str) -> Logger {
         Logger {
-            format: Format::new(format),
-            exclude: HashSet::new(),
+            inner: Rc::new(Inner {
+                format: Format::new(format),
+                exclude: HashSet::new(),
+            }),
         }
     }
 
     /// Ignore and do not log access info for specified path.
     pub fn exclude<T: Into<String>>(mut self, path: T) -> Self {
-        self.exclude.insert(path.into());
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .exclude
+            .insert(path.into());
         self
     }
 }
@@ -102,31 +115,18 @@ impl Default for Logger {
     /// ```
     fn default() -> Logger {
         Logger {
-            format: Format::default(),
-            exclude: HashSet::new(),
+            inner: Rc::new(Inner {
+                format: Format::default(),
+                exclude: HashSet::new(),
+            }),
         }
     }
 }
 
-struct StartTime(time::Tm);
-
-impl Logger {
-    fn log<S>(&self, req: &ServiceRequest<S>, resp: &Response) {
-        if let Some(entry_time) = req.extensions().get::<StartTime>() {
-            let render = |fmt: &mut Formatter| {
-                for unit in &self.format.0 {
-                    unit.render(fmt, req, resp, entry_time.0)?;
-                }
-                Ok(())
-            };
-            info!("{}", FormatDisplay(&render));
-        }
-    }
-}
-
-impl<S, P> IntoNewTransform<MiddlewareFactory<Logger, S>, S> for Logger
+impl<S, P, B> IntoNewTransform<MiddlewareFactory<Logger, S>, S> for Logger
 where
-    S: Service<Request = ServiceRequest<P>, Response = Response>,
+    B: MessageBody,
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse<B>>,
     S::Future: 'static,
 {
     fn into_new_transform(self) -> MiddlewareFactory<Logger, S> {
@@ -134,13 +134,14 @@ where
     }
 }
 
-impl<S, P> Transform<S> for Logger
+impl<S, P, B> Transform<S> for Logger
 where
-    S: Service<Request = ServiceRequest<P>, Response = Response>,
+    B: MessageBody,
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse<B>>,
     S::Future: 'static,
 {
     type Request = ServiceRequest<P>;
-    type Response = Response;
+    type Response = ServiceResponse<StreamLog<B>>;
     type Error = S::Error;
     type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
 
@@ -149,29 +150,76 @@ where
     }
 
     fn call(&mut self, req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
-        if !self.exclude.contains(req.path()) {
-            req.extensions_mut().insert(StartTime(time::now()));
-        }
-        let x = srv.call(req).map(move |mut resp| {
-        });
+        let start = Instant::now();
+
+        // request related units are rendered right away, request is
+        // not available once response is sent
+        let mut format = if self.inner.exclude.contains(req.path()) {
+            None
+        } else {
+            let now = time::now();
+            let mut format = self.inner.format.clone();
+            for unit in &mut format.0 {
+                unit.render_request(now, req.request());
+            }
+            Some(format)
+        };
 
-        unimplemented!()
+        Box::new(srv.call(req).map(move |res| {
+            if let Some(ref mut format) = format {
+                for unit in &mut format.0 {
+                    unit.render_response(res.response());
+                }
+            }
+            res.map_body(move |_, body| {
+                ResponseBody::Body(StreamLog {
+                    body,
+                    format,
+                    size: 0,
+                    start,
+                })
+            })
+        }))
     }
 }
 
-// impl<S> Middleware<S> for Logger {
-//     fn start(&self, req: &ServiceRequest<S>) -> Result<Started> {
-//         if !self.exclude.contains(req.path()) {
-//             req.extensions_mut().insert(StartTime(time::now()));
-//         }
-//         Ok(Started::Done)
-//     }
+#[doc(hidden)]
+pub struct StreamLog<B> {
+    body: ResponseBody<B>,
+    format: Option<Format>,
+    size: usize,
+    start: Instant,
+}
 
-//     fn finish(&self, req: &ServiceRequest<S>, resp: &Response) -> Finished {
-//         self.log(req, resp);
-//         Finished::Done
-//     }
-// }
+impl<B: MessageBody> MessageBody for StreamLog<B> {
+    fn length(&self) -> BodyLength {
+        self.body.length()
+    }
+
+    fn poll_next(&mut self) -> Poll<Option<Bytes>, Error> {
+        match self.body.poll_next()? {
+            Async::Ready(Some(chunk)) => {
+                self.size += chunk.len();
+                Ok(Async::Ready(Some(chunk)))
+            }
+            res => Ok(res),
+        }
+    }
+}
+
+impl<B> Drop for StreamLog<B> {
+    fn drop(&mut self) {
+        if let Some(ref format) = self.format {
+            let render = |fmt: &mut Formatter| {
+                for unit in &format.0 {
+                    unit.render(fmt, self.size, self.start)?;
+                }
+                Ok(())
+            };
+            info!("{}", FormatDisplay(&render));
+        }
+    }
+}
 
 /// A formatting style for the `Logger`, consisting of multiple
 /// `FormatText`s concatenated into one line.
@@ -192,7 +240,7 @@ impl Format {
     /// Returns `None` if the format string syntax is incorrect.
     pub fn new(s: &str) -> Format {
         trace!("Access log format: {}", s);
-        let fmt = Regex::new(r"%(\{([A-Za-z0-9\-_]+)\}([ioe])|[atPrsbTD]?)").unwrap();
+        let fmt = Regex::new(r"%(\{([A-Za-z0-9\-_]+)\}([ioe])|[%atPrsbTD]?)").unwrap();
 
         let mut idx = 0;
         let mut results = Vec::new();
@@ -254,89 +302,98 @@ pub enum FormatText {
 }
 
 impl FormatText {
-    fn render<S>(
-        &self, fmt: &mut Formatter, req: &ServiceRequest<S>, resp: &Response,
-        entry_time: time::Tm,
+    /// Render units that are known once response body is complete
+    fn render(
+        &self,
+        fmt: &mut Formatter,
+        size: usize,
+        entry_time: Instant,
     ) -> Result<(), fmt::Error> {
         match *self {
             FormatText::Str(ref string) => fmt.write_str(string),
             FormatText::Percent => "%".fmt(fmt),
+            FormatText::ResponseSize => size.fmt(fmt),
+            FormatText::Time => {
+                let rt = entry_time.elapsed();
+                let rt = rt.as_secs() as f64 + f64::from(rt.subsec_nanos()) / 1e9;
+                fmt.write_fmt(format_args!("{:.6}", rt))
+            }
+            FormatText::TimeMillis => {
+                let rt = entry_time.elapsed();
+                let rt = rt.as_secs() as f64 * 1e3 + f64::from(rt.subsec_nanos()) / 1e6;
+                fmt.write_fmt(format_args!("{:.6}", rt))
+            }
+            FormatText::EnvironHeader(ref name) => {
+                if let Ok(val) = env::var(name) {
+                    fmt.write_fmt(format_args!("{}", val))
+                } else {
+                    "-".fmt(fmt)
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Replace request related units with rendered text
+    fn render_request(&mut self, now: time::Tm, req: &HttpRequest) {
+        match *self {
             FormatText::RequestLine => {
-                if req.query_string().is_empty() {
-                    fmt.write_fmt(format_args!(
+                *self = if req.query_string().is_empty() {
+                    FormatText::Str(format!(
                         "{} {} {:?}",
                         req.method(),
                         req.path(),
                         req.version()
                     ))
                 } else {
-                    fmt.write_fmt(format_args!(
+                    FormatText::Str(format!(
                         "{} {}?{} {:?}",
                         req.method(),
                         req.path(),
                         req.query_string(),
                         req.version()
                     ))
-                }
+                };
             }
-            FormatText::ResponseStatus => resp.status().as_u16().fmt(fmt),
-            FormatText::ResponseSize => resp.response_size().fmt(fmt),
-            FormatText::Time => {
-                let rt = time::now() - entry_time;
-                let rt = (rt.num_nanoseconds().unwrap_or(0) as f64) / 1_000_000_000.0;
-                fmt.write_fmt(format_args!("{:.6}", rt))
-            }
-            FormatText::TimeMillis => {
-                let rt = time::now() - entry_time;
-                let rt = (rt.num_nanoseconds().unwrap_or(0) as f64) / 1_000_000.0;
-                fmt.write_fmt(format_args!("{:.6}", rt))
+            FormatText::RequestTime => {
+                *self = FormatText::Str(format!(
+                    "{}",
+                    now.strftime("[%d/%b/%Y:%H:%M:%S %z]").unwrap()
+                ))
             }
             FormatText::RemoteAddr => {
-                if let Some(remote) = req.connection_info().remote() {
-                    return remote.fmt(fmt);
-                } else {
-                    "-".fmt(fmt)
-                }
+                let s = req.connection_info().remote().unwrap_or("-").to_owned();
+                *self = FormatText::Str(s);
             }
-            FormatText::RequestTime => entry_time
-                .strftime("[%d/%b/%Y:%H:%M:%S %z]")
-                .unwrap()
-                .fmt(fmt),
             FormatText::RequestHeader(ref name) => {
-                let s = if let Some(val) = req.headers().get(name) {
-                    if let Ok(s) = val.to_str() {
-                        s
-                    } else {
-                        "-"
-                    }
-                } else {
-                    "-"
-                };
-                fmt.write_fmt(format_args!("{}", s))
+                let s = header_value(req.headers().get(name.as_str()));
+                *self = FormatText::Str(s);
             }
-            FormatText::ResponseHeader(ref name) => {
-                let s = if let Some(val) = resp.headers().get(name) {
-                    if let Ok(s) = val.to_str() {
-                        s
-                    } else {
-                        "-"
-                    }
-                } else {
-                    "-"
-                };
-                fmt.write_fmt(format_args!("{}", s))
+            _ => (),
+        }
+    }
+
+    /// Replace response related units with rendered text
+    fn render_response<B>(&mut self, res: &Response<B>) {
+        match *self {
+            FormatText::ResponseStatus => {
+                *self = FormatText::Str(format!("{}", res.status().as_u16()))
             }
-            FormatText::EnvironHeader(ref name) => {
-                if let Ok(val) = env::var(name) {
-                    fmt.write_fmt(format_args!("{}", val))
-                } else {
-                    "-".fmt(fmt)
-                }
+            FormatText::ResponseHeader(ref name) => {
+                let s = header_value(res.headers().get(name.as_str()));
+                *self = FormatText::Str(s);
             }
+            _ => (),
         }
     }
 }
 
+fn header_value(val: Option<&HeaderValue>) -> String {
+    val.and_then(|val| val.to_str().ok())
+        .unwrap_or("-")
+        .to_owned()
+}
+
 pub(crate) struct FormatDisplay<'a>(&'a Fn(&mut Formatter) -> Result<(), fmt::Error>);
 
 impl<'a> fmt::Display for FormatDisplay<'a> {
@@ -347,42 +404,66 @@ impl<'a> fmt::Display for FormatDisplay<'a> {
 
 #[cfg(test)]
 mod tests {
+    use actix_http::http::{header, StatusCode};
     use time;
 
     use super::*;
-    use http::{header, StatusCode};
-    use test::TestRequest;
+    use crate::info::PeerAddr;
+    use crate::test::TestRequest;
+
+    fn render(
+        format: &Format,
+        req: &HttpRequest,
+        resp: &Response,
+        size: usize,
+    ) -> String {
+        let mut format = format.clone();
+        for unit in &mut format.0 {
+            unit.render_request(time::now(), req);
+            unit.render_response(resp);
+        }
+        let entry_time = Instant::now();
+        let render = |fmt: &mut Formatter| {
+            for unit in &format.0 {
+                unit.render(fmt, size, entry_time)?;
+            }
+            Ok(())
+        };
+        format!("{}", FormatDisplay(&render))
+    }
 
     #[test]
     fn test_logger() {
-        let logger = Logger::new("%% %{User-Agent}i %{X-Test}o %{HOME}e %D test");
+        let format = Format::new("%% %{User-Agent}i %{X-Test}o %{HOME}e %D %a test");
 
         let req = TestRequest::with_header(
             header::USER_AGENT,
             header::HeaderValue::from_static("ACTIX-WEB"),
-        ).finish();
+        )
+        .header("x-forwarded-for", "192.0.2.1, 10.0.0.1")
+        .request();
         let resp = Response::build(StatusCode::OK)
             .header("X-Test", "ttt")
-            .force_close()
             .finish();
 
-        match logger.start(&req) {
-            Ok(Started::Done) => (),
-            _ => panic!(),
-        };
-        match logger.finish(&req, &resp) {
-            Finished::Done => (),
-            _ => panic!(),
-        }
-        let entry_time = time::now();
-        let render = |fmt: &mut Formatter| {
-            for unit in &logger.format.0 {
-                unit.render(fmt, &req, &resp, entry_time)?;
-            }
-            Ok(())
-        };
-        let s = format!("{}", FormatDisplay(&render));
-        assert!(s.contains("ACTIX-WEB ttt"));
+        let s = render(&format, &req, &resp, 0);
+        assert!(s.starts_with("% ACTIX-WEB ttt"));
+        assert!(s.ends_with(" 192.0.2.1 test"));
+    }
+
+    #[test]
+    fn test_remote_addr() {
+        let format = Format::new("%a");
+        let resp = Response::build(StatusCode::OK).finish();
+
+        // peer address is logged for direct connections
+        let req = TestRequest::default().request();
+        req.extensions_mut()
+            .insert(PeerAddr("192.0.2.7:40000".parse().unwrap()));
+        assert_eq!(render(&format, &req, &resp, 0), "192.0.2.7");
+
+        let req = TestRequest::default().request();
+        assert_eq!(render(&format, &req, &resp, 0), "-");
     }
 
     #[test]
@@ -392,32 +473,17 @@ mod tests {
         let req = TestRequest::with_header(
             header::USER_AGENT,
             header::HeaderValue::from_static("ACTIX-WEB"),
-        ).finish();
-        let resp = Response::build(StatusCode::OK).force_close().finish();
-        let entry_time = time::now();
+        )
+        .request();
+        let resp = Response::build(StatusCode::OK).finish();
 
-        let render = |fmt: &mut Formatter| {
-            for unit in &format.0 {
-                unit.render(fmt, &req, &resp, entry_time)?;
-            }
-            Ok(())
-        };
-        let s = format!("{}", FormatDisplay(&render));
+        let s = render(&format, &req, &resp, 10);
         assert!(s.contains("GET / HTTP/1.1"));
-        assert!(s.contains("200 0"));
+        assert!(s.contains("200 10"));
         assert!(s.contains("ACTIX-WEB"));
 
-        let req = TestRequest::with_uri("/?test").finish();
-        let resp = Response::build(StatusCode::OK).force_close().finish();
-        let entry_time = time::now();
-
-        let render = |fmt: &mut Formatter| {
-            for unit in &format.0 {
-                unit.render(fmt, &req, &resp, entry_time)?;
-            }
-            Ok(())
-        };
-        let s = format!("{}", FormatDisplay(&render));
+        let req = TestRequest::with_uri("/?test").request();
+        let s = render(&format, &req, &resp, 0);
         assert!(s.contains("GET /?test HTTP/1.1"));
     }
 }
@@ -0,0 +1,159 @@
+//! Middleware for maintenance mode
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use actix_http::http::header::RETRY_AFTER;
+use actix_http::Response;
+use actix_service::{IntoNewTransform, Service, Transform};
+use bytes::Bytes;
+use futures::future::{ok, Either, FutureResult};
+use futures::{Async, Poll};
+
+use crate::middleware::MiddlewareFactory;
+use crate::service::{ServiceRequest, ServiceResponse};
+
+/// Shared maintenance mode flag.
+///
+/// Clones share the same flag, so flag could be created outside of
+/// application factory and toggled at runtime for all server workers.
+#[derive(Clone, Default)]
+pub struct MaintenanceMode(Arc<AtomicBool>);
+
+impl MaintenanceMode {
+    /// Create new flag, maintenance mode is disabled
+    pub fn new() -> Self {
+        MaintenanceMode::default()
+    }
+
+    /// Enable maintenance mode
+    pub fn enable(&self) {
+        self.0.store(true, Ordering::SeqCst)
+    }
+
+    /// Disable maintenance mode
+    pub fn disable(&self) {
+        self.0.store(false, Ordering::SeqCst)
+    }
+
+    /// Check if maintenance mode is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// `Middleware` that responds with *503 Service Unavailable* while
+/// maintenance mode is enabled.
+///
+/// Requests to allowed paths, i.e. health checks, are passed to the
+/// application as usual. By default `Retry-After` header is set to 60 seconds.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::middleware::MaintenanceMode;
+/// use actix_web2::{middleware, App, HttpResponse};
+///
+/// fn main() {
+///     let mode = MaintenanceMode::new();
+///
+///     let app = App::new()
+///         .middleware(
+///             middleware::Maintenance::new(mode.clone())
+///                 .allow("/health")
+///                 .retry_after(300)
+///                 .body("Back soon"),
+///         )
+///         .resource("/health", |r| r.get(|| HttpResponse::Ok()))
+///         .resource("/index.html", |r| r.get(|| HttpResponse::Ok()));
+///
+///     // later, i.e. from a signal handler
+///     mode.enable();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Maintenance {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    mode: MaintenanceMode,
+    allowed: Vec<String>,
+    retry_after: u32,
+    body: Bytes,
+}
+
+impl Maintenance {
+    /// Construct `Maintenance` middleware controlled by specified flag.
+    pub fn new(mode: MaintenanceMode) -> Self {
+        Maintenance {
+            inner: Rc::new(Inner {
+                mode,
+                allowed: Vec::new(),
+                retry_after: 60,
+                body: Bytes::from_static(b"Service is under maintenance"),
+            }),
+        }
+    }
+
+    /// Allow requests to the path during maintenance.
+    pub fn allow<T: Into<String>>(mut self, path: T) -> Self {
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .allowed
+            .push(path.into());
+        self
+    }
+
+    /// Set `Retry-After` header value in seconds.
+    pub fn retry_after(mut self, secs: u32) -> Self {
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .retry_after = secs;
+        self
+    }
+
+    /// Set body of the maintenance response.
+    pub fn body<B: Into<Bytes>>(mut self, body: B) -> Self {
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .body = body.into();
+        self
+    }
+}
+
+impl<S, P> IntoNewTransform<MiddlewareFactory<Maintenance, S>, S> for Maintenance
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+{
+    fn into_new_transform(self) -> MiddlewareFactory<Maintenance, S> {
+        MiddlewareFactory::new(self)
+    }
+}
+
+impl<S, P> Transform<S> for Maintenance
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+{
+    type Request = ServiceRequest<P>;
+    type Response = ServiceResponse;
+    type Error = S::Error;
+    type Future = Either<S::Future, FutureResult<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
+        let inner = &self.inner;
+
+        if inner.mode.is_enabled() && !inner.allowed.iter().any(|p| p == req.path()) {
+            let res = Response::ServiceUnavailable()
+                .header(RETRY_AFTER, inner.retry_after.to_string())
+                .content_type("text/plain")
+                .body(inner.body.clone());
+            Either::B(ok(req.into_response(res)))
+        } else {
+            Either::A(srv.call(req))
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Middleware for rejecting requests with too large declared payload
+use actix_http::http::header::CONTENT_LENGTH;
+use actix_http::Response;
+use actix_service::{IntoNewTransform, Service, Transform};
+use futures::future::{ok, Either, FutureResult};
+use futures::{Async, Poll};
+
+use crate::middleware::MiddlewareFactory;
+use crate::service::{ServiceRequest, ServiceResponse};
+
+/// `Middleware` that rejects requests with too large `Content-Length`.
+///
+/// Requests that declare payload larger than the limit get
+/// *413 Payload Too Large* response, handler is not called. Requests
+/// without `Content-Length` header, i.e. chunked requests, are passed
+/// through, extractors still enforce their own limits.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{middleware, App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .middleware(middleware::MaxPayload::new(1_048_576))
+///         .resource("/upload", |r| r.post(|| HttpResponse::Ok()));
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct MaxPayload(u64);
+
+impl MaxPayload {
+    /// Construct `MaxPayload` middleware with limit in bytes.
+    pub fn new(limit: u64) -> MaxPayload {
+        MaxPayload(limit)
+    }
+}
+
+impl<S, P> IntoNewTransform<MiddlewareFactory<MaxPayload, S>, S> for MaxPayload
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+{
+    fn into_new_transform(self) -> MiddlewareFactory<MaxPayload, S> {
+        MiddlewareFactory::new(self)
+    }
+}
+
+impl<S, P> Transform<S> for MaxPayload
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+{
+    type Request = ServiceRequest<P>;
+    type Response = ServiceResponse;
+    type Error = S::Error;
+    type Future = Either<S::Future, FutureResult<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
+        let length = req
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|val| val.to_str().ok())
+            .and_then(|val| val.parse::<u64>().ok());
+
+        match length {
+            Some(length) if length > self.0 => {
+                let res = Response::PayloadTooLarge().finish();
+                Either::B(ok(req.into_response(res)))
+            }
+            _ => Either::A(srv.call(req)),
+        }
+    }
+}
@@ -8,10 +8,39 @@ mod compress;
 #[cfg(any(feature = "brotli", feature = "flate2"))]
 pub use self::compress::Compress;
 
+mod capture;
+mod complete;
+mod condition;
+mod conditional;
+mod cors;
 mod defaultheaders;
 mod errhandlers;
+mod headerlimits;
 mod logger;
+mod maintenance;
+mod maxpayload;
+mod normalize;
+mod ratelimit;
+mod requireheaders;
+mod tagged;
+mod timeout;
+pub use self::capture::BodyCapture;
+pub use self::complete::{OnComplete, ResponseSummary};
+pub use self::condition::Condition;
+pub use self::conditional::ConditionalGet;
+pub use self::cors::Cors;
 pub use self::defaultheaders::DefaultHeaders;
+pub use self::errhandlers::ErrorHandlers;
+pub use self::headerlimits::HeaderLimits;
+pub use self::logger::Logger;
+pub use self::maintenance::{Maintenance, MaintenanceMode};
+pub use self::maxpayload::MaxPayload;
+pub use self::normalize::{NormalizePath, TrailingSlash};
+pub use self::ratelimit::RateLimit;
+pub use self::requireheaders::RequireHeaders;
+pub use self::tagged::Tagged;
+pub(crate) use self::tagged::TaggedChecks;
+pub use self::timeout::Timeout;
 
 /// Helper for middleware service factory
 pub struct MiddlewareFactory<T, S>
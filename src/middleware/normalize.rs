@@ -0,0 +1,147 @@
+//! Middleware for normalizing request path
+use actix_http::http::uri::PathAndQuery;
+use actix_http::http::Uri;
+use actix_service::{IntoNewTransform, Service, Transform};
+use bytes::Bytes;
+use futures::{Async, Poll};
+
+use crate::middleware::MiddlewareFactory;
+use crate::service::ServiceRequest;
+
+/// Trailing slash handling mode, see `NormalizePath`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Only merge repeated slashes, trailing slash is kept as is
+    MergeOnly,
+    /// Remove trailing slash
+    Trim,
+    /// Add trailing slash if it is missing
+    Always,
+}
+
+/// `Middleware` that normalizes request path before routing.
+///
+/// Repeated slashes are merged, i.e. `//foo//bar` becomes `/foo/bar`,
+/// trailing slash is handled according to `TrailingSlash` mode. Root path
+/// `/` and query string are never changed. Middleware should be
+/// registered on the `App`, so path is normalized before resource lookup.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::middleware::{NormalizePath, TrailingSlash};
+/// use actix_web2::{App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .middleware(NormalizePath::new(TrailingSlash::Trim))
+///         .resource("/foo/bar", |r| r.get(|| HttpResponse::Ok()));
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct NormalizePath(TrailingSlash);
+
+impl NormalizePath {
+    /// Construct `NormalizePath` middleware with specified trailing slash mode.
+    pub fn new(mode: TrailingSlash) -> Self {
+        NormalizePath(mode)
+    }
+}
+
+impl Default for NormalizePath {
+    fn default() -> Self {
+        NormalizePath::new(TrailingSlash::MergeOnly)
+    }
+}
+
+impl<S, P> IntoNewTransform<MiddlewareFactory<NormalizePath, S>, S> for NormalizePath
+where
+    S: Service<Request = ServiceRequest<P>>,
+{
+    fn into_new_transform(self) -> MiddlewareFactory<NormalizePath, S> {
+        MiddlewareFactory::new(self)
+    }
+}
+
+impl<S, P> Transform<S> for NormalizePath
+where
+    S: Service<Request = ServiceRequest<P>>,
+{
+    type Request = ServiceRequest<P>;
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, mut req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
+        let path = normalize(req.path(), self.0);
+        if path != req.path() {
+            if let Some(uri) = replace_path(req.uri(), path) {
+                req.set_uri(uri);
+            }
+        }
+        srv.call(req)
+    }
+}
+
+fn normalize(path: &str, mode: TrailingSlash) -> String {
+    let mut res = String::with_capacity(path.len() + 1);
+    for ch in path.chars() {
+        if ch == '/' && res.ends_with('/') {
+            continue;
+        }
+        res.push(ch);
+    }
+
+    match mode {
+        TrailingSlash::MergeOnly => (),
+        TrailingSlash::Trim => {
+            if res.len() > 1 && res.ends_with('/') {
+                res.pop();
+            }
+        }
+        TrailingSlash::Always => {
+            if !res.ends_with('/') {
+                res.push('/');
+            }
+        }
+    }
+    if res.is_empty() {
+        res.push('/');
+    }
+    res
+}
+
+fn replace_path(uri: &Uri, mut path: String) -> Option<Uri> {
+    if let Some(query) = uri.query() {
+        path.push('?');
+        path.push_str(query);
+    }
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(PathAndQuery::from_shared(Bytes::from(path)).ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        use self::TrailingSlash::*;
+
+        assert_eq!(normalize("/", MergeOnly), "/");
+        assert_eq!(normalize("/", Trim), "/");
+        assert_eq!(normalize("/", Always), "/");
+        assert_eq!(normalize("//", Trim), "/");
+
+        assert_eq!(normalize("//foo//bar/", MergeOnly), "/foo/bar/");
+        assert_eq!(normalize("//foo//bar", MergeOnly), "/foo/bar");
+        assert_eq!(normalize("//foo//bar//", Trim), "/foo/bar");
+        assert_eq!(normalize("/foo/bar", Trim), "/foo/bar");
+        assert_eq!(normalize("//foo//bar", Always), "/foo/bar/");
+        assert_eq!(normalize("/foo/bar//", Always), "/foo/bar/");
+    }
+}
@@ -0,0 +1,219 @@
+//! Middleware for limiting request rate per client
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use actix_http::http::header::RETRY_AFTER;
+use actix_http::Response;
+use actix_service::{IntoNewTransform, Service, Transform};
+use futures::future::{ok, Either, FutureResult};
+use futures::{Async, Poll};
+use hashbrown::hash_map::HashMap;
+
+use crate::middleware::MiddlewareFactory;
+use crate::request::HttpRequest;
+use crate::service::{ServiceRequest, ServiceResponse};
+
+/// Number of tracked clients after which full buckets are dropped
+const SWEEP_THRESHOLD: usize = 10_000;
+
+/// Default maximum number of tracked clients
+const MAX_CLIENTS: usize = 100_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// `Middleware` that limits request rate with a token bucket per client.
+///
+/// Client is identified by the key function, i.e. by remote address or
+/// by api key header. Requests without a key are not limited. Each
+/// request takes one token from the client's bucket, tokens are refilled
+/// continuously up to the bucket capacity. If bucket is empty, request
+/// gets *429 Too Many Requests* response with `Retry-After` header.
+///
+/// Buckets are stored in memory of the worker, so every worker limits
+/// requests independently. Number of tracked clients is limited, see
+/// `RateLimit::max_clients()`.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use std::time::Duration;
+///
+/// use actix_web2::{middleware, App, HttpRequest, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .middleware(
+///             middleware::RateLimit::new(|req: &HttpRequest| {
+///                 req.headers()
+///                     .get("x-api-key")
+///                     .and_then(|key| key.to_str().ok())
+///                     .map(|key| key.to_owned())
+///             })
+///             .capacity(100)
+///             .refill(10, Duration::from_secs(1)),
+///         )
+///         .resource("/index.html", |r| r.get(|| HttpResponse::Ok()));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct RateLimit {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    key: Box<Fn(&HttpRequest) -> Option<String>>,
+    capacity: f64,
+    rate: f64,
+    max_clients: usize,
+    buckets: RefCell<HashMap<String, Bucket>>,
+    sweep_at: Cell<usize>,
+}
+
+impl RateLimit {
+    /// Construct `RateLimit` middleware with specified key function.
+    ///
+    /// By default bucket capacity is 60 requests, refill rate is one
+    /// request per second.
+    pub fn new<F>(key: F) -> RateLimit
+    where
+        F: Fn(&HttpRequest) -> Option<String> + 'static,
+    {
+        RateLimit {
+            inner: Rc::new(Inner {
+                key: Box::new(key),
+                capacity: 60.0,
+                rate: 1.0,
+                max_clients: MAX_CLIENTS,
+                buckets: RefCell::new(HashMap::new()),
+                sweep_at: Cell::new(SWEEP_THRESHOLD),
+            }),
+        }
+    }
+
+    /// Set bucket capacity, maximum number of requests in a burst.
+    pub fn capacity(mut self, capacity: u32) -> Self {
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .capacity = f64::from(capacity);
+        self
+    }
+
+    /// Set refill rate, number of tokens added to the bucket per period.
+    pub fn refill(mut self, tokens: u32, period: Duration) -> Self {
+        let secs = period.as_secs() as f64 + f64::from(period.subsec_nanos()) / 1e9;
+        assert!(secs > 0.0, "Refill period must not be zero");
+
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .rate = f64::from(tokens) / secs;
+        self
+    }
+
+    /// Set maximum number of tracked clients. By default it is 100 000.
+    ///
+    /// If limit is reached, bucket of some other client is dropped to
+    /// track a new client, so memory used by the middleware is bounded
+    /// even if clients keep changing their keys.
+    pub fn max_clients(mut self, max: usize) -> Self {
+        assert!(max > 0, "Max clients must not be zero");
+
+        Rc::get_mut(&mut self.inner)
+            .expect("Multiple copies exist")
+            .max_clients = max;
+        self
+    }
+}
+
+impl Inner {
+    /// Take a token from the client's bucket, returns seconds to wait
+    /// for the next token if bucket is empty
+    fn acquire(&self, key: String) -> Result<(), u64> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.borrow_mut();
+
+        if !buckets.contains_key(&key) {
+            // full buckets are dropped once number of clients doubles
+            // since previous sweep, so sweeping cost is amortized
+            if buckets.len() >= self.sweep_at.get() {
+                let (capacity, rate) = (self.capacity, self.rate);
+                buckets.retain(|_, bucket| {
+                    refilled(bucket, now, capacity, rate) < capacity
+                });
+                self.sweep_at.set((buckets.len() * 2).max(SWEEP_THRESHOLD));
+            }
+
+            if buckets.len() >= self.max_clients {
+                let evicted = buckets.keys().next().cloned();
+                if let Some(evicted) = evicted {
+                    buckets.remove(&evicted);
+                }
+            }
+        }
+
+        let capacity = self.capacity;
+        let bucket = buckets.entry(key).or_insert_with(|| Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        bucket.tokens = refilled(bucket, now, self.capacity, self.rate);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if self.rate > 0.0 {
+            Err(((1.0 - bucket.tokens) / self.rate).ceil().max(1.0) as u64)
+        } else {
+            Err(u64::from(u32::max_value()))
+        }
+    }
+}
+
+fn refilled(bucket: &Bucket, now: Instant, capacity: f64, rate: f64) -> f64 {
+    let elapsed = now.duration_since(bucket.updated);
+    let secs = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+    (bucket.tokens + secs * rate).min(capacity)
+}
+
+impl<S, P> IntoNewTransform<MiddlewareFactory<RateLimit, S>, S> for RateLimit
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+{
+    fn into_new_transform(self) -> MiddlewareFactory<RateLimit, S> {
+        MiddlewareFactory::new(self)
+    }
+}
+
+impl<S, P> Transform<S> for RateLimit
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+{
+    type Request = ServiceRequest<P>;
+    type Response = ServiceResponse;
+    type Error = S::Error;
+    type Future = Either<S::Future, FutureResult<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
+        let key = match (self.inner.key)(req.request()) {
+            Some(key) => key,
+            None => return Either::A(srv.call(req)),
+        };
+
+        match self.inner.acquire(key) {
+            Ok(()) => Either::A(srv.call(req)),
+            Err(retry) => {
+                let res = Response::TooManyRequests()
+                    .header(RETRY_AFTER, retry.to_string())
+                    .finish();
+                Either::B(ok(req.into_response(res)))
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Middleware for rejecting requests without required headers
+use std::rc::Rc;
+
+use actix_http::http::{HeaderName, HttpTryFrom};
+use actix_http::Response;
+use actix_service::{IntoNewTransform, Service, Transform};
+use futures::future::{ok, Either, FutureResult};
+use futures::{Async, Poll};
+
+use crate::middleware::MiddlewareFactory;
+use crate::service::{ServiceRequest, ServiceResponse};
+
+/// `Middleware` that rejects requests without required headers.
+///
+/// Requests missing any of the required headers get *400 Bad Request*
+/// response, response body lists all missing headers, i.e.
+/// `Missing required headers: x-tenant-id, x-request-id`.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{middleware, App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .middleware(
+///             middleware::RequireHeaders::new()
+///                 .header("X-Tenant-Id")
+///                 .header("X-Request-Id"),
+///         )
+///         .resource("/index.html", |r| r.get(|| HttpResponse::Ok()));
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct RequireHeaders {
+    headers: Rc<Vec<HeaderName>>,
+}
+
+impl RequireHeaders {
+    /// Construct `RequireHeaders` middleware.
+    pub fn new() -> RequireHeaders {
+        RequireHeaders::default()
+    }
+
+    /// Add required header.
+    pub fn header<K>(mut self, key: K) -> Self
+    where
+        HeaderName: HttpTryFrom<K>,
+    {
+        #[allow(clippy::match_wild_err_arm)]
+        match HeaderName::try_from(key) {
+            Ok(key) => Rc::get_mut(&mut self.headers)
+                .expect("Multiple copies exist")
+                .push(key),
+            Err(_) => panic!("Can not create header name"),
+        }
+        self
+    }
+}
+
+impl<S, P> IntoNewTransform<MiddlewareFactory<RequireHeaders, S>, S> for RequireHeaders
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+{
+    fn into_new_transform(self) -> MiddlewareFactory<RequireHeaders, S> {
+        MiddlewareFactory::new(self)
+    }
+}
+
+impl<S, P> Transform<S> for RequireHeaders
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+{
+    type Request = ServiceRequest<P>;
+    type Response = ServiceResponse;
+    type Error = S::Error;
+    type Future = Either<S::Future, FutureResult<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
+        let missing: Vec<_> = self
+            .headers
+            .iter()
+            .filter(|name| !req.headers().contains_key(*name))
+            .map(|name| name.as_str())
+            .collect();
+
+        if missing.is_empty() {
+            Either::A(srv.call(req))
+        } else {
+            let res = Response::BadRequest()
+                .content_type("text/plain")
+                .body(format!("Missing required headers: {}", missing.join(", ")));
+            Either::B(ok(req.into_response(res)))
+        }
+    }
+}
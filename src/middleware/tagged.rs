@@ -0,0 +1,151 @@
+//! Middleware for processing responses of tagged routes
+use std::rc::Rc;
+
+use actix_http::Response;
+use actix_service::{IntoNewTransform, Service, Transform};
+use futures::{Async, Future, Poll};
+
+use crate::middleware::MiddlewareFactory;
+use crate::request::HttpRequest;
+use crate::service::{ServiceRequest, ServiceResponse};
+
+type CheckFn = Rc<Fn(&HttpRequest) -> Option<Response>>;
+
+/// `Middleware` that is active only for routes marked with specific tag.
+///
+/// Route tags are set with `RouteBuilder::tag()` method during route
+/// registration. Tags are known only after routing, so handler gets invoked
+/// for responses of matched routes that carry the tag. Responses of all other
+/// routes are passed through unchanged. Requests could be checked before
+/// route handler is called with `Tagged::check()` method.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::http::header::CACHE_CONTROL;
+/// use actix_web2::{middleware, App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .middleware(
+///             middleware::Tagged::new("private", |res| {
+///                 res.headers_mut()
+///                     .insert(CACHE_CONTROL, "no-store".parse().unwrap());
+///             })
+///             .check(|req| {
+///                 if req.headers().contains_key("x-session") {
+///                     None
+///                 } else {
+///                     Some(HttpResponse::Unauthorized().finish())
+///                 }
+///             }),
+///         )
+///         .resource("/account", |r| {
+///             r.route(|r| r.tag("private").to(|| HttpResponse::Ok()))
+///         })
+///         .resource("/index.html", |r| r.get(|| HttpResponse::Ok()));
+/// }
+/// ```
+pub struct Tagged<B> {
+    tag: Rc<String>,
+    handler: Rc<Fn(&mut ServiceResponse<B>)>,
+    check: Option<CheckFn>,
+}
+
+impl<B> Tagged<B> {
+    /// Construct `Tagged` middleware for routes with specified tag.
+    pub fn new<T, F>(tag: T, handler: F) -> Self
+    where
+        T: Into<String>,
+        F: Fn(&mut ServiceResponse<B>) + 'static,
+    {
+        Tagged {
+            tag: Rc::new(tag.into()),
+            handler: Rc::new(handler),
+            check: None,
+        }
+    }
+
+    /// Set check for requests to tagged routes.
+    ///
+    /// Check is called after routing, before route handler. If check
+    /// returns a response, route handler is not called and the response is
+    /// sent instead.
+    pub fn check<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&HttpRequest) -> Option<Response> + 'static,
+    {
+        self.check = Some(Rc::new(check));
+        self
+    }
+}
+
+impl<B> Clone for Tagged<B> {
+    fn clone(&self) -> Self {
+        Tagged {
+            tag: self.tag.clone(),
+            handler: self.handler.clone(),
+            check: self.check.clone(),
+        }
+    }
+}
+
+impl<S, P, B> IntoNewTransform<MiddlewareFactory<Tagged<B>, S>, S> for Tagged<B>
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse<B>>,
+    S::Future: 'static,
+{
+    fn into_new_transform(self) -> MiddlewareFactory<Tagged<B>, S> {
+        MiddlewareFactory::new(self)
+    }
+}
+
+impl<S, P, B> Transform<S> for Tagged<B>
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse<B>>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest<P>;
+    type Response = ServiceResponse<B>;
+    type Error = S::Error;
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
+        let inner = self.clone();
+
+        // route checks tags of the matched route, see `TaggedChecks`
+        if let Some(ref check) = self.check {
+            let mut extensions = req.extensions_mut();
+            if !extensions.contains::<TaggedChecks>() {
+                extensions.insert(TaggedChecks(Vec::new()));
+            }
+            if let Some(checks) = extensions.get_mut::<TaggedChecks>() {
+                checks.0.push((self.tag.clone(), check.clone()));
+            }
+        }
+
+        Box::new(srv.call(req).map(move |mut res| {
+            if res.request().route_tag(&inner.tag) {
+                (inner.handler)(&mut res);
+            }
+            res
+        }))
+    }
+}
+
+/// Checks of `Tagged` middlewares, stored in request extensions
+#[derive(Clone)]
+pub(crate) struct TaggedChecks(Vec<(Rc<String>, CheckFn)>);
+
+impl TaggedChecks {
+    /// Response of the first failed check for the route with specified tags
+    pub(crate) fn check(&self, req: &HttpRequest, tags: &[String]) -> Option<Response> {
+        self.0
+            .iter()
+            .filter(|(tag, _)| tags.iter().any(|t| t.as_str() == tag.as_str()))
+            .find_map(|(_, check)| check(req))
+    }
+}
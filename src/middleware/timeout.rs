@@ -0,0 +1,143 @@
+//! Middleware for limiting request processing time
+use std::time::{Duration, Instant};
+
+use actix_http::http::StatusCode;
+use actix_http::Response;
+use actix_service::{IntoNewTransform, Service, Transform};
+use bytes::Bytes;
+use futures::{Async, Future, Poll};
+use log::warn;
+use tokio_timer::Delay;
+
+use crate::middleware::MiddlewareFactory;
+use crate::request::HttpRequest;
+use crate::service::{ServiceRequest, ServiceResponse};
+
+/// `Middleware` that limits request processing time.
+///
+/// If inner service does not produce response within the timeout, its
+/// future is dropped and *503 Service Unavailable* response is sent.
+/// Status and body of the timeout response could be changed with
+/// `Timeout::status()` and `Timeout::body()`.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use std::time::Duration;
+///
+/// use actix_web2::http::StatusCode;
+/// use actix_web2::{middleware, App, HttpResponse};
+///
+/// fn main() {
+///     let app = App::new()
+///         .middleware(
+///             middleware::Timeout::new(Duration::from_secs(5))
+///                 .status(StatusCode::GATEWAY_TIMEOUT)
+///                 .body("Request took too long"),
+///         )
+///         .resource("/index.html", |r| r.get(|| HttpResponse::Ok()));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Timeout {
+    timeout: Duration,
+    status: StatusCode,
+    body: Bytes,
+}
+
+impl Timeout {
+    /// Construct `Timeout` middleware with specified timeout.
+    pub fn new(timeout: Duration) -> Timeout {
+        Timeout {
+            timeout,
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            body: Bytes::new(),
+        }
+    }
+
+    /// Set status code of the timeout response.
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Set body of the timeout response. By default body is empty.
+    pub fn body<B: Into<Bytes>>(mut self, body: B) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+impl<S, P> IntoNewTransform<MiddlewareFactory<Timeout, S>, S> for Timeout
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+{
+    fn into_new_transform(self) -> MiddlewareFactory<Timeout, S> {
+        MiddlewareFactory::new(self)
+    }
+}
+
+impl<S, P> Transform<S> for Timeout
+where
+    S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+{
+    type Request = ServiceRequest<P>;
+    type Response = ServiceResponse;
+    type Error = S::Error;
+    type Future = TimeoutResponse<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
+        TimeoutResponse {
+            req: req.request().clone(),
+            status: self.status,
+            body: self.body.clone(),
+            delay: Delay::new(Instant::now() + self.timeout),
+            fut: Some(srv.call(req)),
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct TimeoutResponse<F> {
+    fut: Option<F>,
+    delay: Delay,
+    req: HttpRequest,
+    status: StatusCode,
+    body: Bytes,
+}
+
+impl<F> Future for TimeoutResponse<F>
+where
+    F: Future<Item = ServiceResponse>,
+{
+    type Item = ServiceResponse;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Some(ref mut fut) = self.fut {
+            match fut.poll()? {
+                Async::Ready(res) => return Ok(Async::Ready(res)),
+                Async::NotReady => (),
+            }
+        } else {
+            panic!("TimeoutResponse polled after completion");
+        }
+
+        match self.delay.poll() {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            // timer error means timer is gone, request could not be
+            // limited anymore, treat it as expired
+            Ok(Async::Ready(_)) | Err(_) => {
+                warn!("Request timed out: {}", self.req.path());
+
+                // drop inner future, so its resources are released
+                self.fut.take();
+                let res = Response::build(self.status).body(self.body.clone());
+                Ok(Async::Ready(ServiceResponse::new(self.req.clone(), res)))
+            }
+        }
+    }
+}
@@ -9,12 +9,19 @@ use actix_router::{Path, Url};
 use futures::future::{ok, FutureResult};
 
 use crate::handler::FromRequest;
+use crate::info::ConnectionInfo;
+use crate::route::{BudgetExceeded, RouteConfig, RouteTags};
 use crate::service::ServiceRequest;
 
+/// Pattern of the matched resource
+pub(crate) struct MatchedPattern(pub(crate) Rc<String>);
+
 #[derive(Clone)]
 pub struct HttpRequest {
     head: Message<RequestHead>,
     pub(crate) path: Path<Url>,
+    /// Uri rewritten by middleware, request head is shared and stays intact
+    pub(crate) uri: Option<Uri>,
     extensions: Rc<Extensions>,
 }
 
@@ -28,6 +35,7 @@ impl HttpRequest {
         HttpRequest {
             head,
             path,
+            uri: None,
             extensions,
         }
     }
@@ -41,9 +49,12 @@ impl HttpRequest {
     }
 
     /// Request's uri.
+    ///
+    /// If uri is rewritten by middleware, i.e. `NormalizePath`, rewritten
+    /// uri is returned.
     #[inline]
     pub fn uri(&self) -> &Uri {
-        &self.head().uri
+        self.uri.as_ref().unwrap_or(&self.head().uri)
     }
 
     /// Read the Request method.
@@ -61,7 +72,7 @@ impl HttpRequest {
     /// The target path of this Request.
     #[inline]
     pub fn path(&self) -> &str {
-        self.head().uri.path()
+        self.uri().path()
     }
 
     #[inline]
@@ -82,6 +93,21 @@ impl HttpRequest {
         }
     }
 
+    /// Request target, path and query string as they are received.
+    ///
+    /// Value is not normalized or decoded, so it could be used for
+    /// url signature verification or for proxying request verbatim.
+    /// Also available for extractors via `ServiceRequest`.
+    ///
+    /// E.g., /users/%7Bid%7D?id=10
+    #[inline]
+    pub fn request_target(&self) -> &str {
+        let uri = &self.head().uri;
+        uri.path_and_query()
+            .map(|target| target.as_str())
+            .unwrap_or_else(|| uri.path())
+    }
+
     /// Get a reference to the Path parameters.
     ///
     /// Params is a container for url parameters.
@@ -111,11 +137,54 @@ impl HttpRequest {
         &self.extensions
     }
 
-    // /// Get *ConnectionInfo* for the correct request.
-    // #[inline]
-    // pub fn connection_info(&self) -> Ref<ConnectionInfo> {
-    //    ConnectionInfo::get(&*self)
-    // }
+    /// Extractor configuration registered for the matched route with
+    /// `RouteBuilder::config()`
+    #[inline]
+    pub(crate) fn route_config<C: Clone + 'static>(&self) -> Option<C> {
+        self.extensions()
+            .get::<RouteConfig>()
+            .and_then(|cfg| cfg.get::<C>())
+    }
+
+    /// Pattern of the matched resource, as it is registered.
+    ///
+    /// Pattern is available after routing, i.e. `/users/{id}` for
+    /// `/users/42` request. Unlike request path, pattern has bounded
+    /// cardinality, so it could be used for metrics and logging.
+    #[inline]
+    pub fn matched_path(&self) -> Option<String> {
+        self.extensions()
+            .get::<MatchedPattern>()
+            .map(|pattern| pattern.0.as_ref().clone())
+    }
+
+    /// Response time budget overrun of the matched route.
+    ///
+    /// Available after route handler completes, budget is set with
+    /// `RouteBuilder::budget()` method.
+    #[inline]
+    pub fn budget_exceeded(&self) -> Option<BudgetExceeded> {
+        self.extensions().get::<BudgetExceeded>().cloned()
+    }
+
+    /// Check if matched route is marked with specified tag.
+    ///
+    /// Route tags are available only after routing, route is marked with
+    /// `RouteBuilder::tag()` method.
+    #[inline]
+    pub fn route_tag(&self, tag: &str) -> bool {
+        if let Some(tags) = self.extensions().get::<RouteTags>() {
+            tags.contains(tag)
+        } else {
+            false
+        }
+    }
+
+    /// Get *ConnectionInfo* for the correct request.
+    #[inline]
+    pub fn connection_info(&self) -> Ref<ConnectionInfo> {
+        ConnectionInfo::get(self.head())
+    }
 }
 
 impl Deref for HttpRequest {
@@ -1,12 +1,14 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use actix_http::{http::Method, Error, Response};
+use actix_http::http::{header, Method};
+use actix_http::{Error, Response};
 use actix_service::{
     ApplyNewService, IntoNewService, IntoNewTransform, NewService, NewTransform, Service,
 };
 use futures::future::{ok, Either, FutureResult};
 use futures::{Async, Future, IntoFuture, Poll};
+use hashbrown::HashMap;
 
 use crate::handler::{AsyncFactory, Factory, FromRequest};
 use crate::helpers::{DefaultNewService, HttpDefaultNewService, HttpDefaultService};
@@ -18,8 +20,14 @@ use crate::service::{ServiceRequest, ServiceResponse};
 ///
 /// Route uses builder-like pattern for configuration.
 /// If handler is not explicitly set, default *404 Not Found* handler is used.
+/// If all routes of the resource are restricted to specific methods and
+/// request method does not match any of them, *405 Method Not Allowed*
+/// response with `Allow` header is returned instead. `OPTIONS` requests to
+/// such resource get *200 OK* response with `Allow` header, unless it is
+/// disabled with `Resource::auto_options()`.
 pub struct Resource<P, T = ResourceEndpoint<P>> {
     routes: Vec<Route<P>>,
+    auto_options: bool,
     endpoint: T,
     default: Rc<
         RefCell<Option<Rc<HttpDefaultNewService<ServiceRequest<P>, ServiceResponse>>>>,
@@ -33,6 +41,7 @@ impl<P> Resource<P> {
 
         Resource {
             routes: Vec::new(),
+            auto_options: true,
             endpoint: ResourceEndpoint::new(fref.clone()),
             factory_ref: fref,
             default: Rc::new(RefCell::new(None)),
@@ -261,11 +270,23 @@ where
         Resource {
             endpoint,
             routes: self.routes,
+            auto_options: self.auto_options,
             default: self.default,
             factory_ref: self.factory_ref,
         }
     }
 
+    /// Respond to unhandled `OPTIONS` requests with `Allow` header.
+    ///
+    /// Enabled by default. Response lists methods of the resource's routes,
+    /// it is generated only if all routes are restricted to specific methods.
+    /// Cors middleware handles preflight requests before they reach the
+    /// resource, so it takes precedence for requests with `Origin` header.
+    pub fn auto_options(mut self, enabled: bool) -> Self {
+        self.auto_options = enabled;
+        self
+    }
+
     /// Default resource to be used if no matching route could be found.
     pub fn default_resource<F, R, U>(mut self, f: F) -> Self
     where
@@ -291,6 +312,11 @@ where
     {
         self.default.clone()
     }
+
+    /// Methods of the registered routes, in registration order
+    pub(crate) fn route_methods(&self) -> Vec<Vec<Method>> {
+        self.routes.iter().map(|r| r.methods().to_vec()).collect()
+    }
 }
 
 impl<P, T> IntoNewService<T> for Resource<P, T>
@@ -305,6 +331,7 @@ where
     fn into_new_service(self) -> T {
         *self.factory_ref.borrow_mut() = Some(ResourceFactory {
             routes: self.routes,
+            auto_options: self.auto_options,
             default: self.default,
         });
 
@@ -314,6 +341,7 @@ where
 
 pub struct ResourceFactory<P> {
     routes: Vec<Route<P>>,
+    auto_options: bool,
     default: Rc<
         RefCell<Option<Rc<HttpDefaultNewService<ServiceRequest<P>, ServiceResponse>>>>,
     >,
@@ -340,6 +368,7 @@ impl<P> NewService for ResourceFactory<P> {
                 .iter()
                 .map(|route| CreateRouteServiceItem::Future(route.new_service()))
                 .collect(),
+            auto_options: self.auto_options,
             default: None,
             default_fut,
         }
@@ -353,6 +382,7 @@ enum CreateRouteServiceItem<P> {
 
 pub struct CreateResourceService<P> {
     fut: Vec<CreateRouteServiceItem<P>>,
+    auto_options: bool,
     default: Option<HttpDefaultService<ServiceRequest<P>, ServiceResponse>>,
     default_fut: Option<
         Box<
@@ -394,7 +424,7 @@ impl<P> Future for CreateResourceService<P> {
         }
 
         if done {
-            let routes = self
+            let routes: Vec<_> = self
                 .fut
                 .drain(..)
                 .map(|item| match item {
@@ -403,7 +433,9 @@ impl<P> Future for CreateResourceService<P> {
                 })
                 .collect();
             Ok(Async::Ready(ResourceService {
+                index: RouteIndex::new(&routes),
                 routes,
+                auto_options: self.auto_options,
                 default: self.default.take(),
             }))
         } else {
@@ -414,6 +446,8 @@ impl<P> Future for CreateResourceService<P> {
 
 pub struct ResourceService<P> {
     routes: Vec<RouteService<P>>,
+    auto_options: bool,
+    index: RouteIndex,
     default: Option<HttpDefaultService<ServiceRequest<P>, ServiceResponse>>,
 }
 
@@ -434,19 +468,113 @@ impl<P> Service for ResourceService<P> {
     }
 
     fn call(&mut self, mut req: ServiceRequest<P>) -> Self::Future {
-        for route in self.routes.iter_mut() {
-            if route.check(&mut req) {
-                return Either::A(route.call(req));
+        // check only routes that could match request's method,
+        // in registration order
+        let by_method = self
+            .index
+            .methods
+            .get(req.method())
+            .map(|idx| &idx[..])
+            .unwrap_or(&[]);
+        let any = &self.index.any[..];
+        let (mut i, mut j) = (0, 0);
+        loop {
+            let idx = match (by_method.get(i), any.get(j)) {
+                (Some(a), Some(b)) if a < b => {
+                    i += 1;
+                    *a
+                }
+                (_, Some(b)) => {
+                    j += 1;
+                    *b
+                }
+                (Some(a), None) => {
+                    i += 1;
+                    *a
+                }
+                (None, None) => break,
+            };
+            if self.routes[idx].check(&mut req) {
+                return Either::A(self.routes[idx].call(req));
+            }
+        }
+        if self.auto_options && *req.method() == Method::OPTIONS {
+            if let Some(allowed) = self.allowed_methods(req.method()) {
+                let res = Response::Ok()
+                    .header(header::ALLOW, format!("{}, OPTIONS", allowed))
+                    .finish();
+                return Either::B(Either::B(ok(ServiceResponse::new(
+                    req.into_request(),
+                    res,
+                ))));
             }
         }
         if let Some(ref mut default) = self.default {
             Either::B(Either::A(default.call(req)))
         } else {
-            let req = req.into_request();
-            Either::B(Either::B(ok(ServiceResponse::new(
-                req,
-                Response::NotFound().finish(),
-            ))))
+            let res = if let Some(allowed) = self.allowed_methods(req.method()) {
+                Response::MethodNotAllowed()
+                    .header(header::ALLOW, allowed)
+                    .finish()
+            } else {
+                Response::NotFound().finish()
+            };
+            Either::B(Either::B(ok(ServiceResponse::new(req.into_request(), res))))
+        }
+    }
+}
+
+/// Index of resource's routes by method
+///
+/// Routes without method filters are candidates for any request.
+struct RouteIndex {
+    methods: HashMap<Method, Vec<usize>>,
+    any: Vec<usize>,
+}
+
+impl RouteIndex {
+    fn new<P>(routes: &[RouteService<P>]) -> Self {
+        let mut methods: HashMap<Method, Vec<usize>> = HashMap::new();
+        let mut any = Vec::new();
+
+        for (idx, route) in routes.iter().enumerate() {
+            if route.methods().is_empty() {
+                any.push(idx);
+            } else {
+                for method in route.methods() {
+                    let entry = methods.entry(method.clone()).or_insert_with(Vec::new);
+                    if entry.last() != Some(&idx) {
+                        entry.push(idx);
+                    }
+                }
+            }
+        }
+        RouteIndex { methods, any }
+    }
+}
+
+impl<P> ResourceService<P> {
+    /// Value for `Allow` header, if request could not be handled
+    /// only because of its method.
+    ///
+    /// Extension methods are listed same way as standard one.
+    fn allowed_methods(&self, method: &Method) -> Option<String> {
+        let mut allowed: Vec<&Method> = Vec::new();
+        for route in &self.routes {
+            if route.methods().is_empty() {
+                return None;
+            }
+            for m in route.methods() {
+                if !allowed.contains(&m) {
+                    allowed.push(m);
+                }
+            }
+        }
+        if allowed.is_empty() || allowed.contains(&method) {
+            None
+        } else {
+            let allowed: Vec<&str> = allowed.iter().map(|m| m.as_str()).collect();
+            Some(allowed.join(", "))
         }
     }
 }
@@ -1,11 +1,36 @@
+use std::cell::RefCell;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use std::{env, fmt, process};
+
+use actix_http::body::{Body, BodyLength, MessageBody};
 use actix_http::dev::ResponseBuilder;
-use actix_http::http::StatusCode;
+use actix_http::error::{ErrorInternalServerError, ResponseError};
+use actix_http::http::header::{
+    Charset, ContentDisposition, ContentEncoding, DispositionParam, DispositionType,
+    ETag, EntityTag, ExtendedValue, HeaderName, IntoHeaderValue, ACCEPT, ACCEPT_RANGES,
+    CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_RANGE, CONTENT_TYPE,
+    RANGE, VARY,
+};
+use actix_http::http::{Error as HttpError, HeaderMap, HttpTryFrom, Method, StatusCode};
 use actix_http::{Error, Response};
 use bytes::{Bytes, BytesMut};
 use futures::future::{err, ok, Either as EitherFuture, FutureResult};
-use futures::{Future, Poll};
+use futures::{try_ready, Async, Future, Poll, Stream};
+use futures_cpupool::{CpuFuture, CpuPool};
+use mime::Mime;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tokio_timer::Delay;
 
+use crate::extractor::Json;
+use crate::fs::{none_match, ChunkedReadFile, HttpRange, DEFAULT_CPUPOOL};
 use crate::request::HttpRequest;
+use crate::state::State;
 
 /// Trait implemented by types that generate http responses.
 ///
@@ -19,6 +44,27 @@ pub trait Responder {
 
     /// Convert itself to `AsyncResult` or `Error`.
     fn respond_to(self, req: &HttpRequest) -> Self::Future;
+
+    /// Customize generated response, i.e. override status code or headers.
+    ///
+    /// ```rust,ignore
+    /// # extern crate actix_web2;
+    /// use actix_web2::{http, Responder};
+    ///
+    /// fn index() -> impl Responder {
+    ///     "<h1>Welcome!</h1>"
+    ///         .customize()
+    ///         .status(http::StatusCode::CREATED)
+    ///         .content_type("text/html; charset=utf-8")
+    /// }
+    /// # fn main() {}
+    /// ```
+    fn customize(self) -> CustomizeResponder<Self>
+    where
+        Self: Sized,
+    {
+        CustomizeResponder::new(self)
+    }
 }
 
 impl Responder for Response {
@@ -46,6 +92,24 @@ where
     }
 }
 
+/// `Err` is converted to `Error` and rendered with its `ResponseError`
+/// implementation.
+///
+/// Implementation for `Result` with responder in both arms would overlap
+/// with this one, convert result to `Either` instead, so error arm renders
+/// its own response:
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::http::StatusCode;
+/// use actix_web2::{Either, Json, Responder};
+///
+/// fn index() -> impl Responder {
+///     let res: Result<Json<User>, (Json<Problem>, StatusCode)> = load_user();
+///     Either::from(res)
+/// }
+/// # fn main() {}
+/// ```
 impl<T, E> Responder for Result<T, E>
 where
     T: Responder,
@@ -138,102 +202,1850 @@ impl Responder for BytesMut {
     }
 }
 
-/// Combines two different responder types into a single type
+/// Responder that overrides status code and headers of the inner responder.
+///
+/// Created by `Responder::customize()` method.
+pub struct CustomizeResponder<T> {
+    responder: T,
+    status: Option<StatusCode>,
+    headers: HeaderMap,
+    error: Option<HttpError>,
+}
+
+impl<T: Responder> CustomizeResponder<T> {
+    fn new(responder: T) -> Self {
+        CustomizeResponder {
+            responder,
+            status: None,
+            headers: HeaderMap::new(),
+            error: None,
+        }
+    }
+
+    /// Override response status code
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Set a header, existing header with the same name gets replaced
+    pub fn header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        HeaderName: HttpTryFrom<K>,
+        V: IntoHeaderValue,
+    {
+        if self.error.is_none() {
+            match HeaderName::try_from(key) {
+                Ok(key) => match value.try_into() {
+                    Ok(value) => {
+                        self.headers.insert(key, value);
+                    }
+                    Err(e) => self.error = Some(e.into()),
+                },
+                Err(e) => self.error = Some(e.into()),
+            }
+        }
+        self
+    }
+
+    /// Override response content type
+    pub fn content_type<V: IntoHeaderValue>(self, value: V) -> Self {
+        self.header(CONTENT_TYPE, value)
+    }
+}
+
+impl<T: Responder> Responder for CustomizeResponder<T> {
+    type Error = Error;
+    type Future =
+        EitherFuture<CustomizeResponse<T::Future>, FutureResult<Response, Error>>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        if let Some(e) = self.error {
+            return EitherFuture::B(err(e.into()));
+        }
+
+        EitherFuture::A(CustomizeResponse {
+            fut: self.responder.respond_to(req),
+            status: self.status,
+            headers: self.headers,
+        })
+    }
+}
+
+/// Headers are merged onto the response of the inner responder, response
+/// headers with the same names get replaced.
 ///
 /// ```rust,ignore
-/// # extern crate actix_web;
-/// # extern crate futures;
-/// # use futures::future::Future;
-/// use actix_web::{AsyncResponder, Either, Error, Request, Response};
-/// use futures::future::result;
+/// # extern crate actix_web2;
+/// use actix_web2::http::{header, HeaderMap};
 ///
-/// type RegisterResult =
-///     Either<Response, Box<Future<Item = Response, Error = Error>>>;
+/// fn index() -> (HeaderMap, &'static str) {
+///     let mut headers = HeaderMap::new();
+///     headers.insert(header::CACHE_CONTROL, "no-cache".parse().unwrap());
+///     headers.insert(header::PRAGMA, "no-cache".parse().unwrap());
+///     (headers, "Welcome!")
+/// }
+/// # fn main() {}
+/// ```
+impl<T: Responder> Responder for (HeaderMap, T) {
+    type Error = Error;
+    type Future = CustomizeResponse<T::Future>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        CustomizeResponse {
+            fut: self.1.respond_to(req),
+            status: None,
+            headers: self.0,
+        }
+    }
+}
+
+/// Status code overrides status of the inner responder's response, errors
+/// of the inner responder are returned as is.
 ///
-/// fn index(req: Request) -> RegisterResult {
-///     if is_a_variant() {
-///         // <- choose variant A
-///         Either::A(Response::BadRequest().body("Bad data"))
-///     } else {
-///         Either::B(
-///             // <- variant B
-///             result(Ok(Response::Ok()
-///                 .content_type("text/html")
-///                 .body("Hello!")))
-///                 .responder(),
-///         )
-///     }
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::http::StatusCode;
+/// use actix_web2::Json;
+///
+/// fn create() -> (Json<User>, StatusCode) {
+///     (Json(User::new()), StatusCode::CREATED)
 /// }
-/// # fn is_a_variant() -> bool { true }
 /// # fn main() {}
 /// ```
-pub enum Either<A, B> {
-    /// First branch of the type
-    A(A),
-    /// Second branch of the type
-    B(B),
+impl<T: Responder> Responder for (T, StatusCode) {
+    type Error = Error;
+    type Future = CustomizeResponse<T::Future>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        CustomizeResponse {
+            fut: self.0.respond_to(req),
+            status: Some(self.1),
+            headers: HeaderMap::new(),
+        }
+    }
 }
 
-impl<A, B> Responder for Either<A, B>
+#[doc(hidden)]
+pub struct CustomizeResponse<T> {
+    fut: T,
+    status: Option<StatusCode>,
+    headers: HeaderMap,
+}
+
+impl<T> Future for CustomizeResponse<T>
 where
-    A: Responder,
-    B: Responder,
+    T: Future<Item = Response>,
+    T::Error: Into<Error>,
 {
+    type Item = Response;
     type Error = Error;
-    type Future = EitherResponder<A::Future, B::Future>;
 
-    fn respond_to(self, req: &HttpRequest) -> Self::Future {
-        match self {
-            Either::A(a) => EitherResponder::A(a.respond_to(req)),
-            Either::B(b) => EitherResponder::B(b.respond_to(req)),
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let mut res = try_ready!(self.fut.poll().map_err(|e| e.into()));
+
+        if let Some(status) = self.status {
+            *res.status_mut() = status;
+        }
+        for key in self.headers.keys() {
+            res.headers_mut().remove(key);
         }
+        for (key, value) in self.headers.iter() {
+            res.headers_mut().append(key.clone(), value.clone());
+        }
+        Ok(Async::Ready(res))
     }
 }
 
-pub enum EitherResponder<A, B>
+/// Responder that streams body chunks from a channel.
+///
+/// Chunks are sent to the client as soon as they are received, response
+/// is complete when all senders are dropped. Any stream of `Bytes`, i.e.
+/// `futures::sync::mpsc::Receiver<Bytes>`, could be used as a channel.
+/// By default `application/octet-stream` content type is used.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// # extern crate bytes;
+/// # extern crate futures;
+/// use actix_web2::dev::Channel;
+/// use bytes::Bytes;
+/// use futures::sync::mpsc;
+///
+/// fn progress() -> Channel<mpsc::UnboundedReceiver<Bytes>> {
+///     let (tx, rx) = mpsc::unbounded();
+///     // pass `tx` to a task that reports progress
+///     # drop(tx);
+///     Channel::new(rx).content_type("text/plain")
+/// }
+/// # fn main() {}
+/// ```
+pub struct Channel<S> {
+    rx: S,
+    content_type: String,
+}
+
+impl<S> Channel<S>
 where
-    A: Future<Item = Response>,
-    A::Error: Into<Error>,
-    B: Future<Item = Response>,
-    B::Error: Into<Error>,
+    S: Stream<Item = Bytes, Error = ()> + 'static,
 {
-    A(A),
-    B(B),
+    /// Create responder for a receiving end of a channel
+    pub fn new(rx: S) -> Self {
+        Channel {
+            rx,
+            content_type: "application/octet-stream".to_owned(),
+        }
+    }
+
+    /// Set response content type
+    pub fn content_type<T: Into<String>>(mut self, content_type: T) -> Self {
+        self.content_type = content_type.into();
+        self
+    }
 }
 
-impl<A, B> Future for EitherResponder<A, B>
+impl<S> Responder for Channel<S>
 where
-    A: Future<Item = Response>,
-    A::Error: Into<Error>,
-    B: Future<Item = Response>,
-    B::Error: Into<Error>,
+    S: Stream<Item = Bytes, Error = ()> + 'static,
 {
-    type Item = Response;
     type Error = Error;
+    type Future = FutureResult<Response, Error>;
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        match self {
-            EitherResponder::A(ref mut fut) => Ok(fut.poll().map_err(|e| e.into())?),
-            EitherResponder::B(ref mut fut) => Ok(fut.poll().map_err(|e| e.into())?),
+    fn respond_to(self, _: &HttpRequest) -> Self::Future {
+        ok(Response::build(StatusCode::OK)
+            .content_type(self.content_type)
+            .streaming(
+                self.rx
+                    .map_err(|_| ErrorInternalServerError("Channel is broken")),
+            ))
+    }
+}
+
+/// Responder that streams body chunks from a stream.
+///
+/// Response is sent with chunked transfer encoding, chunks are written as
+/// soon as stream produces them. Stream error aborts the response. By
+/// default `application/octet-stream` content type is used.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// # extern crate bytes;
+/// # extern crate futures;
+/// use actix_web2::dev::BodyStream;
+/// use actix_web2::Error;
+/// use bytes::Bytes;
+/// use futures::Stream;
+///
+/// fn tail() -> BodyStream<impl Stream<Item = Bytes, Error = Error>> {
+///     BodyStream::new(log_lines()).content_type("text/plain")
+/// }
+/// # fn main() {}
+/// ```
+pub struct BodyStream<S> {
+    stream: S,
+    content_type: String,
+}
+
+impl<S, E> BodyStream<S>
+where
+    S: Stream<Item = Bytes, Error = E> + 'static,
+    E: Into<Error> + 'static,
+{
+    /// Create responder for a stream of body chunks
+    pub fn new(stream: S) -> Self {
+        BodyStream {
+            stream,
+            content_type: "application/octet-stream".to_owned(),
         }
     }
+
+    /// Set response content type
+    pub fn content_type<T: Into<String>>(mut self, content_type: T) -> Self {
+        self.content_type = content_type.into();
+        self
+    }
 }
 
-impl<I, E> Responder for Box<Future<Item = I, Error = E>>
+impl<S, E> Responder for BodyStream<S>
 where
-    I: Responder + 'static,
+    S: Stream<Item = Bytes, Error = E> + 'static,
     E: Into<Error> + 'static,
 {
     type Error = Error;
-    type Future = Box<Future<Item = Response, Error = Error>>;
+    type Future = FutureResult<Response, Error>;
 
-    #[inline]
-    fn respond_to(self, req: &HttpRequest) -> Self::Future {
-        let req = req.clone();
-        Box::new(
-            self.map_err(|e| e.into())
-                .and_then(move |r| ResponseFuture(r.respond_to(&req))),
-        )
+    fn respond_to(self, _: &HttpRequest) -> Self::Future {
+        ok(Response::build(StatusCode::OK)
+            .content_type(self.content_type)
+            .streaming(self.stream.map_err(|e| e.into())))
+    }
+}
+
+/// Single server-sent event, see `Sse`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    event: Option<String>,
+    id: Option<String>,
+    retry: Option<u64>,
+    data: String,
+}
+
+impl SseEvent {
+    /// Create event with specified data
+    pub fn new<T: Into<String>>(data: T) -> Self {
+        SseEvent {
+            data: data.into(),
+            ..SseEvent::default()
+        }
+    }
+
+    /// Set event type, line breaks are removed
+    pub fn event<T: Into<String>>(mut self, event: T) -> Self {
+        self.event = Some(single_line(event.into()));
+        self
+    }
+
+    /// Set event id, line breaks are removed
+    pub fn id<T: Into<String>>(mut self, id: T) -> Self {
+        self.id = Some(single_line(id.into()));
+        self
+    }
+
+    /// Set client's reconnection time
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry.as_secs() * 1000 + u64::from(retry.subsec_millis()));
+        self
+    }
+
+    /// Serialize event to `text/event-stream` format
+    fn encode(&self) -> Bytes {
+        let mut buf = String::with_capacity(self.data.len() + 16);
+        if let Some(ref event) = self.event {
+            buf.push_str("event: ");
+            buf.push_str(event);
+            buf.push('\n');
+        }
+        if let Some(ref id) = self.id {
+            buf.push_str("id: ");
+            buf.push_str(id);
+            buf.push('\n');
+        }
+        if let Some(retry) = self.retry {
+            buf.push_str(&format!("retry: {}\n", retry));
+        }
+        // `\r\n`, `\r` and `\n` all terminate a line
+        let data = self.data.replace("\r\n", "\n");
+        for line in data.split(|c| c == '\n' || c == '\r') {
+            buf.push_str("data: ");
+            buf.push_str(line);
+            buf.push('\n');
+        }
+        buf.push('\n');
+        Bytes::from(buf)
+    }
+}
+
+fn single_line(s: String) -> String {
+    if s.contains(|c| c == '\n' || c == '\r') {
+        s.replace(|c| c == '\n' || c == '\r', "")
+    } else {
+        s
+    }
+}
+
+/// Responder for server-sent events.
+///
+/// Events are sent to the client as soon as stream produces them, with
+/// `text/event-stream` content type and disabled caching. Multi-line data
+/// is sent as multiple `data:` lines.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// # extern crate futures;
+/// use actix_web2::dev::{Sse, SseEvent};
+/// use actix_web2::Error;
+/// use futures::Stream;
+///
+/// fn updates() -> Sse<impl Stream<Item = SseEvent, Error = Error>> {
+///     Sse::new(metrics().map(|m| SseEvent::new(m.to_string()).event("metrics")))
+/// }
+/// # fn main() {}
+/// ```
+pub struct Sse<S> {
+    stream: S,
+}
+
+impl<S, E> Sse<S>
+where
+    S: Stream<Item = SseEvent, Error = E> + 'static,
+    E: Into<Error> + 'static,
+{
+    /// Create responder for a stream of events
+    pub fn new(stream: S) -> Self {
+        Sse { stream }
+    }
+}
+
+impl<S, E> Responder for Sse<S>
+where
+    S: Stream<Item = SseEvent, Error = E> + 'static,
+    E: Into<Error> + 'static,
+{
+    type Error = Error;
+    type Future = FutureResult<Response, Error>;
+
+    fn respond_to(self, _: &HttpRequest) -> Self::Future {
+        ok(Response::build(StatusCode::OK)
+            .content_type("text/event-stream")
+            .header(CACHE_CONTROL, "no-cache")
+            .streaming(
+                self.stream
+                    .map(|event| event.encode())
+                    .map_err(|e| e.into()),
+            ))
+    }
+}
+
+/// Responder that spools large bodies to a temporary file.
+///
+/// Body chunks are buffered in memory until buffered size exceeds the
+/// threshold, then buffer and the rest of the body are written to a
+/// temporary file and response is streamed from the disk with chunked
+/// encoding. File operations are done on a `CpuPool`. Bodies below the
+/// threshold are sent from memory. Temporary file is removed when response
+/// is complete or connection is dropped. By default threshold is 1Mb,
+/// system temporary directory and `application/octet-stream` content type
+/// are used.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::Spooled;
+///
+/// fn report() -> Spooled<impl Stream<Item = Bytes, Error = Error>> {
+///     Spooled::new(generate_report()).content_type("text/csv")
+/// }
+/// # fn main() {}
+/// ```
+pub struct Spooled<S> {
+    stream: S,
+    threshold: usize,
+    dir: PathBuf,
+    content_type: String,
+}
+
+impl<S> Spooled<S>
+where
+    S: Stream<Item = Bytes> + 'static,
+    S::Error: Into<Error>,
+{
+    /// Create responder for a stream of body chunks
+    pub fn new(stream: S) -> Self {
+        Spooled {
+            stream,
+            threshold: 1_048_576,
+            dir: env::temp_dir(),
+            content_type: "application/octet-stream".to_owned(),
+        }
+    }
+
+    /// Set size of the body that is kept in memory
+    pub fn threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Set directory for temporary files
+    pub fn dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    /// Set response content type
+    pub fn content_type<T: Into<String>>(mut self, content_type: T) -> Self {
+        self.content_type = content_type.into();
+        self
+    }
+}
+
+impl<S> Responder for Spooled<S>
+where
+    S: Stream<Item = Bytes> + 'static,
+    S::Error: Into<Error>,
+{
+    type Error = Error;
+    type Future = Spool<S>;
+
+    fn respond_to(self, _: &HttpRequest) -> Self::Future {
+        Spool {
+            stream: self.stream,
+            threshold: self.threshold,
+            dir: self.dir,
+            content_type: self.content_type,
+            cpu_pool: DEFAULT_CPUPOOL.lock().clone(),
+            buf: BytesMut::new(),
+            file: None,
+            fut: None,
+            size: 0,
+            done: false,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct Spool<S> {
+    stream: S,
+    threshold: usize,
+    dir: PathBuf,
+    content_type: String,
+    cpu_pool: CpuPool,
+    buf: BytesMut,
+    file: Option<(File, SpoolPath)>,
+    fut: Option<CpuFuture<(File, SpoolPath), io::Error>>,
+    size: u64,
+    done: bool,
+}
+
+impl<S> Spool<S> {
+    /// Write buffered chunks to the temporary file on the cpu pool,
+    /// file is created with the first write
+    fn spool(&mut self) {
+        let buf = self.buf.take().freeze();
+        self.size += buf.len() as u64;
+        let file = match self.file.take() {
+            Some(file) => Ok(file),
+            None => Err(SpoolPath::new(&self.dir)),
+        };
+
+        self.fut = Some(self.cpu_pool.spawn_fn(move || {
+            let (mut file, path) = match file {
+                Ok(file) => file,
+                Err(path) => {
+                    let file = OpenOptions::new()
+                        .read(true)
+                        .write(true)
+                        .create_new(true)
+                        .open(&path.0)?;
+                    (file, path)
+                }
+            };
+            file.write_all(&buf)?;
+            Ok((file, path))
+        }));
+    }
+
+    fn response(&mut self) -> Response {
+        let mut res = Response::build(StatusCode::OK);
+        res.content_type(self.content_type.as_str());
+        match self.file.take() {
+            Some((file, path)) => res.streaming(SpoolBody {
+                reader: ChunkedReadFile::new(file, self.size),
+                _path: path,
+            }),
+            None => res.body(self.buf.take().freeze()),
+        }
+    }
+}
+
+impl<S> Future for Spool<S>
+where
+    S: Stream<Item = Bytes>,
+    S::Error: Into<Error>,
+{
+    type Item = Response;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Response, Error> {
+        loop {
+            // body stream is not polled until pending write completes
+            if self.fut.is_some() {
+                match self.fut.as_mut().unwrap().poll()? {
+                    Async::Ready(file) => {
+                        self.fut.take();
+                        self.file = Some(file);
+                    }
+                    Async::NotReady => return Ok(Async::NotReady),
+                }
+            }
+            if self.done {
+                return Ok(Async::Ready(self.response()));
+            }
+
+            match self.stream.poll().map_err(|e| e.into())? {
+                Async::Ready(Some(chunk)) => {
+                    self.buf.extend_from_slice(&chunk);
+                    if self.file.is_some() || self.buf.len() > self.threshold {
+                        self.spool();
+                    }
+                }
+                Async::Ready(None) => {
+                    self.done = true;
+                    if self.file.is_some() && !self.buf.is_empty() {
+                        self.spool();
+                    }
+                }
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+/// Path of the temporary file, file is removed on drop
+struct SpoolPath(PathBuf);
+
+impl SpoolPath {
+    fn new(dir: &Path) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let idx = COUNTER.fetch_add(1, Ordering::Relaxed);
+        SpoolPath(dir.join(format!("actix-spool-{}-{}", process::id(), idx)))
+    }
+}
+
+impl Drop for SpoolPath {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Body stream that reads spooled file
+struct SpoolBody {
+    reader: ChunkedReadFile,
+    _path: SpoolPath,
+}
+
+impl Stream for SpoolBody {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Bytes>, Error> {
+        self.reader.poll()
+    }
+}
+
+/// Responder for file downloads.
+///
+/// Sets `Content-Disposition: attachment` header, so browsers save response
+/// body as a file instead of displaying it. Non-ASCII file names are sent
+/// with RFC 5987 `filename*` parameter, plain `filename` parameter contains
+/// ASCII-only fallback. By default `application/octet-stream` content type
+/// is used.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::Attachment;
+///
+/// fn report() -> Attachment {
+///     Attachment::new("id,name\n1,actix\n")
+///         .filename("report.csv")
+///         .content_type("text/csv")
+/// }
+/// # fn main() {}
+/// ```
+pub struct Attachment {
+    body: Body,
+    filename: Option<String>,
+    content_type: String,
+}
+
+impl Attachment {
+    /// Create attachment responder with specified body
+    pub fn new<B: Into<Body>>(body: B) -> Self {
+        Attachment {
+            body: body.into(),
+            filename: None,
+            content_type: "application/octet-stream".to_owned(),
+        }
+    }
+
+    /// Set file name of the attachment
+    pub fn filename<T: Into<String>>(mut self, filename: T) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+
+    /// Set response content type
+    pub fn content_type<T: Into<String>>(mut self, content_type: T) -> Self {
+        self.content_type = content_type.into();
+        self
+    }
+
+    fn content_disposition(&self) -> ContentDisposition {
+        let mut parameters = Vec::new();
+        if let Some(ref filename) = self.filename {
+            if filename.is_ascii() {
+                parameters.push(DispositionParam::Filename(filename.clone()));
+            } else {
+                let fallback = filename
+                    .chars()
+                    .map(|c| if c.is_ascii() { c } else { '_' })
+                    .collect();
+                parameters.push(DispositionParam::Filename(fallback));
+                parameters.push(DispositionParam::FilenameExt(ExtendedValue {
+                    charset: Charset::Ext("UTF-8".to_owned()),
+                    language_tag: None,
+                    value: filename.clone().into_bytes(),
+                }));
+            }
+        }
+        ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters,
+        }
+    }
+}
+
+impl Responder for Attachment {
+    type Error = Error;
+    type Future = FutureResult<Response, Error>;
+
+    fn respond_to(self, _: &HttpRequest) -> Self::Future {
+        let disposition = self.content_disposition();
+
+        ok(Response::build(StatusCode::OK)
+            .content_type(self.content_type)
+            .header(CONTENT_DISPOSITION, disposition.to_string())
+            .header(ACCEPT_RANGES, "none")
+            .body(self.body))
+    }
+}
+
+/// Responder for in-memory assets with caching headers.
+///
+/// Blob is sent with `ETag` and `Cache-Control` headers, conditional
+/// requests with matching `If-None-Match` header get
+/// *304 Not Modified* response without body. ETag has to be computed
+/// once, when blob gets generated. By default clients are allowed to cache
+/// blob for one day.
+///
+/// Blob advertises `Accept-Ranges: bytes`, requests with `Range` header get
+/// *206 Partial Content* response with the first requested range. Other
+/// in-memory responders, i.e. `Attachment` and `Encoded`, advertise
+/// `Accept-Ranges: none`.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::Blob;
+///
+/// static LOGO: &[u8] = b"<svg></svg>";
+///
+/// fn logo() -> Blob {
+///     Blob::new(LOGO, "logo-v1").content_type("image/svg+xml")
+/// }
+/// # fn main() {}
+/// ```
+pub struct Blob {
+    body: Bytes,
+    etag: EntityTag,
+    content_type: String,
+    max_age: u32,
+}
+
+impl Blob {
+    /// Create blob responder with precomputed strong ETag
+    pub fn new<B: Into<Bytes>, T: Into<String>>(body: B, etag: T) -> Self {
+        Blob {
+            body: body.into(),
+            etag: EntityTag::strong(etag.into()),
+            content_type: "application/octet-stream".to_owned(),
+            max_age: 86_400,
+        }
+    }
+
+    /// Set response content type
+    pub fn content_type<T: Into<String>>(mut self, content_type: T) -> Self {
+        self.content_type = content_type.into();
+        self
+    }
+
+    /// Set max age of the blob in seconds
+    pub fn max_age(mut self, max_age: u32) -> Self {
+        self.max_age = max_age;
+        self
+    }
+}
+
+impl Responder for Blob {
+    type Error = Error;
+    type Future = FutureResult<Response, Error>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        let cache_control = format!("public, max-age={}", self.max_age);
+
+        if !none_match(Some(&self.etag), req) {
+            return ok(Response::build(StatusCode::NOT_MODIFIED)
+                .set(ETag(self.etag))
+                .header(CACHE_CONTROL, cache_control)
+                .finish());
+        }
+
+        let mut resp = Response::build(StatusCode::OK);
+        resp.content_type(self.content_type)
+            .set(ETag(self.etag))
+            .header(CACHE_CONTROL, cache_control)
+            .header(ACCEPT_RANGES, "bytes");
+
+        // only first range is served
+        let size = self.body.len() as u64;
+        if let Some(range) = req.headers().get(RANGE) {
+            let ranges = range
+                .to_str()
+                .map_err(|_| ())
+                .and_then(|range| HttpRange::parse(range, size));
+            match ranges {
+                Ok(ranges) => {
+                    if let Some(range) = ranges.first() {
+                        let (start, end) = (range.start, range.start + range.length);
+                        return ok(resp
+                            .status(StatusCode::PARTIAL_CONTENT)
+                            .header(
+                                CONTENT_RANGE,
+                                format!("bytes {}-{}/{}", start, end - 1, size),
+                            )
+                            .body(self.body.slice(start as usize, end as usize)));
+                    }
+                }
+                Err(_) => {
+                    return ok(resp
+                        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                        .header(CONTENT_RANGE, format!("bytes */{}", size))
+                        .finish());
+                }
+            }
+        }
+
+        ok(resp.body(self.body))
+    }
+}
+
+/// Responder for bodies that are already encoded.
+///
+/// Body is sent as is with `Content-Encoding` header, i.e. stored gzip blob.
+/// `Compress` middleware does not touch responses that contain
+/// `Content-Encoding` header, so body does not get compressed twice.
+/// Handler is responsible for checking that client accepts the encoding.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::Encoded;
+/// use actix_web2::http::header::ContentEncoding;
+///
+/// static REPORT: &[u8] = include_bytes!("report.json.gz");
+///
+/// fn report() -> Encoded {
+///     Encoded::new(ContentEncoding::Gzip, REPORT).content_type("application/json")
+/// }
+/// # fn main() {}
+/// ```
+pub struct Encoded {
+    body: Bytes,
+    encoding: ContentEncoding,
+    content_type: String,
+}
+
+impl Encoded {
+    /// Create responder for body encoded with specified encoding
+    pub fn new<B: Into<Bytes>>(encoding: ContentEncoding, body: B) -> Self {
+        Encoded {
+            encoding,
+            body: body.into(),
+            content_type: "application/octet-stream".to_owned(),
+        }
+    }
+
+    /// Set content type of the decoded body
+    pub fn content_type<T: Into<String>>(mut self, content_type: T) -> Self {
+        self.content_type = content_type.into();
+        self
+    }
+}
+
+impl Responder for Encoded {
+    type Error = Error;
+    type Future = FutureResult<Response, Error>;
+
+    fn respond_to(self, _: &HttpRequest) -> Self::Future {
+        ok(Response::build(StatusCode::OK)
+            .content_type(self.content_type)
+            .header(CONTENT_ENCODING, self.encoding.as_str())
+            .header(ACCEPT_RANGES, "none")
+            .body(self.body))
+    }
+}
+
+/// Json responder with configurable status code and formatting.
+///
+/// `Json<T>` responder always sends *200 OK* response with compact json,
+/// `JsonResponse` allows to change status code and to enable pretty
+/// printing, i.e. for *201 Created* responses or for debug endpoints.
+/// Content type is always `application/json`.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::JsonResponse;
+/// use actix_web2::http::StatusCode;
+///
+/// fn create() -> JsonResponse<User> {
+///     JsonResponse::new(User::new()).status(StatusCode::CREATED)
+/// }
+///
+/// fn debug() -> JsonResponse<State> {
+///     JsonResponse::new(State::current()).pretty(true)
+/// }
+/// # fn main() {}
+/// ```
+pub struct JsonResponse<T> {
+    value: T,
+    status: StatusCode,
+    pretty: bool,
+}
+
+impl<T: Serialize> JsonResponse<T> {
+    /// Create json responder, by default response status is *200 OK*
+    pub fn new(value: T) -> Self {
+        JsonResponse {
+            value,
+            status: StatusCode::OK,
+            pretty: false,
+        }
+    }
+
+    /// Set response status code
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Serialize value with `serde_json::to_string_pretty()`
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+}
+
+impl<T: Serialize> From<Json<T>> for JsonResponse<T> {
+    fn from(json: Json<T>) -> Self {
+        JsonResponse::new(json.into_inner())
+    }
+}
+
+impl<T: Serialize> Responder for JsonResponse<T> {
+    type Error = Error;
+    type Future = FutureResult<Response, Error>;
+
+    fn respond_to(self, _: &HttpRequest) -> Self::Future {
+        let body = if self.pretty {
+            serde_json::to_string_pretty(&self.value)
+        } else {
+            serde_json::to_string(&self.value)
+        };
+
+        match body {
+            Ok(body) => ok(Response::build(self.status)
+                .content_type("application/json")
+                .body(body)),
+            Err(e) => err(e.into()),
+        }
+    }
+}
+
+/// Responder that generates response body lazily.
+///
+/// Body generating closure is called only when response body gets written,
+/// so expensive bodies do not get generated for *HEAD* requests. Deferred
+/// body is sent with chunked transfer encoding.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::Deferred;
+/// use actix_web2::App;
+///
+/// fn report() -> Deferred<impl FnOnce() -> String> {
+///     Deferred::new(|| render_report()).content_type("text/csv")
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource("/report.csv", |r| r.get(report).head(report));
+/// }
+/// # fn render_report() -> String { String::new() }
+/// ```
+pub struct Deferred<F> {
+    f: F,
+    content_type: String,
+}
+
+impl<F, B> Deferred<F>
+where
+    F: FnOnce() -> B + 'static,
+    B: Into<Bytes>,
+{
+    /// Create responder with body generating closure
+    pub fn new(f: F) -> Self {
+        Deferred {
+            f,
+            content_type: "application/octet-stream".to_owned(),
+        }
+    }
+
+    /// Set response content type
+    pub fn content_type<T: Into<String>>(mut self, content_type: T) -> Self {
+        self.content_type = content_type.into();
+        self
+    }
+}
+
+impl<F, B> Responder for Deferred<F>
+where
+    F: FnOnce() -> B + 'static,
+    B: Into<Bytes>,
+{
+    type Error = Error;
+    type Future = FutureResult<Response, Error>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        let mut res = Response::build(StatusCode::OK);
+        res.content_type(self.content_type);
+
+        if req.method() == Method::HEAD {
+            ok(res.finish())
+        } else {
+            ok(res.body(Body::Message(Box::new(DeferredBody(Some(self.f))))))
+        }
+    }
+}
+
+/// Body that calls generating closure on the first poll
+struct DeferredBody<F>(Option<F>);
+
+impl<F, B> MessageBody for DeferredBody<F>
+where
+    F: FnOnce() -> B,
+    B: Into<Bytes>,
+{
+    fn length(&self) -> BodyLength {
+        BodyLength::Stream
+    }
+
+    fn poll_next(&mut self) -> Poll<Option<Bytes>, Error> {
+        Ok(Async::Ready(self.0.take().map(|f| f().into())))
+    }
+}
+
+/// Responder that wraps response into uniform JSON envelope.
+///
+/// Data gets serialized under `data` key, i.e. `{"data": {...}}`, errors
+/// get serialized under `error` key with status code and message,
+/// i.e. `{"error": {"status": 404, "message": "User not found"}}`. Keys and
+/// error shape could be configured app-wide with `EnvelopeConfig`.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::{Envelope, EnvelopeError};
+/// use actix_web2::{http::StatusCode, App, Path};
+///
+/// fn user(id: Path<u32>) -> Envelope<User> {
+///     match find_user(*id) {
+///         Some(user) => Envelope::Data(user),
+///         None => Envelope::Error(EnvelopeError::new(
+///             StatusCode::NOT_FOUND,
+///             "User not found",
+///         )),
+///     }
+/// }
+///
+/// fn main() {
+///     let app = App::new().resource("/users/{id}", |r| r.get(user));
+/// }
+/// ```
+pub enum Envelope<T> {
+    /// Successful response data
+    Data(T),
+    /// Error response
+    Error(EnvelopeError),
+}
+
+impl<T, E> From<Result<T, E>> for Envelope<T>
+where
+    E: Into<EnvelopeError>,
+{
+    fn from(res: Result<T, E>) -> Self {
+        match res {
+            Ok(data) => Envelope::Data(data),
+            Err(e) => Envelope::Error(e.into()),
+        }
+    }
+}
+
+impl<T: Serialize> Responder for Envelope<T> {
+    type Error = Error;
+    type Future = FutureResult<Response, Error>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        let cfg = EnvelopeConfig::from_request(req);
+
+        match self {
+            Envelope::Data(data) => match serde_json::to_value(&data) {
+                Ok(data) => ok(cfg.response(StatusCode::OK, &cfg.data_key, data)),
+                Err(e) => err(e.into()),
+            },
+            Envelope::Error(e) => ok(cfg.error_response(&e)),
+        }
+    }
+}
+
+/// Error in JSON envelope.
+///
+/// If error gets returned as `Envelope::Error`, response is rendered with
+/// application's `EnvelopeConfig`. As `ResponseError`, i.e. returned with
+/// `?` operator, request is not available, so error is rendered with
+/// configuration it is created with, see `EnvelopeConfig::error()`, or
+/// with default configuration.
+#[derive(Debug, Clone)]
+pub struct EnvelopeError {
+    status: StatusCode,
+    message: String,
+    config: Option<EnvelopeConfig>,
+}
+
+impl EnvelopeError {
+    /// Create error with response status code and message
+    pub fn new<T: Into<String>>(status: StatusCode, message: T) -> Self {
+        EnvelopeError {
+            status,
+            message: message.into(),
+            config: None,
+        }
+    }
+
+    /// Response status code
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Error message
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl ResponseError for EnvelopeError {
+    fn error_response(&self) -> Response {
+        match self.config {
+            Some(ref cfg) => cfg.error_response(self),
+            None => EnvelopeConfig::default().error_response(self),
+        }
+    }
+}
+
+/// Problem details error, see RFC 7807.
+///
+/// As `ResponseError` problem is rendered as `application/problem+json`
+/// response with problem's status code, so handlers could return
+/// `Result<Json<T>, Problem>` and both variants get serialized properly.
+/// By default problem type is `about:blank` and title is canonical reason
+/// of the status code.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::Problem;
+/// use actix_web2::{http::StatusCode, Json, Path};
+///
+/// fn user(id: Path<u32>) -> Result<Json<User>, Problem> {
+///     find_user(*id).map(Json).ok_or_else(|| {
+///         Problem::new(StatusCode::NOT_FOUND)
+///             .type_uri("https://example.com/probs/no-user")
+///             .detail(format!("User {} does not exist", *id))
+///     })
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Problem {
+    status: StatusCode,
+    type_uri: String,
+    title: String,
+    detail: Option<String>,
+    instance: Option<String>,
+}
+
+impl Problem {
+    /// Create problem with response status code
+    pub fn new(status: StatusCode) -> Self {
+        Problem {
+            status,
+            type_uri: "about:blank".to_owned(),
+            title: status.canonical_reason().unwrap_or("").to_owned(),
+            detail: None,
+            instance: None,
+        }
+    }
+
+    /// Set URI reference that identifies the problem type
+    pub fn type_uri<T: Into<String>>(mut self, type_uri: T) -> Self {
+        self.type_uri = type_uri.into();
+        self
+    }
+
+    /// Set short summary of the problem type
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Set explanation specific to this occurrence of the problem
+    pub fn detail<T: Into<String>>(mut self, detail: T) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Set URI reference that identifies this occurrence of the problem
+    pub fn instance<T: Into<String>>(mut self, instance: T) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Response status code
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.detail {
+            Some(ref detail) => write!(f, "{}: {}", self.title, detail),
+            None => f.write_str(&self.title),
+        }
+    }
+}
+
+impl ResponseError for Problem {
+    fn error_response(&self) -> Response {
+        let mut problem = Map::new();
+        problem.insert("type".to_owned(), Value::from(self.type_uri.clone()));
+        problem.insert("title".to_owned(), Value::from(self.title.clone()));
+        problem.insert("status".to_owned(), Value::from(self.status.as_u16()));
+        if let Some(ref detail) = self.detail {
+            problem.insert("detail".to_owned(), Value::from(detail.clone()));
+        }
+        if let Some(ref instance) = self.instance {
+            problem.insert("instance".to_owned(), Value::from(instance.clone()));
+        }
+
+        Response::build(self.status)
+            .content_type("application/problem+json")
+            .body(Value::Object(problem).to_string())
+    }
+}
+
+/// JSON envelope configuration.
+///
+/// Configuration could be registered as an application state with
+/// `App::state()` method, otherwise default configuration is used.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::dev::EnvelopeConfig;
+/// use actix_web2::App;
+///
+/// fn main() {
+///     let mut cfg = EnvelopeConfig::default();
+///     cfg.data_key("result").error_key("failure").error_status(false);
+///
+///     let app = App::new().state(cfg);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct EnvelopeConfig {
+    data_key: String,
+    error_key: String,
+    error_status: bool,
+}
+
+impl EnvelopeConfig {
+    /// Change key of the response data. By default key is `data`
+    pub fn data_key<T: Into<String>>(&mut self, key: T) -> &mut Self {
+        self.data_key = key.into();
+        self
+    }
+
+    /// Change key of the error object. By default key is `error`
+    pub fn error_key<T: Into<String>>(&mut self, key: T) -> &mut Self {
+        self.error_key = key.into();
+        self
+    }
+
+    /// Include status code into error object. By default status code
+    /// is included.
+    pub fn error_status(&mut self, enabled: bool) -> &mut Self {
+        self.error_status = enabled;
+        self
+    }
+
+    /// Create error that is rendered with this configuration.
+    ///
+    /// Use it for errors returned with `?` operator, configuration
+    /// registered with `App::state()` is available with
+    /// `State<EnvelopeConfig>` extractor.
+    ///
+    /// ```rust,ignore
+    /// # extern crate actix_web2;
+    /// use actix_web2::dev::{EnvelopeConfig, EnvelopeError};
+    /// use actix_web2::{http::StatusCode, State};
+    ///
+    /// fn index(cfg: State<EnvelopeConfig>) -> Result<&'static str, EnvelopeError> {
+    ///     Err(cfg.error(StatusCode::BAD_REQUEST, "Bad input"))
+    /// }
+    /// ```
+    pub fn error<T: Into<String>>(
+        &self,
+        status: StatusCode,
+        message: T,
+    ) -> EnvelopeError {
+        EnvelopeError {
+            status,
+            message: message.into(),
+            config: Some(self.clone()),
+        }
+    }
+
+    /// Configuration registered with `App::state()` or default one
+    fn from_request(req: &HttpRequest) -> Self {
+        if let Some(cfg) = req.app_extensions().get::<State<EnvelopeConfig>>() {
+            cfg.get_ref().clone()
+        } else {
+            EnvelopeConfig::default()
+        }
+    }
+
+    fn error_response(&self, e: &EnvelopeError) -> Response {
+        let mut error = Map::new();
+        if self.error_status {
+            error.insert("status".to_owned(), Value::from(e.status.as_u16()));
+        }
+        error.insert("message".to_owned(), Value::from(e.message.clone()));
+        self.response(e.status, &self.error_key, Value::Object(error))
+    }
+
+    fn response(&self, status: StatusCode, key: &str, value: Value) -> Response {
+        let mut envelope = Map::new();
+        envelope.insert(key.to_owned(), value);
+
+        Response::build(status)
+            .content_type("application/json")
+            .body(Value::Object(envelope).to_string())
+    }
+}
+
+impl Default for EnvelopeConfig {
+    fn default() -> Self {
+        EnvelopeConfig {
+            data_key: "data".to_owned(),
+            error_key: "error".to_owned(),
+            error_status: true,
+        }
+    }
+}
+
+/// Shared progress of the handler, see `Deadline`.
+///
+/// Clones share the same state, so handler's work could record partial
+/// results while `Deadline` keeps a handle for the finalizer.
+pub struct Progress<T>(Rc<RefCell<T>>);
+
+impl<T> Progress<T> {
+    /// Create progress with initial state
+    pub fn new(state: T) -> Self {
+        Progress(Rc::new(RefCell::new(state)))
+    }
+
+    /// Update progress state
+    pub fn update<F: FnOnce(&mut T)>(&self, f: F) {
+        f(&mut *self.0.borrow_mut())
+    }
+}
+
+impl<T> Clone for Progress<T> {
+    fn clone(&self) -> Self {
+        Progress(self.0.clone())
+    }
+}
+
+/// Responder that limits handler's work with a deadline.
+///
+/// If work future completes before the deadline, its result is used as a
+/// response. Otherwise work future is dropped and the finalizer generates
+/// response from partial state recorded with `Progress`, so best-effort
+/// endpoints could respond with data collected so far instead of an error.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use std::time::Duration;
+///
+/// use actix_web2::dev::{Deadline, Progress};
+/// use actix_web2::{HttpResponse, Responder};
+/// use futures::{Future, Stream};
+///
+/// fn aggregate() -> impl Responder {
+///     let progress = Progress::new(Vec::new());
+///     let collected = progress.clone();
+///
+///     let work = fetch_sources()
+///         .for_each(move |item| Ok(collected.update(|items| items.push(item))))
+///         .map(|_| HttpResponse::Ok().body("complete"));
+///
+///     Deadline::new(work, Duration::from_secs(1), progress, |items: &Vec<String>| {
+///         HttpResponse::Ok().body(items.join(","))
+///     })
+/// }
+/// ```
+pub struct Deadline<F, T, R> {
+    fut: F,
+    delay: Delay,
+    progress: Progress<T>,
+    finalizer: Option<R>,
+}
+
+impl<F, T, R, O> Deadline<F, T, R>
+where
+    F: Future,
+    F::Item: Into<Response>,
+    F::Error: Into<Error>,
+    R: FnOnce(&T) -> O,
+    O: Into<Response>,
+{
+    /// Create responder for work future with specified timeout
+    pub fn new(fut: F, timeout: Duration, progress: Progress<T>, finalizer: R) -> Self {
+        Deadline {
+            fut,
+            progress,
+            delay: Delay::new(Instant::now() + timeout),
+            finalizer: Some(finalizer),
+        }
+    }
+}
+
+impl<F, T, R, O> Future for Deadline<F, T, R>
+where
+    F: Future,
+    F::Item: Into<Response>,
+    F::Error: Into<Error>,
+    R: FnOnce(&T) -> O,
+    O: Into<Response>,
+{
+    type Item = Response;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Response, Error> {
+        match self.fut.poll() {
+            Ok(Async::Ready(res)) => return Ok(Async::Ready(res.into())),
+            Ok(Async::NotReady) => (),
+            Err(e) => return Err(e.into()),
+        }
+
+        match self.delay.poll() {
+            Ok(Async::Ready(_)) => {
+                let finalizer = self
+                    .finalizer
+                    .take()
+                    .expect("Deadline polled after completion");
+                let res = finalizer(&*self.progress.0.borrow());
+                Ok(Async::Ready(res.into()))
+            }
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(e) => Err(ErrorInternalServerError(e)),
+        }
+    }
+}
+
+impl<F, T, R, O> Responder for Deadline<F, T, R>
+where
+    F: Future,
+    F::Item: Into<Response>,
+    F::Error: Into<Error>,
+    R: FnOnce(&T) -> O,
+    O: Into<Response>,
+{
+    type Error = Error;
+    type Future = Self;
+
+    fn respond_to(self, _: &HttpRequest) -> Self::Future {
+        self
+    }
+}
+
+/// Responder that selects response format according to `Accept` header.
+///
+/// Supported media types get registered with serializers, in order of
+/// preference. Media type with the highest quality value in `Accept` header
+/// is used, ties are resolved in favor of the default media type and then
+/// by registration order. The default media type, or the first registered
+/// media type if default is not set, is used if request has no `Accept`
+/// header. If none of the media types is acceptable, *406 Not Acceptable*
+/// response is returned.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// # extern crate mime;
+/// use actix_web2::dev::Negotiate;
+/// use actix_web2::Responder;
+///
+/// fn index() -> impl Responder {
+///     Negotiate::new(vec!["a", "b"])
+///         .format(mime::TEXT_PLAIN, |v| Ok::<_, Error>(v.join(",")))
+///         .format(mime::APPLICATION_JSON, |v| serde_json::to_string(v))
+///         .default(mime::APPLICATION_JSON)
+/// }
+/// ```
+pub struct Negotiate<T> {
+    value: T,
+    formats: Vec<(Mime, Box<Fn(&T) -> Result<Bytes, Error>>)>,
+    default: Option<Mime>,
+}
+
+impl<T> Negotiate<T> {
+    /// Create responder for the value
+    pub fn new(value: T) -> Self {
+        Negotiate {
+            value,
+            formats: Vec::new(),
+            default: None,
+        }
+    }
+
+    /// Register supported media type with serializer
+    pub fn format<F, B, E>(mut self, mt: Mime, f: F) -> Self
+    where
+        F: Fn(&T) -> Result<B, E> + 'static,
+        B: Into<Bytes>,
+        E: Into<Error>,
+    {
+        self.formats.push((
+            mt,
+            Box::new(move |value| f(value).map(|b| b.into()).map_err(|e| e.into())),
+        ));
+        self
+    }
+
+    /// Set media type for requests without `Accept` header and for
+    /// ties, media type has to be registered with `format()`
+    pub fn default(mut self, mt: Mime) -> Self {
+        self.default = Some(mt);
+        self
+    }
+
+    /// Select format for the request
+    fn select(&self, req: &HttpRequest) -> Option<usize> {
+        let default = self
+            .default
+            .as_ref()
+            .and_then(|mt| self.formats.iter().position(|(m, _)| m == mt));
+
+        let accept = accept_ranges(req);
+        if accept.is_empty() {
+            return if self.formats.is_empty() {
+                None
+            } else {
+                Some(default.unwrap_or(0))
+            };
+        }
+
+        let mut best: Option<(usize, u16)> = None;
+        for (idx, (mt, _)) in self.formats.iter().enumerate() {
+            let q = match accept_quality(&accept, mt) {
+                Some(q) if q > 0 => q,
+                _ => continue,
+            };
+            best = match best {
+                Some((_, best_q)) if q > best_q => Some((idx, q)),
+                Some((_, best_q)) if q == best_q && Some(idx) == default => {
+                    Some((idx, q))
+                }
+                None => Some((idx, q)),
+                best => best,
+            };
+        }
+        best.map(|(idx, _)| idx)
+    }
+}
+
+/// Media ranges of the request's `Accept` headers, invalid ranges are skipped
+pub(crate) fn accept_ranges(req: &HttpRequest) -> Vec<Mime> {
+    req.headers()
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|hdr| hdr.to_str().ok())
+        .flat_map(|val| val.split(','))
+        .filter_map(|item| item.trim().parse::<Mime>().ok())
+        .collect()
+}
+
+/// Quality of the media type in thousandths, the most specific matching
+/// range is used
+pub(crate) fn accept_quality(accept: &[Mime], mt: &Mime) -> Option<u16> {
+    let mut quality = None;
+    for range in accept {
+        let specificity = if range.type_() == mime::STAR {
+            0
+        } else if range.type_() != mt.type_() {
+            continue;
+        } else if range.subtype() == mime::STAR {
+            1
+        } else if range.subtype() != mt.subtype() {
+            continue;
+        } else {
+            2
+        };
+        let q = range
+            .get_param("q")
+            .and_then(|q| q.as_str().parse::<f32>().ok())
+            .map(|q| (q.max(0.0).min(1.0) * 1000.0) as u16)
+            .unwrap_or(1000);
+
+        quality = match quality {
+            Some((spec, _)) if spec >= specificity => quality,
+            _ => Some((specificity, q)),
+        };
+    }
+    quality.map(|(_, q)| q)
+}
+
+impl<T> Responder for Negotiate<T> {
+    type Error = Error;
+    type Future = FutureResult<Response, Error>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        let idx = match self.select(req) {
+            Some(idx) => idx,
+            None => {
+                return ok(Response::build(StatusCode::NOT_ACCEPTABLE)
+                    .header(VARY, "Accept")
+                    .finish());
+            }
+        };
+        let (ref mt, ref serializer) = self.formats[idx];
+
+        match serializer(&self.value) {
+            Ok(body) => ok(Response::build(StatusCode::OK)
+                .content_type(mt.to_string())
+                .header(VARY, "Accept")
+                .body(body)),
+            Err(e) => err(e),
+        }
+    }
+}
+
+/// Combines two different responder types into a single type
+///
+/// ```rust,ignore
+/// # extern crate actix_web;
+/// # extern crate futures;
+/// # use futures::future::Future;
+/// use actix_web::{AsyncResponder, Either, Error, Request, Response};
+/// use futures::future::result;
+///
+/// type RegisterResult =
+///     Either<Response, Box<Future<Item = Response, Error = Error>>>;
+///
+/// fn index(req: Request) -> RegisterResult {
+///     if is_a_variant() {
+///         // <- choose variant A
+///         Either::A(Response::BadRequest().body("Bad data"))
+///     } else {
+///         Either::B(
+///             // <- variant B
+///             result(Ok(Response::Ok()
+///                 .content_type("text/html")
+///                 .body("Hello!")))
+///                 .responder(),
+///         )
+///     }
+/// }
+/// # fn is_a_variant() -> bool { true }
+/// # fn main() {}
+/// ```
+pub enum Either<A, B> {
+    /// First branch of the type
+    A(A),
+    /// Second branch of the type
+    B(B),
+}
+
+impl<A, B> From<Result<A, B>> for Either<A, B> {
+    fn from(res: Result<A, B>) -> Self {
+        match res {
+            Ok(a) => Either::A(a),
+            Err(b) => Either::B(b),
+        }
+    }
+}
+
+impl<A, B> Responder for Either<A, B>
+where
+    A: Responder,
+    B: Responder,
+{
+    type Error = Error;
+    type Future = EitherResponder<A::Future, B::Future>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        match self {
+            Either::A(a) => EitherResponder::A(a.respond_to(req)),
+            Either::B(b) => EitherResponder::B(b.respond_to(req)),
+        }
+    }
+}
+
+pub enum EitherResponder<A, B>
+where
+    A: Future<Item = Response>,
+    A::Error: Into<Error>,
+    B: Future<Item = Response>,
+    B::Error: Into<Error>,
+{
+    A(A),
+    B(B),
+}
+
+impl<A, B> Future for EitherResponder<A, B>
+where
+    A: Future<Item = Response>,
+    A::Error: Into<Error>,
+    B: Future<Item = Response>,
+    B::Error: Into<Error>,
+{
+    type Item = Response;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self {
+            EitherResponder::A(ref mut fut) => Ok(fut.poll().map_err(|e| e.into())?),
+            EitherResponder::B(ref mut fut) => Ok(fut.poll().map_err(|e| e.into())?),
+        }
+    }
+}
+
+/// Combines three different responder types into a single type
+///
+/// Works like `Either`, but without nesting `Either<A, Either<B, C>>`.
+/// `Either3` could be used as an extractor as well, variants are tried
+/// in order and the first successful one is returned.
+pub enum Either3<A, B, C> {
+    /// First branch of the type
+    A(A),
+    /// Second branch of the type
+    B(B),
+    /// Third branch of the type
+    C(C),
+}
+
+impl<A, B, C> Responder for Either3<A, B, C>
+where
+    A: Responder,
+    B: Responder,
+    C: Responder,
+{
+    type Error = Error;
+    type Future = Either3Responder<A::Future, B::Future, C::Future>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        match self {
+            Either3::A(a) => Either3Responder::A(a.respond_to(req)),
+            Either3::B(b) => Either3Responder::B(b.respond_to(req)),
+            Either3::C(c) => Either3Responder::C(c.respond_to(req)),
+        }
+    }
+}
+
+pub enum Either3Responder<A, B, C>
+where
+    A: Future<Item = Response>,
+    A::Error: Into<Error>,
+    B: Future<Item = Response>,
+    B::Error: Into<Error>,
+    C: Future<Item = Response>,
+    C::Error: Into<Error>,
+{
+    A(A),
+    B(B),
+    C(C),
+}
+
+impl<A, B, C> Future for Either3Responder<A, B, C>
+where
+    A: Future<Item = Response>,
+    A::Error: Into<Error>,
+    B: Future<Item = Response>,
+    B::Error: Into<Error>,
+    C: Future<Item = Response>,
+    C::Error: Into<Error>,
+{
+    type Item = Response;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self {
+            Either3Responder::A(ref mut fut) => Ok(fut.poll().map_err(|e| e.into())?),
+            Either3Responder::B(ref mut fut) => Ok(fut.poll().map_err(|e| e.into())?),
+            Either3Responder::C(ref mut fut) => Ok(fut.poll().map_err(|e| e.into())?),
+        }
+    }
+}
+
+impl<I, E> Responder for Box<Future<Item = I, Error = E>>
+where
+    I: Responder + 'static,
+    E: Into<Error> + 'static,
+{
+    type Error = Error;
+    type Future = Box<Future<Item = Response, Error = Error>>;
+
+    #[inline]
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        let req = req.clone();
+        Box::new(
+            self.map_err(|e| e.into())
+                .and_then(move |r| ResponseFuture(r.respond_to(&req))),
+        )
+    }
+}
+
+/// Boxed stream of byte chunks is sent as a chunked body with
+/// `application/octet-stream` content type.
+///
+/// Blanket implementation for any `Stream` would conflict with other
+/// responders, so stream has to be boxed. Use `Channel` or
+/// `Responder::customize()` for other content types.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// # extern crate bytes;
+/// # extern crate futures;
+/// use actix_web2::Error;
+/// use bytes::Bytes;
+/// use futures::{stream, Stream};
+///
+/// fn index() -> Box<Stream<Item = Bytes, Error = Error>> {
+///     Box::new(stream::iter_ok(vec![Bytes::from("chunk1"), Bytes::from("chunk2")]))
+/// }
+/// # fn main() {}
+/// ```
+impl<E> Responder for Box<Stream<Item = Bytes, Error = E>>
+where
+    E: Into<Error> + 'static,
+{
+    type Error = Error;
+    type Future = FutureResult<Response, Error>;
+
+    #[inline]
+    fn respond_to(self, _: &HttpRequest) -> Self::Future {
+        ok(Response::build(StatusCode::OK)
+            .content_type("application/octet-stream")
+            .streaming(self.map_err(|e| e.into())))
     }
 }
 
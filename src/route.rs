@@ -1,12 +1,19 @@
 use std::marker::PhantomData;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use actix_http::{http::Method, Error, Response};
-use actix_service::{NewService, Service};
+use actix_http::{http::Method, Error, Extensions, Response};
+use actix_service::{
+    ApplyNewService, IntoNewTransform, NewService, NewTransform, Service, Transform,
+};
+use futures::future::ok;
 use futures::{Async, Future, IntoFuture, Poll};
+use log::warn;
 
+use crate::app::ErrorRenderer;
 use crate::filter::{self, Filter};
 use crate::handler::{AsyncFactory, AsyncHandle, Extract, Factory, FromRequest, Handle};
+use crate::middleware::TaggedChecks;
 use crate::responder::Responder;
 use crate::service::{ServiceRequest, ServiceResponse};
 
@@ -30,6 +37,12 @@ type BoxedRouteNewService<Req, Res> = Box<
     >,
 >;
 
+type RouteWrap<P> = Box<
+    FnMut(
+        BoxedRouteNewService<ServiceRequest<P>, ServiceResponse>,
+    ) -> BoxedRouteNewService<ServiceRequest<P>, ServiceResponse>,
+>;
+
 /// Resource route definition
 ///
 /// Route uses builder-like pattern for configuration.
@@ -37,9 +50,18 @@ type BoxedRouteNewService<Req, Res> = Box<
 pub struct Route<P> {
     service: BoxedRouteNewService<ServiceRequest<P>, ServiceResponse>,
     filters: Rc<Vec<Box<Filter>>>,
+    methods: Rc<Vec<Method>>,
+    tags: Rc<Vec<String>>,
+    config: Option<Rc<Extensions>>,
+    budget: Option<Duration>,
 }
 
 impl<P: 'static> Route<P> {
+    /// Methods registered with `RouteBuilder::method()` or method filters
+    pub(crate) fn methods(&self) -> &[Method] {
+        &self.methods
+    }
+
     pub fn build() -> RouteBuilder<P> {
         RouteBuilder::new()
     }
@@ -73,6 +95,10 @@ impl<P> NewService for Route<P> {
         CreateRouteService {
             fut: self.service.new_service(),
             filters: self.filters.clone(),
+            methods: self.methods.clone(),
+            tags: self.tags.clone(),
+            config: self.config.clone(),
+            budget: self.budget,
         }
     }
 }
@@ -84,6 +110,10 @@ type RouteFuture<P> = Box<
 pub struct CreateRouteService<P> {
     fut: RouteFuture<P>,
     filters: Rc<Vec<Box<Filter>>>,
+    methods: Rc<Vec<Method>>,
+    tags: Rc<Vec<String>>,
+    config: Option<Rc<Extensions>>,
+    budget: Option<Duration>,
 }
 
 impl<P> Future for CreateRouteService<P> {
@@ -95,6 +125,10 @@ impl<P> Future for CreateRouteService<P> {
             Async::Ready(service) => Ok(Async::Ready(RouteService {
                 service,
                 filters: self.filters.clone(),
+                methods: self.methods.clone(),
+                tags: self.tags.clone(),
+                config: self.config.clone(),
+                budget: self.budget,
             })),
             Async::NotReady => Ok(Async::NotReady),
         }
@@ -104,9 +138,18 @@ impl<P> Future for CreateRouteService<P> {
 pub struct RouteService<P> {
     service: BoxedRouteService<ServiceRequest<P>, ServiceResponse>,
     filters: Rc<Vec<Box<Filter>>>,
+    methods: Rc<Vec<Method>>,
+    tags: Rc<Vec<String>>,
+    config: Option<Rc<Extensions>>,
+    budget: Option<Duration>,
 }
 
 impl<P> RouteService<P> {
+    /// Methods registered with `RouteBuilder::method()` or method filters
+    pub(crate) fn methods(&self) -> &[Method] {
+        &self.methods
+    }
+
     pub fn check(&self, req: &mut ServiceRequest<P>) -> bool {
         for f in self.filters.iter() {
             if !f.check(req.request()) {
@@ -128,12 +171,86 @@ impl<P> Service for RouteService<P> {
     }
 
     fn call(&mut self, req: Self::Request) -> Self::Future {
-        self.service.call(req)
+        if !self.tags.is_empty() {
+            req.extensions_mut().insert(RouteTags(self.tags.clone()));
+
+            let checks = req.extensions().get::<TaggedChecks>().cloned();
+            if let Some(res) = checks.and_then(|c| c.check(req.request(), &self.tags)) {
+                return Box::new(ok(req.into_response(res)));
+            }
+        }
+        if let Some(ref config) = self.config {
+            req.extensions_mut().insert(RouteConfig(config.clone()));
+        }
+        if let Some(budget) = self.budget {
+            let start = Instant::now();
+            Box::new(self.service.call(req).map(move |res| {
+                let elapsed = start.elapsed();
+                if elapsed > budget {
+                    warn!(
+                        "budget_exceeded: {} {} took {:?}, budget {:?}",
+                        res.request().method(),
+                        res.request().path(),
+                        elapsed,
+                        budget
+                    );
+                    res.request()
+                        .extensions_mut()
+                        .insert(BudgetExceeded { budget, elapsed });
+                }
+                res
+            }))
+        } else {
+            self.service.call(req)
+        }
+    }
+}
+
+/// Response time budget overrun of the matched route, see
+/// `RouteBuilder::budget()`
+#[derive(Clone, Copy, Debug)]
+pub struct BudgetExceeded {
+    budget: Duration,
+    elapsed: Duration,
+}
+
+impl BudgetExceeded {
+    /// Route's response time budget
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Actual handling time
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// Tags of the matched route, stored in request extensions
+pub(crate) struct RouteTags(Rc<Vec<String>>);
+
+impl RouteTags {
+    pub(crate) fn contains(&self, tag: &str) -> bool {
+        self.0.iter().any(|t| t == tag)
+    }
+}
+
+/// Extractor configurations of the matched route, stored in request extensions
+pub(crate) struct RouteConfig(Rc<Extensions>);
+
+impl RouteConfig {
+    pub(crate) fn get<C: Clone + 'static>(&self) -> Option<C> {
+        self.0.get::<C>().cloned()
     }
 }
 
 pub struct RouteBuilder<P> {
     filters: Vec<Box<Filter>>,
+    methods: Vec<Method>,
+    tags: Vec<String>,
+    config: Option<Extensions>,
+    budget: Option<Duration>,
+    wraps: Vec<RouteWrap<P>>,
     _t: PhantomData<P>,
 }
 
@@ -141,6 +258,11 @@ impl<P: 'static> RouteBuilder<P> {
     fn new() -> RouteBuilder<P> {
         RouteBuilder {
             filters: Vec::new(),
+            methods: Vec::new(),
+            tags: Vec::new(),
+            config: None,
+            budget: None,
+            wraps: Vec::new(),
             _t: PhantomData,
         }
     }
@@ -161,7 +283,7 @@ impl<P: 'static> RouteBuilder<P> {
     /// # }
     /// ```
     pub fn method(mut self, method: Method) -> Self {
-        self.filters.push(Box::new(filter::Method(method)));
+        self.filter(filter::Method(method));
         self
     }
 
@@ -181,10 +303,151 @@ impl<P: 'static> RouteBuilder<P> {
     /// # }
     /// ```
     pub fn filter<F: Filter + 'static>(&mut self, f: F) -> &mut Self {
+        if let Some(methods) = f.methods() {
+            // route matches methods allowed by every method filter
+            if self.methods.is_empty() {
+                self.methods.extend_from_slice(methods);
+            } else {
+                self.methods.retain(|m| methods.contains(m));
+            }
+        }
         self.filters.push(Box::new(f));
         self
     }
 
+    /// Mark route with a tag.
+    ///
+    /// Tags of the matched route are available after routing via
+    /// `HttpRequest::route_tag()` method, so middlewares could apply
+    /// policies to a tagged routes only.
+    ///
+    /// ```rust,ignore
+    /// # extern crate actix_web2;
+    /// use actix_web2::{App, HttpResponse};
+    ///
+    /// fn main() {
+    ///     App::new().resource("/admin", |r| {
+    ///         r.route(|r| r.tag("authenticated").to(|| HttpResponse::Ok()))
+    ///     });
+    /// }
+    /// ```
+    pub fn tag<T: Into<String>>(mut self, tag: T) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Set extractor configuration for this route.
+    ///
+    /// Route configuration overrides configuration registered with
+    /// `App::state()`, so same extractor could behave differently for
+    /// different routes, i.e. use different error handler.
+    ///
+    /// ```rust,ignore
+    /// # extern crate actix_web2;
+    /// use actix_http::error;
+    /// use actix_web2::dev::JsonConfig;
+    /// use actix_web2::{App, HttpResponse, Json};
+    ///
+    /// fn main() {
+    ///     let mut cfg = JsonConfig::default();
+    ///     cfg.limit(4096).error_handler(|err, _| {
+    ///         error::InternalError::from_response(err, HttpResponse::Conflict().finish())
+    ///             .into()
+    ///     });
+    ///
+    ///     App::new().resource("/index.html", |r| {
+    ///         r.route(|r| r.config(cfg).to(|body: Json<String>| body.into_inner()))
+    ///     });
+    /// }
+    /// ```
+    pub fn config<C: Clone + 'static>(mut self, cfg: C) -> Self {
+        self.config.get_or_insert_with(Extensions::new).insert(cfg);
+        self
+    }
+
+    /// Set response time budget of the route.
+    ///
+    /// Unlike timeout, budget does not abort slow requests. If handling
+    /// takes longer than the budget, overrun is logged with `warn` level and
+    /// `budget_exceeded` label, outer middlewares could check it with
+    /// `HttpRequest::budget_exceeded()` method.
+    ///
+    /// ```rust,ignore
+    /// # extern crate actix_web2;
+    /// use std::time::Duration;
+    /// use actix_web2::{App, HttpResponse};
+    ///
+    /// fn main() {
+    ///     App::new().resource("/report", |r| {
+    ///         r.route(|r| {
+    ///             r.budget(Duration::from_millis(200))
+    ///                 .to(|| HttpResponse::Ok())
+    ///         })
+    ///     });
+    /// }
+    /// ```
+    pub fn budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Register a route middleware.
+    ///
+    /// Route middleware is invoked only for requests handled by this route,
+    /// after application and resource middlewares. Same as for resource
+    /// middlewares, the last registered route middleware is invoked first.
+    ///
+    /// ```rust,ignore
+    /// # extern crate actix_web2;
+    /// use actix_web2::{http, middleware, App, HttpResponse};
+    ///
+    /// fn main() {
+    ///     App::new().resource("/index.html", |r| {
+    ///         r.route(|r| {
+    ///             r.method(http::Method::GET)
+    ///                 .wrap(middleware::DefaultHeaders::new().header("X-Route", "index"))
+    ///                 .to(|| HttpResponse::Ok())
+    ///         })
+    ///     });
+    /// }
+    /// ```
+    pub fn wrap<M, F>(mut self, mw: F) -> Self
+    where
+        M: NewTransform<
+                BoxedRouteService<ServiceRequest<P>, ServiceResponse>,
+                Request = ServiceRequest<P>,
+                Response = ServiceResponse,
+                Error = (),
+                InitError = (),
+            > + 'static,
+        M::Future: 'static,
+        M::Transform: 'static,
+        <M::Transform as Transform<
+            BoxedRouteService<ServiceRequest<P>, ServiceResponse>,
+        >>::Future: 'static,
+        F: IntoNewTransform<M, BoxedRouteService<ServiceRequest<P>, ServiceResponse>>,
+    {
+        let mut mw = Some(mw.into_new_transform());
+        self.wraps.push(Box::new(move |service| {
+            let mw = mw.take().expect("Route middleware is already applied");
+            Box::new(WrapNewService::new(ApplyNewService::new(
+                mw,
+                RouteEndpoint(service),
+            )))
+        }));
+        self
+    }
+
+    /// Apply registered route middlewares to the route service
+    fn apply_wraps(
+        &mut self,
+        service: BoxedRouteNewService<ServiceRequest<P>, ServiceResponse>,
+    ) -> BoxedRouteNewService<ServiceRequest<P>, ServiceResponse> {
+        self.wraps
+            .iter_mut()
+            .fold(service, |service, wrap| wrap(service))
+    }
+
     // pub fn map<T, U, F: IntoNewService<T>>(
     //     self,
     //     md: F,
@@ -259,17 +522,22 @@ impl<P: 'static> RouteBuilder<P> {
     ///     ); // <- use `with` extractor
     /// }
     /// ```
-    pub fn to<F, T, R>(self, handler: F) -> Route<P>
+    pub fn to<F, T, R>(mut self, handler: F) -> Route<P>
     where
         F: Factory<T, R> + 'static,
         T: FromRequest<P> + 'static,
         R: Responder + 'static,
     {
+        let service = self.apply_wraps(Box::new(RouteNewService::new(
+            Extract::new().and_then(Handle::new(handler).map_err(|_| panic!())),
+        )));
         Route {
-            service: Box::new(RouteNewService::new(
-                Extract::new().and_then(Handle::new(handler).map_err(|_| panic!())),
-            )),
+            service,
             filters: Rc::new(self.filters),
+            methods: Rc::new(self.methods),
+            tags: Rc::new(self.tags),
+            config: self.config.map(Rc::new),
+            budget: self.budget,
         }
     }
 
@@ -303,7 +571,7 @@ impl<P: 'static> RouteBuilder<P> {
     /// }
     /// ```
     #[allow(clippy::wrong_self_convention)]
-    pub fn to_async<F, T, R>(self, handler: F) -> Route<P>
+    pub fn to_async<F, T, R>(mut self, handler: F) -> Route<P>
     where
         F: AsyncFactory<T, R>,
         T: FromRequest<P> + 'static,
@@ -311,11 +579,16 @@ impl<P: 'static> RouteBuilder<P> {
         R::Item: Into<Response>,
         R::Error: Into<Error>,
     {
+        let service = self.apply_wraps(Box::new(RouteNewService::new(
+            Extract::new().and_then(AsyncHandle::new(handler).map_err(|_| panic!())),
+        )));
         Route {
-            service: Box::new(RouteNewService::new(
-                Extract::new().and_then(AsyncHandle::new(handler).map_err(|_| panic!())),
-            )),
+            service,
             filters: Rc::new(self.filters),
+            methods: Rc::new(self.methods),
+            tags: Rc::new(self.tags),
+            config: self.config.map(Rc::new),
+            budget: self.budget,
         }
     }
 }
@@ -528,7 +801,102 @@ where
     fn call(&mut self, req: ServiceRequest<P>) -> Self::Future {
         Box::new(self.service.call(req).then(|res| match res {
             Ok(res) => Ok(res),
-            Err((err, req)) => Ok(req.error_response(err)),
+            Err((err, req)) => {
+                let res = match req.app_extensions().get::<ErrorRenderer>() {
+                    Some(renderer) => renderer.render(err),
+                    None => err.into(),
+                };
+                Ok(req.into_response(res))
+            }
         }))
     }
 }
+
+/// Boxed route service factory, used as an endpoint for route middlewares
+struct RouteEndpoint<P>(BoxedRouteNewService<ServiceRequest<P>, ServiceResponse>);
+
+impl<P> NewService for RouteEndpoint<P> {
+    type Request = ServiceRequest<P>;
+    type Response = ServiceResponse;
+    type Error = ();
+    type InitError = ();
+    type Service = BoxedRouteService<ServiceRequest<P>, ServiceResponse>;
+    type Future = Box<Future<Item = Self::Service, Error = Self::InitError>>;
+
+    fn new_service(&self) -> Self::Future {
+        self.0.new_service()
+    }
+}
+
+/// Route service factory wrapped with route middleware
+struct WrapNewService<T> {
+    service: T,
+}
+
+impl<P: 'static, T> WrapNewService<T>
+where
+    T: NewService<
+        Request = ServiceRequest<P>,
+        Response = ServiceResponse,
+        Error = (),
+        InitError = (),
+    >,
+    T::Future: 'static,
+    T::Service: 'static,
+    <T::Service as Service>::Future: 'static,
+{
+    fn new(service: T) -> Self {
+        WrapNewService { service }
+    }
+}
+
+impl<P: 'static, T> NewService for WrapNewService<T>
+where
+    T: NewService<
+        Request = ServiceRequest<P>,
+        Response = ServiceResponse,
+        Error = (),
+        InitError = (),
+    >,
+    T::Future: 'static,
+    T::Service: 'static,
+    <T::Service as Service>::Future: 'static,
+{
+    type Request = ServiceRequest<P>;
+    type Response = ServiceResponse;
+    type Error = ();
+    type InitError = ();
+    type Service = BoxedRouteService<Self::Request, Self::Response>;
+    type Future = Box<Future<Item = Self::Service, Error = Self::InitError>>;
+
+    fn new_service(&self) -> Self::Future {
+        Box::new(self.service.new_service().map(|service| {
+            let service: BoxedRouteService<_, _> =
+                Box::new(WrapServiceWrapper { service });
+            service
+        }))
+    }
+}
+
+struct WrapServiceWrapper<T> {
+    service: T,
+}
+
+impl<T> Service for WrapServiceWrapper<T>
+where
+    T: Service<Response = ServiceResponse, Error = ()>,
+    T::Future: 'static,
+{
+    type Request = T::Request;
+    type Response = ServiceResponse;
+    type Error = ();
+    type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.service.poll_ready()
+    }
+
+    fn call(&mut self, req: T::Request) -> Self::Future {
+        Box::new(self.service.call(req))
+    }
+}
@@ -1,7 +1,7 @@
 use std::rc::Rc;
 
 use actix_http::body::{Body, MessageBody, ResponseBody};
-use actix_http::http::HeaderMap;
+use actix_http::http::{HeaderMap, Uri};
 use actix_http::{
     Error, Extensions, HttpMessage, Payload, Request, Response, ResponseHead,
 };
@@ -27,6 +27,12 @@ impl<P> ServiceRequest<P> {
         }
     }
 
+    /// Construct service request from request and payload
+    #[inline]
+    pub(crate) fn from_parts(req: HttpRequest, payload: Payload<P>) -> Self {
+        ServiceRequest { req, payload }
+    }
+
     #[inline]
     pub fn request(&self) -> &HttpRequest {
         &self.req
@@ -49,10 +55,25 @@ impl<P> ServiceRequest<P> {
         ServiceResponse::new(self.req, err.into().into())
     }
 
+    /// Replace request's payload
+    #[inline]
+    pub fn set_payload(&mut self, payload: Payload<P>) {
+        self.payload = payload;
+    }
+
     #[inline]
     pub fn match_info_mut(&mut self) -> &mut Path<Url> {
         &mut self.req.path
     }
+
+    /// Replace request's uri, match info is reset for the new uri.
+    ///
+    /// Request head could be shared with outer middlewares, so it is not
+    /// modified, new uri is stored in the request itself.
+    pub(crate) fn set_uri(&mut self, uri: Uri) {
+        self.req.path = Path::new(Url::new(uri.clone()));
+        self.req.uri = Some(uri);
+    }
 }
 
 impl<P> HttpMessage for ServiceRequest<P> {
@@ -88,6 +109,12 @@ impl<B> ServiceResponse<B> {
         ServiceResponse { request, response }
     }
 
+    /// Create service response for error, original response is dropped
+    #[inline]
+    pub fn error_response<E: Into<Error>>(self, err: E) -> ServiceResponse {
+        ServiceResponse::new(self.request, err.into().into())
+    }
+
     /// Get reference to original request
     #[inline]
     pub fn request(&self) -> &HttpRequest {
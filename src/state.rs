@@ -1,20 +1,58 @@
+use std::mem;
 use std::ops::Deref;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use actix_http::error::{Error, ErrorInternalServerError};
 use actix_http::Extensions;
 use futures::future::{err, ok, FutureResult};
 use futures::{Async, Future, IntoFuture, Poll};
+use parking_lot::RwLock;
+use tokio_timer::Delay;
 
 use crate::handler::FromRequest;
 use crate::service::ServiceRequest;
 
 /// Application state factory
-pub(crate) trait StateFactory {
+///
+/// Implemented for state factory closures, see `App::state_factory()`.
+pub trait StateFactory {
+    #[doc(hidden)]
     fn construct(&self) -> Box<StateFactoryResult>;
+
+    /// Retry failed state construction.
+    ///
+    /// If constructed future fails, factory is called again after `delay`,
+    /// up to `retries` times. Last error stops application initialization
+    /// as usual. Every failed attempt is logged.
+    ///
+    /// ```rust,ignore
+    /// # extern crate actix_web2;
+    /// use std::time::Duration;
+    /// use actix_web2::dev::StateFactory;
+    /// use actix_web2::App;
+    ///
+    /// fn main() {
+    ///     let app = App::new().state_factory(
+    ///         (|| connect_db()).with_retries(5, Duration::from_millis(500)),
+    ///     );
+    /// }
+    /// ```
+    fn with_retries(self, retries: usize, delay: Duration) -> StateRetry<Self>
+    where
+        Self: Sized,
+    {
+        StateRetry {
+            factory: Rc::new(self),
+            retries,
+            delay,
+        }
+    }
 }
 
-pub(crate) trait StateFactoryResult {
+#[doc(hidden)]
+pub trait StateFactoryResult {
     fn poll_result(&mut self, extensions: &mut Extensions) -> Poll<(), ()>;
 }
 
@@ -61,6 +99,173 @@ impl<S: 'static, P> FromRequest<P> for State<S> {
     }
 }
 
+/// Application data.
+///
+/// Unlike `State<S>`, data is stored in an `Arc`, so clones of one
+/// `Data<T>` instance share the same value. Several values of different
+/// types could be registered with `App::data()` method, each value is
+/// extracted by its type. Extraction fails with
+/// *500 Internal Server Error* if value of requested type is not
+/// registered.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{App, Data};
+///
+/// struct Settings {
+///     greeting: String,
+/// }
+///
+/// fn index(settings: Data<Settings>, hits: Data<AtomicUsize>) -> String {
+///     hits.fetch_add(1, Ordering::Relaxed);
+///     settings.greeting.clone()
+/// }
+///
+/// fn main() {
+///     let app = App::new()
+///         .data(Settings { greeting: "Hello".to_owned() })
+///         .data(AtomicUsize::new(0))
+///         .resource("/", |r| r.get(index));
+/// }
+/// ```
+pub struct Data<T>(Arc<T>);
+
+impl<T> Data<T> {
+    pub fn new(data: T) -> Data<T> {
+        Data(Arc::new(data))
+    }
+
+    pub fn get_ref(&self) -> &T {
+        self.0.as_ref()
+    }
+}
+
+impl<T> Deref for Data<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.0.as_ref()
+    }
+}
+
+impl<T> Clone for Data<T> {
+    fn clone(&self) -> Data<T> {
+        Data(self.0.clone())
+    }
+}
+
+impl<T: 'static, P> FromRequest<P> for Data<T> {
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        if let Some(data) = req.app_extensions().get::<Data<T>>() {
+            ok(data.clone())
+        } else {
+            err(ErrorInternalServerError(
+                "Data is not configured, use App::data()",
+            ))
+        }
+    }
+}
+
+impl<T: 'static> StateFactory for Data<T> {
+    fn construct(&self) -> Box<StateFactoryResult> {
+        Box::new(DataFut { data: self.clone() })
+    }
+}
+
+struct DataFut<T> {
+    data: Data<T>,
+}
+
+impl<T: 'static> StateFactoryResult for DataFut<T> {
+    fn poll_result(&mut self, extensions: &mut Extensions) -> Poll<(), ()> {
+        extensions.insert(self.data.clone());
+        Ok(Async::Ready(()))
+    }
+}
+
+/// Application state that could be replaced at runtime.
+///
+/// Unlike `State<S>`, value could be replaced with
+/// `ReloadableState::reload()` method without application restart,
+/// for example on configuration change. `ReloadableState::get()` returns
+/// current value, readers never observe partially updated value and
+/// a value returned by `get()` stays valid even if state gets reloaded.
+///
+/// Clones share the same value, so one instance could be created outside
+/// of application factory, shared between server workers and registered
+/// with `App::state()` method.
+///
+/// ```rust,ignore
+/// # extern crate actix_web2;
+/// use actix_web2::{App, ReloadableState};
+///
+/// struct Config {
+///     greeting: String,
+/// }
+///
+/// fn index(config: ReloadableState<Config>) -> String {
+///     config.get().greeting.clone()
+/// }
+///
+/// fn main() {
+///     let config = ReloadableState::new(Config {
+///         greeting: "Hello".to_owned(),
+///     });
+///
+///     let app = App::new()
+///         .state(config.clone())
+///         .resource("/", |r| r.get(index));
+///
+///     // later, e.g. on SIGHUP
+///     config.reload(Config {
+///         greeting: "Welcome".to_owned(),
+///     });
+/// }
+/// ```
+pub struct ReloadableState<S>(Arc<RwLock<Arc<S>>>);
+
+impl<S> ReloadableState<S> {
+    pub fn new(state: S) -> ReloadableState<S> {
+        ReloadableState(Arc::new(RwLock::new(Arc::new(state))))
+    }
+
+    /// Get current value of the state
+    pub fn get(&self) -> Arc<S> {
+        self.0.read().clone()
+    }
+
+    /// Replace value of the state, previous value is returned
+    pub fn reload(&self, state: S) -> Arc<S> {
+        mem::replace(&mut *self.0.write(), Arc::new(state))
+    }
+}
+
+impl<S> Clone for ReloadableState<S> {
+    fn clone(&self) -> ReloadableState<S> {
+        ReloadableState(self.0.clone())
+    }
+}
+
+impl<S: 'static, P> FromRequest<P> for ReloadableState<S> {
+    type Error = Error;
+    type Future = FutureResult<Self, Error>;
+
+    #[inline]
+    fn from_request(req: &mut ServiceRequest<P>) -> Self::Future {
+        if let Some(st) = req.app_extensions().get::<State<ReloadableState<S>>>() {
+            ok(st.get_ref().clone())
+        } else {
+            err(ErrorInternalServerError(
+                "State is not configured, use App::state()",
+            ))
+        }
+    }
+}
+
 impl<S: 'static> StateFactory for State<S> {
     fn construct(&self) -> Box<StateFactoryResult> {
         Box::new(StateFut { st: self.clone() })
@@ -118,3 +323,96 @@ where
         }
     }
 }
+
+/// State factory that retries failed construction, see
+/// `StateFactory::with_retries()`
+pub struct StateRetry<T> {
+    factory: Rc<T>,
+    retries: usize,
+    delay: Duration,
+}
+
+impl<T: StateFactory + 'static> StateFactory for StateRetry<T> {
+    fn construct(&self) -> Box<StateFactoryResult> {
+        Box::new(StateRetryFut {
+            fut: self.factory.construct(),
+            factory: self.factory.clone(),
+            retries: self.retries,
+            delay: self.delay,
+            sleep: None,
+        })
+    }
+}
+
+struct StateRetryFut<T> {
+    factory: Rc<T>,
+    fut: Box<StateFactoryResult>,
+    retries: usize,
+    delay: Duration,
+    sleep: Option<Delay>,
+}
+
+impl<T: StateFactory> StateFactoryResult for StateRetryFut<T> {
+    fn poll_result(&mut self, extensions: &mut Extensions) -> Poll<(), ()> {
+        loop {
+            if let Some(ref mut sleep) = self.sleep {
+                if let Ok(Async::NotReady) = sleep.poll() {
+                    return Ok(Async::NotReady);
+                }
+            }
+            if self.sleep.take().is_some() {
+                self.fut = self.factory.construct();
+            }
+
+            match self.fut.poll_result(extensions) {
+                Err(()) if self.retries > 0 => {
+                    log::warn!(
+                        "Retrying application state construction, {} attempts left",
+                        self.retries
+                    );
+                    self.retries -= 1;
+                    self.sleep = Some(Delay::new(Instant::now() + self.delay));
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn test_reloadable_state() {
+        let state = ReloadableState::new((0usize, 0usize));
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let state = state.clone();
+                thread::spawn(move || {
+                    let mut last = 0;
+                    for _ in 0..10_000 {
+                        let val = state.get();
+                        // value is never torn and never goes back
+                        assert_eq!(val.0, val.1);
+                        assert!(val.0 >= last);
+                        last = val.0;
+                    }
+                })
+            })
+            .collect();
+
+        for idx in 1..1_000 {
+            let prev = state.reload((idx, idx));
+            assert_eq!(*prev, (idx - 1, idx - 1));
+        }
+
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(*state.get(), (999, 999));
+    }
+}
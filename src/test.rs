@@ -1,29 +1,32 @@
 //! Various helpers for Actix applications to use during testing.
-use std::str::FromStr;
+use std::rc::Rc;
 
-use bytes::Bytes;
-use futures::IntoFuture;
-use tokio::runtime::current_thread::Runtime;
-
-use actix_http::dev::Payload;
 use actix_http::http::header::{Header, HeaderName, IntoHeaderValue};
-use actix_http::http::{HeaderMap, HttpTryFrom, Method, Uri, Version};
-use actix_http::Request as HttpRequest;
+use actix_http::http::{HttpTryFrom, Method, Version};
+use actix_http::test::TestRequest as HttpTestRequest;
+use actix_http::{Extensions, PayloadStream};
 use actix_router::{Path, Url};
+use actix_rt::System;
+use bytes::Bytes;
+use futures::IntoFuture;
 
-use crate::app::State;
-use crate::request::Request;
+use crate::request::HttpRequest;
+use crate::service::ServiceRequest;
+use crate::state::State;
 
-/// Test `Request` builder
+/// Test `ServiceRequest` builder
+///
+/// Request is constructed without application and router, so extractors
+/// could be tested directly. Application state is registered with
+/// `TestRequest::state()`, match info with `TestRequest::param()`.
 ///
 /// ```rust,ignore
-/// # extern crate http;
-/// # extern crate actix_web;
-/// # use http::{header, StatusCode};
-/// # use actix_web::*;
-/// use actix_web::test::TestRequest;
+/// # extern crate actix_web2;
+/// use actix_web2::http::{header, StatusCode};
+/// use actix_web2::test::{self, TestRequest};
+/// use actix_web2::{FromRequest, HttpRequest, HttpResponse, Path, State};
 ///
-/// fn index(req: &HttpRequest) -> HttpResponse {
+/// fn index(req: HttpRequest) -> HttpResponse {
 ///     if let Some(hdr) = req.headers().get(header::CONTENT_TYPE) {
 ///         HttpResponse::Ok().into()
 ///     } else {
@@ -32,99 +35,76 @@ use crate::request::Request;
 /// }
 ///
 /// fn main() {
-///     let resp = TestRequest::with_header("content-type", "text/plain")
-///         .run(&index)
-///         .unwrap();
-///     assert_eq!(resp.status(), StatusCode::OK);
+///     let req = TestRequest::with_header("content-type", "text/plain").request();
+///     assert_eq!(index(req).status(), StatusCode::OK);
 ///
-///     let resp = TestRequest::default().run(&index).unwrap();
-///     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+///     let mut req = TestRequest::with_uri("/users/42")
+///         .state(10usize)
+///         .param("id", "42")
+///         .finish();
+///     let state = test::block_on(State::<usize>::from_request(&mut req)).unwrap();
+///     let id = test::block_on(Path::<u32>::from_request(&mut req)).unwrap();
+///     assert_eq!(*state + *id, 52);
 /// }
 /// ```
-pub struct TestRequest<S> {
-    state: S,
-    version: Version,
-    method: Method,
-    uri: Uri,
-    headers: HeaderMap,
-    params: Path<Url>,
-    payload: Option<Payload>,
+pub struct TestRequest {
+    req: HttpTestRequest,
+    extensions: Extensions,
+    params: Vec<(&'static str, &'static str)>,
 }
 
-impl Default for TestRequest<()> {
-    fn default() -> TestRequest<()> {
+impl Default for TestRequest {
+    fn default() -> TestRequest {
         TestRequest {
-            state: (),
-            method: Method::GET,
-            uri: Uri::from_str("/").unwrap(),
-            version: Version::HTTP_11,
-            headers: HeaderMap::new(),
-            params: Path::new(Url::default()),
-            payload: None,
+            req: HttpTestRequest::default(),
+            extensions: Extensions::new(),
+            params: Vec::new(),
         }
     }
 }
 
-impl TestRequest<()> {
+impl TestRequest {
     /// Create TestRequest and set request uri
-    pub fn with_uri(path: &str) -> TestRequest<()> {
+    pub fn with_uri(path: &str) -> TestRequest {
         TestRequest::default().uri(path)
     }
 
     /// Create TestRequest and set header
-    pub fn with_hdr<H: Header>(hdr: H) -> TestRequest<()> {
+    pub fn with_hdr<H: Header>(hdr: H) -> TestRequest {
         TestRequest::default().set(hdr)
     }
 
     /// Create TestRequest and set header
-    pub fn with_header<K, V>(key: K, value: V) -> TestRequest<()>
+    pub fn with_header<K, V>(key: K, value: V) -> TestRequest
     where
         HeaderName: HttpTryFrom<K>,
         V: IntoHeaderValue,
     {
         TestRequest::default().header(key, value)
     }
-}
-
-impl<S: 'static> TestRequest<S> {
-    /// Start HttpRequest build process with application state
-    pub fn with_state(state: S) -> TestRequest<S> {
-        TestRequest {
-            state,
-            method: Method::GET,
-            uri: Uri::from_str("/").unwrap(),
-            version: Version::HTTP_11,
-            headers: HeaderMap::new(),
-            params: Path::new(Url::default()),
-            payload: None,
-        }
-    }
 
     /// Set HTTP version of this request
     pub fn version(mut self, ver: Version) -> Self {
-        self.version = ver;
+        self.req.version(ver);
         self
     }
 
     /// Set HTTP method of this request
     pub fn method(mut self, meth: Method) -> Self {
-        self.method = meth;
+        self.req.method(meth);
         self
     }
 
     /// Set HTTP Uri of this request
     pub fn uri(mut self, path: &str) -> Self {
-        self.uri = Uri::from_str(path).unwrap();
+        self.req.uri(path);
         self
     }
 
     /// Set a header
     pub fn set<H: Header>(mut self, hdr: H) -> Self {
-        if let Ok(value) = hdr.try_into() {
-            self.headers.append(H::name(), value);
-            return self;
-        }
-        panic!("Can not set header");
+        self.req.set(hdr);
+        self
     }
 
     /// Set a header
@@ -133,63 +113,52 @@ impl<S: 'static> TestRequest<S> {
         HeaderName: HttpTryFrom<K>,
         V: IntoHeaderValue,
     {
-        if let Ok(key) = HeaderName::try_from(key) {
-            if let Ok(value) = value.try_into() {
-                self.headers.append(key, value);
-                return self;
-            }
-        }
-        panic!("Can not create header");
+        self.req.header(key, value);
+        self
     }
 
     /// Set request path pattern parameter
     pub fn param(mut self, name: &'static str, value: &'static str) -> Self {
-        self.params.add_static(name, value);
+        self.params.push((name, value));
         self
     }
 
     /// Set request payload
     pub fn set_payload<B: Into<Bytes>>(mut self, data: B) -> Self {
-        let mut payload = Payload::empty();
-        payload.unread_data(data.into());
-        self.payload = Some(payload);
+        self.req.set_payload(data);
         self
     }
 
-    /// Complete request creation and generate `HttpRequest` instance
-    pub fn finish(self) -> Request<S> {
-        let TestRequest {
-            state,
-            method,
-            uri,
-            version,
-            headers,
-            mut params,
-            payload,
-        } = self;
-
-        params.get_mut().update(&uri);
-
-        let mut req = HttpRequest::new();
-        {
-            let inner = req.inner_mut();
-            inner.head.uri = uri;
-            inner.head.method = method;
-            inner.head.version = version;
-            inner.head.headers = headers;
-            *inner.payload.borrow_mut() = payload;
-        }
+    /// Set application state, same as `App::state()`
+    pub fn state<S: 'static>(mut self, state: S) -> Self {
+        self.extensions.insert(State::new(state));
+        self
+    }
 
-        Request::new(State::new(state), req, params)
+    /// Complete request creation and generate `ServiceRequest` instance
+    pub fn finish(mut self) -> ServiceRequest<PayloadStream> {
+        let req = self.req.finish();
+
+        let mut req = ServiceRequest::new(
+            Path::new(Url::new(req.uri().clone())),
+            req,
+            Rc::new(self.extensions),
+        );
+        for (name, value) in self.params.drain(..) {
+            req.match_info_mut().add_static(name, value);
+        }
+        req
     }
 
-    /// This method generates `HttpRequest` instance and executes handler
-    pub fn run_async<F, R, I, E>(self, f: F) -> Result<I, E>
-    where
-        F: FnOnce(&Request<S>) -> R,
-        R: IntoFuture<Item = I, Error = E>,
-    {
-        let mut rt = Runtime::new().unwrap();
-        rt.block_on(f(&self.finish()).into_future())
+    /// Complete request creation and generate `HttpRequest` instance
+    pub fn request(self) -> HttpRequest {
+        self.finish().into_request()
     }
 }
+
+/// Execute future on a new system and return its result.
+///
+/// Could be used for running extractors and async handlers within tests.
+pub fn block_on<F: IntoFuture>(fut: F) -> Result<F::Item, F::Error> {
+    System::new("actix-test").block_on(fut.into_future())
+}
@@ -0,0 +1,255 @@
+//! TLS connection info
+use std::fmt;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+
+use actix_codec::{AsyncRead, AsyncWrite};
+use actix_http::body::MessageBody;
+use actix_http::h1::Dispatcher;
+use actix_http::{Request, Response, ServiceConfig};
+use actix_service::{IntoNewService, NewService, Service};
+use actix_utils::cloneable::CloneableService;
+use futures::{try_ready, Async, Future, Poll};
+
+use crate::extractor::{ClientCert, TlsInfo};
+use crate::info::PeerAddr;
+
+/// Established TLS connection.
+///
+/// Implemented by streams produced by TLS acceptor. `TlsH1Service` uses it
+/// to store connection info in request extensions, so it is available for
+/// `ClientCert` and `TlsInfo` extractors and `ConnectionInfo`.
+pub trait TlsStream {
+    /// Verified peer certificate, if client presented one
+    fn peer_cert(&self) -> Option<ClientCert>;
+
+    /// Negotiated session parameters, once handshake is complete
+    fn tls_info(&self) -> Option<TlsInfo>;
+
+    /// Address of the connection peer
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+}
+
+/// Http/1 service for TLS connections.
+///
+/// Same as `h1::H1Service`, but stores info of the connection in request
+/// extensions of every request received on it.
+///
+/// ```rust,ignore
+/// let acceptor = OpensslAcceptor::new(builder.build());
+///
+/// TestServer::new(move || {
+///     acceptor
+///         .clone()
+///         .map_err(|_| ())
+///         .and_then(TlsH1Service::new(App::new()).map_err(|_| ()))
+/// });
+/// ```
+pub struct TlsH1Service<T, S, B> {
+    srv: S,
+    cfg: ServiceConfig,
+    _t: PhantomData<(T, B)>,
+}
+
+impl<T, S, B> TlsH1Service<T, S, B>
+where
+    S: NewService<Request = Request>,
+    S::Error: fmt::Debug,
+    S::Response: Into<Response<B>>,
+    S::Service: 'static,
+    B: MessageBody,
+{
+    /// Create new `TlsH1Service` instance with default config.
+    pub fn new<F: IntoNewService<S>>(service: F) -> Self {
+        Self::with_config(ServiceConfig::default(), service)
+    }
+
+    /// Create new `TlsH1Service` instance with custom config.
+    pub fn with_config<F: IntoNewService<S>>(cfg: ServiceConfig, service: F) -> Self {
+        TlsH1Service {
+            cfg,
+            srv: service.into_new_service(),
+            _t: PhantomData,
+        }
+    }
+}
+
+impl<T, S, B> NewService for TlsH1Service<T, S, B>
+where
+    T: TlsStream + AsyncRead + AsyncWrite,
+    S: NewService<Request = Request>,
+    S::Error: fmt::Debug,
+    S::Response: Into<Response<B>>,
+    S::Service: 'static,
+    B: MessageBody,
+{
+    type Request = T;
+    type Response = ();
+    type Error = <Dispatcher<T, TlsConnService<S::Service>, B> as Future>::Error;
+    type InitError = S::InitError;
+    type Service = TlsH1ServiceHandler<T, S::Service, B>;
+    type Future = TlsH1ServiceResponse<T, S, B>;
+
+    fn new_service(&self) -> Self::Future {
+        TlsH1ServiceResponse {
+            fut: self.srv.new_service(),
+            cfg: Some(self.cfg.clone()),
+            _t: PhantomData,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct TlsH1ServiceResponse<T, S: NewService, B> {
+    fut: S::Future,
+    cfg: Option<ServiceConfig>,
+    _t: PhantomData<(T, B)>,
+}
+
+impl<T, S, B> Future for TlsH1ServiceResponse<T, S, B>
+where
+    S: NewService<Request = Request>,
+    S::Service: 'static,
+{
+    type Item = TlsH1ServiceHandler<T, S::Service, B>;
+    type Error = S::InitError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let srv = try_ready!(self.fut.poll());
+        Ok(Async::Ready(TlsH1ServiceHandler {
+            srv: CloneableService::new(srv),
+            cfg: self.cfg.take().unwrap(),
+            _t: PhantomData,
+        }))
+    }
+}
+
+#[doc(hidden)]
+pub struct TlsH1ServiceHandler<T, S: 'static, B> {
+    srv: CloneableService<S>,
+    cfg: ServiceConfig,
+    _t: PhantomData<(T, B)>,
+}
+
+impl<T, S, B> Service for TlsH1ServiceHandler<T, S, B>
+where
+    T: TlsStream + AsyncRead + AsyncWrite,
+    S: Service<Request = Request> + 'static,
+    S::Error: fmt::Debug,
+    S::Response: Into<Response<B>>,
+    B: MessageBody,
+{
+    type Request = T;
+    type Response = ();
+    type Error = <Dispatcher<T, TlsConnService<S>, B> as Future>::Error;
+    type Future = Dispatcher<T, TlsConnService<S>, B>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        // readiness of the application service is checked by dispatcher
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, io: T) -> Self::Future {
+        let srv = TlsConnService {
+            cert: io.peer_cert(),
+            info: io.tls_info(),
+            peer: io.peer_addr(),
+            srv: self.srv.clone(),
+        };
+        Dispatcher::new(io, self.cfg.clone(), CloneableService::new(srv))
+    }
+}
+
+/// Per-connection service, stores connection info in request extensions
+#[doc(hidden)]
+pub struct TlsConnService<S: 'static> {
+    srv: CloneableService<S>,
+    cert: Option<ClientCert>,
+    info: Option<TlsInfo>,
+    peer: Option<SocketAddr>,
+}
+
+impl<S> Service for TlsConnService<S>
+where
+    S: Service<Request = Request> + 'static,
+{
+    type Request = Request;
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.srv.poll_ready()
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        if let Some(ref cert) = self.cert {
+            req.extensions_mut().insert(cert.clone());
+        }
+        if let Some(ref info) = self.info {
+            req.extensions_mut().insert(info.clone());
+        }
+        if let Some(peer) = self.peer {
+            req.extensions_mut().insert(PeerAddr(peer));
+        }
+        self.srv.call(req)
+    }
+}
+
+#[cfg(feature = "ssl")]
+mod ssl {
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::x509::X509VerifyResult;
+    use tokio_openssl::SslStream;
+    use tokio_tcp::TcpStream;
+
+    use super::*;
+
+    impl TlsStream for SslStream<TcpStream> {
+        fn peer_cert(&self) -> Option<ClientCert> {
+            let ssl = self.get_ref().ssl();
+            if ssl.verify_result() != X509VerifyResult::OK {
+                return None;
+            }
+            let cert = ssl.peer_certificate()?;
+
+            let subject = cert
+                .subject_name()
+                .entries()
+                .filter_map(|entry| {
+                    let name = entry.object().nid().short_name().ok()?;
+                    let value = entry.data().as_utf8().ok()?;
+                    Some(format!("{}={}", name, value))
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            let common_name = cert
+                .subject_name()
+                .entries_by_nid(Nid::COMMONNAME)
+                .next()
+                .and_then(|entry| entry.data().as_utf8().ok())
+                .map(|cn| cn.to_string());
+            let fingerprint = cert
+                .digest(MessageDigest::sha256())
+                .ok()?
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(":");
+            Some(ClientCert::new(subject, common_name, fingerprint))
+        }
+
+        fn tls_info(&self) -> Option<TlsInfo> {
+            let ssl = self.get_ref().ssl();
+            let cipher = ssl.current_cipher()?;
+            Some(TlsInfo::new(ssl.version_str(), cipher.name()))
+        }
+
+        fn peer_addr(&self) -> Option<SocketAddr> {
+            self.get_ref().get_ref().peer_addr().ok()
+        }
+    }
+}
@@ -1,18 +1,46 @@
-use std::io::{Read, Write};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
 
+use actix_codec::{AsyncRead, AsyncWrite};
 use actix_http::http::header::{
-    ContentEncoding, ACCEPT_ENCODING, CONTENT_LENGTH, TRANSFER_ENCODING,
+    ContentEncoding, HeaderValue, ACCEPT_ENCODING, ACCEPT_RANGES, ALLOW, AUTHORIZATION,
+    CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE,
+    CONTENT_TYPE, COOKIE, ETAG, IF_NONE_MATCH, RANGE, RETRY_AFTER, TRANSFER_ENCODING,
+    VARY, WWW_AUTHENTICATE,
 };
+use actix_http::http::{HeaderMap, Method, StatusCode};
 use actix_http::{h1, Error, HttpMessage, Response};
 use actix_http_test::TestServer;
+use actix_service::{NewService, Service};
 use brotli2::write::BrotliDecoder;
 use bytes::Bytes;
 use flate2::read::GzDecoder;
 use flate2::write::ZlibDecoder;
+use futures::future::{ok, FutureResult};
 use futures::stream::once; //Future, Stream
+use futures::sync::mpsc;
+use futures::{Async, Poll};
+use hmac::{Hmac, Mac};
 use rand::{distributions::Alphanumeric, Rng};
+use serde_derive::Deserialize;
+use sha2::Sha256;
 
-use actix_web2::{middleware, App};
+use actix_web2::dev::{
+    self, Attachment, Blob, Channel, CookieConfig, Deadline, Deferred, DuplicateHost,
+    Encoded, Envelope, EnvelopeConfig, EnvelopeError, ExtractConfig, FormConfig,
+    HeaderConfig, JsonConfig, JsonKind, MultiSourceConfig, MultipartConfig, Negotiate,
+    PayloadConfig, Problem, Progress, QueryConfig, RequireScopesConfig,
+    SignedPayloadConfig, Spooled, TlsH1Service, TlsStream,
+};
+use actix_web2::test::{self, TestRequest};
+use actix_web2::{
+    filter, middleware, App, Authorization, Body, ClientCert, Cookie, Cookies, Data,
+    DecodedPath, Favicon, Form, Forwarded, FromRequest, HttpRequest, Json, MatchedPath,
+    MultiSource, Multipart, Path, Payload, QsQuery, Query, RawQuery, ReqData,
+    RequireScopes, Responder, SignedPayload, State, Tail, TlsInfo, Valid,
+};
 
 const STR: &str = "Hello World Hello World Hello World Hello World Hello World \
                    Hello World Hello World Hello World Hello World Hello World \
@@ -296,6 +324,4910 @@ fn test_body_brotli() {
     assert_eq!(Bytes::from(dec), Bytes::from_static(STR.as_ref()));
 }
 
+#[test]
+fn test_signed_payload() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(
+            App::new()
+                .state(SignedPayloadConfig::new("secret", "x-signature"))
+                .resource("/", |r| {
+                    r.post(|body: SignedPayload| Response::Ok().body(body.into_inner()))
+                }),
+        )
+    });
+
+    let mut mac = Hmac::<Sha256>::new_varkey(b"secret").unwrap();
+    mac.input(STR.as_ref());
+    let signature: String = mac
+        .result()
+        .code()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    // valid signature
+    let request = srv
+        .post()
+        .header("x-signature", format!("sha256={}", signature))
+        .body(STR)
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(STR.as_ref()));
+
+    // invalid signature
+    let request = srv
+        .post()
+        .header("x-signature", format!("sha256={}", &signature[..62]))
+        .body(STR)
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // missing signature
+    let request = srv.post().body(STR).unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn test_tagged_middleware() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(
+            App::new()
+                .middleware(
+                    middleware::Tagged::new("auth", |res| {
+                        res.headers_mut()
+                            .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+                    })
+                    .check(|req| {
+                        if req.headers().contains_key(AUTHORIZATION) {
+                            None
+                        } else {
+                            Some(Response::Unauthorized().finish())
+                        }
+                    }),
+                )
+                .resource("/tagged", |r| {
+                    r.route(|r| {
+                        r.tag("auth").to(|| {
+                            CALLS.fetch_add(1, Ordering::SeqCst);
+                            Response::Ok()
+                        })
+                    })
+                })
+                .resource("/untagged", |r| r.route(|r| r.to(|| Response::Ok()))),
+        )
+    });
+
+    // check rejects request before route handler is called
+    let request = srv.get().uri(srv.url("/tagged")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers().get(CACHE_CONTROL).unwrap(), "no-store");
+    assert_eq!(CALLS.load(Ordering::SeqCst), 0);
+
+    let request = srv
+        .get()
+        .uri(srv.url("/tagged"))
+        .header(AUTHORIZATION, "Bearer token")
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get(CACHE_CONTROL).unwrap(), "no-store");
+    assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+
+    let request = srv.get().uri(srv.url("/untagged")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert!(!response.headers().contains_key(CACHE_CONTROL));
+}
+
+#[test]
+fn test_path_array() {
+    fn index(point: Path<[u32; 3]>) -> String {
+        format!("{}-{}-{}", point[0], point[1], point[2])
+    }
+
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(
+            App::new()
+                .resource("/point/{x}/{y}/{z}", |r| r.get(index))
+                .resource("/short/{x}/{y}", |r| r.get(index))
+                .resource("/long/{x}/{y}/{z}/{w}", |r| r.get(index)),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/point/1/2/3")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"1-2-3"));
+
+    let request = srv.get().uri(srv.url("/short/1/2")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let request = srv.get().uri(srv.url("/long/1/2/3/4")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let req = TestRequest::default()
+        .param("x", "1")
+        .param("y", "2")
+        .param("z", "3")
+        .finish();
+    let e = Path::<[u32; 2]>::extract(&req).err().unwrap();
+    assert_eq!(e.to_string(), "wrong number of parameters: 3 expected 2");
+
+    // tuples do not have to cover all segments
+    let path = Path::<(u32, u32)>::extract(&req).unwrap();
+    assert_eq!(*path, (1, 2));
+}
+
+#[test]
+fn test_decoded_path() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(
+            App::new()
+                .resource("/{tail:.*}", |r| r.get(|p: DecodedPath| p.into_inner())),
+        )
+    });
+
+    let request = srv
+        .get()
+        .uri(srv.url("/hello%20world/index.html?q=%20"))
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"/hello world/index.html"));
+
+    // encoded slash is decoded
+    let request = srv.get().uri(srv.url("/a%2Fb/c")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"/a/b/c"));
+}
+
+#[test]
+fn test_text_charset() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(
+            App::new()
+                .resource("/text", |r| r.get(|| "text".to_owned()))
+                .resource("/html", |r| {
+                    r.get(|| "<h1>html</h1>".customize().content_type("text/html"))
+                }),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/text")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "text/plain; charset=utf-8"
+    );
+
+    let request = srv.get().uri(srv.url("/html")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), "text/html");
+}
+
+#[test]
+fn test_favicon() {
+    let path = std::env::temp_dir().join("actix-web2-test-favicon.ico");
+    std::fs::write(&path, b"icon").unwrap();
+
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(
+            App::new()
+                .service("/favicon.ico", Favicon::new(&path))
+                .service("/missing.ico", Favicon::new("/not/exists/favicon.ico")),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/favicon.ico")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "image/x-icon"
+    );
+    assert!(response.headers().contains_key(CACHE_CONTROL));
+
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"icon"));
+
+    let request = srv.get().uri(srv.url("/missing.ico")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+}
+
+#[test]
+fn test_cookies() {
+    #[derive(Deserialize)]
+    struct Info {
+        session: String,
+        theme: String,
+    }
+
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.get(|c: Cookies<Info>| format!("{}:{}", c.session, c.theme))
+        }))
+    });
+
+    let request = srv
+        .get()
+        .header(COOKIE, "session=abc; lang=en")
+        .header(COOKIE, "theme=dark")
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"abc:dark"));
+
+    // theme cookie is missing
+    let request = srv.get().header(COOKIE, "session=abc").finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn test_channel_body() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.get(|| {
+                let (tx, rx) = mpsc::unbounded();
+                for chunk in &["first;", "second;", "third"] {
+                    tx.unbounded_send(Bytes::from_static(chunk.as_bytes()))
+                        .unwrap();
+                }
+                Channel::new(rx).content_type("text/plain")
+            })
+        }))
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), "text/plain");
+
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"first;second;third"));
+}
+
+#[test]
+fn test_body_content_type_dispatch() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.post(|body: Body| match body {
+                Body::Json(val) => format!("json:{}", val["name"]),
+                Body::Form(map) => format!("form:{}", map["name"]),
+                Body::Multipart(_) => "multipart".to_owned(),
+                Body::Bytes(data) => format!("bytes:{}", data.len()),
+            })
+        }))
+    });
+
+    let cases: &[(&str, &str, &[u8])] = &[
+        (
+            "application/json",
+            r#"{"name":"actix"}"#,
+            br#"json:"actix""#,
+        ),
+        (
+            "application/x-www-form-urlencoded",
+            "name=actix",
+            b"form:actix",
+        ),
+        (
+            "multipart/form-data; boundary=abc",
+            "--abc--\r\n",
+            b"multipart",
+        ),
+        ("text/plain", "actix", b"bytes:5"),
+    ];
+
+    for (content_type, payload, expected) in cases {
+        let request = srv
+            .post()
+            .header(CONTENT_TYPE, *content_type)
+            .body(*payload)
+            .unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(&bytes[..], *expected);
+    }
+}
+
+/// Logger that keeps log lines in memory, shared by all tests
+struct CaptureLogger;
+
+lazy_static::lazy_static! {
+    static ref LOGS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+}
+
+impl log::Log for CaptureLogger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        LOGS.lock()
+            .unwrap()
+            .push((record.target().to_owned(), format!("{}", record.args())));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CaptureLogger = CaptureLogger;
+
+/// Install capturing logger, it could be installed by another test already
+fn capture_logs() {
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(log::LevelFilter::Debug);
+}
+
+/// Captured log lines of the module
+fn captured_logs(module: &str) -> Vec<String> {
+    LOGS.lock()
+        .unwrap()
+        .iter()
+        .filter(|(target, _)| target.ends_with(module))
+        .map(|(_, line)| line.clone())
+        .collect()
+}
+
+#[test]
+fn test_body_capture_limit() {
+    capture_logs();
+
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.middleware(middleware::BodyCapture::new().limit(16))
+                .post(|body: Bytes| Response::Ok().body(body))
+        }))
+    });
+
+    let request = srv.post().body(STR).unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(STR.as_ref()));
+
+    let logs = captured_logs("middleware::capture");
+    let expected = format!(
+        "POST / request body: Hello World Hell... [truncated, 16 of {} bytes]",
+        STR.len()
+    );
+    assert!(logs.contains(&expected));
+}
+
+#[test]
+fn test_extension_method() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(
+            App::new()
+                .resource("/", |r| {
+                    r.route(|r| {
+                        r.method(Method::from_bytes(b"PROPFIND").unwrap())
+                            .to(|| Response::Ok())
+                    })
+                    .get(|| Response::Ok())
+                })
+                .resource("/filter", |r| {
+                    r.route(|mut r| {
+                        r.filter(filter::Method(
+                            Method::from_bytes(b"PROPFIND").unwrap(),
+                        ));
+                        r.to(|| Response::Ok())
+                    })
+                })
+                .resource("/methods", |r| {
+                    r.route(|mut r| {
+                        r.filter(filter::Methods(&[Method::GET, Method::HEAD]));
+                        r.to(|| Response::Ok())
+                    })
+                }),
+        )
+    });
+
+    let method = Method::from_bytes(b"PROPFIND").unwrap();
+    let request = srv.get().method(method.clone()).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    let request = srv.post().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.headers().get(ALLOW).unwrap(), "PROPFIND, GET");
+
+    let request = srv
+        .get()
+        .method(method)
+        .uri(srv.url("/filter"))
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    // methods of route filters are allowed as well
+    let request = srv.get().uri(srv.url("/filter")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.headers().get(ALLOW).unwrap(), "PROPFIND");
+
+    let request = srv.post().uri(srv.url("/methods")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.headers().get(ALLOW).unwrap(), "GET, HEAD");
+}
+
+#[test]
+fn test_attachment() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(
+            App::new()
+                .resource("/ascii", |r| {
+                    r.get(|| {
+                        Attachment::new("id,name")
+                            .filename("report.csv")
+                            .content_type("text/csv")
+                    })
+                })
+                .resource("/utf8", |r| {
+                    r.get(|| Attachment::new("id,name").filename("отчет.csv"))
+                }),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/ascii")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), "text/csv");
+    assert_eq!(
+        response.headers().get(CONTENT_DISPOSITION).unwrap(),
+        "attachment; filename=\"report.csv\""
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"id,name"));
+
+    let request = srv.get().uri(srv.url("/utf8")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/octet-stream"
+    );
+    assert_eq!(
+        response.headers().get(CONTENT_DISPOSITION).unwrap(),
+        "attachment; filename=\"_____.csv\"; \
+         filename*=UTF-8''%D0%BE%D1%82%D1%87%D0%B5%D1%82.csv"
+    );
+}
+
+#[test]
+fn test_payload_config_buffer_size() {
+    let mut srv = TestServer::new(move || {
+        let mut cfg = PayloadConfig::default();
+        cfg.limit(65_536).buffer_size(1024);
+
+        h1::H1Service::new(
+            App::new()
+                .state(cfg)
+                .resource("/", |r| r.post(|body: Bytes| Response::Ok().body(body))),
+        )
+    });
+
+    let data = STR.repeat(8);
+    let request = srv.post().body(data.clone()).unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from(data));
+
+    let request = srv.post().body(STR.repeat(64)).unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[test]
+fn test_payload_stream_buffer_size() {
+    use futures::{Future, Stream};
+
+    fn chunks(body: Payload) -> impl Future<Item = String, Error = Error> {
+        body.map(|chunk| chunk.len().to_string())
+            .collect()
+            .map(|sizes| sizes.join(","))
+            .from_err()
+    }
+
+    let mut srv = TestServer::new(move || {
+        let mut cfg = PayloadConfig::default();
+        cfg.buffer_size(4096);
+
+        h1::H1Service::new(App::new().state(cfg).resource("/", |r| r.to_async(chunks)))
+    });
+
+    // payload is delivered in chunks of the buffer size
+    let data = STR.repeat(8);
+    let request = srv.post().body(data.clone()).unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(
+        &bytes[..],
+        format!("4096,4096,{}", data.len() - 8192).as_bytes()
+    );
+}
+
+#[test]
+fn test_authorization() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.get(|auth: Authorization| {
+                if auth.is_scheme("basic") {
+                    format!("basic:{}", auth.credentials())
+                } else if auth.is_scheme("bearer") {
+                    format!("bearer:{}", auth.credentials())
+                } else {
+                    format!("{}:{}", auth.scheme(), auth.credentials())
+                }
+            })
+        }))
+    });
+
+    let cases: &[(&str, &[u8])] = &[
+        ("Basic dXNlcjpwYXNz", b"basic:dXNlcjpwYXNz"),
+        ("Bearer abc.def.ghi", b"bearer:abc.def.ghi"),
+        (
+            "Signature keyId=\"k\", sig=\"s\"",
+            b"Signature:keyId=\"k\", sig=\"s\"",
+        ),
+    ];
+
+    for (value, expected) in cases {
+        let request = srv.get().header(AUTHORIZATION, *value).finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(&bytes[..], *expected);
+    }
+
+    let request = srv.get().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn test_resource_routes_order() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.route(|mut r| {
+                r.filter(filter::Header("x-first", "1"));
+                r.method(Method::GET).to(|| "get-first")
+            })
+            .route(|mut r| {
+                r.filter(filter::Header("x-any", "1"));
+                r.to(|| "any")
+            })
+            .get(|| "get")
+            .post(|| "post")
+        }))
+    });
+
+    let cases: &[(Method, &[(&str, &str)], &[u8])] = &[
+        (Method::GET, &[("x-first", "1")], b"get-first"),
+        (
+            Method::GET,
+            &[("x-first", "1"), ("x-any", "1")],
+            b"get-first",
+        ),
+        (Method::GET, &[("x-any", "1")], b"any"),
+        (Method::GET, &[], b"get"),
+        (Method::POST, &[("x-first", "1")], b"post"),
+        (Method::POST, &[("x-any", "1")], b"any"),
+        (Method::POST, &[], b"post"),
+    ];
+
+    for (method, headers, expected) in cases {
+        let mut request = srv.get();
+        request.method(method.clone());
+        for (name, value) in headers.iter() {
+            request.header(*name, *value);
+        }
+        let request = request.finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(&bytes[..], *expected);
+    }
+}
+
+#[test]
+fn test_maintenance_mode() {
+    let mode = middleware::MaintenanceMode::new();
+    let srv_mode = mode.clone();
+
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(
+            App::new()
+                .middleware(
+                    middleware::Maintenance::new(srv_mode.clone())
+                        .allow("/health")
+                        .retry_after(120)
+                        .body("maintenance"),
+                )
+                .resource("/health", |r| r.get(|| Response::Ok()))
+                .resource("/", |r| r.get(|| Response::Ok())),
+        )
+    });
+
+    let request = srv.get().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    mode.enable();
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "120");
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"maintenance"));
+
+    let request = srv.get().uri(srv.url("/health")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    mode.disable();
+
+    let request = srv.get().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+}
+
+#[test]
+fn test_request_target() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(App::new().resource("/{tail:.*}", |r| {
+            r.to(|req: HttpRequest| req.request_target().to_owned())
+        }))
+    });
+
+    let target = "/a%2Fb/./c?sig=a%2Bb&x=1&&y";
+    let request = srv.get().uri(srv.url(target)).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(&bytes[..], target.as_bytes());
+}
+
+#[test]
+fn test_cookie_filter() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(
+            App::new()
+                .resource("/", |r| {
+                    r.route(|mut r| {
+                        r.filter(filter::Cookie("visited", "true"));
+                        r.to(|| "returning")
+                    })
+                    .to(|| "new")
+                })
+                .resource("/present", |r| {
+                    r.route(|mut r| {
+                        r.filter(
+                            filter::Any(filter::CookiePresent("session"))
+                                .or(filter::All(filter::CookiePresent("visited"))
+                                    .and(filter::Header("x-debug", "1"))),
+                        );
+                        r.to(|| "present")
+                    })
+                    .to(|| "absent")
+                }),
+        )
+    });
+
+    let cases: &[(&str, Option<&str>, &[u8])] = &[
+        ("/", Some("visited=true"), b"returning"),
+        ("/", Some("theme=dark; visited=true"), b"returning"),
+        ("/", Some("visited=false"), b"new"),
+        ("/", None, b"new"),
+        ("/present", Some("session=abc"), b"present"),
+        ("/present", Some("visited=true"), b"absent"),
+        ("/present", None, b"absent"),
+    ];
+
+    for (path, cookie, expected) in cases {
+        let mut request = srv.get();
+        request.uri(srv.url(path));
+        if let Some(cookie) = cookie {
+            request.header(COOKIE, *cookie);
+        }
+        let request = request.finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(&bytes[..], *expected);
+    }
+}
+
+#[test]
+fn test_extract_errors_aggregation() {
+    #[derive(Deserialize)]
+    struct Params {
+        id: u32,
+    }
+
+    #[derive(Deserialize)]
+    struct Session {
+        session: String,
+    }
+
+    let mut srv = TestServer::new(move || {
+        let mut cfg = ExtractConfig::default();
+        cfg.aggregate_errors(true);
+
+        h1::H1Service::new(
+            App::new()
+                .state(cfg)
+                .resource("/", |r| {
+                    r.get(|res: Result<(Query<Params>, Cookies<Session>), Error>| {
+                        match res {
+                            Ok((params, cookies)) => {
+                                format!("{}:{}", params.id, cookies.session)
+                            }
+                            Err(e) => format!("{}", e),
+                        }
+                    })
+                })
+                .resource("/cert", |r| {
+                    r.get(|params: Query<Params>, _: ClientCert| {
+                        format!("{}", params.id)
+                    })
+                }),
+        )
+    });
+
+    let request = srv
+        .get()
+        .uri(srv.url("/?id=1"))
+        .header(COOKIE, "session=abc")
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(&bytes[..], b"1:abc");
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(
+        &bytes[..],
+        &b"argument 0: missing field `id`\nargument 1: missing field `session`"[..]
+    );
+
+    // error of the only failed extractor is returned as is
+    let request = srv.get().header(COOKIE, "session=abc").finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(&bytes[..], &b"missing field `id`"[..]);
+
+    // status of the most severe error is kept
+    let request = srv.get().uri(srv.url("/cert")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn test_blob_conditional() {
+    static LOGO: &[u8] = b"<svg></svg>";
+
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.get(|| Blob::new(LOGO, "logo-v1").content_type("image/svg+xml"))
+        }))
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get(ETAG).unwrap(), "\"logo-v1\"");
+    assert_eq!(
+        response.headers().get(CACHE_CONTROL).unwrap(),
+        "public, max-age=86400"
+    );
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "image/svg+xml"
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(&bytes[..], LOGO);
+
+    let request = srv
+        .get()
+        .header(IF_NONE_MATCH, "\"logo-v1\"")
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers().get(ETAG).unwrap(), "\"logo-v1\"");
+    let bytes = srv.execute(response.body()).unwrap();
+    assert!(bytes.is_empty());
+
+    let request = srv
+        .get()
+        .header(IF_NONE_MATCH, "\"logo-v0\"")
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn test_header_limits() {
+    fn many_headers() -> Response {
+        let mut res = Response::Ok();
+        for idx in 0..5 {
+            res.header(format!("x-header-{}", idx).as_str(), "value");
+        }
+        res.finish()
+    }
+
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(
+            App::new()
+                .resource("/error", |r| {
+                    r.middleware(middleware::HeaderLimits::new().max_headers(3))
+                        .get(many_headers)
+                })
+                .resource("/truncate", |r| {
+                    r.middleware(
+                        middleware::HeaderLimits::new().max_headers(3).truncate(),
+                    )
+                    .get(many_headers)
+                })
+                .resource("/size", |r| {
+                    r.middleware(middleware::HeaderLimits::new().max_size(32))
+                        .get(many_headers)
+                }),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/error")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let request = srv.get().uri(srv.url("/truncate")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let count = response
+        .headers()
+        .keys()
+        .filter(|name| name.as_str().starts_with("x-header-"))
+        .count();
+    assert_eq!(count, 3);
+
+    let request = srv.get().uri(srv.url("/size")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+/// Mocked TLS acceptor, wraps accepted connections into streams that
+/// report preset connection info
+struct MockTls<T> {
+    cert: Option<ClientCert>,
+    info: Option<TlsInfo>,
+    _t: PhantomData<T>,
+}
+
+impl<T> MockTls<T> {
+    fn new(cert: Option<ClientCert>) -> Self {
+        MockTls {
+            cert,
+            info: Some(TlsInfo::new("TLSv1.3", "TLS_AES_128_GCM_SHA256")),
+            _t: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for MockTls<T> {
+    fn clone(&self) -> Self {
+        MockTls {
+            cert: self.cert.clone(),
+            info: self.info.clone(),
+            _t: PhantomData,
+        }
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite> NewService for MockTls<T> {
+    type Request = T;
+    type Response = MockTlsStream<T>;
+    type Error = ();
+    type InitError = ();
+    type Service = MockTls<T>;
+    type Future = FutureResult<Self::Service, ()>;
+
+    fn new_service(&self) -> Self::Future {
+        ok(self.clone())
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite> Service for MockTls<T> {
+    type Request = T;
+    type Response = MockTlsStream<T>;
+    type Error = ();
+    type Future = FutureResult<Self::Response, ()>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, io: T) -> Self::Future {
+        ok(MockTlsStream {
+            io,
+            cert: self.cert.clone(),
+            info: self.info.clone(),
+        })
+    }
+}
+
+struct MockTlsStream<T> {
+    io: T,
+    cert: Option<ClientCert>,
+    info: Option<TlsInfo>,
+}
+
+impl<T> TlsStream for MockTlsStream<T> {
+    fn peer_cert(&self) -> Option<ClientCert> {
+        self.cert.clone()
+    }
+
+    fn tls_info(&self) -> Option<TlsInfo> {
+        self.info.clone()
+    }
+
+    fn peer_addr(&self) -> Option<std::net::SocketAddr> {
+        Some(([192, 0, 2, 1], 8080).into())
+    }
+}
+
+impl<T: Read> Read for MockTlsStream<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.io.read(buf)
+    }
+}
+
+impl<T: Write> Write for MockTlsStream<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.io.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+impl<T: AsyncRead> AsyncRead for MockTlsStream<T> {}
+
+impl<T: AsyncWrite> AsyncWrite for MockTlsStream<T> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.io.shutdown()
+    }
+}
+
+#[test]
+fn test_client_cert() {
+    // common name is taken as is, even if it contains separators
+    let cert = ClientCert::new(
+        "CN=client\\, Inc,O=CN=admin",
+        Some("client, Inc".to_owned()),
+        "ab:cd:ef",
+    );
+    let app = || {
+        App::new().resource("/", |r| {
+            r.get(|cert: ClientCert| {
+                format!("{}:{}", cert.common_name().unwrap(), cert.fingerprint())
+            })
+        })
+    };
+
+    let mut srv = TestServer::new(move || {
+        MockTls::new(Some(cert.clone()))
+            .and_then(TlsH1Service::new(app()).map_err(|_| ()))
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(&bytes[..], b"client, Inc:ab:cd:ef");
+
+    // tls connection without client certificate
+    let mut srv = TestServer::new(move || {
+        MockTls::new(None).and_then(TlsH1Service::new(app()).map_err(|_| ()))
+    });
+
+    let request = srv.get().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn test_route_table() {
+    let app = App::new()
+        .resource("/users/{id}", |r| {
+            r.get(|| Response::Ok()).post(|| Response::Ok())
+        })
+        .service("/favicon.ico", Favicon::default());
+    let routes: Vec<_> = app.routes().iter().map(|r| r.to_string()).collect();
+    assert_eq!(
+        routes,
+        vec!["GET /users/{id}", "POST /users/{id}", "* /favicon.ico"]
+    );
+    assert_eq!(app.routes()[0].pattern(), "/users/{id}");
+    assert_eq!(app.routes()[0].methods(), &[Method::GET]);
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .route_table("/_routes")
+                .resource("/users/{id}", |r| r.get(|| Response::Ok())),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/_routes")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(
+        bytes,
+        Bytes::from_static(b"GET /_routes\nGET /users/{id}\n")
+    );
+}
+
+#[test]
+fn test_header_config_max_length() {
+    let mut srv = TestServer::new(move || {
+        let mut cfg = HeaderConfig::default();
+        cfg.max_length(32);
+
+        h1::H1Service::new(App::new().state(cfg).resource("/", |r| {
+            r.get(|auth: Authorization| auth.credentials().to_owned())
+        }))
+    });
+
+    let request = srv
+        .get()
+        .header(AUTHORIZATION, "Bearer short")
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(64)
+        .collect();
+    let request = srv
+        .get()
+        .header(AUTHORIZATION, format!("Bearer {}", token))
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn test_deferred_body() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let calls2 = calls.clone();
+    let mut srv = TestServer::new(move || {
+        let calls = calls2.clone();
+        let report = move || {
+            let calls = calls.clone();
+            Deferred::new(move || {
+                calls.fetch_add(1, Ordering::SeqCst);
+                STR
+            })
+        };
+        h1::H1Service::new(
+            App::new().resource("/", |r| r.get(report.clone()).head(report)),
+        )
+    });
+
+    let request = srv.get().method(Method::HEAD).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(STR.as_ref()));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_envelope() {
+    #[derive(serde_derive::Serialize)]
+    struct User {
+        name: String,
+    }
+
+    fn user(name: Path<String>) -> Envelope<User> {
+        if name.as_str() == "missing" {
+            Envelope::Error(EnvelopeError::new(StatusCode::NOT_FOUND, "User not found"))
+        } else {
+            Envelope::Data(User {
+                name: name.into_inner(),
+            })
+        }
+    }
+
+    fn fails() -> Result<&'static str, EnvelopeError> {
+        Err(EnvelopeError::new(StatusCode::BAD_REQUEST, "Bad input"))
+    }
+
+    fn fails_with_config(
+        cfg: State<EnvelopeConfig>,
+    ) -> Result<&'static str, EnvelopeError> {
+        Err(cfg.error(StatusCode::BAD_REQUEST, "Bad input"))
+    }
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .resource("/users/{name}", |r| r.get(user))
+                .resource("/fails", |r| r.get(fails)),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/users/bob")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(value, serde_json::json!({"data": {"name": "bob"}}));
+
+    let request = srv.get().uri(srv.url("/users/missing")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let bytes = srv.execute(response.body()).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!({"error": {"status": 404, "message": "User not found"}})
+    );
+
+    let request = srv.get().uri(srv.url("/fails")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let bytes = srv.execute(response.body()).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!({"error": {"status": 400, "message": "Bad input"}})
+    );
+
+    // app-wide configuration
+    let mut srv = TestServer::new(|| {
+        let mut cfg = EnvelopeConfig::default();
+        cfg.data_key("result")
+            .error_key("failure")
+            .error_status(false);
+
+        h1::H1Service::new(
+            App::new()
+                .state(cfg)
+                .resource("/users/{name}", |r| r.get(user))
+                .resource("/fails", |r| r.get(fails_with_config)),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/users/bob")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    let bytes = srv.execute(response.body()).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(value, serde_json::json!({"result": {"name": "bob"}}));
+
+    let request = srv.get().uri(srv.url("/users/missing")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let bytes = srv.execute(response.body()).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!({"failure": {"message": "User not found"}})
+    );
+
+    let request = srv.get().uri(srv.url("/fails")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let bytes = srv.execute(response.body()).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!({"failure": {"message": "Bad input"}})
+    );
+}
+
+#[test]
+fn test_qs_query() {
+    #[derive(Deserialize)]
+    struct Filter {
+        name: String,
+        age: u32,
+    }
+
+    #[derive(Deserialize)]
+    struct Search {
+        filter: Filter,
+        ids: Vec<u32>,
+    }
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(App::new().resource("/users", |r| {
+            r.get(|search: QsQuery<Search>| {
+                format!(
+                    "{}:{}:{:?}",
+                    search.filter.name, search.filter.age, search.ids
+                )
+            })
+        }))
+    });
+
+    let request = srv
+        .get()
+        .uri(srv.url("/users?filter[name]=x&filter[age]=30&ids[0]=1&ids[1]=2"))
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"x:30:[1, 2]"));
+
+    // percent-encoded brackets
+    let request = srv
+        .get()
+        .uri(srv.url("/users?filter%5Bname%5D=y&filter%5Bage%5D=5&ids%5B0%5D=3"))
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"y:5:[3]"));
+
+    let request = srv
+        .get()
+        .uri(srv.url("/users?filter[name]=x&filter[age]=old"))
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn test_require_headers() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .middleware(
+                    middleware::RequireHeaders::new()
+                        .header("X-Tenant-Id")
+                        .header("X-Request-Id")
+                        .header("X-Client"),
+                )
+                .resource("/", |r| r.get(|| Response::Ok())),
+        )
+    });
+
+    let request = srv
+        .get()
+        .header("x-tenant-id", "1")
+        .header("x-request-id", "2")
+        .header("x-client", "test")
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    let request = srv.get().header("x-client", "test").finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(
+        bytes,
+        Bytes::from_static(b"Missing required headers: x-tenant-id, x-request-id")
+    );
+}
+
+#[test]
+fn test_route_wrap() {
+    use actix_service::{IntoNewTransform, Service, Transform};
+    use actix_web2::middleware::MiddlewareFactory;
+    use actix_web2::{ServiceRequest, ServiceResponse};
+    use futures::{Future, Poll};
+
+    /// Appends its name to `x-trace` response header
+    #[derive(Clone)]
+    struct Trace(&'static str);
+
+    impl<S, P> IntoNewTransform<MiddlewareFactory<Trace, S>, S> for Trace
+    where
+        S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+        S::Future: 'static,
+    {
+        fn into_new_transform(self) -> MiddlewareFactory<Trace, S> {
+            MiddlewareFactory::new(self)
+        }
+    }
+
+    impl<S, P> Transform<S> for Trace
+    where
+        S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+        S::Future: 'static,
+    {
+        type Request = ServiceRequest<P>;
+        type Response = ServiceResponse;
+        type Error = S::Error;
+        type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            Ok(futures::Async::Ready(()))
+        }
+
+        fn call(&mut self, req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
+            let name = self.0;
+            Box::new(srv.call(req).map(move |mut res| {
+                let trace = match res.headers().get("x-trace") {
+                    Some(val) => format!("{},{}", val.to_str().unwrap(), name),
+                    None => name.to_owned(),
+                };
+                res.headers_mut()
+                    .insert("x-trace", HeaderValue::from_str(&trace).unwrap());
+                res
+            }))
+        }
+    }
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.route(|r| {
+                r.method(Method::GET)
+                    .wrap(Trace("route1"))
+                    .wrap(Trace("route2"))
+                    .to(|| Response::Ok())
+            })
+            .route(|r| r.method(Method::POST).to(|| Response::Ok()))
+            .middleware(Trace("resource"))
+        }))
+    });
+
+    let request = srv.get().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers().get("x-trace").unwrap(),
+        "route1,route2,resource"
+    );
+
+    let request = srv.post().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get("x-trace").unwrap(), "resource");
+}
+
+#[test]
+fn test_forwarded() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.get(|forwarded: Forwarded| {
+                forwarded
+                    .hops()
+                    .iter()
+                    .map(|hop| {
+                        format!(
+                            "{}|{}|{}|{}",
+                            hop.forwarded_for().unwrap_or("-"),
+                            hop.by().unwrap_or("-"),
+                            hop.host().unwrap_or("-"),
+                            hop.proto().unwrap_or("-")
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+        }))
+    });
+
+    let request = srv
+        .get()
+        .header(
+            "forwarded",
+            "for=192.0.2.43;proto=https;host=example.com, \
+             for=\"[2001:db8:cafe::17]:4711\";by=\"10.0.0.1\"",
+        )
+        .header("forwarded", "for=\"_hidden, \\\"quoted\\\"\";proto=http")
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(
+        bytes,
+        Bytes::from_static(
+            b"192.0.2.43|-|example.com|https\n\
+              [2001:db8:cafe::17]:4711|10.0.0.1|-|-\n\
+              _hidden, \"quoted\"|-|-|http"
+        )
+    );
+
+    let request = srv.get().header("forwarded", "for").finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let request = srv.get().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn test_deadline_partial_result() {
+    use std::time::Duration;
+
+    use futures::future::{self, Either};
+    use futures::{stream, Future, Stream};
+
+    fn aggregate(fast: bool) -> impl Responder {
+        let progress = Progress::new(Vec::new());
+        let collected = progress.clone();
+
+        let work = stream::iter_ok::<_, Error>(vec![1, 2, 3])
+            .for_each(move |item| {
+                collected.update(|items| items.push(item));
+                Ok(())
+            })
+            .and_then(move |_| {
+                if fast {
+                    Either::A(future::ok(Response::Ok().body("complete")))
+                } else {
+                    // slow source, never completes
+                    Either::B(future::empty())
+                }
+            });
+
+        Deadline::new(
+            work,
+            Duration::from_millis(100),
+            progress,
+            |items: &Vec<u32>| Response::Ok().body(format!("partial:{:?}", items)),
+        )
+    }
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .resource("/slow", |r| r.get(|| aggregate(false)))
+                .resource("/fast", |r| r.get(|| aggregate(true))),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/slow")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"partial:[1, 2, 3]"));
+
+    let request = srv.get().uri(srv.url("/fast")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"complete"));
+}
+
+#[test]
+fn test_problem() {
+    #[derive(serde_derive::Serialize)]
+    struct User {
+        name: String,
+    }
+
+    fn user(name: Path<String>) -> Result<Json<User>, Problem> {
+        if name.as_str() == "missing" {
+            Err(Problem::new(StatusCode::NOT_FOUND)
+                .type_uri("https://example.com/probs/no-user")
+                .detail("User missing does not exist")
+                .instance("/users/missing"))
+        } else {
+            Ok(Json(User {
+                name: name.into_inner(),
+            }))
+        }
+    }
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(App::new().resource("/users/{name}", |r| r.get(user)))
+    });
+
+    let request = srv.get().uri(srv.url("/users/bob")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/json"
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(value, serde_json::json!({"name": "bob"}));
+
+    let request = srv.get().uri(srv.url("/users/missing")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/problem+json"
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!({
+            "type": "https://example.com/probs/no-user",
+            "title": "Not Found",
+            "status": 404,
+            "detail": "User missing does not exist",
+            "instance": "/users/missing",
+        })
+    );
+}
+
+#[test]
+fn test_duplicate_host() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.get(|req: HttpRequest| {
+                req.headers()
+                    .get("host")
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .to_owned()
+            })
+        }))
+    });
+
+    let request = srv
+        .get()
+        .header("host", "tenant1.example.com")
+        .header("host", "tenant2.example.com")
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(App::new().duplicate_host(DuplicateHost::UseFirst).resource(
+            "/",
+            |r| {
+                r.get(|req: HttpRequest| {
+                    req.headers()
+                        .get("host")
+                        .unwrap()
+                        .to_str()
+                        .unwrap()
+                        .to_owned()
+                })
+            },
+        ))
+    });
+
+    let request = srv
+        .get()
+        .header("host", "tenant1.example.com")
+        .header("host", "tenant2.example.com")
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"tenant1.example.com"));
+}
+
+#[cfg(feature = "uuid")]
+#[test]
+fn test_path_uuid() {
+    use actix_http::error::ErrorUnprocessableEntity;
+    use actix_web2::dev::PathConfig;
+    use actix_web2::PathUuid;
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new().resource("/users/{id}", |r| r.get(|id: PathUuid| id.to_string())),
+        )
+    });
+
+    let request = srv
+        .get()
+        .uri(srv.url("/users/936da01f-9abd-4d9d-80c7-02af85c822a8"))
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(
+        bytes,
+        Bytes::from_static(b"936da01f-9abd-4d9d-80c7-02af85c822a8")
+    );
+
+    let request = srv
+        .get()
+        .uri(srv.url("/users/not-a-uuid"))
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let bytes = srv.execute(response.body()).unwrap();
+    assert!(bytes.starts_with(b"Invalid UUID \"not-a-uuid\" in path: "));
+
+    // custom error handler
+    let mut srv = TestServer::new(|| {
+        let mut cfg = PathConfig::default();
+        cfg.error_handler(|_, _| ErrorUnprocessableEntity("Invalid id"));
+
+        h1::H1Service::new(
+            App::new()
+                .state(cfg)
+                .resource("/users/{id}", |r| r.get(|id: PathUuid| id.to_string())),
+        )
+    });
+
+    let request = srv
+        .get()
+        .uri(srv.url("/users/not-a-uuid"))
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[test]
+fn test_negotiate() {
+    fn index() -> Negotiate<Vec<&'static str>> {
+        Negotiate::new(vec!["a", "b"])
+            .format(mime::TEXT_PLAIN, |v| Ok::<_, Error>(v.join(",")))
+            .format(mime::APPLICATION_JSON, |v| serde_json::to_string(v))
+            .format(mime::TEXT_HTML, |v| {
+                Ok::<_, Error>(format!("<p>{}</p>", v.join(",")))
+            })
+            .default(mime::APPLICATION_JSON)
+    }
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(App::new().resource("/", |r| r.get(index)))
+    });
+
+    let cases: &[(Option<&str>, &str, &[u8])] = &[
+        (None, "application/json", b"[\"a\",\"b\"]"),
+        (Some("*/*"), "application/json", b"[\"a\",\"b\"]"),
+        (Some("text/html"), "text/html", b"<p>a,b</p>"),
+        (Some("text/*"), "text/plain", b"a,b"),
+        (
+            Some("text/html;q=0.5, text/plain;q=0.8, */*;q=0.1"),
+            "text/plain",
+            b"a,b",
+        ),
+        (
+            Some("text/*;q=0.9, text/plain;q=0, application/json;q=0.9"),
+            "application/json",
+            b"[\"a\",\"b\"]",
+        ),
+    ];
+
+    for (accept, content_type, body) in cases {
+        let mut request = srv.get();
+        if let Some(accept) = accept {
+            request.header("accept", *accept);
+        }
+        let mut response = srv.send_request(request.finish().unwrap()).unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), content_type);
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(&bytes[..], *body);
+    }
+
+    let request = srv.get().header("accept", "image/png").finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+    assert_eq!(response.headers().get(VARY).unwrap(), "Accept");
+}
+
+#[test]
+fn test_req_data() {
+    #[derive(Clone)]
+    struct Region(&'static str);
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .extension(Region("eu-west"))
+                .resource("/", |r| r.get(|r: ReqData<Region>| r.into_inner().0)),
+        )
+    });
+
+    let request = srv.get().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.block_on(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"eu-west"));
+}
+
+#[test]
+fn test_json_expect_kind() {
+    let mut srv = TestServer::new(|| {
+        let mut cfg = JsonConfig::default();
+        cfg.expect_kind(JsonKind::Object);
+
+        h1::H1Service::new(App::new().state(cfg).resource("/", |r| {
+            r.post(|data: Json<HashMap<String, u32>>| format!("{}", data.len()))
+        }))
+    });
+
+    let request = srv
+        .post()
+        .header(CONTENT_TYPE, "application/json")
+        .body("  {\"a\": 1, \"b\": 2}")
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"2"));
+
+    let request = srv
+        .post()
+        .header(CONTENT_TYPE, "application/json")
+        .body("\n [{\"a\": 1}, {\"b\": 2}]")
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn test_subtree_resource() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .resource("/proxy/{tail:*}", |r| {
+                    r.get(|req: HttpRequest| {
+                        format!("tail:{}", req.match_info().get("tail").unwrap())
+                    })
+                })
+                .resource("/proxy/status", |r| r.get(|| "status")),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/proxy/a/b")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"tail:a/b"));
+
+    let request = srv.get().uri(srv.url("/proxy/status")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"status"));
+}
+
+#[test]
+fn test_route_config() {
+    use actix_http::error::InternalError;
+
+    let mut srv = TestServer::new(|| {
+        let mut text = JsonConfig::default();
+        text.error_handler(|err, _| {
+            InternalError::from_response(
+                err,
+                Response::BadRequest().body("invalid payload"),
+            )
+            .into()
+        });
+        let mut json = JsonConfig::default();
+        json.error_handler(|err, _| {
+            InternalError::from_response(
+                err,
+                Response::BadRequest()
+                    .content_type("application/json")
+                    .body("{\"error\":\"invalid payload\"}"),
+            )
+            .into()
+        });
+
+        h1::H1Service::new(
+            App::new()
+                .resource("/text", |r| {
+                    r.route(|r| {
+                        r.method(Method::POST).config(text).to(
+                            |data: Json<HashMap<String, u32>>| format!("{}", data.len()),
+                        )
+                    })
+                })
+                .resource("/json", |r| {
+                    r.route(|r| {
+                        r.method(Method::POST).config(json).to(
+                            |data: Json<HashMap<String, u32>>| format!("{}", data.len()),
+                        )
+                    })
+                }),
+        )
+    });
+
+    let request = srv
+        .post()
+        .uri(srv.url("/text"))
+        .header(CONTENT_TYPE, "application/json")
+        .body("{\"a\": \"b\"}")
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"invalid payload"));
+
+    let request = srv
+        .post()
+        .uri(srv.url("/json"))
+        .header(CONTENT_TYPE, "application/json")
+        .body("{\"a\": \"b\"}")
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(
+        bytes,
+        Bytes::from_static(b"{\"error\":\"invalid payload\"}")
+    );
+}
+
+#[test]
+fn test_request_builder() {
+    let mut req = TestRequest::with_uri("/users/42")
+        .header(AUTHORIZATION, "Bearer token")
+        .state(10usize)
+        .param("id", "42")
+        .finish();
+
+    let state = test::block_on(State::<usize>::from_request(&mut req)).unwrap();
+    assert_eq!(*state, 10);
+    let id = test::block_on(Path::<u32>::from_request(&mut req)).unwrap();
+    assert_eq!(*id, 42);
+    let auth = test::block_on(Authorization::from_request(&mut req)).unwrap();
+    assert_eq!(auth.credentials(), "token");
+
+    let mut req = TestRequest::default().finish();
+    assert!(test::block_on(State::<usize>::from_request(&mut req)).is_err());
+}
+
+#[test]
+fn test_default_headers_server_date() {
+    use actix_http::http::header::{HttpDate, DATE, SERVER};
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .middleware(
+                    middleware::DefaultHeaders::new()
+                        .server("test-server")
+                        .date(),
+                )
+                .resource("/", |r| {
+                    r.get(|| Response::Ok().header(SERVER, "handler").finish())
+                }),
+        )
+    });
+
+    let request = srv.get().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get(SERVER).unwrap(), "test-server");
+    let date = response.headers().get(DATE).unwrap().to_str().unwrap();
+    assert!(date.parse::<HttpDate>().is_ok());
+    assert!(date.ends_with(" GMT"));
+}
+
+#[test]
+fn test_multi_source() {
+    let mut srv = TestServer::new(|| {
+        let mut cfg = MultiSourceConfig::<String>::default();
+        cfg.header("X-Api-Key").query("api_key").cookie("api_key");
+
+        h1::H1Service::new(
+            App::new()
+                .state(cfg)
+                .resource("/", |r| r.get(|key: MultiSource<String>| key.into_inner())),
+        )
+    });
+
+    // header
+    let request = srv.get().header("X-Api-Key", "header").finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"header"));
+
+    // query
+    let request = srv.get().uri(srv.url("/?api_key=query")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"query"));
+
+    // cookie
+    let request = srv.get().header(COOKIE, "api_key=cookie").finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"cookie"));
+
+    // precedence
+    let request = srv
+        .get()
+        .uri(srv.url("/?api_key=query"))
+        .header("X-Api-Key", "header")
+        .header(COOKIE, "api_key=cookie")
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"header"));
+
+    let request = srv
+        .get()
+        .uri(srv.url("/?api_key=query"))
+        .header(COOKIE, "api_key=cookie")
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"query"));
+
+    // missing
+    let request = srv.get().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn test_not_found_hints() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .not_found_hints(true)
+                .resource("/users/{id}/posts", |r| r.get(|| "posts"))
+                .resource("/status", |r| r.get(|| "ok")),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/users/42/post")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let bytes = srv.execute(response.body()).unwrap();
+    let body = String::from_utf8(bytes.to_vec()).unwrap();
+    assert!(body.contains("/users/{id}/posts"));
+    assert!(!body.contains("/status"));
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new().resource("/users/{id}/posts", |r| r.get(|| "posts")),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/users/42/post")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let bytes = srv.execute(response.body()).unwrap();
+    assert!(bytes.is_empty());
+}
+
+#[test]
+fn test_route_budget() {
+    use std::time::Duration;
+
+    use actix_service::{IntoNewTransform, Service, Transform};
+    use actix_web2::middleware::MiddlewareFactory;
+    use actix_web2::{ServiceRequest, ServiceResponse};
+    use futures::{Future, Poll};
+
+    /// Sets `x-budget-exceeded` response header
+    #[derive(Clone)]
+    struct BudgetFlag;
+
+    impl<S, P> IntoNewTransform<MiddlewareFactory<BudgetFlag, S>, S> for BudgetFlag
+    where
+        S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+        S::Future: 'static,
+    {
+        fn into_new_transform(self) -> MiddlewareFactory<BudgetFlag, S> {
+            MiddlewareFactory::new(self)
+        }
+    }
+
+    impl<S, P> Transform<S> for BudgetFlag
+    where
+        S: Service<Request = ServiceRequest<P>, Response = ServiceResponse>,
+        S::Future: 'static,
+    {
+        type Request = ServiceRequest<P>;
+        type Response = ServiceResponse;
+        type Error = S::Error;
+        type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            Ok(futures::Async::Ready(()))
+        }
+
+        fn call(&mut self, req: ServiceRequest<P>, srv: &mut S) -> Self::Future {
+            Box::new(srv.call(req).map(|mut res| {
+                if res.request().budget_exceeded().is_some() {
+                    res.headers_mut()
+                        .insert("x-budget-exceeded", HeaderValue::from_static("1"));
+                }
+                res
+            }))
+        }
+    }
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .middleware(BudgetFlag)
+                .resource("/slow", |r| {
+                    r.route(|r| {
+                        r.budget(Duration::from_millis(5)).to(|| {
+                            std::thread::sleep(Duration::from_millis(50));
+                            "slow"
+                        })
+                    })
+                })
+                .resource("/fast", |r| {
+                    r.route(|r| r.budget(Duration::from_secs(10)).to(|| "fast"))
+                }),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/slow")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get("x-budget-exceeded").unwrap(), "1");
+
+    let request = srv.get().uri(srv.url("/fast")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert!(response.headers().get("x-budget-exceeded").is_none());
+}
+
+#[test]
+fn test_signed_filter() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(App::new().resource("/hook", |r| {
+            r.route(|mut r| {
+                r.filter(filter::Signed("secret", "X-Signature"));
+                r.to(|| "valid")
+            })
+            .to(|| "invalid")
+        }))
+    });
+
+    let mut mac = Hmac::<Sha256>::new_varkey(b"secret").unwrap();
+    mac.input(b"/hook?id=1");
+    let signature: String = mac
+        .result()
+        .code()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    let request = srv
+        .get()
+        .uri(srv.url("/hook?id=1"))
+        .header("X-Signature", format!("sha256={}", signature))
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"valid"));
+
+    // signature does not cover modified query
+    let request = srv
+        .get()
+        .uri(srv.url("/hook?id=2"))
+        .header("X-Signature", format!("sha256={}", signature))
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"invalid"));
+
+    let request = srv.get().uri(srv.url("/hook?id=1")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"invalid"));
+}
+
+#[test]
+fn test_encoded_body() {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    let mut e = GzEncoder::new(Vec::new(), Compression::default());
+    e.write_all(STR.as_ref()).unwrap();
+    let enc = Bytes::from(e.finish().unwrap());
+    let srv_enc = enc.clone();
+
+    let mut srv = TestServer::new(move || {
+        let enc = srv_enc.clone();
+        h1::H1Service::new(
+            App::new()
+                .middleware(middleware::Compress::new(ContentEncoding::Gzip))
+                .resource("/", |r| {
+                    r.get(move || {
+                        Encoded::new(ContentEncoding::Gzip, enc.clone())
+                            .content_type("text/plain")
+                    })
+                }),
+        )
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+
+    // body is not compressed twice
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, enc);
+
+    let mut e = GzDecoder::new(&bytes[..]);
+    let mut dec = Vec::new();
+    e.read_to_end(&mut dec).unwrap();
+    assert_eq!(Bytes::from(dec), Bytes::from_static(STR.as_ref()));
+}
+
+#[test]
+fn test_error_renderer() {
+    let mut srv = TestServer::new(move || {
+        let mut cfg = PayloadConfig::default();
+        cfg.limit(STR.len() * 2);
+        let scopes = RequireScopesConfig::new(|_| None);
+
+        h1::H1Service::new(
+            App::new()
+                .state(cfg)
+                .state(scopes)
+                .error_renderer(|res, _| {
+                    Response::build(res.status())
+                        .content_type("application/json")
+                        .body(format!("{{\"status\":{}}}", res.status().as_u16()))
+                })
+                .resource("/", |r| r.post(|body: Bytes| Response::Ok().body(body)))
+                .resource("/orders", |r| {
+                    r.get(|token: RequireScopes| token.token().to_owned())
+                }),
+        )
+    });
+
+    let request = srv.post().body(STR).unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(STR.as_ref()));
+
+    let request = srv.post().body(STR.repeat(64)).unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/json"
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"{\"status\":413}"));
+
+    // headers of the extraction error are kept
+    let request = srv.get().uri(srv.url("/orders")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers().get(WWW_AUTHENTICATE).unwrap(), "Bearer");
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/json"
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"{\"status\":401}"));
+}
+
+#[test]
+fn test_tls_info() {
+    let app = || {
+        App::new()
+            .resource("/", |r| {
+                r.get(|tls: TlsInfo| format!("{} {}", tls.version(), tls.cipher()))
+            })
+            .resource("/any", |r| {
+                r.get(|tls: Option<TlsInfo>| match tls {
+                    Some(tls) => tls.version().to_owned(),
+                    None => "plaintext".to_owned(),
+                })
+            })
+    };
+
+    let mut srv = TestServer::new(move || {
+        MockTls::new(None).and_then(TlsH1Service::new(app()).map_err(|_| ()))
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(&bytes[..], b"TLSv1.3 TLS_AES_128_GCM_SHA256");
+
+    // plaintext connection
+    let mut srv = TestServer::new(move || h1::H1Service::new(app()));
+
+    let request = srv.get().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let request = srv.get().uri(srv.url("/any")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(&bytes[..], b"plaintext");
+}
+
+#[test]
+fn test_connection_info_peer_addr() {
+    let mut srv = TestServer::new(move || {
+        MockTls::new(None).and_then(
+            TlsH1Service::new(App::new().resource("/", |r| {
+                r.get(|req: HttpRequest| {
+                    req.connection_info().remote().unwrap_or("-").to_owned()
+                })
+            }))
+            .map_err(|_| ()),
+        )
+    });
+
+    // peer address is used without proxy headers
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(&bytes[..], b"192.0.2.1");
+
+    let request = srv
+        .get()
+        .header("x-forwarded-for", "192.0.2.60")
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(&bytes[..], b"192.0.2.60");
+}
+
+#[test]
+fn test_blob_ranges() {
+    static LOGO: &[u8] = b"<svg></svg>";
+
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(
+            App::new()
+                .resource("/", |r| r.get(|| Blob::new(LOGO, "logo-v1")))
+                .resource("/download", |r| r.get(|| Attachment::new(LOGO))),
+        )
+    });
+
+    let request = srv.get().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get(ACCEPT_RANGES).unwrap(), "bytes");
+
+    let request = srv.get().header(RANGE, "bytes=1-3").finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        response.headers().get(CONTENT_RANGE).unwrap(),
+        "bytes 1-3/11"
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(&bytes[..], b"svg");
+
+    let request = srv.get().header(RANGE, "bytes=20-30").finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(response.headers().get(CONTENT_RANGE).unwrap(), "bytes */11");
+
+    let request = srv.get().uri(srv.url("/download")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.headers().get(ACCEPT_RANGES).unwrap(), "none");
+}
+
+#[test]
+fn test_query_max_params() {
+    let mut srv = TestServer::new(|| {
+        let mut cfg = QueryConfig::default();
+        cfg.max_params(3);
+
+        h1::H1Service::new(App::new().state(cfg).resource("/", |r| {
+            r.get(|q: Query<HashMap<String, String>>| format!("{}", q.len()))
+        }))
+    });
+
+    let request = srv.get().uri(srv.url("/?a=1&b=2&c=3")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"3"));
+
+    let request = srv
+        .get()
+        .uri(srv.url("/?a=1&b=2&c=3&d=4"))
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn test_header_map_responder() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.get(|| {
+                let mut headers = HeaderMap::new();
+                headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+                headers.append(VARY, HeaderValue::from_static("accept"));
+                headers.append(VARY, HeaderValue::from_static("cookie"));
+                (headers, "welcome")
+            })
+        }))
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get(CACHE_CONTROL).unwrap(), "no-cache");
+    let vary: Vec<_> = response.headers().get_all(VARY).iter().collect();
+    assert_eq!(vary, vec!["accept", "cookie"]);
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "text/plain; charset=utf-8"
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"welcome"));
+}
+
+#[test]
+fn test_payload_content_length_mismatch() {
+    let mut req = TestRequest::with_header(CONTENT_LENGTH, "5")
+        .set_payload("hello")
+        .finish();
+    let body = test::block_on(Bytes::from_request(&mut req)).unwrap();
+    assert_eq!(body, Bytes::from_static(b"hello"));
+
+    // under-length body
+    let mut req = TestRequest::with_header(CONTENT_LENGTH, "100")
+        .set_payload("hello")
+        .finish();
+    let e = test::block_on(Bytes::from_request(&mut req)).err().unwrap();
+    let resp: Response = e.into();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // over-length body
+    let mut req = TestRequest::with_header(CONTENT_LENGTH, "2")
+        .set_payload("hello")
+        .finish();
+    let e = test::block_on(String::from_request(&mut req))
+        .err()
+        .unwrap();
+    assert_eq!(
+        e.to_string(),
+        "Payload is longer than Content-Length, expected 2 bytes, got 5"
+    );
+    let resp: Response = e.into();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // json and form bodies
+    let mut req = TestRequest::with_header(CONTENT_LENGTH, "100")
+        .header(CONTENT_TYPE, "application/json")
+        .set_payload("{}")
+        .finish();
+    let e = test::block_on(Json::<HashMap<String, String>>::from_request(&mut req))
+        .err()
+        .unwrap();
+    assert_eq!(
+        e.to_string(),
+        "Payload is shorter than Content-Length, expected 100 bytes, got 2"
+    );
+
+    let mut req = TestRequest::with_header(CONTENT_LENGTH, "2")
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .set_payload("a=b&c=d")
+        .finish();
+    let e = test::block_on(Form::<HashMap<String, String>>::from_request(&mut req))
+        .err()
+        .unwrap();
+    assert_eq!(
+        e.to_string(),
+        "Payload is longer than Content-Length, expected 2 bytes, got 7"
+    );
+
+    let mut req = TestRequest::with_header(CONTENT_LENGTH, "7")
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .set_payload("a=b&c=d")
+        .finish();
+    let form =
+        test::block_on(Form::<HashMap<String, String>>::from_request(&mut req)).unwrap();
+    assert_eq!(form.get("c").unwrap(), "d");
+}
+
+#[test]
+fn test_form_config() {
+    let mut srv = TestServer::new(|| {
+        let mut limited = FormConfig::default();
+        limited.limit(8);
+        let mut conflict = FormConfig::default();
+        conflict.error_handler(|err, _| {
+            InternalError::from_response(err, Response::Conflict().finish()).into()
+        });
+
+        h1::H1Service::new(
+            App::new()
+                .state(limited)
+                .resource("/", |r| {
+                    r.post(|form: Form<HashMap<String, String>>| {
+                        format!("{}", form.len())
+                    })
+                })
+                .resource("/route", |r| {
+                    r.route(|r| {
+                        r.method(Method::POST).config(conflict).to(
+                            |form: Form<HashMap<String, String>>| {
+                                format!("{}", form.len())
+                            },
+                        )
+                    })
+                }),
+        )
+    });
+
+    let request = srv
+        .post()
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body("a=b")
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    // limit registered as application state
+    let request = srv
+        .post()
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body("a=b&c=d&e=f")
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // route configuration overrides application state
+    let request = srv
+        .post()
+        .uri(srv.url("/route"))
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body("a=b&c=d&e=f")
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    let request = srv
+        .post()
+        .uri(srv.url("/route"))
+        .header(CONTENT_TYPE, "text/plain")
+        .body("a=b")
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[test]
+fn test_strict_headers() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .strict_headers(true)
+                .resource("/", |r| r.get(|| Response::Ok())),
+        )
+    });
+
+    let request = srv.get().header("x-name", "value").finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    let request = srv
+        .get()
+        .header("x-name", HeaderValue::from_bytes(b"caf\xe9").unwrap())
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let request = srv.get().header("x_name", "value").finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(App::new().resource("/", |r| r.get(|| Response::Ok())))
+    });
+
+    let request = srv
+        .get()
+        .header("x-name", HeaderValue::from_bytes(b"caf\xe9").unwrap())
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    let request = srv.get().header("x_name", "value").finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+}
+
+#[test]
+fn test_valid_extractor() {
+    use std::convert::TryFrom;
+
+    #[derive(Deserialize)]
+    struct UserDto {
+        email: String,
+    }
+
+    struct User {
+        email: String,
+    }
+
+    impl TryFrom<UserDto> for User {
+        type Error = &'static str;
+
+        fn try_from(dto: UserDto) -> Result<User, Self::Error> {
+            if dto.email.contains('@') {
+                Ok(User { email: dto.email })
+            } else {
+                Err("Invalid email")
+            }
+        }
+    }
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .resource("/", |r| {
+                    r.post(|user: Valid<Json<UserDto>, User>| user.into_inner().email)
+                })
+                .resource("/form", |r| {
+                    r.post(|user: Valid<Form<UserDto>, User>| user.into_inner().email)
+                }),
+        )
+    });
+
+    let request = srv
+        .post()
+        .header(CONTENT_TYPE, "application/json")
+        .body("{\"email\":\"user@example.com\"}")
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"user@example.com"));
+
+    let request = srv
+        .post()
+        .header(CONTENT_TYPE, "application/json")
+        .body("{\"email\":\"user\"}")
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"Invalid email"));
+
+    let request = srv
+        .post()
+        .uri(srv.url("/form"))
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body("email=user%40example.com")
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"user@example.com"));
+
+    let request = srv
+        .post()
+        .uri(srv.url("/form"))
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body("email=user")
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[test]
+fn test_resource_default_headers() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .resource("/account", |r| {
+                    r.middleware(
+                        middleware::DefaultHeaders::new()
+                            .header(CACHE_CONTROL, "no-store"),
+                    )
+                    .get(|| Response::Ok())
+                })
+                .resource("/", |r| r.get(|| Response::Ok())),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/account")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get(CACHE_CONTROL).unwrap(), "no-store");
+
+    let request = srv.get().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert!(response.headers().get(CACHE_CONTROL).is_none());
+}
+
+#[test]
+fn test_boxed_stream_responder() {
+    fn chunks() -> Box<futures::Stream<Item = Bytes, Error = Error>> {
+        Box::new(futures::stream::iter_ok(vec![
+            Bytes::from_static(b"chunk1"),
+            Bytes::from_static(b"chunk2"),
+        ]))
+    }
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(App::new().resource("/", |r| r.get(chunks)))
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/octet-stream"
+    );
+    assert_eq!(
+        response.headers().get(TRANSFER_ENCODING).unwrap(),
+        "chunked"
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"chunk1chunk2"));
+}
+
+#[test]
+fn test_auto_options() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .resource("/", |r| r.get(|| Response::Ok()).post(|| Response::Ok()))
+                .resource("/manual", |r| r.auto_options(false).get(|| Response::Ok())),
+        )
+    });
+
+    let request = srv.get().method(Method::OPTIONS).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get(ALLOW).unwrap(), "GET, POST, OPTIONS");
+
+    let request = srv
+        .get()
+        .uri(srv.url("/manual"))
+        .method(Method::OPTIONS)
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.headers().get(ALLOW).unwrap(), "GET");
+}
+
+#[test]
+fn test_json_value_limit() {
+    let mut cfg = JsonConfig::default();
+    cfg.limit(16);
+
+    let mut req = TestRequest::with_header(CONTENT_TYPE, "application/json")
+        .state(cfg.clone())
+        .set_payload("{\"name\":\"bob\"}")
+        .finish();
+    let value =
+        test::block_on(Json::<serde_json::Value>::from_request(&mut req)).unwrap();
+    assert_eq!(value.into_inner(), serde_json::json!({"name": "bob"}));
+
+    // payload without content-length is rejected while it is streamed
+    let mut req = TestRequest::with_header(CONTENT_TYPE, "application/json")
+        .state(cfg.clone())
+        .set_payload("{\"name\":\"bob\",\"data\":[1,2,3,4,5,6,7,8]}")
+        .finish();
+    let e = test::block_on(Json::<serde_json::Value>::from_request(&mut req))
+        .err()
+        .unwrap();
+    let resp: Response = e.into();
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let mut req = TestRequest::with_header(CONTENT_TYPE, "application/json")
+        .header(CONTENT_LENGTH, "1024")
+        .state(cfg)
+        .finish();
+    let e = test::block_on(Json::<serde_json::Value>::from_request(&mut req))
+        .err()
+        .unwrap();
+    let resp: Response = e.into();
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[test]
+fn test_on_complete() {
+    let completed = Arc::new(Mutex::new(Vec::new()));
+
+    let completed2 = completed.clone();
+    let mut srv = TestServer::new(move || {
+        let completed = completed2.clone();
+        h1::H1Service::new(
+            App::new()
+                .middleware(middleware::OnComplete::new(move |summary| {
+                    completed
+                        .lock()
+                        .unwrap()
+                        .push((summary.status(), summary.size()));
+                    Ok(())
+                }))
+                .resource("/", |r| r.get(|| Response::Ok().body(STR))),
+        )
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(STR.as_ref()));
+
+    // callback runs on the server's arbiter after response is written
+    for _ in 0..100 {
+        if !completed.lock().unwrap().is_empty() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_eq!(
+        *completed.lock().unwrap(),
+        vec![(StatusCode::OK, STR.len())]
+    );
+}
+
+#[test]
+fn test_prefix_default_resource() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .resource("/api/users", |r| r.get(|| Response::Ok()))
+                .default_resource_for("/api", |r| {
+                    r.to(|| {
+                        Response::NotFound()
+                            .content_type("application/json")
+                            .body("{\"error\":\"not found\"}")
+                    })
+                })
+                .default_resource(|r| {
+                    r.to(|| {
+                        Response::NotFound()
+                            .content_type("text/html")
+                            .body("<h1>Not found</h1>")
+                    })
+                }),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/api/users")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    let request = srv.get().uri(srv.url("/api/foo")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/json"
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"{\"error\":\"not found\"}"));
+
+    let request = srv.get().uri(srv.url("/page")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), "text/html");
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"<h1>Not found</h1>"));
+
+    // prefix matches whole segments only
+    let request = srv.get().uri(srv.url("/apiary")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), "text/html");
+}
+
+#[test]
+fn test_require_scopes() {
+    let mut srv = TestServer::new(|| {
+        let cfg = RequireScopesConfig::new(|token| match token {
+            "reader" => Some(vec!["orders:read".to_owned()]),
+            "admin" => Some(vec!["orders:read".to_owned(), "orders:write".to_owned()]),
+            _ => None,
+        });
+
+        h1::H1Service::new(App::new().state(cfg.clone()).resource("/orders", |r| {
+            r.route(|r| {
+                r.config(cfg.scope("orders:write"))
+                    .to(|token: RequireScopes| token.token().to_owned())
+            })
+        }))
+    });
+
+    let request = srv.get().uri(srv.url("/orders")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers().get(WWW_AUTHENTICATE).unwrap(), "Bearer");
+
+    let request = srv
+        .get()
+        .uri(srv.url("/orders"))
+        .header(AUTHORIZATION, "Bearer forged")
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        response.headers().get(WWW_AUTHENTICATE).unwrap(),
+        "Bearer error=\"invalid_token\""
+    );
+
+    let request = srv
+        .get()
+        .uri(srv.url("/orders"))
+        .header(AUTHORIZATION, "Bearer reader")
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.headers().get(WWW_AUTHENTICATE).unwrap(),
+        "Bearer error=\"insufficient_scope\", scope=\"orders:write\""
+    );
+
+    let request = srv
+        .get()
+        .uri(srv.url("/orders"))
+        .header(AUTHORIZATION, "Bearer admin")
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"admin"));
+}
+
+#[test]
+fn test_spooled_responder() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let dir =
+        std::env::temp_dir().join(format!("actix-spool-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let spooled = Arc::new(AtomicBool::new(false));
+
+    let (d, flag) = (dir.clone(), spooled.clone());
+    let mut srv = TestServer::new(move || {
+        let (d, flag) = (d.clone(), flag.clone());
+        h1::H1Service::new(App::new().resource("/", move |r| {
+            let (d, flag) = (d.clone(), flag.clone());
+            r.get(move || {
+                let mut chunks = vec![Bytes::from_static(STR.as_ref()); 10];
+                let (d, flag) = (d.clone(), flag.clone());
+                let stream = futures::stream::poll_fn(move || -> Poll<_, Error> {
+                    if let Some(chunk) = chunks.pop() {
+                        return Ok(Async::Ready(Some(chunk)));
+                    }
+                    let files = std::fs::read_dir(&d).unwrap().count();
+                    flag.store(files == 1, Ordering::SeqCst);
+                    Ok(Async::Ready(None))
+                });
+                Spooled::new(stream).threshold(STR.len()).dir(d.clone())
+            })
+        }))
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from(STR.repeat(10)));
+    assert!(spooled.load(Ordering::SeqCst));
+
+    // file is removed after response completes
+    let mut removed = false;
+    for _ in 0..50 {
+        if std::fs::read_dir(&dir).unwrap().count() == 0 {
+            removed = true;
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert!(removed);
+    let _ = std::fs::remove_dir(&dir);
+}
+
+#[test]
+fn test_content_type_filter() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.route(|mut r| {
+                r.filter(filter::ContentType("application/json"));
+                r.to(|body: String| format!("json:{}", body))
+            })
+            .route(|mut r| {
+                r.filter(
+                    filter::Any(filter::ContentType(
+                        "application/x-www-form-urlencoded",
+                    ))
+                    .or(filter::ContentType("multipart/form-data")),
+                );
+                r.to(|body: String| format!("form:{}", body))
+            })
+            .to(|| Response::UnsupportedMediaType())
+        }))
+    });
+
+    let cases: &[(Option<&str>, &[u8])] = &[
+        (Some("application/json"), b"json:data"),
+        (Some("Application/JSON; charset=utf-8"), b"json:data"),
+        (Some("application/x-www-form-urlencoded"), b"form:data"),
+        (Some("multipart/form-data; boundary=x"), b"form:data"),
+    ];
+    for (content_type, expected) in cases {
+        let mut request = srv.post();
+        if let Some(content_type) = content_type {
+            request.header(CONTENT_TYPE, *content_type);
+        }
+        let request = request.body("data").unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(&bytes[..], *expected);
+    }
+
+    for content_type in &[
+        Some("text/plain"),
+        Some("application/jsonx"),
+        Some("application/json; charset"),
+        Some("application"),
+        None,
+    ] {
+        let mut request = srv.post();
+        if let Some(content_type) = content_type {
+            request.header(CONTENT_TYPE, *content_type);
+        }
+        let request = request.body("data").unwrap();
+        let response = srv.send_request(request).unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
+
+#[test]
+fn test_cookie_extractor() {
+    let mut srv = TestServer::new(|| {
+        let mut session = CookieConfig::new("session");
+        session.error_handler(|_, _| actix_http::error::ErrorUnauthorized("no session"));
+
+        h1::H1Service::new(
+            App::new()
+                .state(CookieConfig::new("visits"))
+                .resource("/visits", |r| {
+                    r.get(|visits: Cookie<u32>| format!("visits {}", *visits + 1))
+                })
+                .resource("/session", |r| {
+                    r.route(|r| {
+                        r.config(session)
+                            .to(|session: Cookie<String>| session.into_inner())
+                    })
+                })
+                .resource("/all", |r| {
+                    r.get(|cookies: Cookies<HashMap<String, String>>| {
+                        let mut names: Vec<_> = cookies.keys().cloned().collect();
+                        names.sort();
+                        names.join(",")
+                    })
+                }),
+        )
+    });
+
+    let cases: &[(&str, &[&str], StatusCode, &[u8])] = &[
+        ("/visits", &["visits=2"], StatusCode::OK, b"visits 3"),
+        (
+            "/visits",
+            &["theme=dark", "a=1; visits=5"],
+            StatusCode::OK,
+            b"visits 6",
+        ),
+        ("/visits", &["visits=many"], StatusCode::BAD_REQUEST, b""),
+        ("/visits", &[], StatusCode::BAD_REQUEST, b""),
+        (
+            "/session",
+            &["session=abc; visits=1"],
+            StatusCode::OK,
+            b"abc",
+        ),
+        (
+            "/session",
+            &["visits=1"],
+            StatusCode::UNAUTHORIZED,
+            b"no session",
+        ),
+        ("/all", &["b=2; a=1", "c=3"], StatusCode::OK, b"a,b,c"),
+    ];
+
+    for (path, cookies, status, expected) in cases {
+        let mut request = srv.get();
+        request.uri(srv.url(path));
+        for cookie in cookies.iter() {
+            request.header(COOKIE, *cookie);
+        }
+        let request = request.finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert_eq!(response.status(), *status);
+        if !expected.is_empty() {
+            let bytes = srv.execute(response.body()).unwrap();
+            assert_eq!(&bytes[..], *expected);
+        }
+    }
+}
+
+#[test]
+fn test_query_defaults() {
+    #[derive(Deserialize, Default)]
+    struct Pagination {
+        page: u32,
+        per_page: u32,
+    }
+
+    let mut srv = TestServer::new(|| {
+        let mut cfg = QueryConfig::default();
+        cfg.default_if_empty(true).error_handler(|e, _| {
+            actix_http::error::ErrorUnprocessableEntity(format!("invalid query: {}", e))
+        });
+
+        h1::H1Service::new(
+            App::new()
+                .resource("/", |r| {
+                    r.route(|r| {
+                        r.config(cfg).to(|q: Query<Pagination>| {
+                            format!("{}:{}", q.page, q.per_page)
+                        })
+                    })
+                })
+                .resource("/strict", |r| {
+                    r.get(|q: Query<Pagination>| format!("{}:{}", q.page, q.per_page))
+                }),
+        )
+    });
+
+    for (path, status, expected) in &[
+        ("/", StatusCode::OK, "0:0"),
+        ("/?", StatusCode::OK, "0:0"),
+        ("/?page=2&per_page=10", StatusCode::OK, "2:10"),
+        (
+            "/?page=2",
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "invalid query",
+        ),
+        ("/strict", StatusCode::BAD_REQUEST, ""),
+        ("/strict?", StatusCode::BAD_REQUEST, ""),
+    ] {
+        let request = srv.get().uri(srv.url(path)).finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert_eq!(response.status(), *status);
+        let bytes = srv.execute(response.body()).unwrap();
+        assert!(String::from_utf8_lossy(&bytes).starts_with(expected));
+    }
+}
+
+#[test]
+fn test_raw_query() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new().resource("/", |r| r.get(|q: RawQuery| format!("[{}]", &*q))),
+        )
+    });
+
+    for (path, expected) in &[
+        ("/?b=2&a=%20x+y&a=1", "[b=2&a=%20x+y&a=1]"),
+        ("/?", "[]"),
+        ("/", "[]"),
+    ] {
+        let request = srv.get().uri(srv.url(path)).finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(bytes, Bytes::from(*expected));
+    }
+}
+
+#[test]
+fn test_streaming_payload() {
+    use futures::{Future, Stream};
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.to_async(|body: Payload| {
+                body.map_err(Error::from)
+                    .fold((0, 0), |(chunks, lines), chunk| {
+                        let newlines = chunk.iter().filter(|b| **b == b'\n').count();
+                        Ok::<_, Error>((chunks + 1, lines + newlines))
+                    })
+                    .map(|(chunks, lines)| {
+                        assert!(chunks > 0);
+                        Response::Ok().body(format!("{}", lines))
+                    })
+            })
+        }))
+    });
+
+    // larger than default 256Kb payload limit
+    let data = "line of the streamed payload\n".repeat(20_000);
+    let request = srv.post().body(data).unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"20000"));
+}
+
+#[test]
+fn test_multipart() {
+    use futures::{Future, Stream};
+
+    fn fields(mp: Multipart) -> impl Future<Item = Response, Error = Error> {
+        mp.and_then(|field| {
+            let info = format!(
+                "{}:{}:{}",
+                field.name(),
+                field.filename().unwrap_or("-"),
+                field.content_type()
+            );
+            field
+                .fold(Vec::new(), |mut body, chunk| {
+                    body.extend_from_slice(&chunk);
+                    Ok::<_, Error>(body)
+                })
+                .map(move |body| format!("{}:{}", info, String::from_utf8_lossy(&body)))
+        })
+        .collect()
+        .map(|fields| Response::Ok().body(fields.join("\n")))
+    }
+
+    fn names(mp: Multipart) -> impl Future<Item = Response, Error = Error> {
+        mp.map(|field| field.name().to_owned())
+            .collect()
+            .map(|names| Response::Ok().body(names.join(",")))
+    }
+
+    let mut srv = TestServer::new(|| {
+        let mut limits = MultipartConfig::default();
+        limits.field_limit(16).header_limit(128);
+
+        h1::H1Service::new(
+            App::new()
+                .resource("/", |r| r.to_async(fields))
+                .resource("/names", |r| r.to_async(names))
+                .resource("/limited", |r| {
+                    r.route(|r| r.config(limits.clone()).to_async(fields))
+                })
+                .resource("/limited/names", |r| {
+                    r.route(|r| r.config(limits).to_async(names))
+                }),
+        )
+    });
+
+    let body = "preamble\r\n\
+                --xyz\r\n\
+                Content-Disposition: form-data; name=\"title\"\r\n\
+                \r\n\
+                Hello\r\n--not boundary\r\n\
+                --xyz\r\n\
+                Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+                Content-Type: text/csv\r\n\
+                \r\n\
+                a,b\r\n1,2\r\n\
+                --xyz--\r\n";
+
+    let request = srv
+        .post()
+        .header(CONTENT_TYPE, "multipart/form-data; boundary=xyz")
+        .body(body)
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(
+        bytes,
+        Bytes::from_static(
+            b"title:-:text/plain:Hello\r\n--not boundary\nfile:a.txt:text/csv:a,b\r\n1,2"
+        )
+    );
+
+    // unread fields are skipped
+    let request = srv
+        .post()
+        .uri(srv.url("/names"))
+        .header(CONTENT_TYPE, "multipart/form-data; boundary=xyz")
+        .body(body)
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"title,file"));
+
+    // field is larger than the limit
+    let request = srv
+        .post()
+        .uri(srv.url("/limited"))
+        .header(CONTENT_TYPE, "multipart/form-data; boundary=xyz")
+        .body(body)
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // skipped fields are not limited
+    let request = srv
+        .post()
+        .uri(srv.url("/limited/names"))
+        .header(CONTENT_TYPE, "multipart/form-data; boundary=xyz")
+        .body(body)
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"title,file"));
+
+    // part without headers parses, but still needs a name
+    let mut req =
+        TestRequest::with_header(CONTENT_TYPE, "multipart/form-data; boundary=xyz")
+            .set_payload("--xyz\r\n\r\nvalue\r\n--xyz--\r\n")
+            .finish();
+    let mp = test::block_on(Multipart::from_request(&mut req)).unwrap();
+    let (e, _) = test::block_on(mp.into_future()).err().unwrap();
+    assert_eq!(e.to_string(), "Multipart field name is missing");
+
+    // part headers are larger than the limit
+    let oversized = format!(
+        "--xyz\r\nContent-Disposition: form-data; name=\"a\"\r\nX-Padding: {}\r\n\r\n\
+         1\r\n--xyz--\r\n",
+        "x".repeat(256)
+    );
+    let request = srv
+        .post()
+        .uri(srv.url("/limited"))
+        .header(CONTENT_TYPE, "multipart/form-data; boundary=xyz")
+        .body(oversized)
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    for content_type in &["multipart/form-data", "application/json"] {
+        let request = srv
+            .post()
+            .header(CONTENT_TYPE, *content_type)
+            .body(body)
+            .unwrap();
+        let response = srv.send_request(request).unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+#[test]
+fn test_string_decoded_limit() {
+    let mut cfg = PayloadConfig::default();
+    cfg.limit(16).decoded_limit(20);
+
+    // half-width katakana, one byte in Shift-JIS and three bytes in UTF-8
+    let mut req =
+        TestRequest::with_header(CONTENT_TYPE, "text/plain; charset=shift_jis")
+            .state(cfg.clone())
+            .set_payload(vec![0xb1; 6])
+            .finish();
+    let body = test::block_on(String::from_request(&mut req)).unwrap();
+    assert_eq!(body, "ｱｱｱｱｱｱ");
+
+    let mut req =
+        TestRequest::with_header(CONTENT_TYPE, "text/plain; charset=shift_jis")
+            .state(cfg.clone())
+            .set_payload(vec![0xb1; 10])
+            .finish();
+    let e = test::block_on(String::from_request(&mut req))
+        .err()
+        .unwrap();
+    assert_eq!(e.to_string(), "Decoded body is too large");
+    let resp: Response = e.into();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // payload limit is applied to raw bytes
+    let mut req =
+        TestRequest::with_header(CONTENT_TYPE, "text/plain; charset=shift_jis")
+            .state(cfg)
+            .set_payload(vec![b'a'; 17])
+            .finish();
+    let resp: Response = test::block_on(String::from_request(&mut req))
+        .err()
+        .unwrap()
+        .into();
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[test]
+fn test_json_default_content_type() {
+    #[derive(Deserialize)]
+    struct Info {
+        name: String,
+    }
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new().resource("/", |r| r.post(|info: Json<Info>| info.name.clone())),
+        )
+    });
+
+    for (content_type, success) in &[
+        ("application/json", true),
+        ("application/json; charset=utf-8", true),
+        ("application/vnd.api+json", false),
+        ("text/json", false),
+    ] {
+        let request = srv
+            .post()
+            .header(CONTENT_TYPE, *content_type)
+            .body(r#"{"name": "actix"}"#)
+            .unwrap();
+        let response = srv.send_request(request).unwrap();
+        assert_eq!(response.status().is_success(), *success);
+    }
+}
+
+#[test]
+fn test_json_content_types() {
+    #[derive(Deserialize)]
+    struct Info {
+        name: String,
+    }
+
+    let mut srv = TestServer::new(|| {
+        let mut cfg = JsonConfig::default();
+        cfg.content_type("application/vnd.api+json".parse().unwrap())
+            .content_type("text/plain".parse().unwrap())
+            .error_handler(|err, _| {
+                actix_http::error::InternalError::from_response(
+                    err,
+                    Response::UnsupportedMediaType().finish(),
+                )
+                .into()
+            });
+
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.route(|r| r.config(cfg).to(|info: Json<Info>| info.name.clone()))
+        }))
+    });
+
+    for (content_type, status) in &[
+        ("application/vnd.api+json", StatusCode::OK),
+        ("text/plain; charset=utf-8", StatusCode::OK),
+        ("application/json", StatusCode::UNSUPPORTED_MEDIA_TYPE),
+        ("text/json", StatusCode::UNSUPPORTED_MEDIA_TYPE),
+    ] {
+        let request = srv
+            .post()
+            .header(CONTENT_TYPE, *content_type)
+            .body(r#"{"name": "actix"}"#)
+            .unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert_eq!(response.status(), *status);
+        if status.is_success() {
+            let bytes = srv.execute(response.body()).unwrap();
+            assert_eq!(bytes, Bytes::from_static(b"actix"));
+        }
+    }
+}
+
+#[test]
+fn test_json_content_length_overflow() {
+    use futures::Stream;
+
+    let mut cfg = JsonConfig::default();
+    cfg.limit(4096);
+
+    let mut req = TestRequest::with_header(CONTENT_TYPE, "application/json")
+        .header(CONTENT_LENGTH, "999999")
+        .state(cfg)
+        .set_payload(r#"{"name": "actix"}"#)
+        .finish();
+    let e = test::block_on(Json::<serde_json::Value>::from_request(&mut req))
+        .err()
+        .unwrap();
+    let resp: Response = e.into();
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // payload is not consumed
+    let body = test::block_on(req.take_payload().concat2()).unwrap();
+    assert_eq!(body, Bytes::from_static(br#"{"name": "actix"}"#));
+}
+
+#[test]
+fn test_app_data() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Greeting(&'static str);
+
+    let hits = Data::new(AtomicUsize::new(0));
+    let shared = hits.clone();
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(
+            App::new()
+                .data(Greeting("Hello"))
+                .register_data(shared.clone())
+                .resource("/", |r| {
+                    r.get(|greeting: Data<Greeting>, hits: Data<AtomicUsize>| {
+                        let hits = hits.fetch_add(1, Ordering::SeqCst) + 1;
+                        format!("{} {}", greeting.0, hits)
+                    })
+                })
+                .resource("/missing", |r| r.get(|_: Data<String>| "unreachable")),
+        )
+    });
+
+    for expected in &["Hello 1", "Hello 2"] {
+        let request = srv.get().finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(bytes, Bytes::from(*expected));
+    }
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    let request = srv.get().uri(srv.url("/missing")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[test]
+fn test_state_factory_retries() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use actix_web2::dev::StateFactory;
+
+    let mut srv = TestServer::new(|| {
+        let attempts = Rc::new(Cell::new(0));
+        let factory = move || {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() < 3 {
+                Err("unavailable")
+            } else {
+                Ok(attempts.get())
+            }
+        };
+        h1::H1Service::new(
+            App::new()
+                .state_factory(factory.with_retries(3, Duration::from_millis(10)))
+                .resource("/", |r| r.get(|st: State<usize>| format!("{}", *st))),
+        )
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"3"));
+}
+
+#[test]
+fn test_matched_path() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .resource("/users/{id}", |r| {
+                    r.get(|path: MatchedPath, req: HttpRequest| {
+                        assert_eq!(req.matched_path().unwrap(), *path);
+                        path.into_inner()
+                    })
+                })
+                .resource("/files/{tail:*}", |r| r.get(|path: MatchedPath| path.0))
+                .default_resource(|r| {
+                    r.to(|path: Result<MatchedPath, Error>| format!("{}", path.is_err()))
+                }),
+        )
+    });
+
+    for (path, expected) in &[
+        ("/users/42", "/users/{id}"),
+        ("/files/a/b.txt", "/files/{tail:*}"),
+        ("/unknown", "true"),
+    ] {
+        let request = srv.get().uri(srv.url(path)).finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(bytes, Bytes::from(*expected));
+    }
+}
+
+#[test]
+fn test_tail_extractor() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .resource("/files/{tail:.*}", |r| {
+                    r.get(|tail: Tail| tail.into_inner())
+                })
+                .resource("/static/{tail:*}", |r| {
+                    r.get(|tail: Tail| tail.into_inner())
+                })
+                .resource("/plain", |r| r.get(|tail: Tail| tail.into_inner())),
+        )
+    });
+
+    for (path, expected) in &[
+        ("/files/a/b.txt", "a/b.txt"),
+        ("/files/my%20docs/%C3%A9t%C3%A9.txt", "my docs/été.txt"),
+        ("/static/css/site.css", "css/site.css"),
+    ] {
+        let request = srv.get().uri(srv.url(path)).finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(bytes, Bytes::from(*expected));
+    }
+
+    let request = srv.get().uri(srv.url("/plain")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn test_cors() {
+    use actix_http::http::header::{
+        ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
+        ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+        ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS,
+        ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+    };
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .middleware(
+                    middleware::Cors::new()
+                        .allowed_origin("https://www.rust-lang.org")
+                        .allowed_methods(vec![Method::GET, Method::PUT])
+                        .allowed_headers(vec![AUTHORIZATION])
+                        .max_age(3600)
+                        .supports_credentials(),
+                )
+                .resource("/", |r| r.to(|| Response::Ok())),
+        )
+    });
+
+    // preflight
+    let request = srv
+        .request(Method::OPTIONS, "/")
+        .header(ORIGIN, "https://www.rust-lang.org")
+        .header(ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+        .header(ACCESS_CONTROL_REQUEST_HEADERS, "authorization")
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let hdrs = response.headers();
+    assert_eq!(
+        hdrs.get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        "https://www.rust-lang.org"
+    );
+    assert_eq!(hdrs.get(ACCESS_CONTROL_ALLOW_METHODS).unwrap(), "GET, PUT");
+    assert_eq!(
+        hdrs.get(ACCESS_CONTROL_ALLOW_HEADERS).unwrap(),
+        "authorization"
+    );
+    assert_eq!(hdrs.get(ACCESS_CONTROL_MAX_AGE).unwrap(), "3600");
+    assert_eq!(hdrs.get(ACCESS_CONTROL_ALLOW_CREDENTIALS).unwrap(), "true");
+    assert_eq!(hdrs.get(VARY).unwrap(), "Origin");
+
+    // preflight for disallowed method
+    let request = srv
+        .request(Method::OPTIONS, "/")
+        .header(ORIGIN, "https://www.rust-lang.org")
+        .header(ACCESS_CONTROL_REQUEST_METHOD, "DELETE")
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(response
+        .headers()
+        .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+        .is_none());
+
+    // simple request
+    let request = srv
+        .get()
+        .header(ORIGIN, "https://www.rust-lang.org")
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        "https://www.rust-lang.org"
+    );
+    assert_eq!(
+        response
+            .headers()
+            .get(ACCESS_CONTROL_ALLOW_CREDENTIALS)
+            .unwrap(),
+        "true"
+    );
+
+    // disallowed origin
+    let request = srv
+        .get()
+        .header(ORIGIN, "https://example.com")
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert!(response
+        .headers()
+        .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+        .is_none());
+
+    // no origin
+    let request = srv.get().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert!(response
+        .headers()
+        .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+        .is_none());
+
+    // credentials are not allowed for any origin
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .middleware(middleware::Cors::new().supports_credentials())
+                .resource("/", |r| r.to(|| Response::Ok())),
+        )
+    });
+
+    let request = srv
+        .get()
+        .header(ORIGIN, "https://example.com")
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        "*"
+    );
+    assert!(response
+        .headers()
+        .get(ACCESS_CONTROL_ALLOW_CREDENTIALS)
+        .is_none());
+}
+
+#[test]
+fn test_logger() {
+    capture_logs();
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .middleware(
+                    middleware::Logger::new(r#"test-logger %a "%r" %s %b %{X-Test}o"#)
+                        .exclude("/health"),
+                )
+                .resource("/", |r| {
+                    r.get(|| Response::Ok().header("X-Test", "ttt").body(STR))
+                })
+                .resource("/health", |r| r.get(|| Response::Ok())),
+        )
+    });
+
+    let request = srv
+        .get()
+        .header("x-forwarded-for", "192.0.2.60")
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get("x-test").unwrap(), "ttt");
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(STR.as_ref()));
+
+    let request = srv.get().uri(srv.url("/health")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    srv.execute(response.body()).unwrap();
+
+    // log line is emitted once body is sent, excluded path is not logged
+    let logs: Vec<_> = captured_logs("middleware::logger")
+        .into_iter()
+        .filter(|line| line.starts_with("test-logger"))
+        .collect();
+    assert_eq!(
+        logs,
+        vec![format!(
+            "test-logger 192.0.2.60 \"GET / HTTP/1.1\" 200 {} ttt",
+            STR.len()
+        )]
+    );
+}
+
+#[test]
+fn test_compress_negotiation() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .middleware(
+                    middleware::Compress::new(ContentEncoding::Auto)
+                        .level(9)
+                        .min_size(100),
+                )
+                .resource("/", |r| r.get(|| Response::Ok().body(STR)))
+                .resource("/small", |r| r.get(|| Response::Ok().body("small"))),
+        )
+    });
+
+    // highest quality value wins
+    let request = srv
+        .get()
+        .header(ACCEPT_ENCODING, "deflate;q=0.5, gzip;q=0.8, identity;q=0.1")
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+    assert_eq!(response.headers().get(VARY).unwrap(), "accept-encoding");
+
+    let bytes = srv.execute(response.body()).unwrap();
+    let mut e = GzDecoder::new(&bytes[..]);
+    let mut dec = Vec::new();
+    e.read_to_end(&mut dec).unwrap();
+    assert_eq!(Bytes::from(dec), Bytes::from_static(STR.as_ref()));
+
+    // q=0 means not acceptable
+    let request = srv
+        .get()
+        .header(ACCEPT_ENCODING, "gzip;q=0, deflate")
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.headers().get(CONTENT_ENCODING).unwrap(), "deflate");
+
+    // bodies under minimal size are sent as is
+    let request = srv
+        .get()
+        .uri(srv.url("/small"))
+        .header(ACCEPT_ENCODING, "gzip")
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.headers().get(CONTENT_ENCODING).is_none());
+    assert_eq!(response.headers().get(VARY).unwrap(), "accept-encoding");
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"small"));
+
+    // uncompressed response depends on accept-encoding as well
+    let request = srv
+        .get()
+        .header(ACCEPT_ENCODING, "identity")
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert!(response.headers().get(CONTENT_ENCODING).is_none());
+    assert_eq!(response.headers().get(VARY).unwrap(), "accept-encoding");
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(STR.as_ref()));
+}
+
+#[test]
+fn test_error_handlers() {
+    use actix_http::body::{Body, ResponseBody};
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .middleware(middleware::ErrorHandlers::new().handler(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    |res: actix_web2::ServiceResponse| {
+                        res.map_body(|head, _| {
+                            head.headers_mut().insert(
+                                CONTENT_TYPE,
+                                HeaderValue::from_static("application/json"),
+                            );
+                            ResponseBody::Body(Body::from(r#"{"error":"internal"}"#))
+                        })
+                    },
+                ))
+                .resource("/", |r| {
+                    r.get(|| Response::InternalServerError().body("boom"))
+                })
+                .resource("/ok", |r| r.get(|| Response::Ok().body("ok"))),
+        )
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/json"
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(br#"{"error":"internal"}"#));
+
+    // other statuses are untouched
+    let request = srv.get().uri(srv.url("/ok")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"ok"));
+}
+
+#[test]
+fn test_condition() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .middleware(middleware::Condition::new(
+                    true,
+                    middleware::DefaultHeaders::new().header("X-Enabled", "1"),
+                ))
+                .middleware(middleware::Condition::new(
+                    false,
+                    middleware::DefaultHeaders::new().header("X-Disabled", "1"),
+                ))
+                .resource("/", |r| r.get(|| Response::Ok().body(STR))),
+        )
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get("x-enabled").unwrap(), "1");
+    assert!(response.headers().get("x-disabled").is_none());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(STR.as_ref()));
+}
+
+#[test]
+fn test_timeout() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant};
+
+    use futures::Future;
+
+    struct Guard(Arc<AtomicBool>);
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    let dropped = Arc::new(AtomicBool::new(false));
+    let dropped2 = dropped.clone();
+
+    let mut srv = TestServer::new(move || {
+        let dropped = dropped2.clone();
+        h1::H1Service::new(
+            App::new()
+                .middleware(
+                    middleware::Timeout::new(Duration::from_millis(100)).body("timeout"),
+                )
+                .resource("/slow", move |r| {
+                    let dropped = dropped.clone();
+                    r.to_async(move || {
+                        let guard = Guard(dropped.clone());
+                        tokio_timer::Delay::new(Instant::now() + Duration::from_secs(5))
+                            .then(move |_| {
+                                let _ = &guard;
+                                Ok::<_, Error>(Response::Ok().finish())
+                            })
+                    })
+                })
+                .resource("/fast", |r| r.get(|| Response::Ok().body("fast"))),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("/slow")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"timeout"));
+    // handler's future is dropped on timeout
+    assert!(dropped.load(Ordering::SeqCst));
+
+    let request = srv.get().uri(srv.url("/fast")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"fast"));
+}
+
+#[test]
+fn test_normalize_path() {
+    use actix_web2::middleware::{NormalizePath, TrailingSlash};
+
+    fn echo(req: HttpRequest) -> Response {
+        Response::Ok().body(format!("{}?{}", req.path(), req.query_string()))
+    }
+
+    let modes = vec![
+        (
+            TrailingSlash::MergeOnly,
+            "/foo/bar/",
+            "//foo//bar/?a=1",
+            "/foo/bar/?a=1",
+        ),
+        (
+            TrailingSlash::Trim,
+            "/foo/bar",
+            "//foo//bar//?a=1",
+            "/foo/bar?a=1",
+        ),
+        (
+            TrailingSlash::Always,
+            "/foo/bar/",
+            "/foo//bar?a=1",
+            "/foo/bar/?a=1",
+        ),
+    ];
+
+    for (mode, pattern, uri, expected) in modes {
+        let mut srv = TestServer::new(move || {
+            h1::H1Service::new(
+                App::new()
+                    .middleware(NormalizePath::new(mode))
+                    .resource("/", |r| r.get(echo))
+                    .resource(pattern, |r| r.get(echo)),
+            )
+        });
+
+        let request = srv.get().uri(srv.url(uri)).finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success(), "{:?}", mode);
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(bytes, Bytes::from(expected));
+
+        // root path is preserved
+        let request = srv.get().uri(srv.url("/?b=2")).finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success(), "{:?}", mode);
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(bytes, Bytes::from_static(b"/?b=2"));
+    }
+}
+
+#[test]
+fn test_normalize_path_under_timeout() {
+    use std::time::Duration;
+
+    use actix_web2::middleware::{NormalizePath, TrailingSlash};
+
+    // Timeout keeps a copy of the request while NormalizePath rewrites it
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .middleware(NormalizePath::new(TrailingSlash::Trim))
+                .middleware(middleware::Timeout::new(Duration::from_secs(5)))
+                .resource("/foo/bar", |r| {
+                    r.get(|req: HttpRequest| {
+                        Response::Ok().body(format!(
+                            "{} {}",
+                            req.path(),
+                            req.request_target()
+                        ))
+                    })
+                }),
+        )
+    });
+
+    let request = srv.get().uri(srv.url("//foo//bar/?a=1")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"/foo/bar //foo//bar/?a=1"));
+}
+
+#[test]
+fn test_max_payload() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let calls2 = calls.clone();
+
+    let mut srv = TestServer::new(move || {
+        let calls = calls2.clone();
+        h1::H1Service::new(
+            App::new()
+                .middleware(middleware::MaxPayload::new(16))
+                .resource("/", move |r| {
+                    let calls = calls.clone();
+                    r.to(move |body: String| {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        body
+                    })
+                }),
+        )
+    });
+
+    let request = srv.post().body("small").unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"small"));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // declared length is checked before handler runs
+    let request = srv.post().body(STR).unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_rate_limit() {
+    use std::time::Duration;
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .middleware(
+                    middleware::RateLimit::new(|req: &HttpRequest| {
+                        req.headers()
+                            .get("x-api-key")
+                            .and_then(|key| key.to_str().ok())
+                            .map(|key| key.to_owned())
+                    })
+                    .capacity(2)
+                    .refill(1, Duration::from_millis(200)),
+                )
+                .resource("/", |r| r.get(|| Response::Ok())),
+        )
+    });
+
+    // bucket is exhausted after capacity requests
+    for _ in 0..2 {
+        let request = srv.get().header("x-api-key", "a").finish().unwrap();
+        let response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success());
+    }
+    let request = srv.get().header("x-api-key", "a").finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "1");
+
+    // other clients and requests without key are not affected
+    let request = srv.get().header("x-api-key", "b").finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let request = srv.get().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+
+    // bucket is refilled
+    std::thread::sleep(Duration::from_millis(300));
+    let request = srv.get().header("x-api-key", "a").finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+}
+
+#[test]
+fn test_rate_limit_max_clients() {
+    use std::time::Duration;
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .middleware(
+                    middleware::RateLimit::new(|req: &HttpRequest| {
+                        req.headers()
+                            .get("x-api-key")
+                            .and_then(|key| key.to_str().ok())
+                            .map(|key| key.to_owned())
+                    })
+                    .capacity(1)
+                    .refill(1, Duration::from_secs(60))
+                    .max_clients(1),
+                )
+                .resource("/", |r| r.get(|| Response::Ok())),
+        )
+    });
+
+    let expected = vec![
+        ("a", StatusCode::OK),
+        ("a", StatusCode::TOO_MANY_REQUESTS),
+        // new client takes place of the tracked one
+        ("b", StatusCode::OK),
+        ("a", StatusCode::OK),
+        ("a", StatusCode::TOO_MANY_REQUESTS),
+    ];
+    for (key, status) in expected {
+        let request = srv.get().header("x-api-key", key).finish().unwrap();
+        let response = srv.send_request(request).unwrap();
+        assert_eq!(response.status(), status, "{}", key);
+    }
+}
+
+#[test]
+fn test_conditional_get() {
+    use actix_http::http::header::{IF_MODIFIED_SINCE, LAST_MODIFIED};
+
+    let body = Arc::new(Mutex::new("first"));
+    let body2 = body.clone();
+
+    let mut srv = TestServer::new(move || {
+        let body = body2.clone();
+        h1::H1Service::new(
+            App::new()
+                .middleware(middleware::ConditionalGet)
+                .resource("/", move |r| {
+                    let body = body.clone();
+                    r.to(move || Response::Ok().body(*body.lock().unwrap()))
+                })
+                .resource("/dated", |r| {
+                    r.get(|| {
+                        Response::Ok()
+                            .header(LAST_MODIFIED, "Sun, 06 Nov 1994 08:49:37 GMT")
+                            .body("dated")
+                    })
+                }),
+        )
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let etag = response.headers().get(ETAG).unwrap().clone();
+    assert!(etag.to_str().unwrap().starts_with("W/\""));
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"first"));
+
+    // matching etag
+    let request = srv
+        .get()
+        .header(IF_NONE_MATCH, etag.clone())
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers().get(ETAG).unwrap(), &etag);
+    let bytes = srv.execute(response.body()).unwrap();
+    assert!(bytes.is_empty());
+
+    // body changed
+    *body.lock().unwrap() = "second";
+    let request = srv
+        .get()
+        .header(IF_NONE_MATCH, etag.clone())
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_ne!(response.headers().get(ETAG).unwrap(), &etag);
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"second"));
+
+    // only GET and HEAD requests are handled
+    let request = srv.post().header(IF_NONE_MATCH, "*").finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert!(response.headers().get(ETAG).is_none());
+
+    // last modified
+    let request = srv
+        .get()
+        .uri(srv.url("/dated"))
+        .header(IF_MODIFIED_SINCE, "Sun, 06 Nov 1994 08:49:37 GMT")
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    let request = srv
+        .get()
+        .uri(srv.url("/dated"))
+        .header(IF_MODIFIED_SINCE, "Sat, 05 Nov 1994 08:49:37 GMT")
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+}
+
+#[test]
+fn test_status_responder() {
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .resource("/", |r| {
+                    r.get(|| ("accepted".to_owned(), StatusCode::ACCEPTED))
+                })
+                .resource("/error", |r| {
+                    r.get(|| {
+                        let res: Result<String, Error> =
+                            Err(actix_http::error::ErrorConflict("conflict"));
+                        (res, StatusCode::CREATED)
+                    })
+                }),
+        )
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "text/plain; charset=utf-8"
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"accepted"));
+
+    // error of the inner responder is not overridden
+    let request = srv.get().uri(srv.url("/error")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[test]
+fn test_named_file() {
+    use actix_http::http::header::{
+        ContentDisposition, DispositionParam, DispositionType, IF_MODIFIED_SINCE,
+        LAST_MODIFIED,
+    };
+    use actix_web2::fs::NamedFile;
+
+    let path = std::env::temp_dir()
+        .join(format!("actix-named-file-{}.txt", std::process::id()));
+    std::fs::write(&path, STR).unwrap();
+    let srv_path = path.clone();
+
+    let mut srv = TestServer::new(move || {
+        let (path, path2) = (srv_path.clone(), srv_path.clone());
+        h1::H1Service::new(
+            App::new()
+                .resource("/", move |r| r.get(move || NamedFile::open(&path)))
+                .resource("/download", move |r| {
+                    r.get(move || {
+                        NamedFile::open(&path2).map(|file| {
+                            file.set_content_disposition(ContentDisposition {
+                                disposition: DispositionType::Attachment,
+                                parameters: vec![DispositionParam::Filename(
+                                    "hello.txt".to_owned(),
+                                )],
+                            })
+                        })
+                    })
+                }),
+        )
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), "text/plain");
+    assert_eq!(
+        response.headers().get(CONTENT_LENGTH).unwrap(),
+        &STR.len().to_string()
+    );
+    let last_modified = response.headers().get(LAST_MODIFIED).unwrap().clone();
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(STR.as_ref()));
+
+    // unchanged file
+    let request = srv
+        .get()
+        .header(IF_MODIFIED_SINCE, last_modified)
+        .finish()
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    let request = srv.get().uri(srv.url("/download")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers().get(CONTENT_DISPOSITION).unwrap(),
+        "attachment; filename=\"hello.txt\""
+    );
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_named_file_accept_ranges() {
+    use actix_web2::fs::NamedFile;
+
+    let path = std::env::temp_dir()
+        .join(format!("actix-accept-ranges-{}.txt", std::process::id()));
+    std::fs::write(&path, STR).unwrap();
+    let srv_path = path.clone();
+
+    let mut srv = TestServer::new(move || {
+        let (path, path2) = (srv_path.clone(), srv_path.clone());
+        h1::H1Service::new(
+            App::new()
+                .resource("/", move |r| r.get(move || NamedFile::open(&path)))
+                .resource("/missing", move |r| {
+                    r.get(move || {
+                        NamedFile::open(&path2)
+                            .map(|file| file.set_status_code(StatusCode::NOT_FOUND))
+                    })
+                }),
+        )
+    });
+
+    let request = srv.get().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get(ACCEPT_RANGES).unwrap(), "bytes");
+
+    let request = srv.get().uri(srv.url("/missing")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.headers().get(ACCEPT_RANGES).unwrap(), "none");
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_body_stream() {
+    use futures::stream;
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.get(|| {
+                let chunks =
+                    vec![Bytes::from_static(b"first;"), Bytes::from_static(b"second")];
+                dev::BodyStream::new(stream::iter_ok::<_, Error>(chunks))
+                    .content_type("text/plain")
+            })
+        }))
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), "text/plain");
+    assert_eq!(
+        response.headers().get(TRANSFER_ENCODING).unwrap(),
+        "chunked"
+    );
+
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"first;second"));
+}
+
+#[test]
+fn test_sse() {
+    use std::time::Duration;
+
+    use actix_web2::dev::{Sse, SseEvent};
+    use futures::stream;
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.get(|| {
+                Sse::new(stream::iter_ok::<_, Error>(vec![
+                    SseEvent::new("first"),
+                    SseEvent::new("line1\nline2")
+                        .event("update")
+                        .id("2")
+                        .retry(Duration::from_secs(3)),
+                    SseEvent::new("a\r\nb\rc\nd"),
+                ]))
+            })
+        }))
+    });
+
+    let request = srv.get().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "text/event-stream"
+    );
+    assert_eq!(response.headers().get(CACHE_CONTROL).unwrap(), "no-cache");
+
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(
+        bytes,
+        Bytes::from_static(
+            b"data: first\n\nevent: update\nid: 2\nretry: 3000\ndata: line1\ndata: line2\n\n\
+               data: a\ndata: b\ndata: c\ndata: d\n\n"
+        )
+    );
+}
+
+#[test]
+fn test_result_either_responder() {
+    use actix_web2::Either;
+
+    fn validate(
+        name: String,
+    ) -> Either<String, (Json<HashMap<String, String>>, StatusCode)> {
+        let res = if name.is_empty() {
+            let mut problem = HashMap::new();
+            problem.insert("error".to_owned(), "name is empty".to_owned());
+            Err((Json(problem), StatusCode::UNPROCESSABLE_ENTITY))
+        } else {
+            Ok(name)
+        };
+        Either::from(res)
+    }
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(App::new().resource("/", |r| r.post(validate)))
+    });
+
+    let request = srv.post().body("test").unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(b"test"));
+
+    let request = srv.post().finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/json"
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(bytes, Bytes::from_static(br#"{"error":"name is empty"}"#));
+}
+
+#[test]
+fn test_either3() {
+    use actix_web2::Either3;
+
+    #[derive(Deserialize)]
+    struct ById {
+        id: u32,
+    }
+
+    #[derive(Deserialize)]
+    struct ByName {
+        name: String,
+    }
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .resource("/extract", |r| {
+                    r.get(|q: Either3<Query<ById>, Query<ByName>, ()>| match q {
+                        Either3::A(q) => format!("id:{}", q.id),
+                        Either3::B(q) => format!("name:{}", q.name),
+                        Either3::C(_) => "all".to_owned(),
+                    })
+                })
+                .resource("/respond", |r| {
+                    r.get(|q: Query<HashMap<String, String>>| {
+                        match q.get("kind").map(|s| s.as_str()) {
+                            Some("json") => {
+                                let mut body = HashMap::new();
+                                body.insert("kind", "json");
+                                Either3::A(Json(body))
+                            }
+                            Some("text") => Either3::B("text"),
+                            _ => Either3::C(Response::NotFound().finish()),
+                        }
+                    })
+                }),
+        )
+    });
+
+    for (uri, expected) in &[
+        ("/extract?id=1&name=bob", &b"id:1"[..]),
+        ("/extract?id=x&name=bob", &b"name:bob"[..]),
+        ("/extract", &b"all"[..]),
+    ] {
+        let request = srv.get().uri(srv.url(uri)).finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(&bytes[..], *expected);
+    }
+
+    let request = srv
+        .get()
+        .uri(srv.url("/respond?kind=json"))
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/json"
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(&bytes[..], &b"{\"kind\":\"json\"}"[..]);
+
+    let request = srv
+        .get()
+        .uri(srv.url("/respond?kind=text"))
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert!(response.status().is_success());
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(&bytes[..], &b"text"[..]);
+
+    let request = srv.get().uri(srv.url("/respond")).finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn test_either3_body() {
+    use actix_web2::Either3;
+
+    #[derive(Deserialize)]
+    struct ById {
+        id: u32,
+    }
+
+    #[derive(Deserialize)]
+    struct ByName {
+        name: String,
+    }
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .resource("/body", |r| {
+                    r.post(|b: Either3<Json<ById>, Form<ByName>, String>| match b {
+                        Either3::A(b) => format!("id:{}", b.id),
+                        Either3::B(b) => format!("name:{}", b.name),
+                        Either3::C(b) => format!("text:{}", b),
+                    })
+                })
+                .resource("/strict", |r| {
+                    r.post(
+                        |b: Either3<Json<ById>, Form<ByName>, Query<ById>>| match b {
+                            Either3::A(b) => format!("id:{}", b.id),
+                            Either3::B(b) => format!("name:{}", b.name),
+                            Either3::C(b) => format!("query:{}", b.id),
+                        },
+                    )
+                }),
+        )
+    });
+
+    let cases = vec![
+        ("/body", "application/json", "{\"id\":1}", &b"id:1"[..]),
+        (
+            "/body",
+            "application/x-www-form-urlencoded",
+            "name=bob",
+            &b"name:bob"[..],
+        ),
+        ("/body", "text/plain", "hello", &b"text:hello"[..]),
+        // json content type with form body falls back to the last variant
+        (
+            "/body",
+            "application/json",
+            "name=bob",
+            &b"text:name=bob"[..],
+        ),
+        ("/strict?id=3", "text/plain", "hello", &b"query:3"[..]),
+    ];
+    for (uri, content_type, body, expected) in cases {
+        let request = srv
+            .post()
+            .uri(srv.url(uri))
+            .header(CONTENT_TYPE, content_type)
+            .body(body)
+            .unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success(), "{}", body);
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(&bytes[..], expected);
+    }
+
+    // all variants fail, error of the last one is returned
+    let request = srv
+        .post()
+        .uri(srv.url("/strict"))
+        .header(CONTENT_TYPE, "text/plain")
+        .body("hello")
+        .unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn test_json_response() {
+    use actix_web2::dev::JsonResponse;
+
+    let mut srv = TestServer::new(|| {
+        h1::H1Service::new(
+            App::new()
+                .resource("/created", |r| {
+                    r.post(|| {
+                        let mut user = HashMap::new();
+                        user.insert("name", "bob");
+                        JsonResponse::new(user).status(StatusCode::CREATED)
+                    })
+                })
+                .resource("/debug", |r| {
+                    r.get(|| {
+                        let mut state = HashMap::new();
+                        state.insert("workers", 4);
+                        JsonResponse::from(Json(state)).pretty(true)
+                    })
+                }),
+        )
+    });
+
+    let request = srv.post().uri(srv.url("/created")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/json"
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(&bytes[..], &b"{\"name\":\"bob\"}"[..]);
+
+    let request = srv.get().uri(srv.url("/debug")).finish().unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/json"
+    );
+    let bytes = srv.execute(response.body()).unwrap();
+    assert!(bytes.contains(&b'\n'));
+    assert_eq!(&bytes[..], &b"{\n  \"workers\": 4\n}"[..]);
+}
+
+#[test]
+fn test_fn_filter() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.route(|mut r| {
+                r.filter(
+                    filter::All(filter::Pred(|req: &HttpRequest| {
+                        req.query_string()
+                            .split('&')
+                            .any(|param| param == "debug=1")
+                    }))
+                    .and(filter::Not(filter::Header("x-public", "1"))),
+                );
+                r.to(|| "debug")
+            })
+            .to(|| "default")
+        }))
+    });
+
+    let cases: &[(&str, bool, &[u8])] = &[
+        ("/?debug=1", false, b"debug"),
+        ("/?page=2&debug=1", false, b"debug"),
+        ("/?debug=1", true, b"default"),
+        ("/?debug=0", false, b"default"),
+        ("/", false, b"default"),
+    ];
+    for (uri, public, expected) in cases {
+        let mut request = srv.get();
+        request.uri(srv.url(uri));
+        if *public {
+            request.header("x-public", "1");
+        }
+        let request = request.finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(&bytes[..], *expected);
+    }
+}
+
+#[test]
+fn test_host_filter() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.route(|mut r| {
+                r.filter(filter::Host("api.example.com"));
+                r.to(|| "api")
+            })
+            .route(|mut r| {
+                let mut host = filter::Host("www.example.com");
+                host.scheme("https");
+                host.trust_forwarded();
+                r.filter(host);
+                r.to(|| "secure")
+            })
+            .to(|| "default")
+        }))
+    });
+
+    let cases: &[(&str, Option<&str>, &[u8])] = &[
+        ("api.example.com", None, b"api"),
+        ("API.example.com:8080", None, b"api"),
+        ("www.example.com", Some("https"), b"secure"),
+        ("www.example.com", None, b"default"),
+        ("example.com", None, b"default"),
+    ];
+    for (host, proto, expected) in cases {
+        let mut request = srv.get();
+        request.header("host", *host);
+        if let Some(proto) = proto {
+            request.header("x-forwarded-proto", *proto);
+        }
+        let request = request.finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(&bytes[..], *expected);
+    }
+
+    // forwarded headers are not trusted by default
+    let request = srv
+        .get()
+        .header("host", "example.com")
+        .header("x-forwarded-host", "api.example.com")
+        .finish()
+        .unwrap();
+    let mut response = srv.send_request(request).unwrap();
+    let bytes = srv.execute(response.body()).unwrap();
+    assert_eq!(&bytes[..], b"default");
+}
+
+#[test]
+fn test_query_filter() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.route(|mut r| {
+                r.filter(filter::Query("theme", "dark mode"));
+                r.to(|| "dark")
+            })
+            .route(|mut r| {
+                r.filter(
+                    filter::All(filter::QueryPresent("beta"))
+                        .and(filter::Not(filter::Query("beta", "false"))),
+                );
+                r.to(|| "beta")
+            })
+            .to(|| "default")
+        }))
+    });
+
+    let cases: &[(&str, &[u8])] = &[
+        ("/?theme=dark%20mode", b"dark"),
+        ("/?theme=dark+mode", b"dark"),
+        ("/?theme=light&theme=dark%20mode", b"dark"),
+        ("/?theme=dark", b"default"),
+        ("/?beta", b"beta"),
+        ("/?beta=1", b"beta"),
+        ("/?beta=false", b"default"),
+        ("/?betas=1", b"default"),
+        ("/", b"default"),
+    ];
+    for (uri, expected) in cases {
+        let request = srv.get().uri(srv.url(uri)).finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(&bytes[..], *expected);
+    }
+}
+
+#[test]
+fn test_accepts_filter() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.route(|mut r| {
+                r.filter(filter::Accepts("text/html"));
+                r.to(|| "html")
+            })
+            .to(|| "json")
+        }))
+    });
+
+    let cases: &[(Option<&str>, &[u8])] = &[
+        (Some("text/html"), b"html"),
+        (Some("application/json, text/html;q=0.5"), b"html"),
+        (Some("text/*"), b"html"),
+        (Some("*/*"), b"html"),
+        (Some("text/*;q=0, text/html"), b"html"),
+        (None, b"html"),
+        (Some("garbage, text/"), b"html"),
+        (Some("application/json"), b"json"),
+        (Some("text/plain"), b"json"),
+        (Some("text/html;q=0"), b"json"),
+        (Some("*/*, text/*;q=0"), b"json"),
+    ];
+    for (accept, expected) in cases {
+        let mut request = srv.get();
+        if let Some(accept) = accept {
+            request.header("accept", *accept);
+        }
+        let request = request.finish().unwrap();
+        let mut response = srv.send_request(request).unwrap();
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).unwrap();
+        assert_eq!(&bytes[..], *expected);
+    }
+}
+
+#[test]
+fn test_methods_filter() {
+    let mut srv = TestServer::new(move || {
+        h1::H1Service::new(App::new().resource("/", |r| {
+            r.route(|mut r| {
+                r.filter(filter::Methods(&[Method::GET, Method::HEAD]));
+                r.to(|| Response::Ok())
+            })
+            .route(|mut r| {
+                r.filter(filter::Not(filter::Methods(&[Method::GET, Method::HEAD])));
+                r.to(|| Response::Accepted())
+            })
+        }))
+    });
+
+    for method in &[Method::GET, Method::HEAD] {
+        let request = srv.request(method.clone(), "/").finish().unwrap();
+        let response = srv.send_request(request).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let request = srv.post().finish().unwrap();
+    let response = srv.send_request(request).unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+}
+
 // #[test]
 // fn test_gzip_encoding() {
 //     let mut srv = test::TestServer::new(|app| {